@@ -0,0 +1,306 @@
+use indicatif::ProgressBar;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// How often the adaptive controller measures and adjusts.
+const WINDOW: Duration = Duration::from_secs(3);
+
+/// `--concurrency`: a fixed worker count, or `adaptive`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concurrency {
+    Fixed(usize),
+    Adaptive,
+}
+
+pub fn parse_concurrency(value: &str) -> Result<Concurrency, String> {
+    if value.eq_ignore_ascii_case("adaptive") {
+        return Ok(Concurrency::Adaptive);
+    }
+    match value.parse::<usize>() {
+        Ok(0) => Err("concurrency must be at least 1".to_string()),
+        Ok(n) => Ok(Concurrency::Fixed(n)),
+        Err(_) => Err(format!("'{}' is neither a number nor 'adaptive'", value)),
+    }
+}
+
+/// Queueing growth below this is noise, not a disk falling behind.
+const QUEUE_SLACK: Duration = Duration::from_millis(5);
+
+tokio::task_local! {
+    /// When the current file's first texconv was spawned.
+    static TEXCONV_SPAWNED: OnceLock<Instant>;
+}
+
+/// Marks the current file's texconv as spawned. Called where every texconv
+/// command is built; only the first call per file counts, and outside
+/// `queued` it does nothing.
+pub fn texconv_spawned() {
+    let _ = TEXCONV_SPAWNED.try_with(|spawned| {
+        spawned.get_or_init(Instant::now);
+    });
+}
+
+/// Runs one file's conversion and returns how long it waited between
+/// `acquired` (its permit) and its first texconv spawn, or `None` if it
+/// never ran texconv (skipped, linked, failed early).
+pub async fn queued<F: Future>(acquired: Instant, convert: F) -> (F::Output, Option<Duration>) {
+    TEXCONV_SPAWNED
+        .scope(OnceLock::new(), async move {
+            let output = convert.await;
+            let spawned = TEXCONV_SPAWNED.with(|spawned| spawned.get().copied());
+            (output, spawned.map(|spawned| spawned.saturating_duration_since(acquired)))
+        })
+        .await
+}
+
+/// Summed permit-to-texconv delays of the files that reached texconv.
+#[derive(Debug, Default)]
+pub struct Queueing {
+    micros: AtomicU64,
+    files: AtomicU64,
+}
+
+impl Queueing {
+    pub fn record(&self, delay: Duration) {
+        self.micros.fetch_add(delay.as_micros() as u64, Ordering::Relaxed);
+        self.files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn totals(&self) -> (u64, u64) {
+        (self.micros.load(Ordering::Relaxed), self.files.load(Ordering::Relaxed))
+    }
+}
+
+/// One measurement window.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Files finished per second.
+    pub throughput: f64,
+    /// Average delay between a permit being acquired and texconv starting,
+    /// over the files that reached texconv in the window.
+    pub queueing: Duration,
+}
+
+/// Hill-climbing worker count: keeps moving in the same direction while
+/// throughput improves, turns around when it drops, and on a plateau backs
+/// off if queueing before texconv has grown (more workers only wait on the
+/// disk) or holds otherwise. Slow files alone don't count against a level.
+#[derive(Debug)]
+pub struct AdaptiveController {
+    min: usize,
+    max: usize,
+    level: usize,
+    growing: bool,
+    last: Option<Sample>,
+}
+
+impl AdaptiveController {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+        Self { min, max, level: 2.clamp(min, max), growing: true, last: None }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Feeds one window's measurement and returns the new level.
+    pub fn update(&mut self, sample: Sample) -> usize {
+        // Nothing finished: no signal either way
+        if sample.throughput <= 0.0 {
+            return self.level;
+        }
+
+        let Some(last) = self.last.replace(sample) else {
+            return self.step();
+        };
+
+        let change = (sample.throughput - last.throughput) / last.throughput;
+        if change > 0.05 {
+            self.step()
+        } else if change < -0.05 {
+            self.growing = !self.growing;
+            self.step()
+        } else if sample.queueing > last.queueing.mul_f64(1.2) + QUEUE_SLACK {
+            self.growing = false;
+            self.step()
+        } else {
+            self.level
+        }
+    }
+
+    fn step(&mut self) -> usize {
+        self.level = if self.growing { self.level + 1 } else { self.level.saturating_sub(1) };
+        if self.level >= self.max {
+            self.level = self.max;
+            self.growing = false;
+        } else if self.level <= self.min {
+            self.level = self.min;
+            self.growing = true;
+        }
+        self.level
+    }
+}
+
+/// Moves `semaphore` from `current` to `target` permits. Permits held by
+/// running files can't be taken back yet, so a shrink forgets what is free
+/// and records the rest in `owed`, which later calls collect as files
+/// finish and a grow cancels before adding new permits.
+fn resize_permits(semaphore: &Semaphore, owed: &mut usize, current: usize, target: usize) {
+    if target > current {
+        let grow = target - current;
+        let cancelled = grow.min(*owed);
+        *owed -= cancelled;
+        semaphore.add_permits(grow - cancelled);
+    } else {
+        *owed += current - target;
+    }
+    *owed -= semaphore.forget_permits(*owed);
+}
+
+/// Runs `controller` against the live run: every window it turns the
+/// progress position and `queueing` into a sample and grows or shrinks
+/// `semaphore` to the new level.
+pub fn spawn_controller(
+    mut controller: AdaptiveController,
+    semaphore: Arc<Semaphore>,
+    progress: ProgressBar,
+    queueing: Arc<Queueing>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut level = controller.level();
+        progress.set_prefix(format!("[{} workers] ", level));
+
+        let mut last_position = progress.position();
+        let (mut last_micros, mut last_files) = queueing.totals();
+        let mut last_tick = Instant::now();
+        let mut owed = 0;
+
+        loop {
+            tokio::time::sleep(WINDOW).await;
+
+            let position = progress.position();
+            let (micros, files) = queueing.totals();
+            let finished = position - last_position;
+            let elapsed = last_tick.elapsed().as_secs_f64();
+            let queued = match files - last_files {
+                0 => Duration::ZERO,
+                n => Duration::from_micros((micros - last_micros) / n),
+            };
+            (last_position, last_micros, last_files, last_tick) = (position, micros, files, Instant::now());
+
+            let target = controller.update(Sample { throughput: finished as f64 / elapsed, queueing: queued });
+            resize_permits(&semaphore, &mut owed, level, target);
+            level = target;
+            progress.set_prefix(format!("[{} workers] ", level));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(throughput: f64, queueing_ms: u64) -> Sample {
+        Sample { throughput, queueing: Duration::from_millis(queueing_ms) }
+    }
+
+    #[test]
+    fn grows_while_throughput_scales() {
+        let mut controller = AdaptiveController::new(1, 8);
+        assert_eq!(controller.level(), 2);
+        assert_eq!(controller.update(sample(2.0, 500)), 3);
+        assert_eq!(controller.update(sample(3.0, 500)), 4);
+        assert_eq!(controller.update(sample(4.0, 500)), 5);
+    }
+
+    #[test]
+    fn turns_around_when_throughput_regresses() {
+        let mut controller = AdaptiveController::new(1, 8);
+        controller.update(sample(2.0, 500));
+        controller.update(sample(3.0, 500));
+        assert_eq!(controller.update(sample(2.0, 500)), 3);
+        assert_eq!(controller.update(sample(2.5, 500)), 2);
+    }
+
+    #[test]
+    fn plateau_backs_off_only_when_queueing_grows() {
+        let mut controller = AdaptiveController::new(1, 8);
+        controller.update(sample(2.0, 500));
+        assert_eq!(controller.update(sample(2.0, 500)), 3);
+        assert_eq!(controller.update(sample(2.0, 800)), 2);
+    }
+
+    #[test]
+    fn plateau_ignores_queueing_noise() {
+        let mut controller = AdaptiveController::new(1, 8);
+        controller.update(sample(2.0, 0));
+        assert_eq!(controller.update(sample(2.0, 2)), 3);
+    }
+
+    #[tokio::test]
+    async fn queueing_runs_from_the_permit_to_the_first_texconv() {
+        let acquired = Instant::now();
+        let ((), queued) = queued(acquired, async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            texconv_spawned();
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            texconv_spawned();
+        })
+        .await;
+        let queued = queued.unwrap();
+        assert!(queued >= Duration::from_millis(30) && queued < Duration::from_millis(60), "{:?}", queued);
+    }
+
+    #[tokio::test]
+    async fn files_that_never_run_texconv_have_no_queueing() {
+        let ((), queued) = queued(Instant::now(), async {}).await;
+        assert_eq!(queued, None);
+    }
+
+    #[test]
+    fn idle_windows_change_nothing() {
+        let mut controller = AdaptiveController::new(1, 8);
+        assert_eq!(controller.update(sample(0.0, 0)), 2);
+    }
+
+    #[test]
+    fn stays_within_bounds() {
+        let mut controller = AdaptiveController::new(2, 3);
+        let levels: Vec<usize> = (1..=6).map(|n| controller.update(sample(n as f64, 500))).collect();
+        assert!(levels.iter().all(|level| (2..=3).contains(level)), "{:?}", levels);
+    }
+
+    #[test]
+    fn shrinking_takes_back_held_permits_as_they_return() {
+        let semaphore = Arc::new(Semaphore::new(4));
+        let held: Vec<_> = (0..4).map(|_| semaphore.clone().try_acquire_owned().unwrap()).collect();
+        let mut owed = 0;
+
+        resize_permits(&semaphore, &mut owed, 4, 2);
+        assert_eq!(owed, 2);
+
+        drop(held);
+        resize_permits(&semaphore, &mut owed, 2, 2);
+        assert_eq!((owed, semaphore.available_permits()), (0, 2));
+    }
+
+    #[test]
+    fn growing_cancels_what_is_still_owed() {
+        let semaphore = Arc::new(Semaphore::new(4));
+        let held: Vec<_> = (0..4).map(|_| semaphore.clone().try_acquire_owned().unwrap()).collect();
+        let mut owed = 0;
+
+        resize_permits(&semaphore, &mut owed, 4, 2);
+        resize_permits(&semaphore, &mut owed, 2, 5);
+        assert_eq!((owed, semaphore.available_permits()), (0, 1));
+
+        drop(held);
+        assert_eq!(semaphore.available_permits(), 5);
+    }
+}
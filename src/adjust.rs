@@ -0,0 +1,238 @@
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use std::fmt;
+use std::path::Path;
+
+use crate::cli::edit_distance;
+use crate::tint::{linear_to_srgb, srgb_to_linear};
+
+const KEYS: &[&str] = &["brightness", "contrast", "gamma", "saturation"];
+
+/// One step of `--adjust`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Adjustment {
+    /// Multiplies the colour in linear light, like an exposure change.
+    Brightness(f32),
+    /// Stretches the sRGB values away from (or towards) mid-gray.
+    Contrast(f32),
+    /// `value^(1/gamma)` on the sRGB values; above 1 lifts the mid-tones.
+    Gamma(f32),
+    /// Moves each colour away from (or towards) its linear-light luminance.
+    Saturation(f32),
+}
+
+/// `--adjust brightness=1.1,gamma=2.2,saturation=0.9,contrast=1.05`, applied
+/// in the order given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdjustChain(pub Vec<Adjustment>);
+
+pub fn parse_adjust(value: &str) -> Result<AdjustChain, String> {
+    let mut steps = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (key, amount) = part
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' should be NAME=VALUE (e.g. gamma=2.2)", part))?;
+        let key = key.trim().to_lowercase();
+        if !KEYS.contains(&key.as_str()) {
+            let suggestion = KEYS
+                .iter()
+                .map(|known| (edit_distance(&key, known), known))
+                .filter(|(distance, _)| *distance <= 3)
+                .min_by_key(|(distance, _)| *distance)
+                .map(|(_, known)| format!(" (did you mean '{}'?)", known))
+                .unwrap_or_default();
+            return Err(format!("unknown adjustment '{}'{}; use {}", key, suggestion, KEYS.join(", ")));
+        }
+        if seen.contains(&key) {
+            return Err(format!("'{}' is given twice", key));
+        }
+        let amount: f32 = amount
+            .trim()
+            .parse()
+            .ok()
+            .filter(|amount: &f32| amount.is_finite())
+            .ok_or_else(|| format!("{}={} is not a number", key, amount.trim()))?;
+        let step = match key.as_str() {
+            "brightness" if amount > 0.0 && amount <= 10.0 => Adjustment::Brightness(amount),
+            "contrast" if (0.0..=10.0).contains(&amount) => Adjustment::Contrast(amount),
+            "gamma" if amount > 0.0 && amount <= 10.0 => Adjustment::Gamma(amount),
+            "saturation" if (0.0..=10.0).contains(&amount) => Adjustment::Saturation(amount),
+            "brightness" | "gamma" => return Err(format!("{} must be above 0 and at most 10 (1 leaves the image as it is)", key)),
+            _ => return Err(format!("{} must be from 0 to 10 (1 leaves the image as it is)", key)),
+        };
+        seen.push(key);
+        steps.push(step);
+    }
+    if steps.is_empty() {
+        return Err("give at least one adjustment, e.g. gamma=2.2".to_string());
+    }
+    Ok(AdjustChain(steps))
+}
+
+impl fmt::Display for AdjustChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|step| match step {
+                Adjustment::Brightness(amount) => format!("brightness={}", amount),
+                Adjustment::Contrast(amount) => format!("contrast={}", amount),
+                Adjustment::Gamma(amount) => format!("gamma={}", amount),
+                Adjustment::Saturation(amount) => format!("saturation={}", amount),
+            })
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// Linear light to sRGB through a 16-bit table, which is exact to the
+/// 8-bit output even in the steep part of the curve near black.
+struct SrgbEncoder(Vec<u8>);
+
+impl SrgbEncoder {
+    fn new() -> Self {
+        SrgbEncoder((0..=u16::MAX).map(|index| linear_to_srgb(index as f32 / u16::MAX as f32)).collect())
+    }
+
+    fn encode(&self, linear: f32) -> u8 {
+        self.0[(linear.clamp(0.0, 1.0) * u16::MAX as f32).round() as usize]
+    }
+}
+
+impl Adjustment {
+    /// What the step does to one channel value, for steps that work per
+    /// channel; `None` for saturation, which mixes channels.
+    fn channel_map(&self, value: u8) -> Option<u8> {
+        let normalized = value as f32 / 255.0;
+        let mapped = match *self {
+            Adjustment::Brightness(amount) => return Some(linear_to_srgb(srgb_to_linear(value) * amount)),
+            Adjustment::Contrast(amount) => (normalized - 0.5) * amount + 0.5,
+            Adjustment::Gamma(amount) => normalized.powf(1.0 / amount),
+            Adjustment::Saturation(_) => return None,
+        };
+        Some((mapped.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+impl AdjustChain {
+    /// Applies the chain to the RGB of `image`; alpha is left alone.
+    /// Consecutive per-channel steps are folded into one lookup table, so
+    /// each pixel is visited once per run of them and once per saturation.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        let mut lut: Option<[u8; 256]> = None;
+        let mut encoder = None;
+        for step in &self.0 {
+            if let Adjustment::Saturation(amount) = *step {
+                if let Some(lut) = lut.take() {
+                    apply_lut(image, &lut);
+                }
+                let encoder = encoder.get_or_insert_with(SrgbEncoder::new);
+                saturate(image, amount, encoder);
+                continue;
+            }
+            let current = lut.unwrap_or(std::array::from_fn(|index| index as u8));
+            lut = Some(current.map(|value| step.channel_map(value).unwrap_or(value)));
+        }
+        if let Some(lut) = lut {
+            apply_lut(image, &lut);
+        }
+    }
+}
+
+fn apply_lut(image: &mut RgbaImage, lut: &[u8; 256]) {
+    for pixel in image.pixels_mut() {
+        for channel in 0..3 {
+            pixel[channel] = lut[pixel[channel] as usize];
+        }
+    }
+}
+
+/// Rec. 709 luminance weights, for linear RGB.
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+fn saturate(image: &mut RgbaImage, amount: f32, encoder: &SrgbEncoder) {
+    let linear: Vec<f32> = (0..=255u8).map(srgb_to_linear).collect();
+    for pixel in image.pixels_mut() {
+        let rgb = [linear[pixel[0] as usize], linear[pixel[1] as usize], linear[pixel[2] as usize]];
+        let luma: f32 = rgb.iter().zip(LUMA).map(|(channel, weight)| channel * weight).sum();
+        for (channel, value) in rgb.iter().enumerate() {
+            pixel[channel] = encoder.encode(luma + (value - luma) * amount);
+        }
+    }
+}
+
+/// Writes the PNG at `input`, adjusted, to `output`.
+pub fn adjust_png(input: &Path, output: &Path, chain: &AdjustChain) -> Result<()> {
+    let mut image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    chain.apply(&mut image);
+    image.save(output)
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5x1 gray ramp: 0, 64, 128, 192, 255.
+    fn ramp() -> RgbaImage {
+        RgbaImage::from_fn(5, 1, |x, _| {
+            let value = [0, 64, 128, 192, 255][x as usize];
+            image::Rgba([value, value, value, 200])
+        })
+    }
+
+    fn adjusted(spec: &str, mut image: RgbaImage) -> Vec<[u8; 4]> {
+        parse_adjust(spec).unwrap().apply(&mut image);
+        image.pixels().map(|pixel| pixel.0).collect()
+    }
+
+    fn reds(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.iter().map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn gamma_contrast_and_brightness_match_golden_ramps() {
+        // sqrt of the sRGB value: 64 -> 0.501 -> 128, 128 -> 0.708 -> 181
+        assert_eq!(reds(&adjusted("gamma=2", ramp())), [0, 128, 181, 221, 255]);
+        // Twice the distance from 127.5, clamped: 64 -> 0.5 and 128 -> 128.5 round up
+        assert_eq!(reds(&adjusted("contrast=2", ramp())), [0, 1, 129, 255, 255]);
+        // 128 is 0.216 linear; doubled, 0.432 is 176 in sRGB
+        assert_eq!(reds(&adjusted("brightness=2", ramp())), [0, 90, 176, 255, 255]);
+        // Alpha is never touched
+        assert!(adjusted("gamma=2,contrast=2", ramp()).iter().all(|pixel| pixel[3] == 200));
+    }
+
+    #[test]
+    fn steps_apply_in_the_order_given() {
+        let gamma_first = reds(&adjusted("gamma=2,contrast=2", ramp()));
+        let contrast_first = reds(&adjusted("contrast=2,gamma=2", ramp()));
+        assert_eq!(gamma_first, [0, 129, 235, 255, 255]);
+        // Each step rounds to 8 bits: contrast takes 64 to 1, which gamma lifts to 16
+        assert_eq!(contrast_first, [0, 16, 181, 255, 255]);
+    }
+
+    #[test]
+    fn saturation_mixes_towards_linear_luminance() {
+        let red = RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]));
+        // Red's luminance is 0.2126 linear, 127 in sRGB
+        assert_eq!(adjusted("saturation=0", red.clone()), [[127, 127, 127, 255]]);
+        assert_eq!(adjusted("saturation=1", red), [[255, 0, 0, 255]]);
+        // Gray has nothing to saturate
+        assert_eq!(reds(&adjusted("saturation=2", ramp())), [0, 64, 128, 192, 255]);
+    }
+
+    #[test]
+    fn bad_adjustments_say_what_is_wrong() {
+        let error = |spec: &str| parse_adjust(spec).unwrap_err();
+        assert!(error("gama=2.2").contains("did you mean 'gamma'"));
+        assert!(error("gamma=2,gamma=1").contains("given twice"));
+        assert!(error("gamma=0").contains("above 0"));
+        assert!(error("contrast=11").contains("from 0 to 10"));
+        assert!(error("brightness=bright").contains("not a number"));
+        assert!(error("gamma").contains("NAME=VALUE"));
+        assert_eq!(parse_adjust("Gamma=2.2, contrast=1.05").unwrap().to_string(), "gamma=2.2,contrast=1.05");
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageBuffer, ImageError, ImageFormat, Rgb, Rgba};
+use std::io::Cursor;
+use std::path::Path;
+
+/// `--alpha`: what happens to the alpha channel of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum AlphaMode {
+    /// Write alpha as texconv decodes it.
+    #[default]
+    Keep,
+    /// Drop alpha and write the colour channels unchanged.
+    Strip,
+    /// Composite over a white background.
+    White,
+    /// Composite over a black background.
+    Black,
+}
+
+impl AlphaMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlphaMode::Keep => "keep",
+            AlphaMode::Strip => "strip",
+            AlphaMode::White => "white",
+            AlphaMode::Black => "black",
+        }
+    }
+}
+
+/// What `flatten_alpha_in_place` did to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaRewrite {
+    /// `--alpha keep`, or the image has no alpha channel.
+    Unchanged,
+    Rewritten,
+    /// The image crate can't read and write this format.
+    Unsupported,
+}
+
+/// Rewrites the image at `path` without its alpha channel, in the format
+/// its extension names. Images the crate can only decode narrowed (e.g.
+/// float TIFFs) or can't encode in their own depth are left alone.
+pub fn flatten_alpha_in_place(path: &Path, mode: AlphaMode) -> Result<AlphaRewrite> {
+    if mode == AlphaMode::Keep {
+        return Ok(AlphaRewrite::Unchanged);
+    }
+    let format = match ImageFormat::from_path(path) {
+        Ok(format) if format.can_read() && format.can_write() => format,
+        _ => return Ok(AlphaRewrite::Unsupported),
+    };
+    let image = match image::open(path) {
+        Ok(image) => image,
+        Err(ImageError::Unsupported(_)) => return Ok(AlphaRewrite::Unsupported),
+        Err(error) => return Err(error).with_context(|| format!("Failed to open {}", path.display())),
+    };
+    if !image.color().has_alpha() {
+        return Ok(AlphaRewrite::Unchanged);
+    }
+    // Encoded in memory first, so a format that refuses the pixels doesn't
+    // leave a truncated file behind
+    let mut encoded = Cursor::new(Vec::new());
+    match flatten_alpha(&image, mode).write_to(&mut encoded, format) {
+        Ok(()) => {}
+        Err(ImageError::Unsupported(_)) => return Ok(AlphaRewrite::Unsupported),
+        Err(error) => return Err(error).with_context(|| format!("Failed to encode {}", path.display())),
+    }
+    std::fs::write(path, encoded.into_inner()).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(AlphaRewrite::Rewritten)
+}
+
+/// `image` as RGB: alpha dropped for `strip`, blended over the background
+/// for `white` and `black`. 16-bit images stay 16-bit and float ones float.
+pub fn flatten_alpha(image: &DynamicImage, mode: AlphaMode) -> DynamicImage {
+    if matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)) {
+        let rgba = image.to_rgba32f();
+        return DynamicImage::ImageRgb32F(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let Rgba([r, g, b, a]) = *rgba.get_pixel(x, y);
+            Rgb([r, g, b].map(|channel| blend_float(channel, a, mode)))
+        }));
+    }
+    let wide = !matches!(image, DynamicImage::ImageLumaA8(_) | DynamicImage::ImageRgba8(_));
+    if wide {
+        let rgba = image.to_rgba16();
+        DynamicImage::ImageRgb16(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let Rgba([r, g, b, a]) = *rgba.get_pixel(x, y);
+            Rgb([r, g, b].map(|channel| blend(channel as u32, a as u32, u16::MAX as u32, mode) as u16))
+        }))
+    } else {
+        let rgba = image.to_rgba8();
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let Rgba([r, g, b, a]) = *rgba.get_pixel(x, y);
+            Rgb([r, g, b].map(|channel| blend(channel as u32, a as u32, u8::MAX as u32, mode) as u8))
+        }))
+    }
+}
+
+/// One channel of a pixel with alpha `alpha` (both out of `max`), rounded.
+fn blend(channel: u32, alpha: u32, max: u32, mode: AlphaMode) -> u32 {
+    let background = match mode {
+        AlphaMode::Keep | AlphaMode::Strip => return channel,
+        AlphaMode::White => max,
+        AlphaMode::Black => 0,
+    };
+    (channel * alpha + background * (max - alpha) + max / 2) / max
+}
+
+/// `blend` for float channels, where alpha runs from 0 to 1.
+fn blend_float(channel: f32, alpha: f32, mode: AlphaMode) -> f32 {
+    let background = match mode {
+        AlphaMode::Keep | AlphaMode::Strip => return channel,
+        AlphaMode::White => 1.0,
+        AlphaMode::Black => 0.0,
+    };
+    channel * alpha + background * (1.0 - alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb32FImage, Rgba32FImage, RgbaImage};
+
+    #[test]
+    fn eight_bit_images_blend_over_the_background() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([200, 100, 0, 128])));
+        let pixel = |mode| *flatten_alpha(&image, mode).as_rgb8().unwrap().get_pixel(0, 0);
+        assert_eq!(pixel(AlphaMode::Strip), Rgb([200, 100, 0]));
+        assert_eq!(pixel(AlphaMode::Black), Rgb([100, 50, 0]));
+        assert_eq!(pixel(AlphaMode::White), Rgb([227, 177, 127]));
+    }
+
+    #[test]
+    fn float_images_stay_float() {
+        let image = DynamicImage::ImageRgba32F(Rgba32FImage::from_pixel(1, 1, Rgba([4.0, 0.5, 0.0, 0.5])));
+        let flattened = flatten_alpha(&image, AlphaMode::White);
+        let flattened: &Rgb32FImage = flattened.as_rgb32f().expect("float RGB");
+        assert_eq!(*flattened.get_pixel(0, 0), Rgb([2.5, 0.75, 0.5]));
+    }
+
+    #[test]
+    fn files_the_crate_cant_reencode_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let dds = dir.path().join("rock.dds");
+        std::fs::write(&dds, b"DDS ").unwrap();
+        assert_eq!(flatten_alpha_in_place(&dds, AlphaMode::Strip).unwrap(), AlphaRewrite::Unsupported);
+        assert_eq!(std::fs::read(&dds).unwrap(), b"DDS ");
+
+        let png = dir.path().join("rock.png");
+        RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 4])).save(&png).unwrap();
+        assert_eq!(flatten_alpha_in_place(&png, AlphaMode::Keep).unwrap(), AlphaRewrite::Unchanged);
+        assert_eq!(flatten_alpha_in_place(&png, AlphaMode::Strip).unwrap(), AlphaRewrite::Rewritten);
+        assert!(!image::open(&png).unwrap().color().has_alpha());
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::GrayImage;
+use std::path::Path;
+
+use crate::console::Console;
+use crate::errors::{Classified, ErrorCode};
+
+/// Writes `color_png` with its alpha channel replaced by the luminance of
+/// `alpha_png` to `output_png`.
+///
+/// Attached alpha textures are sometimes stored a mip level or more smaller
+/// than the color texture; a size ratio that is an exact power of two is
+/// resized to fit (with a warning), anything else is an error.
+pub fn composite_alpha(color_png: &Path, alpha_png: &Path, output_png: &Path, console: &Console) -> Result<()> {
+    let mut color = image::open(color_png)
+        .with_context(|| format!("Failed to load {}", color_png.display()))?
+        .to_rgba8();
+    let mut alpha = image::open(alpha_png)
+        .with_context(|| format!("Failed to load {}", alpha_png.display()))?
+        .to_luma8();
+
+    let (width, height) = color.dimensions();
+    if alpha.dimensions() != (width, height) {
+        let (alpha_width, alpha_height) = alpha.dimensions();
+        if !is_mip_factor(width, alpha_width) || !is_mip_factor(height, alpha_height) {
+            return Err(Classified::error(
+                ErrorCode::CorruptInput,
+                format!("Alpha part is {}x{} but the texture is {}x{}", alpha_width, alpha_height, width, height),
+            ));
+        }
+
+        console.println(format!(
+            "⚠️  Alpha part is {}x{}, texture is {}x{}; resizing to match",
+            alpha_width, alpha_height, width, height
+        ));
+        if alpha_width > width {
+            color = image::imageops::resize(&color, alpha_width, alpha_height, FilterType::Lanczos3);
+        } else {
+            alpha = image::imageops::resize(&alpha, width, height, FilterType::Lanczos3);
+        }
+    }
+
+    apply_alpha(&mut color, &alpha);
+    color.save(output_png)
+        .with_context(|| format!("Failed to write {}", output_png.display()))?;
+    Ok(())
+}
+
+fn apply_alpha(color: &mut image::RgbaImage, alpha: &GrayImage) {
+    for (pixel, luma) in color.pixels_mut().zip(alpha.pixels()) {
+        pixel[3] = luma[0];
+    }
+}
+
+/// Whether `a` and `b` differ by a whole number of mip levels (a power of two).
+fn is_mip_factor(a: u32, b: u32) -> bool {
+    let (large, small) = if a >= b { (a, b) } else { (b, a) };
+    small > 0 && large % small == 0 && (large / small).is_power_of_two()
+}
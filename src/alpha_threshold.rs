@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use std::path::Path;
+
+use crate::quantize::BAYER_4X4;
+
+/// `--alpha-threshold`: alpha below `threshold` becomes 0, the rest 255.
+/// With `dither`, the threshold is shifted per pixel by a 4x4 ordered
+/// pattern so soft edges turn into a screen of opaque pixels whose density
+/// follows the original alpha.
+pub fn binarize_alpha(image: &mut RgbaImage, threshold: u8, dither: bool) {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let alpha = pixel[3] as i32;
+        let cutoff = if dither {
+            // Spread the pattern over -120..120 around the threshold, but
+            // never turn fully transparent or fully opaque pixels over
+            let rank = BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as i32;
+            (threshold as i32 + rank * 16 - 120).clamp(1, 255)
+        } else {
+            threshold as i32
+        };
+        pixel[3] = if alpha >= cutoff { 255 } else { 0 };
+    }
+}
+
+/// Writes the PNG at `input` with binarized alpha to `output`.
+pub fn threshold_png(input: &Path, output: &Path, threshold: u8, dither: bool) -> Result<()> {
+    let mut image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    binarize_alpha(&mut image, threshold, dither);
+    image.save(output)
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Every alpha value 0-255, with colour, on a 16x16 image.
+    fn alpha_ramp() -> RgbaImage {
+        RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8, y as u8, 7, (y * 16 + x) as u8]))
+    }
+
+    fn distinct_alpha(image: &RgbaImage) -> BTreeSet<u8> {
+        image.pixels().map(|pixel| pixel[3]).collect()
+    }
+
+    #[test]
+    fn threshold_leaves_two_alpha_values() {
+        let mut image = alpha_ramp();
+        assert_eq!(distinct_alpha(&image).len(), 256);
+        binarize_alpha(&mut image, 128, false);
+        assert_eq!(distinct_alpha(&image), BTreeSet::from([0, 255]));
+        // Alpha 128 and up is opaque; colours are kept
+        assert_eq!(image.pixels().filter(|pixel| pixel[3] == 255).count(), 128);
+        assert_eq!(image.get_pixel(0, 8).0, [0, 8, 7, 255]);
+        assert_eq!(image.get_pixel(15, 7).0, [15, 7, 7, 0]);
+    }
+
+    #[test]
+    fn dithering_keeps_two_values_with_density_following_alpha() {
+        let mut image = alpha_ramp();
+        binarize_alpha(&mut image, 128, true);
+        assert_eq!(distinct_alpha(&image), BTreeSet::from([0, 255]));
+
+        // A 4x4 block covers the whole pattern once
+        let opaque = |alpha: u8| {
+            let mut block = RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 0, alpha]));
+            binarize_alpha(&mut block, 128, true);
+            block.pixels().filter(|pixel| pixel[3] == 255).count()
+        };
+        assert_eq!([0, 64, 128, 192, 255].map(opaque), [0, 4, 8, 12, 16]);
+    }
+
+    #[test]
+    fn thresholded_png_has_binary_alpha() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.png");
+        let output = dir.path().join("out.png");
+        alpha_ramp().save(&input).unwrap();
+        threshold_png(&input, &output, 1, false).unwrap();
+        let image = image::open(&output).unwrap().to_rgba8();
+        assert_eq!(distinct_alpha(&image), BTreeSet::from([0, 255]));
+        assert_eq!(image.pixels().filter(|pixel| pixel[3] == 0).count(), 1);
+    }
+}
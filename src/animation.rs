@@ -1,36 +1,182 @@
-use crate::sprite::SpriteSheet;
+use crate::console::Console;
+use crate::processor::calculate_output_path;
+use crate::timeout::output_with_timeout;
+use crate::encoding::decode_process_output;
+use crate::explode::decode_animation;
+use crate::sprite::{SpriteSheet, SpriteTimeUnit};
+use crate::tint::Tint;
+use crate::watermark::Watermark;
+use crate::utils::{is_excluded, texture_stem, NameCleanup};
+use crate::webp_anim::encode_animated_webp;
 use anyhow::{Context, Result};
 use image::{DynamicImage, RgbaImage};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use walkdir::WalkDir;
 
-pub fn find_sprite_sequences(input_dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+/// A sprite sheet: its metadata file and its texture pages, the first of
+/// which is the DDS the sheet was found by, or the first page the sprite
+/// file declares.
+#[derive(Debug, Clone)]
+pub struct SpriteSource {
+    pub pages: Vec<PathBuf>,
+    pub sprite: PathBuf,
+}
+
+impl SpriteSource {
+    pub fn anchor(&self) -> &Path {
+        &self.pages[0]
+    }
+
+    /// Every file the animation is made from, for sidecars.
+    pub fn sources(&self) -> Vec<PathBuf> {
+        self.pages.iter().cloned().chain([self.sprite.clone()]).collect()
+    }
+}
+
+/// The sheet `dds` is the first page of: `name.sprite` next to it, or
+/// `effect.sprite` for a numbered first page `effect_0.dds`.
+pub fn sprite_source(dds: &Path) -> Option<SpriteSource> {
+    let stem = texture_stem(dds);
+    let sprite = [dds.with_extension("sprite")]
+        .into_iter()
+        .chain(stem.strip_suffix("_0").map(|base| dds.with_file_name(format!("{}.sprite", base))))
+        .find(|sprite| sprite.exists())?;
+    // An unreadable sprite file is reported when the sheet is processed
+    let pages = match SpriteSheet::from_xml_file(&sprite) {
+        Ok(sheet) => sheet.page_paths(dds, &sprite),
+        Err(_) => vec![dds.to_path_buf()],
+    };
+    Some(SpriteSource { pages, sprite })
+}
+
+/// `input_dir` and every folder below it, sorted, leaving out `excluded`.
+/// Folders whose files are at most `max_depth` levels below `input_dir`.
+fn source_folders(input_dir: &Path, excluded: Option<&Path>, max_depth: Option<usize>) -> Vec<PathBuf> {
+    WalkDir::new(input_dir)
+        .max_depth(max_depth.map_or(usize::MAX, |depth| depth.saturating_sub(1)))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !is_excluded(entry.path(), excluded))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_dir())
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Sprite sheets in `input_dir` and its subfolders.
+pub fn find_sprite_sequences(input_dir: &Path, excluded: Option<&Path>, max_depth: Option<usize>) -> Result<Vec<SpriteSource>> {
+    let mut sequences = Vec::new();
+    for folder in source_folders(input_dir, excluded, max_depth) {
+        sequences.extend(sprite_sequences_in(&folder, excluded)?);
+    }
+    Ok(sequences)
+}
+
+fn sprite_sequences_in(folder: &Path, excluded: Option<&Path>) -> Result<Vec<SpriteSource>> {
     let mut sequences = Vec::new();
 
-    let entries: Vec<_> = std::fs::read_dir(input_dir)?
+    let mut entries: Vec<_> = std::fs::read_dir(folder)?
         .filter_map(|entry| entry.ok())
+        .filter(|entry| !is_excluded(&entry.path(), excluded))
         .collect();
+    entries.sort_by_key(|entry| entry.file_name());
 
-    for entry in entries {
+    for entry in &entries {
         let path = entry.path();
         if let Some(ext) = path.extension() {
             if ext == "dds" {
-                let sprite_path = path.with_extension("sprite");
-                if sprite_path.exists() {
-                    sequences.push((path, sprite_path));
-                }
+                sequences.extend(sprite_source(&path));
             }
         }
     }
 
+    // Sheets that name their own pages (like the PNG atlases `explode --to
+    // sprite` writes) don't need a DDS next to them
+    for entry in &entries {
+        let sprite = entry.path();
+        if sprite.extension().is_none_or(|ext| ext != "sprite") || sequences.iter().any(|source| source.sprite == sprite) {
+            continue;
+        }
+        let Ok(sheet) = SpriteSheet::from_xml_file(&sprite) else { continue };
+        if !sheet.textures.is_empty() {
+            let pages = sheet.page_paths(&sprite, &sprite);
+            sequences.push(SpriteSource { pages, sprite });
+        }
+    }
+
+    // Later pages of a sheet aren't sheets of their own
+    let later_pages: Vec<PathBuf> = sequences.iter().flat_map(|source| source.pages[1..].to_vec()).collect();
+    sequences.retain(|source| !later_pages.contains(&source.pages[0]));
+
     Ok(sequences)
 }
 
-pub fn find_image_sequences(input_dir: &Path) -> Result<Vec<Vec<PathBuf>>> {
+/// One animation for `animate` to create.
+pub enum AnimationSource {
+    Sheet(SpriteSource),
+    /// A GIF or WebP, re-encoded as it is.
+    Animated(PathBuf),
+    /// Numbered frames from `find_image_sequences`, in order.
+    Sequence(Vec<PathBuf>),
+}
+
+/// GIFs and WebPs in `input_dir` and its subfolders, which may already be
+/// animations of their own. Whether they are is only known once decoded.
+pub fn find_animated_files(input_dir: &Path, excluded: Option<&Path>, max_depth: Option<usize>) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for folder in source_folders(input_dir, excluded, max_depth) {
+        let mut in_folder: Vec<PathBuf> = std::fs::read_dir(&folder)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && !is_excluded(path, excluded))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "gif" | "webp"))
+            })
+            .collect();
+        in_folder.sort();
+        files.extend(in_folder);
+    }
+    Ok(files)
+}
+
+/// Loads `source`'s sprite file and decodes its texture pages.
+fn load_sprite_sheet(source: &SpriteSource) -> Result<(SpriteSheet, Vec<DynamicImage>)> {
+    let mut sprite_sheet = SpriteSheet::from_xml_file(&source.sprite)
+        .with_context(|| format!("Failed to load sprite sheet: {}", source.sprite.display()))?;
+    sprite_sheet.assign_pages(&source.pages)?;
+
+    let mut textures = Vec::new();
+    for page in &source.pages {
+        if !page.exists() {
+            anyhow::bail!("Texture page {} of {} not found", page.display(), source.sprite.display());
+        }
+        textures.push(image::open(page).with_context(|| format!("Failed to load texture page: {}", page.display()))?);
+    }
+    Ok((sprite_sheet, textures))
+}
+
+/// Numbered image sequences in `input_dir` and its subfolders; frames are
+/// only grouped with frames in the same folder.
+pub fn find_image_sequences(input_dir: &Path, excluded: Option<&Path>, max_depth: Option<usize>) -> Result<Vec<Vec<PathBuf>>> {
+    let mut sequences = Vec::new();
+    for folder in source_folders(input_dir, excluded, max_depth) {
+        sequences.extend(image_sequences_in(&folder, excluded)?);
+    }
+    Ok(sequences)
+}
+
+fn image_sequences_in(folder: &Path, excluded: Option<&Path>) -> Result<Vec<Vec<PathBuf>>> {
     let mut sequences = Vec::new();
-    let mut files: Vec<PathBuf> = std::fs::read_dir(input_dir)?
+    let mut files: Vec<PathBuf> = std::fs::read_dir(folder)?
         .filter_map(|entry| {
             let entry = entry.ok()?;
             let path = entry.path();
+            if is_excluded(&path, excluded) {
+                return None;
+            }
             let ext = path.extension()?.to_str()?;
             if matches!(ext, "png" | "dds" | "jpg" | "jpeg" | "bmp" | "tga") {
                 Some(path)
@@ -42,54 +188,243 @@ pub fn find_image_sequences(input_dir: &Path) -> Result<Vec<Vec<PathBuf>>> {
 
     files.sort();
 
-    let mut groups: std::collections::HashMap<String, Vec<PathBuf>> =
-        std::collections::HashMap::new();
+    let mut groups: std::collections::BTreeMap<(String, String), Vec<(FrameName, PathBuf)>> =
+        std::collections::BTreeMap::new();
 
     for file in files {
-        let file_name = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let frame = FrameName::parse(&texture_stem(&file));
+        groups.entry((frame.key.clone(), frame.suffix.clone())).or_default().push((frame, file));
+    }
 
-        let base_name = if let Some(pos) = file_name.rfind('_') {
-            let (base, suffix) = file_name.split_at(pos);
-            if suffix[1..].chars().all(|c| c.is_ascii_digit()) {
-                base.to_string()
-            } else {
-                file_name.to_string()
+    for (_, group) in groups {
+        for mut group in split_colliding_frames(group) {
+            if group.len() > 1 {
+                group.sort_by(|(a, a_path), (b, b_path)| (a.index, a_path).cmp(&(b.index, b_path)));
+                sequences.push(group.into_iter().map(|(_, path)| path).collect());
             }
-        } else {
-            let mut base_end = file_name.len();
-            while base_end > 0
-                && file_name
-                    .chars()
-                    .nth(base_end - 1)
-                    .unwrap_or('a')
-                    .is_ascii_digit()
-            {
-                base_end -= 1;
+        }
+    }
+
+    Ok(sequences)
+}
+
+/// A sequence frame's stem split into its grouping key and frame number.
+/// The frame number is the last run of digits, which is trailing when the
+/// stem ends in one (`glow_01`) and may be followed by a tag otherwise
+/// (`fx_flame_07_loop`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameName {
+    /// Everything before the frame number without the separator in front
+    /// of it, so `glow_1`, `glow_01` and `glow1` share the key `glow`.
+    pub key: String,
+    /// Everything after the frame number (`_loop`); frames of a sequence
+    /// share it as well as the key.
+    pub suffix: String,
+    /// Frame number with any zero padding stripped (`01` and `1` are both 1).
+    pub index: Option<u64>,
+    /// Number of digits as written, used to pick one of two frames that
+    /// share an index (`glow_1` next to `glow_01`).
+    pub width: usize,
+    /// Whether the stem has other runs of digits (`fx2_flame_07`), any of
+    /// which could have been the frame number.
+    pub ambiguous: bool,
+}
+
+impl FrameName {
+    pub fn parse(stem: &str) -> Self {
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for (pos, c) in stem.char_indices() {
+            if !c.is_ascii_digit() {
+                continue;
             }
-            if base_end < file_name.len() {
-                file_name[..base_end].to_string()
-            } else {
-                file_name.to_string()
+            match runs.last_mut() {
+                Some((_, end)) if *end == pos => *end = pos + 1,
+                _ => runs.push((pos, pos + 1)),
             }
-        };
+        }
 
-        groups.entry(base_name).or_insert_with(Vec::new).push(file);
+        match runs.last() {
+            Some(&(start, end)) => {
+                let digits = &stem[start..end];
+                Self {
+                    key: stem[..start].trim_end_matches(SEPARATORS).to_string(),
+                    suffix: stem[end..].to_string(),
+                    index: digits.parse().ok(),
+                    width: digits.len(),
+                    ambiguous: runs.len() > 1,
+                }
+            }
+            None => Self { key: stem.to_string(), suffix: String::new(), index: None, width: 0, ambiguous: false },
+        }
+    }
+
+    /// The stem with `*` for the frame number and its separator, e.g.
+    /// `fx_flame*_loop`.
+    pub fn pattern(&self) -> String {
+        format!("{}*{}", self.key, self.suffix)
+    }
+}
+
+const SEPARATORS: [char; 4] = ['_', '-', '.', ' '];
+
+/// Base name used for a sequence's output file: the stem without its frame
+/// number and the separator before it (`glow_01` -> `glow`,
+/// `fx_flame_07_loop` -> `fx_flame_loop`).
+pub fn sequence_base_name(first_frame: &Path) -> String {
+    let stem = texture_stem(first_frame);
+    let frame = FrameName::parse(&stem);
+    let prefix = frame.key.trim_end_matches(SEPARATORS);
+    let base = if prefix.is_empty() {
+        frame.suffix.trim_start_matches(SEPARATORS).to_string()
+    } else {
+        format!("{}{}", prefix, frame.suffix)
+    };
+
+    if frame.index.is_none() || base.is_empty() {
+        stem
+    } else {
+        base
     }
+}
 
-    for (_, mut group) in groups {
-        if group.len() > 1 {
-            group.sort();
-            sequences.push(group);
+/// Where one animation is written.
+#[derive(Debug, Clone)]
+pub struct PlannedAnimation {
+    /// The file the animation is known by: the sheet's first page, the
+    /// GIF/WebP, or the sequence's first frame.
+    pub source: PathBuf,
+    pub output: PathBuf,
+    /// Set when the name got its source folder's name in front because
+    /// another animation would have had the same output path.
+    pub folder_prefixed: bool,
+}
+
+/// Output paths for animations given as (source, base name) pairs:
+/// `<base>.<format>` in the folder under `output_dir` that mirrors the
+/// source's folder under `input_dir`, named like `calculate_output_path`
+/// names stills. Animations from different folders that would share a
+/// file name (`a/glow_01.png` and `b/glow_01.png`) are named
+/// `<folder>_<base>` instead (`a_glow`, `b_glow`), so names stay unique
+/// across the tree; clashes within one folder are left for the collision
+/// policy.
+pub fn plan_animation_outputs(
+    named: &[(PathBuf, String)],
+    input_dir: &Path,
+    output_dir: &Path,
+    format: &str,
+    names: NameCleanup,
+) -> Vec<PlannedAnimation> {
+    let output_for = |source: &Path, base: &str| {
+        let renamed = source.with_file_name(format!("{}.{}", base, format));
+        calculate_output_path(&renamed, input_dir, output_dir, 0, format, names, None, ("", ""))
+    };
+    let mut planned: Vec<PlannedAnimation> = named
+        .iter()
+        .map(|(source, base)| PlannedAnimation { source: source.clone(), output: output_for(source, base), folder_prefixed: false })
+        .collect();
+
+    let mut clashes: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, animation) in planned.iter().enumerate() {
+        let name = animation.output.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        clashes.entry(name).or_default().push(index);
+    }
+    let folder_name = |source: &Path| {
+        source
+            .parent()
+            .and_then(|parent| parent.file_name().or_else(|| input_dir.file_name()))
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+    for members in clashes.values().filter(|members| members.len() > 1) {
+        // Prefixing the same folder name (or one differing in case) changes nothing
+        let folders: Vec<Option<String>> = members.iter().map(|&index| folder_name(&named[index].0)).collect();
+        let first = folders[0].as_ref().map(|name| name.to_lowercase());
+        if folders.iter().all(|folder| folder.as_ref().map(|name| name.to_lowercase()) == first) {
+            continue;
+        }
+        for (&index, folder) in members.iter().zip(folders) {
+            let Some(folder) = folder else { continue };
+            let (source, base) = &named[index];
+            planned[index].output = output_for(source, &format!("{}_{}", folder, base));
+            planned[index].folder_prefixed = true;
         }
     }
+    planned
+}
 
-    Ok(sequences)
+/// Splits a group whose frame numbers repeat (e.g. `glow_1.dds` next to
+/// `glow_01.png`) into one group per extension, so two exports of the same
+/// effect don't interleave into one sequence. Within one extension padded
+/// and unpadded frames stay together; where an index is there twice
+/// (`glow_1.png` and `glow_01.png`) only the zero-padded frame is kept.
+fn split_colliding_frames(group: Vec<(FrameName, PathBuf)>) -> Vec<Vec<(FrameName, PathBuf)>> {
+    fn has_collisions(group: &[(FrameName, PathBuf)]) -> bool {
+        let mut seen = std::collections::HashSet::new();
+        group.iter().any(|(frame, _)| !seen.insert(frame.index))
+    }
+
+    if !has_collisions(&group) {
+        return vec![group];
+    }
+
+    let mut by_extension: std::collections::BTreeMap<String, Vec<(FrameName, PathBuf)>> =
+        std::collections::BTreeMap::new();
+    for (frame, path) in group {
+        let ext = path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        by_extension.entry(ext).or_default().push((frame, path));
+    }
+
+    by_extension
+        .into_values()
+        .map(|subgroup| {
+            let mut by_index: std::collections::BTreeMap<Option<u64>, (FrameName, PathBuf)> =
+                std::collections::BTreeMap::new();
+            for (frame, path) in subgroup {
+                match by_index.get(&frame.index) {
+                    Some((kept, _)) if kept.width >= frame.width => {}
+                    _ => {
+                        by_index.insert(frame.index, (frame, path));
+                    }
+                }
+            }
+            by_index.into_values().collect()
+        })
+        .collect()
+}
+
+/// `--tint` and `--watermark` for every frame of an animation.
+pub struct FrameFilters<'a> {
+    pub tint: Option<Tint>,
+    pub watermark: Option<&'a Watermark>,
+    pub watermark_margin: f32,
+    pub watermark_min_size: u32,
+}
+
+impl FrameFilters<'_> {
+    pub fn apply(&self, frames: &mut [RgbaImage]) {
+        for frame in frames {
+            if let Some(tint) = self.tint {
+                tint.apply(frame);
+            }
+            match self.watermark {
+                Some(watermark) if frame.width().min(frame.height()) >= self.watermark_min_size => {
+                    watermark.apply(frame, self.watermark_margin);
+                }
+                _ => {}
+            }
+        }
+    }
 }
 
-pub fn create_webp_animation(
+pub async fn create_webp_animation(
     image_files: &[PathBuf],
     output_path: &Path,
     frame_delay: u16,
+    filters: &FrameFilters<'_>,
+    fallback_static: bool,
+    timeout: Option<Duration>,
+    console: &Console,
 ) -> Result<()> {
     let mut frames = Vec::new();
 
@@ -98,44 +433,123 @@ pub fn create_webp_animation(
             .with_context(|| format!("Failed to open image file: {}", image_path.display()))?;
         frames.push(img.to_rgba8());
     }
+    filters.apply(&mut frames);
 
-    create_webp_animation_with_ffmpeg(&frames, output_path, frame_delay)
+    let durations = vec![frame_delay as u32; frames.len()];
+    encode_webp_animation(&frames, output_path, &durations, fallback_static, timeout, console).await
 }
 
 fn load_image_file(path: &Path) -> Result<DynamicImage> {
     image::open(path).context("Failed to load image")
 }
 
-fn create_webp_animation_with_ffmpeg(
+/// Encodes `frames` as an animated WebP, showing each for its entry of
+/// `durations_ms`, trying ffmpeg first and the built-in libwebp encoder second. Writing a single still frame is only done when the
+/// caller explicitly asked for it with `fallback_static`; otherwise failing
+/// both encoders is an error. ffmpeg is stopped after `timeout`.
+pub async fn encode_webp_animation(
     frames: &[RgbaImage],
     output_path: &Path,
-    frame_delay: u16,
+    durations_ms: &[u32],
+    fallback_static: bool,
+    timeout: Option<Duration>,
+    console: &Console,
 ) -> Result<()> {
     if frames.is_empty() {
         anyhow::bail!("No frames to create WebP animation");
     }
 
-    println!("Creating WebP animation with {} frames and transparency using ffmpeg", frames.len());
+    let ffmpeg_error = match create_webp_animation_with_ffmpeg(frames, output_path, durations_ms, timeout, console).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    console.println(format!("⚠️  {:#}", ffmpeg_error));
+    console.println("Trying the built-in WebP encoder instead...");
+
+    let native_error = match encode_animated_webp(frames, durations_ms, 85.0)
+        .and_then(|bytes| std::fs::write(output_path, bytes).context("Failed to write WebP animation"))
+    {
+        Ok(()) => {
+            console.println(format!("WebP animation created with the built-in encoder ({} frames)", frames.len()));
+            return Ok(());
+        }
+        Err(e) => e,
+    };
+
+    if !fallback_static {
+        anyhow::bail!(
+            "Could not create WebP animation {}\n  ffmpeg: {:#}\n  built-in encoder: {:#}\n\
+             Install ffmpeg (https://ffmpeg.org/download.html) and make sure it is on PATH, \
+             or pass --fallback-static to write the first frame as a still image instead",
+            output_path.display(),
+            ffmpeg_error,
+            native_error
+        );
+    }
+
+    console.println(format!("⚠️  Built-in encoder failed: {:#}", native_error));
+    console.println("⚠️  --fallback-static: writing the FIRST FRAME ONLY as a still WebP");
+    let encoder = webp::Encoder::from_rgba(&frames[0], frames[0].width(), frames[0].height());
+    let encoded = encoder.encode(85.0);
+    std::fs::write(output_path, &*encoded)?;
+    console.println(format!("Created static WebP (not animated): {}", output_path.display()));
+
+    Ok(())
+}
+
+async fn create_webp_animation_with_ffmpeg(
+    frames: &[RgbaImage],
+    output_path: &Path,
+    durations_ms: &[u32],
+    timeout: Option<Duration>,
+    console: &Console,
+) -> Result<()> {
+    console.println(format!("Creating WebP animation with {} frames and transparency using ffmpeg", frames.len()));
     
     // Criar diretório temporário
-    let temp_dir = std::env::temp_dir().join("webp_animation_frames");
-    std::fs::create_dir_all(&temp_dir)?;
+    let temp_dir = tempfile::Builder::new()
+        .prefix("webp_animation_frames")
+        .tempdir()?;
     
     // Salvar frames como PNG temporários (preserva transparência)
     for (i, frame) in frames.iter().enumerate() {
-        let frame_path = temp_dir.join(format!("frame_{:04}.png", i));
+        let frame_path = temp_dir.path().join(format!("frame_{:04}.png", i));
         frame.save(&frame_path)?;
     }
     
-    let framerate = 1000.0 / frame_delay as f32;
+    // Uniform timing is a plain frame rate; per-frame timing goes through a
+    // concat list with a duration for every frame
+    let mut command = tokio::process::Command::new("ffmpeg");
+    command.arg("-y"); // Overwrite output
+    if durations_ms.windows(2).all(|pair| pair[0] == pair[1]) {
+        let framerate = 1000.0 / durations_ms[0].max(1) as f32;
+        command
+            .arg("-framerate")
+            .arg(framerate.to_string())
+            .arg("-i")
+            .arg(temp_dir.path().join("frame_%04d.png"));
+    } else {
+        let mut list = String::from("ffconcat version 1.0\n");
+        for (i, duration) in durations_ms.iter().enumerate() {
+            list.push_str(&format!("file 'frame_{:04}.png'\nduration {:.3}\n", i, *duration as f32 / 1000.0));
+        }
+        // The concat demuxer ignores the duration of the last entry unless it is repeated
+        list.push_str(&format!("file 'frame_{:04}.png'\n", durations_ms.len() - 1));
+        let list_path = temp_dir.path().join("frames.txt");
+        std::fs::write(&list_path, list)?;
+        command
+            .arg("-f")
+            .arg("concat")
+            .arg("-safe")
+            .arg("0")
+            .arg("-i")
+            .arg(&list_path)
+            .arg("-vsync")
+            .arg("vfr");
+    }
     
     // Executar ffmpeg para criar WebP animado com transparência
-    let output = std::process::Command::new("ffmpeg")
-        .arg("-y") // Overwrite output
-        .arg("-framerate")
-        .arg(framerate.to_string())
-        .arg("-i")
-        .arg(temp_dir.join("frame_%04d.png"))
+    command
         .arg("-c:v")
         .arg("libwebp")
         .arg("-lossless")
@@ -146,44 +560,24 @@ fn create_webp_animation_with_ffmpeg(
         .arg("85")
         .arg("-loop")
         .arg("0") // Infinite loop
-        .arg(output_path)
-        .output();
-    
-    // Limpar arquivos temporários
-    let _ = std::fs::remove_dir_all(&temp_dir);
+        .arg(output_path);
+    let output = output_with_timeout(&mut command, timeout, "ffmpeg").await;
     
     match output {
-        Ok(result) => {
-            if result.status.success() {
-                println!("WebP animation created successfully with {} frames and transparency!", frames.len());
-                Ok(())
-            } else {
-                let stderr = String::from_utf8_lossy(&result.stderr);
-                println!("ffmpeg failed: {}", stderr);
-                
-                // Fallback: criar WebP estático do primeiro frame
-                println!("Creating static WebP as fallback...");
-                let encoder = webp::Encoder::from_rgba(&frames[0], frames[0].width(), frames[0].height());
-                let encoded = encoder.encode(85.0);
-                std::fs::write(output_path, &*encoded)?;
-                println!("Created static WebP with transparency: {}", output_path.display());
-                
-                Ok(())
-            }
-        }
-        Err(e) => {
-            println!("ffmpeg not found: {}", e);
-            println!("Install ffmpeg for animated WebP support");
-            
-            // Fallback: criar WebP estático do primeiro frame
-            println!("Creating static WebP as fallback...");
-            let encoder = webp::Encoder::from_rgba(&frames[0], frames[0].width(), frames[0].height());
-            let encoded = encoder.encode(85.0);
-            std::fs::write(output_path, &*encoded)?;
-            println!("Created static WebP with transparency: {}", output_path.display());
-            
+        Ok(result) if result.status.success() => {
+            console.println(format!("WebP animation created successfully with {} frames and transparency!", frames.len()));
             Ok(())
         }
+        Ok(result) => {
+            let stderr = decode_process_output(&result.stderr);
+            anyhow::bail!(
+                "ffmpeg failed with code {}: {}",
+                result.status.code().unwrap_or(-1),
+                stderr.trim()
+            )
+        }
+        Err(e) if e.is::<std::io::Error>() => anyhow::bail!("ffmpeg could not be started ({:#}); is it installed and on PATH?", e),
+        Err(e) => Err(e),
     }
 }
 
@@ -206,37 +600,63 @@ fn is_frame_mostly_black(frame: &RgbaImage) -> bool {
     (empty_pixels as f32 / total_pixels as f32) > 0.95
 }
 
-pub fn create_animation_from_sprite_sheet(
-    dds_path: &Path,
-    sprite_path: &Path,
+#[allow(clippy::too_many_arguments)]
+pub async fn create_animation_from_sprite_sheet(
+    source: &SpriteSource,
     output_path: &Path,
     frame_delay: u16,
+    time_unit: SpriteTimeUnit,
+    filters: &FrameFilters<'_>,
     format: &str,
+    fallback_static: bool,
+    timeout: Option<Duration>,
+    verbose: bool,
+    console: &Console,
 ) -> Result<()> {
-    let sprite_sheet = SpriteSheet::from_xml_file(sprite_path)
-        .with_context(|| format!("Failed to load sprite sheet: {}", sprite_path.display()))?;
-
-    println!("Found {} frames in sprite sheet", sprite_sheet.cells.len());
+    let (sprite_sheet, textures) = load_sprite_sheet(source)?;
 
-    let texture = image::open(dds_path)
-        .with_context(|| format!("Failed to load DDS texture: {}", dds_path.display()))?;
+    console.println(format!("Found {} frames in sprite sheet", sprite_sheet.cells.len()));
+    if textures.len() > 1 {
+        console.println(format!("Sheet spans {} texture pages", textures.len()));
+    }
 
     let mut frames = sprite_sheet
-        .extract_frames(&texture)
+        .extract_frames(&textures)
         .context("Failed to extract frames from sprite sheet")?;
 
-    println!("Extracted {} frames from texture", frames.len());
+    console.println(format!("Extracted {} frames from texture", frames.len()));
+
+    let page_sizes: Vec<(u32, u32)> = textures.iter().map(|texture| (texture.width(), texture.height())).collect();
+    let timing = sprite_sheet.frame_durations(&page_sizes, time_unit, frame_delay);
+    let mut durations = match &timing {
+        Some((durations, unit)) => {
+            if verbose {
+                let timed = sprite_sheet.cells.iter().filter(|cell| cell.duration.is_some()).count();
+                console.println(format!(
+                    "⏱️  Using per-frame timing from the sprite file ({} of {} cells timed, plain values read as {}; others {} ms)",
+                    timed,
+                    sprite_sheet.cells.len(),
+                    if *unit == SpriteTimeUnit::S { "seconds" } else { "milliseconds" },
+                    frame_delay
+                ));
+            }
+            durations.clone()
+        }
+        None => vec![frame_delay as u32; frames.len()],
+    };
     
     // Manter exatamente 23 frames (remover apenas o último se for preto)
     if frames.len() == 24 && is_frame_mostly_black(&frames[23]) {
         frames.pop();
-        println!("Removed last black frame");
+        durations.pop();
+        console.println("Removed last black frame");
     }
-    println!("Using {} frames for animation", frames.len());
+    console.println(format!("Using {} frames for animation", frames.len()));
+    filters.apply(&mut frames);
 
     match format {
         "webp" => {
-            create_webp_animation_with_ffmpeg(&frames, output_path, frame_delay)?;
+            encode_webp_animation(&frames, output_path, &durations, fallback_static, timeout, console).await?;
         }
         _ => {
             anyhow::bail!("Only WebP format is supported (with transparency)");
@@ -244,4 +664,216 @@ pub fn create_animation_from_sprite_sheet(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Re-encodes an animated GIF or WebP as `format` (through `filters`),
+/// keeping its frame timing unless `frame_delay` overrides it. Returns `false` without writing
+/// anything if `source` is a still image.
+#[allow(clippy::too_many_arguments)]
+pub async fn reencode_animation(
+    source: &Path,
+    output_path: &Path,
+    frame_delay: Option<u16>,
+    filters: &FrameFilters<'_>,
+    format: &str,
+    fallback_static: bool,
+    timeout: Option<Duration>,
+    console: &Console,
+) -> Result<bool> {
+    let frames = decode_animation(source)?;
+    if frames.len() < 2 {
+        return Ok(false);
+    }
+    let durations: Vec<u32> = match frame_delay {
+        Some(delay) => vec![delay as u32; frames.len()],
+        None => frames.iter().map(|(_, delay)| *delay).collect(),
+    };
+    let mut frames: Vec<RgbaImage> = frames.into_iter().map(|(frame, _)| frame).collect();
+    filters.apply(&mut frames);
+    console.println(format!("Decoded {} frames ({} ms)", frames.len(), durations.iter().sum::<u32>()));
+
+    match format {
+        "webp" => encode_webp_animation(&frames, output_path, &durations, fallback_static, timeout, console).await?,
+        _ => anyhow::bail!("Only WebP format is supported (with transparency)"),
+    }
+    Ok(true)
+}
+
+/// Writes every cell of a sprite sheet as `<frame_prefix>_NNN.png` into `output_dir`.
+pub fn export_sprite_frames(
+    source: &SpriteSource,
+    output_dir: &Path,
+    frame_prefix: &str,
+    console: &Console,
+) -> Result<usize> {
+    let (sprite_sheet, textures) = load_sprite_sheet(source)?;
+
+    let frames = sprite_sheet
+        .extract_frames(&textures)
+        .context("Failed to extract frames from sprite sheet")?;
+
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+
+    for (i, frame) in frames.iter().enumerate() {
+        let frame_path = output_dir.join(format!("{}_{:03}.png", frame_prefix, i + 1));
+        frame.save(&frame_path)
+            .with_context(|| format!("Failed to write {}", frame_path.display()))?;
+        console.println(format!("  {}", frame_path.display()));
+    }
+
+    Ok(frames.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::UnicodeForm;
+
+    fn sequences(names: &[&str]) -> Vec<Vec<String>> {
+        let dir = tempfile::tempdir().unwrap();
+        for name in names {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        image_sequences_in(dir.path(), None)
+            .unwrap()
+            .into_iter()
+            .map(|sequence| {
+                sequence.iter().map(|path| path.file_name().unwrap().to_string_lossy().into_owned()).collect()
+            })
+            .collect()
+    }
+
+    fn numbered(pattern: &str, range: std::ops::RangeInclusive<u32>, width: usize) -> Vec<String> {
+        range.map(|index| pattern.replace('#', &format!("{:0width$}", index, width = width))).collect()
+    }
+
+    fn as_strs(names: &[String]) -> Vec<&str> {
+        names.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn unpadded_frames_are_ordered_numerically() {
+        let names = numbered("glow_#.png", 1..=12, 1);
+        assert_eq!(sequences(&as_strs(&names)), vec![names.clone()]);
+    }
+
+    #[test]
+    fn padded_frames_form_one_sequence() {
+        let names = numbered("glow_#.png", 1..=12, 2);
+        assert_eq!(sequences(&as_strs(&names)), vec![names.clone()]);
+    }
+
+    #[test]
+    fn mixed_padding_forms_one_ordered_sequence() {
+        let names = ["glow_1.png", "glow_02.png", "glow_3.png", "glow_004.png", "glow_10.png"];
+        assert_eq!(sequences(&names), vec![names.map(String::from).to_vec()]);
+    }
+
+    #[test]
+    fn padded_and_unpadded_copies_of_one_sequence_merge() {
+        let mut names = numbered("glow_#.png", 1..=12, 1);
+        names.extend(numbered("glow_#.png", 1..=12, 2));
+        let mut expected = numbered("glow_#.png", 1..=9, 2);
+        expected.extend(numbered("glow_#.png", 10..=12, 2));
+        assert_eq!(sequences(&as_strs(&names)), vec![expected]);
+    }
+
+    #[test]
+    fn colliding_exports_in_different_formats_stay_apart() {
+        let mut names = numbered("glow_#.dds", 1..=10, 1);
+        names.extend(numbered("glow_#.png", 1..=24, 2));
+        assert_eq!(
+            sequences(&as_strs(&names)),
+            vec![numbered("glow_#.dds", 1..=10, 1), numbered("glow_#.png", 1..=24, 2)]
+        );
+    }
+
+    #[test]
+    fn distinct_bases_stay_separate() {
+        let names = ["glow_1.png", "glow_2.png", "glow2_frame_1.png", "glow2_frame_2.png"];
+        assert_eq!(
+            sequences(&names),
+            vec![
+                vec!["glow_1.png".to_string(), "glow_2.png".to_string()],
+                vec!["glow2_frame_1.png".to_string(), "glow2_frame_2.png".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn clashing_names_across_folders_get_the_folder_in_front() {
+        let names = NameCleanup { sanitize: None, unicode: UnicodeForm::None, lowercase: false };
+        let named = [
+            (PathBuf::from("in/fire/glow_01.png"), "glow".to_string()),
+            (PathBuf::from("in/ice/glow_01.png"), "glow".to_string()),
+            (PathBuf::from("in/ice/spark_01.png"), "spark".to_string()),
+        ];
+        let planned = plan_animation_outputs(&named, Path::new("in"), Path::new("out"), "webp", names);
+        let outputs: Vec<(&Path, bool)> = planned.iter().map(|animation| (animation.output.as_path(), animation.folder_prefixed)).collect();
+        assert_eq!(
+            outputs,
+            [
+                (Path::new("out/fire/fire_glow.webp"), true),
+                (Path::new("out/ice/ice_glow.webp"), true),
+                (Path::new("out/ice/spark.webp"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn clashes_within_one_folder_are_left_for_the_collision_policy() {
+        let names = NameCleanup { sanitize: None, unicode: UnicodeForm::None, lowercase: false };
+        let named = [(PathBuf::from("in/fx/glow_01.png"), "glow".to_string()), (PathBuf::from("in/fx/glow_01.dds"), "glow".to_string())];
+        let planned = plan_animation_outputs(&named, Path::new("in"), Path::new("out"), "webp", names);
+        assert!(planned.iter().all(|animation| animation.output == Path::new("out/fx/glow.webp") && !animation.folder_prefixed));
+    }
+
+    #[test]
+    fn the_last_digit_run_is_the_frame_number() {
+        // (stem, key, suffix, index, width, ambiguous, base name)
+        let cases = [
+            ("glow_07", "glow", "", Some(7), 2, false, "glow"),
+            ("glow7", "glow", "", Some(7), 1, false, "glow"),
+            ("fx_flame_07_loop", "fx_flame", "_loop", Some(7), 2, false, "fx_flame_loop"),
+            ("flame-003.alpha", "flame", ".alpha", Some(3), 3, false, "flame.alpha"),
+            // A trailing number wins over any earlier one
+            ("fx2_flame_07", "fx2_flame", "", Some(7), 2, true, "fx2_flame"),
+            ("lod1_fire_12_loop", "lod1_fire", "_loop", Some(12), 2, true, "lod1_fire_loop"),
+            ("v2_4", "v2", "", Some(4), 1, true, "v2"),
+            ("007_loop", "", "_loop", Some(7), 3, false, "loop"),
+            ("glow", "glow", "", None, 0, false, "glow"),
+        ];
+        for (stem, key, suffix, index, width, ambiguous, base) in cases {
+            let frame = FrameName::parse(stem);
+            assert_eq!(
+                (frame.key.as_str(), frame.suffix.as_str(), frame.index, frame.width, frame.ambiguous),
+                (key, suffix, index, width, ambiguous),
+                "{}",
+                stem
+            );
+            assert_eq!(sequence_base_name(Path::new(&format!("{}.png", stem))), base, "{}", stem);
+        }
+    }
+
+    #[test]
+    fn mid_name_frame_numbers_group_by_prefix_and_suffix() {
+        let names = ["fx_flame_10_loop.dds", "fx_flame_2_loop.dds", "fx_flame_1_loop.dds", "fx_flame_1_start.dds", "fx_flame_2_start.dds"];
+        assert_eq!(
+            sequences(&names),
+            vec![
+                vec!["fx_flame_1_loop.dds".to_string(), "fx_flame_2_loop.dds".to_string(), "fx_flame_10_loop.dds".to_string()],
+                vec!["fx_flame_1_start.dds".to_string(), "fx_flame_2_start.dds".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_key_drops_the_separator_and_padding() {
+        let padded = FrameName::parse("glow_01");
+        let unpadded = FrameName::parse("glow1");
+        assert_eq!((padded.key.as_str(), padded.index), ("glow", Some(1)));
+        assert_eq!((unpadded.key.as_str(), unpadded.index), ("glow", Some(1)));
+        assert_eq!(sequence_base_name(Path::new("fx_flame_07_loop.png")), "fx_flame_loop");
+    }
+}
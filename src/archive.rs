@@ -0,0 +1,325 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use tempfile::TempDir;
+use tokio::sync::oneshot;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+/// `--output-zip` target. Entries are appended as conversions finish, into
+/// `<name>.zip.partial`; only a completed run renames it to the final name,
+/// so an interrupted run never leaves a broken archive under that name.
+/// The archive is written by its own thread, so conversions waiting for
+/// their entry to go in don't hold up tokio's workers.
+///
+/// With `--reproducible`, entries are held in the staging directory instead
+/// and written sorted by name when the archive is finished, so the archive
+/// doesn't depend on which conversion happened to finish first.
+///
+/// With `--skip-existing`, an archive already at the final name is read
+/// first: files whose entry it holds are skipped, and its entries that this
+/// run didn't write are copied over when the archive is finished.
+pub struct ZipOutput {
+    entries: Mutex<Option<Sender<Entry>>>,
+    writer: Mutex<Option<JoinHandle<Result<()>>>>,
+    partial_path: PathBuf,
+    final_path: PathBuf,
+    staging: TempDir,
+    existing: HashSet<String>,
+}
+
+/// An entry for the writer thread, and where to send how it went.
+struct Entry {
+    name: PathBuf,
+    source: PathBuf,
+    done: oneshot::Sender<Result<()>>,
+}
+
+impl ZipOutput {
+    pub fn create(final_path: &Path, reproducible: bool, keep_existing: bool) -> Result<Self> {
+        let parent = match final_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        std::fs::create_dir_all(&parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+
+        let mut partial_name = final_path.file_name().unwrap_or_default().to_os_string();
+        partial_name.push(".partial");
+        let partial_path = final_path.with_file_name(partial_name);
+
+        let file = File::create(&partial_path)
+            .with_context(|| format!("Failed to create {}", partial_path.display()))?;
+        let staging = tempfile::Builder::new()
+            .prefix(".dds-converter-")
+            .tempdir_in(&parent)
+            .context("Failed to create staging directory")?;
+
+        let previous = (keep_existing && final_path.is_file()).then(|| final_path.to_path_buf());
+        let existing = match &previous {
+            Some(previous) => entry_names(previous)?,
+            None => HashSet::new(),
+        };
+
+        let (sender, entries) = mpsc::channel();
+        let held_dir = staging.path().to_path_buf();
+        let writer = std::thread::spawn(move || {
+            write_archive(ZipWriter::new(file), entries, reproducible.then_some(held_dir), previous)
+        });
+
+        Ok(Self {
+            entries: Mutex::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+            partial_path,
+            final_path: final_path.to_path_buf(),
+            staging,
+            existing,
+        })
+    }
+
+    /// Whether the archive this run replaces already holds `entry`.
+    pub fn contains(&self, entry: &Path) -> bool {
+        self.existing.contains(&entry_name(entry))
+    }
+
+    /// Where per-file texconv staging directories go, since there is no
+    /// output folder to put them in.
+    pub fn staging_dir(&self) -> &Path {
+        self.staging.path()
+    }
+
+    /// Appends `source` as `entry` (a path relative to the archive root),
+    /// returning once it is in the archive (or held for it), so `source`
+    /// can be deleted.
+    pub async fn add_file(&self, entry: &Path, source: &Path) -> Result<()> {
+        let (done, result) = oneshot::channel();
+        let entry = Entry { name: entry.to_path_buf(), source: source.to_path_buf(), done };
+        let sent = self.entries.lock().unwrap().as_ref().is_some_and(|entries| entries.send(entry).is_ok());
+        if !sent {
+            anyhow::bail!("ZIP archive is already closed");
+        }
+        result.await.context("ZIP archive is already closed")?
+    }
+
+    /// Writes the central directory. A complete run moves the archive to its
+    /// final name; otherwise it stays readable under the `.partial` name.
+    pub fn finish(&self, complete: bool) -> Result<PathBuf> {
+        // Closing the channel lets the writer thread finish the archive
+        self.entries.lock().unwrap().take();
+        if let Some(writer) = self.writer.lock().unwrap().take() {
+            writer.join().map_err(|_| anyhow::anyhow!("The ZIP archive writer panicked"))??;
+        }
+
+        if !complete {
+            return Ok(self.partial_path.clone());
+        }
+
+        std::fs::rename(&self.partial_path, &self.final_path)
+            .with_context(|| format!("Failed to move the archive to {}", self.final_path.display()))?;
+        Ok(self.final_path.clone())
+    }
+}
+
+/// The writer thread: appends entries as they arrive until the channel is
+/// closed, then copies over the entries of the `previous` archive that
+/// weren't written again and finishes the archive. With `held_dir`
+/// (`--reproducible`) entries are copied there and everything is written
+/// sorted at the end instead.
+fn write_archive(
+    mut writer: ZipWriter<File>,
+    entries: mpsc::Receiver<Entry>,
+    held_dir: Option<PathBuf>,
+    previous: Option<PathBuf>,
+) -> Result<()> {
+    // Entries still to write at the end: a held copy, or `None` for one of `previous`
+    let mut held: Vec<(String, Option<PathBuf>)> = Vec::new();
+    let mut written = HashSet::new();
+    for entry in entries {
+        let result = match &held_dir {
+            None => write_entry(&mut writer, &entry.name, &entry.source),
+            // The per-file staging directory is gone by the time the archive is finished
+            Some(dir) => {
+                let copy = dir.join(format!("entry-{}", held.len()));
+                let result = std::fs::copy(&entry.source, &copy)
+                    .map(|_| ())
+                    .with_context(|| format!("Failed to stage {} for the archive", entry.name.display()));
+                if result.is_ok() {
+                    held.push((entry_name(&entry.name), Some(copy)));
+                }
+                result
+            }
+        };
+        if result.is_ok() {
+            written.insert(entry_name(&entry.name));
+        }
+        let _ = entry.done.send(result);
+    }
+
+    let mut previous = match &previous {
+        Some(path) => {
+            let archive = ZipArchive::new(File::open(path).with_context(|| format!("Failed to open {}", path.display()))?)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let kept: Vec<String> = archive.file_names().filter(|name| !written.contains(*name)).map(str::to_string).collect();
+            held.extend(kept.into_iter().map(|name| (name, None)));
+            Some(archive)
+        }
+        None => None,
+    };
+
+    held.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, copy) in &held {
+        match (copy, &mut previous) {
+            (Some(copy), _) => write_entry(&mut writer, Path::new(name), copy)?,
+            (None, Some(previous)) => {
+                let file = previous.by_name(name).with_context(|| format!("Failed to read {} from the previous archive", name))?;
+                writer.raw_copy_file(file).with_context(|| format!("Failed to copy {} from the previous archive", name))?;
+            }
+            (None, None) => {}
+        }
+    }
+    writer.finish().context("Failed to finalize the ZIP archive")?;
+    Ok(())
+}
+
+fn write_entry(writer: &mut ZipWriter<File>, entry: &Path, source: &Path) -> Result<()> {
+    let mut contents = File::open(source)
+        .with_context(|| format!("Failed to open {}", source.display()))?;
+
+    // PNG and JPEG are already compressed; deflating them again only costs time
+    let already_compressed = entry
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg" | "webp"))
+        .unwrap_or(false);
+    let method = if already_compressed { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+    let options = SimpleFileOptions::default()
+        .compression_method(method)
+        .last_modified_time(DateTime::default());
+
+    writer.start_file(entry_name(entry), options)
+        .with_context(|| format!("Failed to add {} to the archive", entry.display()))?;
+    std::io::copy(&mut contents, writer)
+        .with_context(|| format!("Failed to write {} to the archive", entry.display()))?;
+    Ok(())
+}
+
+/// The names of the entries in the archive at `path`, read from its central
+/// directory.
+pub fn entry_names(path: &Path) -> Result<HashSet<String>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let archive = ZipArchive::new(file).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(archive.file_names().map(str::to_string).collect())
+}
+
+/// The contents of entry `name` of the archive at `path`, if it has one.
+pub fn read_entry(path: &Path, name: &str) -> Result<Option<String>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut archive = ZipArchive::new(file).with_context(|| format!("Failed to read {}", path.display()))?;
+    let Ok(mut entry) = archive.by_name(name) else {
+        return Ok(None);
+    };
+    let mut contents = String::new();
+    std::io::Read::read_to_string(&mut entry, &mut contents)
+        .with_context(|| format!("Failed to read {} from {}", name, path.display()))?;
+    Ok(Some(contents))
+}
+
+/// Archive-internal name for a relative output path: always `/`-separated.
+pub fn entry_name(entry: &Path) -> String {
+    entry
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Adds `count` entries from concurrent tasks and returns the names in
+    /// the finished archive.
+    async fn archive_names(reproducible: bool, count: usize) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("out.zip");
+        let zip = Arc::new(ZipOutput::create(&archive, reproducible, false).unwrap());
+        let mut tasks = Vec::new();
+        for index in 0..count {
+            let zip = zip.clone();
+            let source = dir.path().join(format!("{}.png", index));
+            std::fs::write(&source, vec![index as u8; 64]).unwrap();
+            tasks.push(tokio::spawn(async move {
+                zip.add_file(&Path::new("textures").join(format!("{:02}.png", index)), &source).await.unwrap();
+                // The entry is written (or held) by now
+                std::fs::remove_file(&source).unwrap();
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(zip.finish(true).unwrap(), archive);
+        let reader = zip::ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        reader.file_names().map(str::to_string).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn entries_from_concurrent_tasks_all_land() {
+        let mut names = archive_names(false, 16).await;
+        names.sort();
+        let expected: Vec<String> = (0..16).map(|index| format!("textures/{:02}.png", index)).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn reproducible_archives_are_sorted() {
+        let names = archive_names(true, 16).await;
+        let expected: Vec<String> = (0..16).map(|index| format!("textures/{:02}.png", index)).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test]
+    async fn a_finished_archive_takes_no_more_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = ZipOutput::create(&dir.path().join("out.zip"), false, false).unwrap();
+        zip.finish(false).unwrap();
+        let source = dir.path().join("late.png");
+        std::fs::write(&source, b"late").unwrap();
+        assert!(zip.add_file(Path::new("late.png"), &source).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_kept_archive_skips_its_entries_and_carries_them_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("out.zip");
+        let source = dir.path().join("entry.png");
+        std::fs::write(&source, b"first").unwrap();
+        let zip = ZipOutput::create(&archive, false, true).unwrap();
+        zip.add_file(Path::new("a/old.png"), &source).await.unwrap();
+        zip.add_file(Path::new("a/both.png"), &source).await.unwrap();
+        zip.finish(true).unwrap();
+
+        let zip = ZipOutput::create(&archive, false, true).unwrap();
+        assert!(zip.contains(Path::new("a").join("old.png").as_path()));
+        assert!(!zip.contains(Path::new("a/new.png")));
+        std::fs::write(&source, b"second").unwrap();
+        zip.add_file(Path::new("a/new.png"), &source).await.unwrap();
+        zip.add_file(Path::new("a/both.png"), &source).await.unwrap();
+        zip.finish(true).unwrap();
+
+        let mut reader = zip::ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        let mut names: Vec<String> = reader.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, ["a/both.png", "a/new.png", "a/old.png"]);
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut reader.by_name("a/old.png").unwrap(), &mut contents).unwrap();
+        assert_eq!(contents, "first");
+        contents.clear();
+        std::io::Read::read_to_string(&mut reader.by_name("a/both.png").unwrap(), &mut contents).unwrap();
+        assert_eq!(contents, "second");
+    }
+}
@@ -1,51 +1,1340 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use regex::Regex;
+use std::ffi::OsString;
+
+use crate::adaptive::{parse_concurrency, Concurrency};
+use crate::adjust::{parse_adjust, AdjustChain};
+use crate::collision::{CollisionPolicy, ExistingAction};
+use crate::config::ConfigDefaults;
+use crate::composite::{parse_composite_spec, CompositeSpec};
+use crate::cubemap::CubemapLayout;
+use crate::crop::{parse_crop, CropMode, CropRect};
+use crate::dedupe::LinkMode;
+use crate::explode::ExplodeTarget;
+use crate::ico::{parse_ico_sizes, IcoSizes};
+use crate::info::parse_size;
+use crate::name_template::{parse_name_template, NameTemplate};
+use crate::pixel_format::{parse_pixel_format, PixelFormatChoice};
+use crate::srgb::SrgbMode;
+use crate::alpha_flatten::AlphaMode;
+use crate::target_size::JpegSubsampling;
+use crate::resize::{parse_dimensions, Size};
+use crate::pack::{parse_pack_spec, PackSpec};
+use crate::pot::PotAlign;
+use crate::preview::PreviewColors;
+use crate::unpack::{parse_unpack_spec, UnpackSpec};
+use crate::watermark::{parse_margin, parse_watermark, Watermark};
+use crate::quantize::Dither;
+use crate::sprite::SpriteTimeUnit;
+use crate::stitch::parse_stitch_pattern;
+use crate::tint::{load_tint_map, parse_tint, Tint, TintMap};
+use crate::metadata::parse_metadata_pair;
+use crate::error_budget::parse_error_rate;
+use crate::texconv::{parse_gpu, parse_swizzle, TexconvGpu, TexconvOptions};
+use crate::utils::{parse_glob, parse_name_affix, parse_sanitize_replacement, GlobPattern, NameCleanup, UnicodeForm};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "dds-converter")]
 #[command(about = "DDS file converter using embedded texconv.exe")]
+#[command(arg_required_else_help = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Print the supported output and animation formats and exit
+    #[arg(long, conflicts_with = "list_gpus")]
+    pub list_formats: bool,
+
+    /// Print the GPU adapters texconv can use, with the indexes --gpu takes, and exit
+    #[arg(long)]
+    pub list_gpus: bool,
+
+    /// Use this texconv.exe instead of the embedded one
+    #[arg(long, global = true, value_name = "EXE", env = "DDS_CONVERTER_TEXCONV_PATH")]
+    pub texconv_path: Option<PathBuf>,
+
+    /// TOML file with option defaults, e.g. format = "webp" (default: dds-converter.toml in the working folder, if there is one)
+    #[arg(long, global = true, value_name = "FILE", env = "DDS_CONVERTER_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Convert DDS files to another image format
+    Convert(Box<ConvertArgs>),
+    /// Create animations from sprite sheets (DDS + .sprite) or numbered image sequences
+    Animate(AnimateArgs),
+    /// Extract the frames of sprite sheets (DDS + .sprite) as individual images
+    Sprite(SpriteArgs),
+    /// Split animated WebP, GIF or APNG files into frames, a sprite sheet, or a re-encoded animation
+    Explode(ExplodeArgs),
+    /// Stitch grid-named tile textures (e.g. map_00_00.dds ... map_07_07.dds) into one PNG
+    Stitch(StitchArgs),
+    /// Print DDS header details (format, size, mips, cubemap, alpha, sRGB) without converting
+    Info(InfoArgs),
+    /// List the textures added, changed, removed or renamed between two folders (e.g. game patches)
+    Diff(DiffArgs),
+    /// Show textures in the terminal with coloured half-block characters, without converting them
+    Preview(PreviewArgs),
+    /// Check that texconv, ffmpeg and the built-in encoders work on this machine
+    SelfTest,
+    /// Print a shell completion script to stdout
+    Completions(CompletionsArgs),
+}
+
+#[derive(clap::Args, Clone)]
+pub struct ConvertArgs {
     /// Input folder with .dds files
-    #[arg(short, long)]
+    #[arg(short, long, env = "DDS_CONVERTER_INPUT")]
     pub input: PathBuf,
 
     /// Output folder for converted files
-    #[arg(short, long)]
-    pub output: PathBuf,
+    #[arg(short, long, env = "DDS_CONVERTER_OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Write the converted files into this ZIP archive instead of a folder
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output", "sidecar", "copy_companions", "dedupe_link"])]
+    pub output_zip: Option<PathBuf>,
+
+    /// Convert into a temporary folder and report how the results differ from this existing output tree (which is left untouched)
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["output", "output_zip"])]
+    pub compare: Option<PathBuf>,
 
-    /// Output format (png, jpg, bmp, tga, dds, etc.)
-    #[arg(short, long, default_value = "png")]
+    /// With --compare, treat files whose pixels differ by at most this RMSE (0-255 scale) as unchanged
+    #[arg(long, value_name = "RMSE", requires = "compare", conflicts_with_all = ["output", "output_zip"])]
+    pub compare_tolerance: Option<f64>,
+
+    /// Output format (png, png8, jpg, bmp, tga, tif, dds, hdr, ico; see --list-formats)
+    #[arg(short, long, env = "DDS_CONVERTER_FORMAT", default_value = "png", value_parser = parse_output_format)]
     pub format: String,
 
     /// Number of folder segments to remove from output path
     #[arg(short, long, default_value = "0")]
     pub strip_segments: usize,
 
-    /// Number of parallel processes
-    #[arg(short, long, default_value = "4")]
-    pub concurrency: usize,
+    /// Output file name pattern, e.g. "{parent}_{stem}.{ext}": {stem}, {ext}, {parent} (the input's folder), {relpath} (its path with _ for separators, no extension) and {index} or {index:N} (1-based, zero-padded to N digits)
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_name_template)]
+    pub name_template: Option<NameTemplate>,
+
+    /// Text put before every output file name, e.g. "conv_" writes rock.dds as conv_rock.png
+    #[arg(long, value_name = "TEXT", value_parser = parse_name_affix, conflicts_with = "name_template")]
+    pub prefix: Option<String>,
+
+    /// Text put between every output file's stem and extension, e.g. "_conv" writes rock.dds as rock_conv.png
+    #[arg(long, value_name = "TEXT", value_parser = parse_name_affix, conflicts_with = "name_template")]
+    pub suffix: Option<String>,
+
+    /// Write every output straight into the output folder, dropping the input's folders; clashing names get a hash suffix unless --on-collision says otherwise
+    #[arg(long, conflicts_with = "strip_segments")]
+    pub flatten: bool,
+
+    /// Skip input files smaller than this, e.g. 4KB or 2MiB (plain numbers are bytes)
+    #[arg(long, value_name = "SIZE", default_value = "128", value_parser = parse_size)]
+    pub min_size: u64,
+
+    /// Skip input files larger than this, e.g. 64MiB
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Number of parallel processes, or "adaptive" to tune it while running
+    #[arg(short, long, env = "DDS_CONVERTER_CONCURRENCY", default_value = "4", value_parser = parse_concurrency)]
+    pub concurrency: Concurrency,
+
+    /// Options whose value came from an environment variable, as (flag, variable), for --verbose
+    #[arg(skip)]
+    pub from_env: Vec<(String, String)>,
+
+    /// Options whose value came from the config file, as (flag, value), for --verbose
+    #[arg(skip)]
+    pub from_config: Vec<(String, String)>,
+
+    /// The global --texconv-path
+    #[arg(skip)]
+    pub texconv_path: Option<PathBuf>,
+
+    /// Lower bound for --concurrency adaptive
+    #[arg(long, value_name = "N", default_value = "1")]
+    pub concurrency_min: usize,
+
+    /// Upper bound for --concurrency adaptive (default: number of CPUs)
+    #[arg(long, value_name = "N")]
+    pub concurrency_max: Option<usize>,
+
+    /// GPU adapter texconv compresses BC6H/BC7 on: an index from --list-gpus, cpu for the software codecs, or auto
+    #[arg(long, value_name = "INDEX|cpu", value_parser = parse_gpu, default_value = "auto")]
+    pub gpu: TexconvGpu,
 
     /// Only show which files would be processed
     #[arg(short, long)]
     pub dry_run: bool,
 
+    /// Like --dry-run, but print only a JSON array to stdout: input, output, size_bytes and skipped_reason for every file found
+    #[arg(long, conflicts_with_all = ["verbose", "interactive"])]
+    pub dry_run_json: bool,
+
     /// Show detailed information during processing
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Print only errors: no progress bar, status lines or summary. The exit code tells how the run went
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Show one progress line per file currently being converted, with its elapsed time
+    #[arg(long)]
+    pub show_active: bool,
+
+    /// Print periodic status lines instead of the progress bar (automatic when stdout isn't a terminal)
+    #[arg(long)]
+    pub no_progress: bool,
+
+    /// Seconds between status lines when the progress bar is off
+    #[arg(long, value_name = "SECS", default_value = "10")]
+    pub progress_interval: u64,
+
     /// Continue processing even if errors occur in specific files
     #[arg(long)]
     pub continue_on_error: bool,
 
-    /// Create animated GIF/WebP from PNG sequence (requires --animation-mode)
+    /// Retry a file up to N times, with a growing pause, when it fails because it is locked or access is denied (e.g. by an antivirus scan)
+    #[arg(long, value_name = "N", default_value = "0")]
+    pub retries: u32,
+
+    /// Pass a switch to texconv as-is, after the built-in ones (repeatable; a switch's value is its own --texconv-arg, e.g. --texconv-arg -gpu --texconv-arg 1)
+    #[arg(long = "texconv-arg", value_name = "ARG", allow_hyphen_values = true, value_parser = parse_texconv_arg)]
+    pub texconv_args: Vec<String>,
+
+    /// Stop texconv after this many seconds and report the file as failed (default: no limit)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+
+    /// With --continue-on-error, stop the run once more than N files have failed
+    #[arg(long, value_name = "N", requires = "continue_on_error")]
+    pub max_errors: Option<usize>,
+
+    /// With --continue-on-error, stop the run once more than this share of files has failed (checked after the first 100)
+    #[arg(long, value_name = "RATE", value_parser = parse_error_rate, requires = "continue_on_error")]
+    pub max_error_rate: Option<f64>,
+
+    /// Fail files when the output volume is full instead of pausing until space is freed
+    #[arg(long)]
+    pub no_pause_on_full: bool,
+
+    /// Allow the output folder to live inside the input folder without excluding it from discovery
+    #[arg(long)]
+    pub allow_nested_output: bool,
+
+    /// Write a <output>.json provenance sidecar next to every output
+    #[arg(long)]
+    pub sidecar: bool,
+
+    /// Don't use an attached .dds.a texture as the output's alpha channel
+    #[arg(long)]
+    pub no_merge_alpha_part: bool,
+
+    /// Strip timestamps and other volatile metadata so reruns give byte-identical outputs
+    #[arg(long)]
+    pub reproducible: bool,
+
+    /// Read every output back after writing it; one that doesn't decode is deleted and the file fails with corrupt_output
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Delete each source .dds (with its split and alpha parts) once its output was written and, with --verify, verified
+    #[arg(long, conflicts_with_all = ["compare", "watch"])]
+    pub delete_source: bool,
+
+    /// Allow --delete-source when --output is the same folder as --input
+    #[arg(long, requires = "delete_source")]
+    pub yes_really_delete: bool,
+
+    /// Leave outputs that already hold exactly the new bytes untouched, mtime included
+    #[arg(long, conflicts_with_all = ["output_zip", "compare"])]
+    pub write_if_changed: bool,
+
+    /// What to do when an output already exists from an earlier run
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "overwrite", conflicts_with_all = ["output_zip", "compare"])]
+    pub on_conflict: ExistingAction,
+
+    /// Only convert files that are newer than their output (or have none), so re-extracted textures are converted again
+    #[arg(long, conflicts_with_all = ["skip_existing", "output_zip", "compare"])]
+    pub incremental: bool,
+
+    /// Write the end-of-run summary to this file as JSON (counts, failures with their errors, bytes, duration), also when a fatal error stops the run
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Append a JSON line per processed file (input, output, duration, status, error) plus run start and end records to this file
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// After converting, keep running and convert .dds files that appear or change under --input until Ctrl-C
+    #[arg(long, conflicts_with_all = ["output_zip", "compare", "dry_run", "dry_run_json", "diff_against", "files_from", "retry_failed", "dedupe_link", "pack", "unpack", "composite", "interactive"])]
+    pub watch: bool,
+
+    /// Don't convert files whose output already exists (checked by name only, without running texconv, or against the entries of the existing --output-zip archive); short for --on-conflict skip
+    #[arg(long, conflicts_with_all = ["on_conflict", "compare"])]
+    pub skip_existing: bool,
+
+    /// Resize every output to exactly WxH pixels (e.g. 512x512) while texconv converts it
+    #[arg(long, value_name = "WxH", value_parser = parse_dimensions, conflicts_with_all = ["max_dimension", "all_mips", "pack", "composite", "unpack"])]
+    pub resize: Option<Size>,
+
+    /// Shrink outputs so their longer side is at most N pixels, keeping the aspect ratio
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["all_mips", "pack", "composite", "unpack"])]
+    pub max_dimension: Option<u32>,
+
+    /// Let --resize and --max-dimension enlarge textures smaller than the target
+    #[arg(long)]
+    pub allow_upscale: bool,
+
+    /// Only convert this region of each texture, as X,Y,WxH in pixels (e.g. 1024,0,512x512)
+    #[arg(long, value_name = "X,Y,WxH", value_parser = parse_crop)]
+    pub crop: Option<CropRect>,
+
+    /// What to do when a texture is too small for --crop
+    #[arg(long, value_enum, default_value = "skip", requires = "crop")]
+    pub crop_mode: CropMode,
+
+    /// Cut away the transparent border around each image
+    #[arg(long)]
+    pub trim: bool,
+
+    /// With --trim, pixels with alpha at or below this count as transparent
+    #[arg(long, value_name = "ALPHA", default_value = "0", requires = "trim")]
+    pub trim_threshold: u8,
+
+    /// With --trim, keep this many pixels of border around the content
+    #[arg(long, value_name = "PX", default_value = "0", requires = "trim")]
+    pub trim_padding: u32,
+
+    /// Colour space handling: auto keeps sRGB textures (by their DDS format) sRGB except _ddna normal maps and _mask masks, force treats every texture as sRGB, linear leaves it to texconv
+    #[arg(long, value_enum, default_value = "auto")]
+    pub srgb: SrgbMode,
+
+    /// Reorder or replicate channels as texconv reads each texture, e.g. bgra, rrr1 or g (r, g, b, a, 0 and 1, up to four)
+    #[arg(long, value_name = "MASK", value_parser = parse_swizzle)]
+    pub swizzle: Option<String>,
+
+    /// TOML file of [[rule]] tables: a match glob (relative to --input) and the keys a <name>.dds.toml takes, applied to the matching files (a file's own .dds.toml wins)
+    #[arg(long, value_name = "FILE")]
+    pub rules: Option<PathBuf>,
+
+    /// DXGI format texconv writes outputs in, e.g. R16G16B16A16_FLOAT; auto picks float for HDR textures (dds, hdr and tif outputs), R8_UNORM for single-channel ones and R8G8B8A8_UNORM otherwise
+    #[arg(long, value_name = "FORMAT", default_value = "auto", value_parser = parse_pixel_format)]
+    pub pixel_format: PixelFormatChoice,
+
+    /// Dithering for the tool's own bit-depth reductions: high-bit-depth textures to 8 bits, and --format png8 palettes
+    #[arg(long, value_enum, default_value = "none")]
+    pub dither: Dither,
+
+    /// With --format png8, keep images that need more than 256 colours as regular PNG instead of quantizing them
+    #[arg(long)]
+    pub png8_fallback: bool,
+
+    /// With --format ico, the icon sizes to embed (1-256 pixels)
+    #[arg(long, value_name = "LIST", default_value = "16,24,32,48,64,128,256", value_parser = parse_ico_sizes)]
+    pub ico_sizes: IcoSizes,
+
+    /// For jpg and webp outputs, lower the quality (from 90, in at most 6 tries) until each file is at most this many bytes
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub target_size: Option<u64>,
+
+    /// JPEG quality (1-100) for --format jpg, encoded in-process instead of by texconv (default: 90)
+    #[arg(long, value_name = "1-100", value_parser = clap::value_parser!(u8).range(1..=100), conflicts_with = "target_size")]
+    pub jpeg_quality: Option<u8>,
+
+    /// Chroma subsampling for --format jpg, encoded in-process instead of by texconv (default: 444, none)
+    #[arg(long, value_name = "420|422|444")]
+    pub jpeg_subsampling: Option<JpegSubsampling>,
+
+    /// Recompress PNG outputs in-process, keeping the smaller file: 1 = row filters and deflate, 2 = also zopfli for small files, 3 = also lossless colour type and palette reduction
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(1..=3))]
+    pub optimize: Option<u8>,
+
+    /// With --optimize 2 or 3, leave PNG outputs larger than this many bytes as they are
+    #[arg(long, value_name = "BYTES", default_value = "33554432", requires = "optimize")]
+    pub optimize_max_size: u64,
+
+    /// Make alpha binary: below N becomes fully transparent, N and above fully opaque
+    #[arg(long, value_name = "N")]
+    pub alpha_threshold: Option<u8>,
+
+    /// With --alpha-threshold, use ordered dithering so soft edges keep their apparent softness
+    #[arg(long, requires = "alpha_threshold")]
+    pub alpha_dither: bool,
+
+    /// What to do with the output's alpha channel: keep it, strip it, or flatten onto a white or black background
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = AlphaMode::Keep)]
+    pub alpha: AlphaMode,
+
+    /// Pad each image with transparency up to power-of-two width and height
+    #[arg(long)]
+    pub pad_pot: bool,
+
+    /// Where the original pixels go inside the --pad-pot image
+    #[arg(long, value_enum, default_value = "top-left", requires = "pad_pot")]
+    pub pot_align: PotAlign,
+
+    /// Split each texture into tiles of this many pixels square, named <stem>_xNN_yNN
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["dedupe_link", "compare"])]
+    pub tile: Option<u32>,
+
+    /// Pad edge tiles with transparent pixels to the full --tile size
+    #[arg(long, requires = "tile")]
+    pub tile_pad: bool,
+
+    /// Write a <stem>.tiles.json manifest describing the tile grid
+    #[arg(long, requires = "tile")]
+    pub tile_manifest: bool,
+
+    /// Export every stored mip level as its own image, suffixed _mip0, _mip1, ...
+    /// (_posx_mip0, _slice1_mip0, ... for each face or slice of cubemaps and arrays)
+    #[arg(long, conflicts_with_all = ["crop", "trim", "pad_pot", "tile", "alpha_threshold", "compare", "dedupe_link"])]
+    pub all_mips: bool,
+
+    /// Export only this mip level (0 is the full-size image)
+    #[arg(long, value_name = "N", conflicts_with_all = ["all_mips", "cubemap_layout", "pack", "composite", "unpack"])]
+    pub mip: Option<u32>,
+
+    /// With --mip, use the smallest stored level for textures with fewer levels instead of failing them
+    #[arg(long, requires = "mip")]
+    pub mip_clamp: bool,
+
+    /// How to write cubemaps: one image per face, a horizontal cross, or an equirectangular panorama
+    #[arg(long, value_enum, value_name = "LAYOUT", conflicts_with_all = ["all_mips", "crop", "trim", "pad_pot", "tile", "alpha_threshold", "compare", "dedupe_link"])]
+    pub cubemap_layout: Option<CubemapLayout>,
+
+    /// Width of --cubemap-layout equirect panoramas (default: four times the face size)
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(2..))]
+    pub equirect_width: Option<u32>,
+
+    /// Pack channels of several maps into one image per texture, e.g. r=*_ao.dds,g=*_r.dds,b=*_m.dds,a=white
+    #[arg(long, value_name = "SPEC", value_parser = parse_pack_spec, conflicts_with_all = ["all_mips", "cubemap_layout", "crop", "trim", "pad_pot", "tile", "alpha_threshold", "compare", "dedupe_link"])]
+    pub pack: Option<PackSpec>,
+
+    /// Write channels of packed textures as separate grayscale maps, e.g. r=ao,g=roughness,b=metal[,match=*_orm]
+    #[arg(long, value_name = "SPEC", value_parser = parse_unpack_spec, conflicts_with_all = ["pack", "all_mips", "cubemap_layout", "crop", "trim", "pad_pot", "tile", "alpha_threshold", "compare", "dedupe_link"])]
+    pub unpack: Option<UnpackSpec>,
+
+    /// Layer an overlay texture over a base texture per shared name, e.g. *_frame.dds+*_glyph.dds; either side may add a tint, as in *_glyph.dds@#ffb000
+    #[arg(long, value_name = "SPEC", value_parser = parse_composite_spec, conflicts_with_all = ["pack", "unpack", "all_mips", "cubemap_layout", "crop", "trim", "pad_pot", "tile", "alpha_threshold", "tint", "tint_map", "adjust", "compare", "dedupe_link"])]
+    pub composite: Option<CompositeSpec>,
+
+    /// With --unpack, also write channels that are all 0 or all 255
+    #[arg(long, requires = "unpack")]
+    pub keep_constant_channels: bool,
+
+    /// Multiply the colours by this tint (in linear light), for grayscale masks the game tints at runtime, e.g. #0070dd
+    #[arg(long, value_name = "HEX", value_parser = parse_tint, conflicts_with_all = ["pack", "unpack", "all_mips", "cubemap_layout"])]
+    pub tint: Option<Tint>,
+
+    /// Colour adjustments applied in the order given, after --tint: brightness, contrast, gamma, saturation, e.g. brightness=1.1,gamma=1.2
+    #[arg(long, value_name = "SPEC", value_parser = parse_adjust, conflicts_with_all = ["pack", "unpack", "all_mips", "cubemap_layout"])]
+    pub adjust: Option<AdjustChain>,
+
+    /// TOML file of suffix = colour entries (optionally with a match pattern); each matching texture gets one tinted <stem>_<suffix> output per entry
+    #[arg(long, value_name = "FILE", value_parser = load_tint_map, conflicts_with_all = ["tint", "pack", "unpack", "all_mips", "cubemap_layout", "tile", "compare", "dedupe_link"])]
+    pub tint_map: Option<TintMap>,
+
+    /// Remove all metadata from PNG/JPEG outputs that isn't needed to display them
     #[arg(long)]
-    pub animation_mode: bool,
+    pub strip_metadata: bool,
+
+    /// With --strip-metadata, keep embedded ICC colour profiles
+    #[arg(long, requires = "strip_metadata")]
+    pub keep_icc: bool,
+
+    /// Embed the source path and hash, DDS pixel format and tool version into PNG/JPEG outputs
+    #[arg(long)]
+    pub embed_metadata: bool,
+
+    /// Extra KEY=VALUE to embed with --embed-metadata (repeatable)
+    #[arg(long = "metadata", value_name = "KEY=VALUE", requires = "embed_metadata", value_parser = parse_metadata_pair)]
+    pub metadata: Vec<(String, String)>,
+
+    /// Add a hash of each output's final bytes to its name (name.3fa2b1c9.png), LEN hex digits long
+    #[arg(
+        long,
+        value_name = "LEN",
+        num_args = 0..=1,
+        default_missing_value = "8",
+        value_parser = clap::value_parser!(u8).range(4..=64),
+        conflicts_with_all = ["compare", "dedupe_link", "copy_companions"]
+    )]
+    pub content_hash: Option<u8>,
+
+    /// With --content-hash, write manifest.json mapping each plain output name to its hashed name
+    #[arg(long, requires = "content_hash")]
+    pub hash_manifest: bool,
+
+    /// What to do when several inputs map to the same output path [default: error, or suffix-hash with --flatten]
+    #[arg(long, value_name = "POLICY")]
+    pub on_collision: Option<CollisionPolicy>,
+
+    /// Give inputs that map to the same output (e.g. after --strip-segments) names with a stable hash suffix instead of stopping; short for --on-collision suffix-hash
+    #[arg(long, conflicts_with = "on_collision")]
+    pub dedupe_collisions: bool,
+
+    /// Ask on the terminal what to do when an output already exists or several inputs map to the same output
+    #[arg(long, conflicts_with = "compare")]
+    pub interactive: bool,
+
+    /// Seconds an --interactive question waits before falling back to the usual behavior
+    #[arg(long, value_name = "SECS", default_value = "30", value_parser = clap::value_parser!(u64).range(1..), requires = "interactive")]
+    pub prompt_timeout: u64,
+
+    /// Convert byte-identical inputs once and give the duplicates the same output via a hardlink, symlink or copy
+    #[arg(long, value_name = "MODE")]
+    pub dedupe_link: Option<LinkMode>,
+
+    /// Also copy same-stem files with these extensions (e.g. sprite,mtl,json) next to each output, unless an identical copy is already there
+    #[arg(long, visible_alias = "copy-sidecars", value_name = "EXT,EXT", value_delimiter = ',', value_parser = parse_companion_extension)]
+    pub copy_companions: Vec<String>,
+
+    /// Only convert textures that are new or changed compared to this earlier copy of the input folder
+    #[arg(long, value_name = "DIR", conflicts_with = "files_from")]
+    pub diff_against: Option<PathBuf>,
+
+    /// With --diff-against, also write the added/changed/removed/renamed report as JSON
+    #[arg(long, value_name = "FILE", requires = "diff_against")]
+    pub diff_json: Option<PathBuf>,
 
-    /// Frame delay in milliseconds for animations (default: 100ms)
-    #[arg(long, default_value = "100")]
-    pub frame_delay: u16,
+    /// Convert only the files listed in a failed-files.txt written by an earlier run (paths relative to --input), and rewrite that list with what still fails
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["files_from", "diff_against"])]
+    pub retry_failed: Option<PathBuf>,
+
+    /// Convert exactly the textures listed in this file (`-` for stdin) instead of searching --input: paths relative to --input, one per line, or a diff JSON report
+    #[arg(long, visible_alias = "file-list", value_name = "FILE")]
+    pub files_from: Option<PathBuf>,
+
+    /// Only convert textures whose path relative to --input matches this glob (`*`, `?`, `**`); repeatable, e.g. --include "lyshineui/**"
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub include: Vec<GlobPattern>,
+
+    /// Skip textures whose path relative to --input matches this glob (`*`, `?`, `**`); repeatable, e.g. --exclude "**/lightmaps/**" --exclude "*_lod?.dds"
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub exclude: Vec<GlobPattern>,
+
+    #[command(flatten)]
+    pub watermark: WatermarkArgs,
+
+    #[command(flatten)]
+    pub naming: NamingArgs,
+
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+}
+
+#[derive(clap::Args)]
+pub struct AnimateArgs {
+    /// Input folder with sprite sheets or image sequences
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    /// Output folder for the animations
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// Frame delay in milliseconds (default: 100); when given, it also replaces the timing of GIF and WebP inputs
+    #[arg(long)]
+    pub frame_delay: Option<u16>,
+
+    /// Multiply every frame by this tint (in linear light), e.g. #a335ee
+    #[arg(long, value_name = "HEX", value_parser = parse_tint)]
+    pub tint: Option<Tint>,
+
+    /// Unit of per-cell duration/time values in sprite files that have no ms or s suffix
+    #[arg(long, value_enum, default_value = "auto")]
+    pub sprite_time_unit: SpriteTimeUnit,
 
     /// Animation output format (webp with transparency)
-    #[arg(long, default_value = "webp")]
+    #[arg(long, default_value = "webp", value_parser = parse_animation_format)]
+    pub animation_format: String,
+
+    /// If no animation encoder works, write the first frame as a still WebP instead of failing
+    #[arg(long)]
+    pub fallback_static: bool,
+
+    /// Pass a switch to texconv as-is when converting DDS frames (repeatable)
+    #[arg(long = "texconv-arg", value_name = "ARG", allow_hyphen_values = true, value_parser = parse_texconv_arg)]
+    pub texconv_args: Vec<String>,
+
+    /// GPU adapter texconv decodes DDS frames on: an index from --list-gpus, cpu for the software codecs, or auto
+    #[arg(long, value_name = "INDEX|cpu", value_parser = parse_gpu, default_value = "auto")]
+    pub gpu: TexconvGpu,
+
+    /// Stop texconv or ffmpeg after this many seconds and fail the animation (default: no limit)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+
+    /// Show detailed information during processing
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Allow the output folder to live inside the input folder without excluding it from discovery
+    #[arg(long)]
+    pub allow_nested_output: bool,
+
+    /// Write a <output>.json provenance sidecar next to every output
+    #[arg(long)]
+    pub sidecar: bool,
+
+    /// Strip timestamps and other volatile metadata so reruns give byte-identical outputs
+    #[arg(long)]
+    pub reproducible: bool,
+
+    /// Only show where each animation would be written
+    #[arg(short, long)]
+    pub dry_run: bool,
+
+    /// What to do when several animations map to the same output path
+    #[arg(long, value_name = "POLICY", default_value = "error")]
+    pub on_collision: CollisionPolicy,
+
+    #[command(flatten)]
+    pub watermark: WatermarkArgs,
+
+    #[command(flatten)]
+    pub naming: NamingArgs,
+
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+}
+
+/// `--watermark` and its options, shared by `convert` and `animate`.
+#[derive(clap::Args, Clone)]
+pub struct WatermarkArgs {
+    /// Stamp an image on every output (every frame of animations) after all other processing: IMAGE[:POSITION[:OPACITY[:SCALE]]], e.g. logo.png:bottom-right:0.5:0.1
+    #[arg(long, value_name = "SPEC", value_parser = parse_watermark)]
+    pub watermark: Option<Watermark>,
+
+    /// Gap between the watermark and the image edges, as a fraction of the image's smaller side
+    #[arg(long, value_name = "FRACTION", default_value = "0.02", value_parser = parse_margin, requires = "watermark")]
+    pub watermark_margin: f32,
+
+    /// Leave images whose width or height is under this many pixels (icons) without a watermark
+    #[arg(long, value_name = "PX", default_value = "128", requires = "watermark")]
+    pub watermark_min_size: u32,
+}
+
+/// Options for making output names valid on Windows and the same bytes
+/// on every platform.
+#[derive(clap::Args, Clone)]
+pub struct NamingArgs {
+    /// Keep output names as they are, even if Windows can't store them
+    #[arg(long)]
+    pub no_sanitize: bool,
+
+    /// Replacement for characters that are invalid in file names
+    #[arg(long, value_name = "TEXT", default_value = "_", value_parser = parse_sanitize_replacement)]
+    pub sanitize_replacement: String,
+
+    /// Unicode normalization form for output file and folder names
+    #[arg(long, value_name = "FORM", value_enum, default_value_t = UnicodeForm::Nfc)]
+    pub normalize_unicode: UnicodeForm,
+
+    /// Lowercase output file and folder names (below the output folder); the inputs are left as they are
+    #[arg(long)]
+    pub lowercase: bool,
+}
+
+impl ConvertArgs {
+    /// Root that output paths are computed against: the output folder, or
+    /// the archive root (an empty path) with `--output-zip`.
+    pub fn output_root(&self) -> &Path {
+        self.output.as_deref().unwrap_or(Path::new(""))
+    }
+
+    /// The folder outputs go to: the output folder, or the archive's folder
+    /// with `--output-zip`.
+    pub fn target_folder(&self) -> &Path {
+        self.output_zip.as_deref().and_then(Path::parent).or(self.output.as_deref()).unwrap_or(Path::new("."))
+    }
+
+    /// Where the run writes its failure list: the `--retry-failed` list being
+    /// retried, or `failed-files.txt` in the output folder or next to the
+    /// archive. `None` for `--compare` runs, whose output is a scratch folder.
+    pub fn failure_list_path(&self) -> Option<PathBuf> {
+        const NAME: &str = "failed-files.txt";
+        match (&self.output_zip, &self.output) {
+            _ if self.compare.is_some() => None,
+            _ if self.retry_failed.is_some() => self.retry_failed.clone(),
+            (Some(zip), _) => Some(zip.parent().unwrap_or(Path::new("")).join(NAME)),
+            (None, Some(output)) => Some(output.join(NAME)),
+            (None, None) => None,
+        }
+    }
+
+    /// `--on-collision`, with `--dedupe-collisions` as `suffix-hash`, which
+    /// is also the default under `--flatten` since a flattened game dump is
+    /// full of clashing names.
+    pub fn collision_policy(&self) -> CollisionPolicy {
+        match self.on_collision {
+            Some(policy) => policy,
+            None if self.flatten || self.dedupe_collisions => CollisionPolicy::SuffixHash,
+            None => CollisionPolicy::Error,
+        }
+    }
+
+    /// `--on-conflict`, with `--skip-existing` as `skip`.
+    pub fn existing_policy(&self) -> ExistingAction {
+        if self.skip_existing { ExistingAction::Skip } else { self.on_conflict }
+    }
+
+    /// The texconv runs' `--gpu`, `--timeout`, `--srgb`, `--swizzle` and
+    /// `--texconv-arg`s.
+    pub fn texconv(&self) -> TexconvOptions {
+        TexconvOptions {
+            gpu: self.gpu,
+            timeout: self.timeout.map(Duration::from_secs),
+            srgb: self.srgb,
+            swizzle: self.swizzle.clone(),
+            extra_args: self.texconv_args.clone(),
+            echo: self.verbose,
+        }
+    }
+}
+
+impl AnimateArgs {
+    /// `--frame-delay`, or the 100 ms default.
+    pub fn frame_delay_ms(&self) -> u16 {
+        self.frame_delay.unwrap_or(100)
+    }
+
+    /// The texconv runs' `--gpu`, `--timeout` and `--texconv-arg`s.
+    pub fn texconv(&self) -> TexconvOptions {
+        TexconvOptions {
+            gpu: self.gpu,
+            timeout: self.timeout.map(Duration::from_secs),
+            extra_args: self.texconv_args.clone(),
+            echo: self.verbose,
+            ..TexconvOptions::default()
+        }
+    }
+}
+
+impl NamingArgs {
+    /// How output names are cleaned up: `--normalize-unicode`, then
+    /// sanitizing with the replacement unless `--no-sanitize`.
+    pub fn cleanup(&self) -> NameCleanup<'_> {
+        NameCleanup {
+            sanitize: (!self.no_sanitize).then_some(self.sanitize_replacement.as_str()),
+            unicode: self.normalize_unicode,
+            lowercase: self.lowercase,
+        }
+    }
+}
+
+/// Options for trial runs over part of the discovered inputs.
+#[derive(clap::Args, Clone)]
+pub struct SelectionArgs {
+    /// Only process the first N discovered items (files, or animations in `animate`), in sorted path order
+    #[arg(long, value_name = "N", conflicts_with = "sample", value_parser = clap::value_parser!(u64).range(1..))]
+    pub limit: Option<u64>,
+
+    /// Process N items picked uniformly at random
+    #[arg(long, value_name = "N")]
+    pub sample: Option<usize>,
+
+    /// Seed for --sample, to pick the same items again
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Only search N folder levels deep: 1 is just the files directly inside --input. Output paths still mirror the full relative path, so --strip-segments counts from --input as usual
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_depth: Option<u64>,
+}
+
+impl SelectionArgs {
+    /// `--max-depth` for the discovery walk.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth.map(|depth| depth as usize)
+    }
+}
+
+#[derive(clap::Args)]
+pub struct SpriteArgs {
+    /// Sprite sheet DDS file, or a folder with DDS + .sprite pairs
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    /// Output folder; each sheet's frames go into a subfolder named after it
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// Show detailed information during processing
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[command(flatten)]
+    pub naming: NamingArgs,
+}
+
+#[derive(clap::Args)]
+pub struct ExplodeArgs {
+    /// Animated WebP, GIF or APNG file, or a folder of them
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    /// Output folder
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// What to write for each animation
+    #[arg(long, value_enum, default_value = "frames")]
+    pub to: ExplodeTarget,
+
+    /// Animation format for --to animation
+    #[arg(long, default_value = "webp", value_parser = parse_animation_format)]
     pub animation_format: String,
+
+    /// Columns of the --to sprite atlas (default: as square as possible)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub columns: Option<u32>,
+
+    /// Show detailed information during processing
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[command(flatten)]
+    pub naming: NamingArgs,
+}
+
+#[derive(clap::Args)]
+pub struct StitchArgs {
+    /// Folder with grid-named tile textures
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    /// Output folder for the stitched PNGs
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// Regex matched against each file name without extension; needs named captures x and y, and an optional name capture that groups tiles into images
+    #[arg(long, default_value = r"^(?P<name>.+)_(?P<x>\d+)_(?P<y>\d+)$", value_parser = parse_stitch_pattern)]
+    pub pattern: Regex,
+
+    /// Ask before stitching an image with more pixels than this
+    #[arg(long, value_name = "PIXELS", default_value = "268435456")]
+    pub max_pixels: u64,
+
+    /// Stitch images over --max-pixels without asking
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Show detailed information during processing
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[command(flatten)]
+    pub naming: NamingArgs,
+}
+
+#[derive(clap::Args)]
+pub struct InfoArgs {
+    /// DDS file or folder to inspect
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    /// Print the details as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct CompletionsArgs {
+    /// Shell to complete in
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+#[derive(clap::Args)]
+pub struct DiffArgs {
+    /// Folder with the earlier textures
+    #[arg(long)]
+    pub old: PathBuf,
+
+    /// Folder with the current textures
+    #[arg(long)]
+    pub new: PathBuf,
+
+    /// Also write the report as JSON, usable later with convert --files-from
+    #[arg(long, value_name = "FILE")]
+    pub json: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct PreviewArgs {
+    /// DDS file, or a folder to page through
+    pub input: PathBuf,
+
+    /// Width of the preview in characters (default: the terminal width)
+    #[arg(long, value_name = "COLUMNS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub preview_width: Option<u32>,
+
+    /// Colour escape codes: truecolor, 256, or auto-detected from COLORTERM
+    #[arg(long, value_enum, default_value = "auto")]
+    pub colors: PreviewColors,
+
+    /// Textures per page when previewing a folder in a terminal
+    #[arg(long, value_name = "N", default_value = "4", value_parser = parse_page_size)]
+    pub page_size: usize,
+}
+
+fn parse_page_size(value: &str) -> Result<usize, String> {
+    value.parse().ok().filter(|size| *size > 0).ok_or_else(|| format!("'{}' is not a page size of 1 or more", value))
+}
+
+impl Cli {
+    /// Parses the command line, accepting the pre-subcommand flat flags
+    /// (`dds-converter -i in -o out`, `--animation-mode`, `--info`) by mapping
+    /// them onto the matching subcommand with a deprecation notice.
+    pub fn parse_with_legacy() -> Self {
+        let args: Vec<OsString> = std::env::args_os().collect();
+        match rewrite_legacy_args(&args) {
+            Some((subcommand, rewritten)) => {
+                eprintln!(
+                    "⚠️  Flags without a subcommand are deprecated; use `dds-converter {} ...` instead",
+                    subcommand
+                );
+                Cli::parse_or_exit(rewritten)
+            }
+            None => Cli::parse_or_exit(args),
+        }
+    }
+
+    /// Writes the completion script for `shell` to stdout, e.g.
+    /// `dds-converter completions bash > /etc/bash_completion.d/dds-converter`.
+    pub fn print_completions(shell: Shell) {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+    }
+
+    /// Like `parse_from`, but argument errors exit with 1 like any other
+    /// fatal error rather than clap's 2, which means "some files failed".
+    fn parse_or_exit(args: Vec<OsString>) -> Self {
+        let config = ConfigDefaults::locate(&args).unwrap_or_else(|e| {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        });
+        Cli::try_parse_args(args, config.as_ref()).unwrap_or_else(|e| {
+            let code = if e.use_stderr() { 1 } else { 0 };
+            let _ = e.print();
+            std::process::exit(code);
+        })
+    }
+
+    /// Parses `args` with `config` supplying the defaults, noting which
+    /// `convert` values came from the environment or the config file.
+    pub fn try_parse_args(args: Vec<OsString>, config: Option<&ConfigDefaults>) -> Result<Self, clap::Error> {
+        let command = match config {
+            Some(config) => config
+                .apply(Cli::command())
+                .map_err(|e| clap::Error::raw(ErrorKind::InvalidValue, format!("{:#}\n", e)))?,
+            None => Cli::command(),
+        };
+        let matches = command.try_get_matches_from(args)?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        if (cli.list_formats || cli.list_gpus) && cli.command.is_some() {
+            return Err(Cli::command().error(ErrorKind::ArgumentConflict, "--list-formats and --list-gpus can't be combined with a subcommand"));
+        }
+        // Checked here rather than by clap, so that an output from the
+        // config file counts
+        if let Some(Command::Convert(args)) = &cli.command {
+            if args.output.is_none() && args.output_zip.is_none() && args.compare.is_none() {
+                return Err(Cli::command().error(ErrorKind::MissingRequiredArgument, "convert needs --output, --output-zip or --compare"));
+            }
+        }
+        if let (Some(Command::Convert(args)), Some(("convert", convert))) = (&mut cli.command, matches.subcommand()) {
+            args.from_env = values_from_env(convert);
+            args.from_config = config.map(|config| values_from_config(convert, config)).unwrap_or_default();
+            args.texconv_path = cli.texconv_path.clone();
+        }
+        Ok(cli)
+    }
+}
+
+/// The options in `matches` that were filled from their environment
+/// variable, as (flag, variable).
+fn values_from_env(matches: &clap::ArgMatches) -> Vec<(String, String)> {
+    let command = Cli::command();
+    let Some(convert) = command.find_subcommand("convert") else {
+        return Vec::new();
+    };
+    convert
+        .get_arguments()
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::EnvVariable))
+        .filter_map(|arg| {
+            let variable = arg.get_env()?.to_string_lossy().into_owned();
+            Some((format!("--{}", arg.get_long()?), variable))
+        })
+        .collect()
+}
+
+/// The options in `matches` that were filled from `config`, as (flag, value).
+fn values_from_config(matches: &clap::ArgMatches, config: &ConfigDefaults) -> Vec<(String, String)> {
+    let command = Cli::command();
+    let Some(convert) = command.find_subcommand("convert") else {
+        return Vec::new();
+    };
+    convert
+        .get_arguments()
+        .filter(|arg| arg.get_long().is_some_and(|long| config.contains(long)))
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::DefaultValue))
+        .filter_map(|arg| {
+            let values: Vec<String> = matches.get_raw(arg.get_id().as_str())?.map(|value| value.to_string_lossy().into_owned()).collect();
+            Some((format!("--{}", arg.get_long()?), values.join(",")))
+        })
+        .collect()
+}
+
+/// Turns a flat legacy invocation into `<program> <subcommand> <args...>`,
+/// dropping flags the target subcommand doesn't have (the old CLI accepted
+/// and silently ignored them). Returns `None` for new-style invocations.
+fn rewrite_legacy_args(args: &[OsString]) -> Option<(&'static str, Vec<OsString>)> {
+    // Global options may come before the subcommand
+    let mut position = 1;
+    while let Some(token) = args.get(position).and_then(|arg| arg.to_str()) {
+        match token {
+            "--texconv-path" | "--config" => position += 2,
+            _ if token.starts_with("--texconv-path=") || token.starts_with("--config=") => position += 1,
+            _ => break,
+        }
+    }
+    let first = args.get(position)?.to_str()?;
+    let passthrough = ["-h", "--help", "-V", "--version", "--list-formats", "--list-gpus"];
+    if !first.starts_with('-') || passthrough.contains(&first) {
+        return None;
+    }
+
+    let has_flag = |flag: &str| args.iter().skip(1).any(|arg| arg == flag);
+    let (subcommand, mode_flag) = if has_flag("--animation-mode") {
+        ("animate", "--animation-mode")
+    } else if has_flag("--info") {
+        ("info", "--info")
+    } else {
+        ("convert", "")
+    };
+
+    let command = Cli::command();
+    let target = command.find_subcommand(subcommand)?;
+    let all_args: Vec<&clap::Arg> = command.get_subcommands().flat_map(|sub| sub.get_arguments()).collect();
+    let lookup = |token: &str| -> Option<&clap::Arg> {
+        let matches = |arg: &&clap::Arg| match token.strip_prefix("--") {
+            Some(long) => arg.get_long() == Some(long.split('=').next().unwrap_or(long)),
+            None => token.len() == 2 && arg.get_short() == token.chars().nth(1),
+        };
+        target.get_arguments().find(matches).or_else(|| all_args.iter().copied().find(matches))
+    };
+
+    let mut rewritten = vec![args[0].clone(), OsString::from(subcommand)];
+    let mut tokens = args.iter().skip(1);
+    while let Some(token) = tokens.next() {
+        let Some(text) = token.to_str() else {
+            rewritten.push(token.clone());
+            continue;
+        };
+        if text == mode_flag {
+            continue;
+        }
+
+        let Some(arg) = lookup(text).filter(|_| text.starts_with('-')) else {
+            rewritten.push(token.clone());
+            continue;
+        };
+        let takes_value = !matches!(arg.get_action(), ArgAction::SetTrue | ArgAction::SetFalse | ArgAction::Count)
+            && !text.contains('=');
+        let known_to_target = target.get_arguments().any(|own| own.get_id() == arg.get_id());
+
+        if known_to_target {
+            rewritten.push(token.clone());
+            if takes_value {
+                rewritten.extend(tokens.next().cloned());
+            }
+        } else if takes_value {
+            tokens.next();
+        }
+    }
+
+    Some((subcommand, rewritten))
+}
+
+pub struct FormatInfo {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub backend: &'static str,
+    pub quality_flags: &'static str,
+}
+
+pub const OUTPUT_FORMATS: &[FormatInfo] = &[
+    FormatInfo { name: "png", aliases: &[], backend: "texconv", quality_flags: "-" },
+    FormatInfo { name: "png8", aliases: &[], backend: "texconv, built-in palette", quality_flags: "--dither, --png8-fallback" },
+    FormatInfo { name: "jpg", aliases: &["jpeg"], backend: "texconv, built-in with --target-size", quality_flags: "--target-size, --jpeg-quality, --jpeg-subsampling" },
+    FormatInfo { name: "bmp", aliases: &[], backend: "texconv", quality_flags: "-" },
+    FormatInfo { name: "tga", aliases: &[], backend: "texconv", quality_flags: "-" },
+    FormatInfo { name: "tif", aliases: &["tiff"], backend: "texconv", quality_flags: "-" },
+    FormatInfo { name: "dds", aliases: &[], backend: "texconv", quality_flags: "-" },
+    FormatInfo { name: "hdr", aliases: &[], backend: "texconv", quality_flags: "-" },
+    FormatInfo { name: "ico", aliases: &[], backend: "texconv, built-in ICO writer", quality_flags: "--ico-sizes" },
+    FormatInfo { name: "webp", aliases: &[], backend: "texconv, built-in libwebp", quality_flags: "--target-size" },
+];
+
+/// Formats the tool encodes itself from a decoded PNG, which texconv can't
+/// write.
+pub fn built_in_format(format: &str) -> bool {
+    matches!(format, "png8" | "ico" | "webp")
+}
+
+/// File extension for outputs of `format`; `png8` files are PNGs.
+pub fn output_extension(format: &str) -> &str {
+    match format {
+        "png8" => "png",
+        other => other,
+    }
+}
+
+pub const ANIMATION_FORMATS: &[FormatInfo] = &[
+    FormatInfo { name: "webp", aliases: &[], backend: "ffmpeg, built-in libwebp", quality_flags: "-" },
+];
+
+/// Rejects texconv switches that would take over what the tool controls:
+/// where the output goes, its file type and which files are converted.
+fn parse_texconv_arg(value: &str) -> Result<String, String> {
+    let Some(switch) = value.strip_prefix(['-', '/']) else {
+        return Ok(value.to_string());
+    };
+    let name = switch.split(':').next().unwrap_or_default().to_lowercase();
+    match name.as_str() {
+        "o" => Err("texconv's output folder is set by the tool; use --output".to_string()),
+        "ft" => Err("texconv's output file type is set by the tool; use --format".to_string()),
+        "r" | "flist" => Err(format!("'{}' would make texconv convert other files than the one given", value)),
+        _ => Ok(value.to_string()),
+    }
+}
+
+fn parse_companion_extension(value: &str) -> Result<String, String> {
+    let ext = value.trim().trim_start_matches('.');
+    if ext.is_empty() || ext.contains(['/', '\\']) {
+        return Err(format!("'{}' is not a file extension", value));
+    }
+    Ok(ext.to_string())
+}
+
+pub fn parse_output_format(value: &str) -> Result<String, String> {
+    resolve_format(value, OUTPUT_FORMATS, "output format")
+}
+
+fn parse_animation_format(value: &str) -> Result<String, String> {
+    resolve_format(value, ANIMATION_FORMATS, "animation format")
+}
+
+/// Maps a user-supplied format (case-insensitive, aliases allowed) to its
+/// canonical name, or explains what was probably meant.
+fn resolve_format(value: &str, table: &[FormatInfo], kind: &str) -> Result<String, String> {
+    let wanted = value.trim().to_lowercase();
+
+    if let Some(info) = table.iter().find(|info| info.name == wanted || info.aliases.contains(&wanted.as_str())) {
+        return Ok(info.name.to_string());
+    }
+
+    let suggestion = table.iter()
+        .flat_map(|info| std::iter::once(info.name).chain(info.aliases.iter().copied()))
+        .map(|name| (edit_distance(&wanted, name), name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| format!(" (did you mean '{}'?)", name))
+        .unwrap_or_default();
+
+    Err(format!("unknown {} '{}'{}; run with --list-formats to see what is supported", kind, value, suggestion))
+}
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+pub fn print_format_table() {
+    println!("Output formats (--format):");
+    print_formats(OUTPUT_FORMATS);
+    println!();
+    println!("Animation formats (--animation-format):");
+    print_formats(ANIMATION_FORMATS);
+}
+
+fn print_formats(table: &[FormatInfo]) {
+    println!("  {:<8} {:<12} {:<26} Quality flags", "Format", "Aliases", "Backend");
+    for info in table {
+        let aliases = if info.aliases.is_empty() { "-".to_string() } else { info.aliases.join(", ") };
+        println!("  {:<8} {:<12} {:<26} {}", info.name, aliases, info.backend, info.quality_flags);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// The tests below set process-wide environment variables.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const VARIABLES: [&str; 5] = [
+        "DDS_CONVERTER_INPUT",
+        "DDS_CONVERTER_OUTPUT",
+        "DDS_CONVERTER_FORMAT",
+        "DDS_CONVERTER_CONCURRENCY",
+        "DDS_CONVERTER_TEXCONV_PATH",
+    ];
+
+    /// Parses `args` after `dds-converter` with `env` set and `config` as
+    /// the config file's contents.
+    fn parse(args: &[&str], env: &[(&str, &str)], config: Option<&str>) -> Result<Cli, clap::Error> {
+        let _guard = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for variable in VARIABLES {
+            std::env::remove_var(variable);
+        }
+        for (variable, value) in env {
+            std::env::set_var(variable, value);
+        }
+        let config = config.map(|text| ConfigDefaults::parse(Path::new("test.toml"), text).unwrap());
+        let argv = std::iter::once("dds-converter").chain(args.iter().copied()).map(OsString::from).collect();
+        let result = Cli::try_parse_args(argv, config.as_ref());
+        for (variable, _) in env {
+            std::env::remove_var(variable);
+        }
+        result
+    }
+
+    fn convert(cli: Cli) -> ConvertArgs {
+        match cli.command {
+            Some(Command::Convert(args)) => *args,
+            _ => panic!("not a convert invocation"),
+        }
+    }
+
+    #[test]
+    fn environment_fills_options_missing_from_the_command_line() {
+        let env = [
+            ("DDS_CONVERTER_INPUT", "in"),
+            ("DDS_CONVERTER_OUTPUT", "out"),
+            ("DDS_CONVERTER_FORMAT", "tga"),
+            ("DDS_CONVERTER_CONCURRENCY", "7"),
+        ];
+        let args = convert(parse(&["convert"], &env, None).unwrap());
+        assert_eq!(args.input, PathBuf::from("in"));
+        assert_eq!(args.output, Some(PathBuf::from("out")));
+        assert_eq!(args.format, "tga");
+        assert!(matches!(args.concurrency, Concurrency::Fixed(7)));
+        assert_eq!(args.from_env.len(), 4);
+        assert!(args.from_env.contains(&("--format".to_string(), "DDS_CONVERTER_FORMAT".to_string())));
+    }
+
+    #[test]
+    fn command_line_beats_environment() {
+        let env = [("DDS_CONVERTER_FORMAT", "tga")];
+        let args = convert(parse(&["convert", "-i", "in", "-o", "out", "--format", "bmp"], &env, None).unwrap());
+        assert_eq!(args.format, "bmp");
+        assert!(args.from_env.is_empty());
+    }
+
+    #[test]
+    fn environment_beats_config_file() {
+        let config = "input = \"cfg-in\"\noutput = \"cfg-out\"\nformat = \"jpg\"\nconcurrency = 3\n";
+        let args = convert(parse(&["convert"], &[("DDS_CONVERTER_FORMAT", "tga")], Some(config)).unwrap());
+        assert_eq!(args.format, "tga");
+        assert_eq!(args.input, PathBuf::from("cfg-in"));
+        assert_eq!(args.output, Some(PathBuf::from("cfg-out")));
+        assert!(matches!(args.concurrency, Concurrency::Fixed(3)));
+        assert_eq!(args.from_env, vec![("--format".to_string(), "DDS_CONVERTER_FORMAT".to_string())]);
+        assert!(args.from_config.contains(&("--input".to_string(), "cfg-in".to_string())));
+        assert!(!args.from_config.iter().any(|(flag, _)| flag == "--format"));
+    }
+
+    #[test]
+    fn config_file_beats_built_in_defaults() {
+        let args = convert(parse(&["convert", "-i", "in", "-o", "out"], &[], Some("format = \"bmp\"\nexclude = [\"a/**\", \"b/**\"]\n")).unwrap());
+        assert_eq!(args.format, "bmp");
+        assert_eq!(args.exclude.len(), 2);
+        let args = convert(parse(&["convert", "-i", "in", "-o", "out"], &[], None).unwrap());
+        assert_eq!(args.format, "png");
+    }
+
+    #[test]
+    fn texconv_path_follows_the_same_precedence() {
+        let config = Some("texconv-path = \"from-config.exe\"\n");
+        let args = convert(parse(&["convert", "-i", "in", "-o", "out"], &[], config).unwrap());
+        assert_eq!(args.texconv_path, Some(PathBuf::from("from-config.exe")));
+        let env = [("DDS_CONVERTER_TEXCONV_PATH", "from-env.exe")];
+        let args = convert(parse(&["convert", "-i", "in", "-o", "out"], &env, config).unwrap());
+        assert_eq!(args.texconv_path, Some(PathBuf::from("from-env.exe")));
+        let args = convert(parse(&["--texconv-path", "cli.exe", "convert", "-i", "in", "-o", "out"], &env, config).unwrap());
+        assert_eq!(args.texconv_path, Some(PathBuf::from("cli.exe")));
+    }
+
+    #[test]
+    fn unknown_config_keys_and_missing_output_are_errors() {
+        assert!(parse(&["convert", "-i", "in", "-o", "out"], &[], Some("no_such_option = 1\n")).is_err());
+        let error = parse(&["convert", "-i", "in"], &[], None).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn formats_resolve_case_insensitively_through_aliases() {
+        assert_eq!(parse_output_format("JPEG").unwrap(), "jpg");
+        assert_eq!(parse_output_format(" Tiff ").unwrap(), "tif");
+        assert_eq!(parse_output_format("png8").unwrap(), "png8");
+        assert_eq!(parse_animation_format("WebP").unwrap(), "webp");
+        // Still formats, but not animation ones
+        assert!(parse_animation_format("png").unwrap_err().starts_with("unknown animation format 'png'"));
+    }
+
+    #[test]
+    fn unknown_formats_suggest_the_closest_name() {
+        assert_eq!(
+            parse_output_format("webpp").unwrap_err(),
+            "unknown output format 'webpp' (did you mean 'webp'?); run with --list-formats to see what is supported"
+        );
+        assert!(parse_output_format("pgn").unwrap_err().contains("(did you mean 'png'?)"));
+        // One edit from jpg beats two from jpeg
+        assert!(parse_output_format("jpge").unwrap_err().contains("(did you mean 'jpg'?)"));
+        assert!(parse_output_format("tiffs").unwrap_err().contains("(did you mean 'tiff'?)"));
+        // Nothing within two edits
+        assert!(!parse_output_format("heic").unwrap_err().contains("did you mean"));
+    }
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("png", "png"), 0);
+        assert_eq!(edit_distance("", "tga"), 3);
+        assert_eq!(edit_distance("webpp", "webp"), 1);
+        assert_eq!(edit_distance("jpg", "jpeg"), 1);
+        assert_eq!(edit_distance("bmp", "bpm"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn jpg_lists_every_flag_that_sets_its_quality() {
+        let jpg = OUTPUT_FORMATS.iter().find(|info| info.name == "jpg").unwrap();
+        for flag in ["--target-size", "--jpeg-quality", "--jpeg-subsampling"] {
+            assert!(jpg.quality_flags.contains(flag), "{} missing", flag);
+        }
+    }
 }
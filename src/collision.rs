@@ -0,0 +1,230 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::utils::with_suffix;
+
+/// What to do when several inputs map to the same output path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CollisionPolicy {
+    /// Abort before converting anything and list every collision
+    Error,
+    /// Keep the first input in discovery order and skip the rest
+    Skip,
+    /// Append a short hash of each colliding input's relative path
+    SuffixHash,
+    /// Keep the first name and append _2, _3, ... to the rest in discovery order
+    SuffixCounter,
+}
+
+/// What to do when an output already exists from an earlier run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExistingAction {
+    /// Replace it
+    Overwrite,
+    /// Leave it and don't convert the file
+    Skip,
+    /// Write next to it with a _2, _3, ... suffix
+    Rename,
+    /// Fail the file
+    Error,
+}
+
+/// The first `_2`, `_3`, ... variant of `output` that doesn't exist yet.
+pub fn free_name(output: &Path) -> PathBuf {
+    (2..)
+        .map(|n: u32| with_suffix(output, &n.to_string()))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| output.to_path_buf())
+}
+
+/// Final output for one input, with why its name differs from the computed one.
+#[derive(Clone)]
+pub struct ResolvedOutput {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub note: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CollisionPlan {
+    pub outputs: Vec<ResolvedOutput>,
+    /// Inputs dropped by `skip`, with the input that kept the name.
+    pub skipped: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Resolves output name clashes among `planned` (input, output) pairs, which
+/// must be in discovery order. With `fold_case` (a case-insensitive target,
+/// see `case_insensitive_names`) names differing only in case clash too.
+pub fn resolve_collisions(
+    planned: Vec<(PathBuf, PathBuf)>,
+    input_dir: &Path,
+    fold_case: bool,
+    policy: CollisionPolicy,
+) -> Result<CollisionPlan> {
+    resolve_collisions_with(planned, input_dir, fold_case, |_, _| Ok(policy))
+}
+
+/// `resolve_collisions` with the policy picked per clash by `choose`, which
+/// gets the shared output and the inputs in discovery order (`--interactive`).
+pub fn resolve_collisions_with(
+    planned: Vec<(PathBuf, PathBuf)>,
+    input_dir: &Path,
+    fold_case: bool,
+    mut choose: impl FnMut(&Path, &[&PathBuf]) -> Result<CollisionPolicy>,
+) -> Result<CollisionPlan> {
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, (_, output)) in planned.iter().enumerate() {
+        groups.entry(collision_key(output, fold_case)).or_default().push(i);
+    }
+
+    let mut colliding: Vec<&Vec<usize>> = groups.values().filter(|members| members.len() > 1).collect();
+    colliding.sort();
+
+    if colliding.is_empty() {
+        let outputs = planned
+            .into_iter()
+            .map(|(input, output)| ResolvedOutput { input, output, note: None })
+            .collect();
+        return Ok(CollisionPlan { outputs, skipped: Vec::new() });
+    }
+
+    let mut policies = Vec::with_capacity(colliding.len());
+    for members in &colliding {
+        let inputs: Vec<&PathBuf> = members.iter().map(|&i| &planned[i].0).collect();
+        policies.push(choose(&planned[members[0]].1, &inputs)?);
+    }
+
+    let unresolved: Vec<&&Vec<usize>> = colliding
+        .iter()
+        .zip(&policies)
+        .filter(|(_, policy)| **policy == CollisionPolicy::Error)
+        .map(|(members, _)| members)
+        .collect();
+    if !unresolved.is_empty() {
+        let mut message = format!("{} output path(s) would be written by more than one input:", unresolved.len());
+        for members in &unresolved {
+            message.push_str(&format!("\n  {}", planned[members[0]].1.display()));
+            for &i in members.iter() {
+                message.push_str(&format!("\n    <- {}", planned[i].0.display()));
+            }
+        }
+        message.push_str("\nPick a strategy with --on-collision skip|suffix-hash|suffix-counter");
+        anyhow::bail!(message);
+    }
+
+    let mut taken: HashSet<String> = groups.keys().cloned().collect();
+    let mut renamed: HashMap<usize, (PathBuf, String)> = HashMap::new();
+    let mut skipped_at: HashMap<usize, usize> = HashMap::new();
+
+    for (members, &policy) in colliding.iter().zip(&policies) {
+        let first = members[0];
+        let first_input = &planned[first].0;
+
+        for (position, &i) in members.iter().enumerate() {
+            let (input, output) = &planned[i];
+            match policy {
+                CollisionPolicy::Skip if position > 0 => {
+                    skipped_at.insert(i, first);
+                }
+                CollisionPolicy::SuffixCounter if position > 0 => {
+                    let mut counter = position + 1;
+                    let new_output = loop {
+                        let candidate = with_suffix(output, &counter.to_string());
+                        if taken.insert(collision_key(&candidate, fold_case)) {
+                            break candidate;
+                        }
+                        counter += 1;
+                    };
+                    let note = format!("suffixed: collides with {}", first_input.display());
+                    renamed.insert(i, (new_output, note));
+                }
+                CollisionPolicy::SuffixHash => {
+                    let relative = input.strip_prefix(input_dir).unwrap_or(input);
+                    let new_output = with_suffix(output, &path_hash(relative));
+                    taken.insert(collision_key(&new_output, fold_case));
+                    let note = format!("suffixed with a hash of its path: {} inputs share this name", members.len());
+                    renamed.insert(i, (new_output, note));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut plan = CollisionPlan::default();
+    for (i, (input, output)) in planned.iter().enumerate() {
+        if let Some(&first) = skipped_at.get(&i) {
+            plan.skipped.push((input.clone(), planned[first].0.clone()));
+            continue;
+        }
+        let (output, note) = match renamed.remove(&i) {
+            Some((new_output, note)) => (new_output, Some(note)),
+            None => (output.clone(), None),
+        };
+        plan.outputs.push(ResolvedOutput { input: input.clone(), output, note });
+    }
+
+    Ok(plan)
+}
+
+fn collision_key(path: &Path, fold_case: bool) -> String {
+    let key = path.to_string_lossy();
+    if fold_case { key.to_lowercase() } else { key.into_owned() }
+}
+
+/// First 8 hex digits of the SHA-256 of `relative`, with `/` separators so
+/// the suffix is the same on every platform.
+fn path_hash(relative: &Path) -> String {
+    let normalized = relative.to_string_lossy().replace('\\', "/");
+    let digest = Sha256::digest(normalized.as_bytes());
+    digest.iter().take(4).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(outputs: &[&str]) -> Vec<(PathBuf, PathBuf)> {
+        outputs
+            .iter()
+            .enumerate()
+            .map(|(index, output)| (PathBuf::from(format!("in/{}.dds", index)), PathBuf::from(output)))
+            .collect()
+    }
+
+    #[test]
+    fn names_differing_in_case_only_clash_on_case_insensitive_targets() {
+        let outputs = planned(&["out/Rock.png", "out/rock.png"]);
+        let sensitive = resolve_collisions(outputs.clone(), Path::new("in"), false, CollisionPolicy::Error).unwrap();
+        assert_eq!(sensitive.outputs.len(), 2);
+        assert!(sensitive.outputs.iter().all(|resolved| resolved.note.is_none()));
+
+        assert!(resolve_collisions(outputs.clone(), Path::new("in"), true, CollisionPolicy::Error).is_err());
+        let insensitive = resolve_collisions(outputs, Path::new("in"), true, CollisionPolicy::Skip).unwrap();
+        assert_eq!(insensitive.outputs.len(), 1);
+        assert_eq!(insensitive.skipped, [(PathBuf::from("in/1.dds"), PathBuf::from("in/0.dds"))]);
+    }
+
+    #[test]
+    fn suffixes_depend_on_the_input_path_or_discovery_order() {
+        let outputs = vec![
+            (PathBuf::from("in/a/b/c/rock.dds"), PathBuf::from("out/rock.png")),
+            (PathBuf::from("in/x/y/z/rock.dds"), PathBuf::from("out/rock.png")),
+            (PathBuf::from("in/rock_2.dds"), PathBuf::from("out/rock_2.png")),
+        ];
+        let hashed = resolve_collisions(outputs.clone(), Path::new("in"), false, CollisionPolicy::SuffixHash).unwrap();
+        let names: Vec<PathBuf> = hashed.outputs.iter().map(|resolved| resolved.output.clone()).collect();
+        assert_eq!(names[0], PathBuf::from(format!("out/rock_{}.png", path_hash(Path::new("a/b/c/rock.dds")))));
+        assert_eq!(names[1], PathBuf::from(format!("out/rock_{}.png", path_hash(Path::new("x/y/z/rock.dds")))));
+        assert_ne!(names[0], names[1]);
+        // Backslashes hash like slashes, so Windows runs pick the same names
+        assert_eq!(path_hash(Path::new("a\\b\\c\\rock.dds")), path_hash(Path::new("a/b/c/rock.dds")));
+
+        // The counter steps past names another input already has
+        let counted = resolve_collisions(outputs, Path::new("in"), false, CollisionPolicy::SuffixCounter).unwrap();
+        let names: Vec<&Path> = counted.outputs.iter().map(|resolved| resolved.output.as_path()).collect();
+        assert_eq!(names, [Path::new("out/rock.png"), Path::new("out/rock_3.png"), Path::new("out/rock_2.png")]);
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::console::status;
+
+/// How a freshly converted file relates to its counterpart in the
+/// `--compare` tree.
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Identical,
+    /// Bytes differ, but the pixels are within `--compare-tolerance`.
+    WithinTolerance { rmse: f64 },
+    /// `rmse` is known when a tolerance was given and both images decoded
+    /// to the same size.
+    Changed { rmse: Option<f64> },
+    /// No existing file at that path.
+    Added,
+}
+
+/// `--compare`: the run converts into `staging_root` and every result is
+/// checked against the same relative path under `existing_root`, which is
+/// only ever read.
+pub struct CompareTarget {
+    pub staging_root: PathBuf,
+    pub existing_root: PathBuf,
+    pub tolerance: Option<f64>,
+}
+
+impl CompareTarget {
+    /// Compares the converted file at `output` (under `staging_root`) and
+    /// returns its path relative to the tree root with the result.
+    pub fn check(&self, output: &Path) -> Result<(PathBuf, Comparison)> {
+        let relative = output.strip_prefix(&self.staging_root).unwrap_or(output).to_path_buf();
+        let existing = self.existing_root.join(&relative);
+        if !existing.is_file() {
+            return Ok((relative, Comparison::Added));
+        }
+
+        let new_bytes = std::fs::read(output)
+            .with_context(|| format!("Failed to read {}", output.display()))?;
+        let old_bytes = std::fs::read(&existing)
+            .with_context(|| format!("Failed to read {}", existing.display()))?;
+        if new_bytes == old_bytes {
+            return Ok((relative, Comparison::Identical));
+        }
+
+        let Some(tolerance) = self.tolerance else {
+            return Ok((relative, Comparison::Changed { rmse: None }));
+        };
+        let comparison = match rmse(&new_bytes, &old_bytes) {
+            Some(rmse) if rmse <= tolerance => Comparison::WithinTolerance { rmse },
+            rmse => Comparison::Changed { rmse },
+        };
+        Ok((relative, comparison))
+    }
+
+    /// Files of `format` in the existing tree that the run didn't produce.
+    /// `planned` holds every output path of the run (under `staging_root`).
+    pub fn find_missing(&self, planned: &[PathBuf], format: &str) -> Vec<PathBuf> {
+        // Windows paths compare case-insensitively
+        let key = |path: &Path| path.to_string_lossy().replace('\\', "/").to_lowercase();
+        let planned: HashSet<String> = planned
+            .iter()
+            .map(|output| key(output.strip_prefix(&self.staging_root).unwrap_or(output)))
+            .collect();
+
+        WalkDir::new(&self.existing_root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| {
+                entry.path()
+                    .extension()
+                    .map(|ext| ext.eq_ignore_ascii_case(format))
+                    .unwrap_or(false)
+            })
+            .filter_map(|entry| entry.path().strip_prefix(&self.existing_root).ok().map(Path::to_path_buf))
+            .filter(|relative| !planned.contains(&key(relative)))
+            .collect()
+    }
+}
+
+/// Root-mean-square difference over the RGBA channels (0-255), or `None`
+/// when either file doesn't decode or their sizes differ.
+fn rmse(a: &[u8], b: &[u8]) -> Option<f64> {
+    let a = image::load_from_memory(a).ok()?.to_rgba8();
+    let b = image::load_from_memory(b).ok()?.to_rgba8();
+    if a.dimensions() != b.dimensions() {
+        return None;
+    }
+
+    let sum: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+        .sum();
+    Some((sum / a.as_raw().len().max(1) as f64).sqrt())
+}
+
+/// What `--compare` found over the whole run.
+#[derive(Default)]
+pub struct CompareReport {
+    pub results: Vec<(PathBuf, Comparison)>,
+    pub missing: Vec<PathBuf>,
+}
+
+impl CompareReport {
+    pub fn differences(&self) -> usize {
+        let changed = self.results
+            .iter()
+            .filter(|(_, comparison)| matches!(comparison, Comparison::Changed { .. } | Comparison::Added))
+            .count();
+        changed + self.missing.len()
+    }
+
+    pub fn print(&self, existing_root: &Path, verbose: bool) {
+        let count = |wanted: fn(&Comparison) -> bool| self.results.iter().filter(|(_, c)| wanted(c)).count();
+        status!(
+            "🔎 Compared against {}: {} identical, {} within tolerance, {} changed, {} added, {} missing",
+            existing_root.display(),
+            count(|c| matches!(c, Comparison::Identical)),
+            count(|c| matches!(c, Comparison::WithinTolerance { .. })),
+            count(|c| matches!(c, Comparison::Changed { .. })),
+            count(|c| matches!(c, Comparison::Added)),
+            self.missing.len(),
+        );
+
+        for (path, comparison) in &self.results {
+            match comparison {
+                Comparison::Changed { rmse: Some(rmse) } => status!("     changed  {} (rmse {:.3})", path.display(), rmse),
+                Comparison::Changed { rmse: None } => status!("     changed  {}", path.display()),
+                Comparison::Added => status!("     added    {}", path.display()),
+                Comparison::WithinTolerance { rmse } if verbose => {
+                    status!("     ~        {} (rmse {:.3})", path.display(), rmse)
+                }
+                _ => {}
+            }
+        }
+        for path in &self.missing {
+            status!("     missing  {}", path.display());
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::Result;
+use image::{imageops::FilterType, RgbaImage};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::tint::{parse_tint, Tint};
+use crate::watermark::{blend_over, premultiplied};
+
+/// One side of `--composite`: a file name pattern with one `*` for the
+/// shared stem, and an optional tint.
+#[derive(Debug, Clone)]
+pub struct CompositeLayer {
+    pub pattern: String,
+    regex: Regex,
+    pub tint: Option<Tint>,
+}
+
+/// `--composite "*_frame.dds+*_glyph.dds@#ffb000"`
+#[derive(Debug, Clone)]
+pub struct CompositeSpec {
+    pub base: CompositeLayer,
+    pub overlay: CompositeLayer,
+}
+
+pub fn parse_composite_spec(value: &str) -> Result<CompositeSpec, String> {
+    let (base, overlay) = value
+        .split_once('+')
+        .ok_or_else(|| format!("'{}' should be BASE+OVERLAY (e.g. *_frame.dds+*_glyph.dds)", value))?;
+    let base = parse_layer(base.trim())?;
+    let overlay = parse_layer(overlay.trim())?;
+    if base.pattern.eq_ignore_ascii_case(&overlay.pattern) {
+        return Err("the base and overlay patterns must differ".to_string());
+    }
+    Ok(CompositeSpec { base, overlay })
+}
+
+/// `PATTERN[@#RRGGBB]`
+fn parse_layer(layer: &str) -> Result<CompositeLayer, String> {
+    let (pattern, tint) = match layer.split_once('@') {
+        Some((pattern, tint)) => (pattern.trim(), Some(parse_tint(tint)?)),
+        None => (layer, None),
+    };
+    if pattern.matches('*').count() != 1 {
+        return Err(format!("'{}' needs exactly one '*' standing for the shared name (e.g. *_glyph.dds)", pattern));
+    }
+    if pattern.contains(['/', '\\']) {
+        return Err(format!("'{}' must be a file name pattern, without folders", pattern));
+    }
+    let (before, after) = pattern.split_once('*').unwrap_or_default();
+    let regex = Regex::new(&format!("(?i)^{}(.+){}$", regex::escape(before), regex::escape(after)))
+        .map_err(|e| e.to_string())?;
+    Ok(CompositeLayer { pattern: pattern.to_string(), regex, tint })
+}
+
+impl CompositeLayer {
+    fn stem(&self, file: &Path) -> Option<String> {
+        let name = file.file_name()?.to_string_lossy();
+        self.regex.captures(&name).map(|captures| captures[1].to_string())
+    }
+
+    fn path_for(&self, next_to: &Path, stem: &str) -> PathBuf {
+        next_to.with_file_name(self.pattern.replacen('*', stem, 1))
+    }
+}
+
+/// How the discovered files pair up under a `--composite` spec.
+#[derive(Debug, Default)]
+pub struct CompositePairs {
+    /// Base and overlay, for each base whose overlay exists.
+    pub pairs: Vec<(PathBuf, PathBuf)>,
+    pub bases_without_overlay: Vec<PathBuf>,
+    pub overlays_without_base: Vec<PathBuf>,
+}
+
+impl CompositeSpec {
+    /// The overlay that goes on `base`, if `base` matches the base pattern.
+    pub fn overlay_for(&self, base: &Path) -> Option<PathBuf> {
+        let stem = self.base.stem(base)?;
+        Some(self.overlay.path_for(base, &stem))
+    }
+
+    /// Pairs the bases among `files` with their overlays, which are looked
+    /// for on disk next to each base.
+    pub fn pair(&self, files: &[PathBuf]) -> CompositePairs {
+        let mut result = CompositePairs::default();
+        for file in files {
+            // Overlays can match the base pattern too (*.dds+*_glyph.dds);
+            // they count as overlays
+            if let Some(stem) = self.overlay.stem(file) {
+                if !self.base.path_for(file, &stem).is_file() {
+                    result.overlays_without_base.push(file.clone());
+                }
+                continue;
+            }
+            match self.overlay_for(file) {
+                Some(overlay) if overlay.is_file() => result.pairs.push((file.clone(), overlay)),
+                Some(_) => result.bases_without_overlay.push(file.clone()),
+                None => {}
+            }
+        }
+        result
+    }
+}
+
+/// Lays `overlay` over `base`. An overlay of a different size but the same
+/// aspect ratio is resized to the base (in premultiplied alpha), with a
+/// warning; any other mismatch is an error. Returns the image and the
+/// warning, if any.
+pub fn composite_layers(base: &RgbaImage, overlay: &RgbaImage) -> Result<(RgbaImage, Option<String>)> {
+    let (width, height) = base.dimensions();
+    let (w, h) = overlay.dimensions();
+    let mut layer = premultiplied(overlay);
+    let mut warning = None;
+    if (w, h) != (width, height) {
+        if w as u64 * height as u64 != h as u64 * width as u64 {
+            anyhow::bail!("the overlay is {}x{}, which doesn't fit the {}x{} base", w, h, width, height);
+        }
+        warning = Some(format!("overlay is {}x{}; resized to {}x{}", w, h, width, height));
+        layer = image::imageops::resize(&layer, width, height, FilterType::Triangle);
+    }
+
+    let mut composited = base.clone();
+    for (pixel, source) in composited.pixels_mut().zip(layer.pixels()) {
+        blend_over(pixel, source.0);
+    }
+    Ok((composited, warning))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn overlay_is_blended_premultiplied_over_the_base() {
+        let base = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { Rgba([0, 0, 255, 255]) } else { Rgba([0, 0, 255, 128]) });
+        let overlay = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 128]));
+        let (composited, warning) = composite_layers(&base, &overlay).unwrap();
+        assert!(warning.is_none());
+        assert_eq!(composited.get_pixel(0, 0).0, [128, 0, 127, 255]);
+        // Alpha 0.752; half the red over a quarter of the blue
+        assert_eq!(composited.get_pixel(1, 0).0, [170, 0, 85, 192]);
+    }
+
+    #[test]
+    fn resized_overlay_keeps_its_colour_at_soft_edges() {
+        // Opaque red beside fully transparent black: straight-alpha resizing
+        // would pull the soft edge towards black
+        let overlay = RgbaImage::from_fn(2, 2, |x, _| if x == 0 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 0, 0]) });
+        let base = RgbaImage::new(8, 8);
+        let (composited, warning) = composite_layers(&base, &overlay).unwrap();
+        assert_eq!(warning.as_deref(), Some("overlay is 2x2; resized to 8x8"));
+        let soft: Vec<_> = composited.pixels().filter(|pixel| pixel[3] > 0 && pixel[3] < 255).collect();
+        assert!(!soft.is_empty());
+        assert!(soft.iter().all(|pixel| pixel[0] >= 254 && pixel[1] == 0 && pixel[2] == 0), "{:?}", soft);
+    }
+
+    #[test]
+    fn overlay_of_another_shape_is_refused() {
+        let error = composite_layers(&RgbaImage::new(4, 4), &RgbaImage::new(4, 2)).unwrap_err();
+        assert_eq!(error.to_string(), "the overlay is 4x2, which doesn't fit the 4x4 base");
+    }
+
+    #[test]
+    fn files_pair_by_stem_and_strays_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["sword_frame.dds", "sword_glyph.dds", "shield_frame.dds", "bow_glyph.dds"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let files: Vec<PathBuf> = ["sword_frame.dds", "sword_glyph.dds", "shield_frame.dds", "bow_glyph.dds"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        let spec = parse_composite_spec("*_frame.dds + *_glyph.dds@#ffb000").unwrap();
+        assert!(spec.overlay.tint.is_some());
+        let pairs = spec.pair(&files);
+        assert_eq!(pairs.pairs, [(files[0].clone(), files[1].clone())]);
+        assert_eq!(pairs.bases_without_overlay, [files[2].clone()]);
+        assert_eq!(pairs.overlays_without_base, [files[3].clone()]);
+
+        assert!(parse_composite_spec("*_frame.dds").unwrap_err().contains("BASE+OVERLAY"));
+        assert!(parse_composite_spec("*.dds+glyph.dds").unwrap_err().contains("exactly one '*'"));
+        assert!(parse_composite_spec("*.DDS+*.dds").unwrap_err().contains("must differ"));
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Looked for in the working directory when neither `--config` nor
+/// `DDS_CONVERTER_CONFIG` names a file.
+pub const DEFAULT_CONFIG_FILE: &str = "dds-converter.toml";
+
+/// Option defaults from a TOML config file: the long flag names of
+/// `convert` (with `-` or `_`) and `texconv-path`, e.g.
+/// `format = "webp"` or `concurrency = 8`. Command-line flags and
+/// environment variables override them.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigDefaults {
+    pub path: PathBuf,
+    /// (long flag name, values).
+    pub values: Vec<(String, Vec<String>)>,
+}
+
+impl ConfigDefaults {
+    /// The config for an invocation: `--config FILE` from `args`, else
+    /// `DDS_CONVERTER_CONFIG`, else `dds-converter.toml` if there is one.
+    pub fn locate(args: &[OsString]) -> Result<Option<Self>> {
+        let path = match config_arg(args).or_else(|| std::env::var_os("DDS_CONVERTER_CONFIG").map(PathBuf::from)) {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => PathBuf::from(DEFAULT_CONFIG_FILE),
+            None => return Ok(None),
+        };
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&path, &text).map(Some)
+    }
+
+    pub fn parse(path: &Path, text: &str) -> Result<Self> {
+        let table: toml::value::Table = toml::from_str(text).with_context(|| format!("{} is not valid TOML", path.display()))?;
+        let mut values = Vec::new();
+        for (key, value) in table {
+            let values_of = |value: &toml::Value| -> Result<String> {
+                match value {
+                    toml::Value::String(text) => Ok(text.clone()),
+                    toml::Value::Integer(number) => Ok(number.to_string()),
+                    toml::Value::Float(number) => Ok(number.to_string()),
+                    toml::Value::Boolean(flag) => Ok(flag.to_string()),
+                    _ => anyhow::bail!("{}: `{}` must be a string, number, boolean or a list of those", path.display(), key),
+                }
+            };
+            let list = match &value {
+                toml::Value::Array(items) => items.iter().map(values_of).collect::<Result<Vec<_>>>()?,
+                other => vec![values_of(other)?],
+            };
+            values.push((key.replace('_', "-"), list));
+        }
+        Ok(ConfigDefaults { path: path.to_path_buf(), values })
+    }
+
+    /// `command` with the config values as the defaults of their options,
+    /// so clap's own order applies: command line, then environment, then
+    /// these. Options given here no longer have to be passed.
+    pub fn apply(&self, mut command: clap::Command) -> Result<clap::Command> {
+        for (key, values) in &self.values {
+            if key == "texconv-path" {
+                command = command.mut_arg("texconv_path", |arg| arg.default_values(values.clone()));
+                continue;
+            }
+            let id = command
+                .find_subcommand("convert")
+                .and_then(|convert| convert.get_arguments().find(|arg| arg.get_long() == Some(key.as_str())))
+                .map(|arg| arg.get_id().clone())
+                .with_context(|| format!("{}: `{}` is not a convert option", self.path.display(), key))?;
+            command = command.mut_subcommand("convert", |convert| {
+                convert.mut_arg(id, |arg| arg.default_values(values.clone()).required(false))
+            });
+        }
+        Ok(command)
+    }
+
+    pub fn contains(&self, long: &str) -> bool {
+        self.values.iter().any(|(key, _)| key == long)
+    }
+}
+
+/// The value of a `--config FILE` / `--config=FILE` argument.
+fn config_arg(args: &[OsString]) -> Option<PathBuf> {
+    let mut tokens = args.iter().skip(1);
+    while let Some(token) = tokens.next() {
+        let text = token.to_string_lossy();
+        if text == "--config" {
+            return tokens.next().map(PathBuf::from);
+        }
+        if let Some(path) = text.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
@@ -0,0 +1,352 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::run_log::RunLog;
+
+/// `--quiet`: status output is dropped and only errors are printed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// `--dry-run-json`: stdout carries only the JSON, so the lines that still
+/// print under `--quiet` go to stderr.
+static JSON_STDOUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json_stdout(json: bool) {
+    JSON_STDOUT.store(json, Ordering::Relaxed);
+}
+
+pub fn json_stdout() -> bool {
+    JSON_STDOUT.load(Ordering::Relaxed)
+}
+
+/// `println!` for status output, which `--quiet` drops.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::console::quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use status;
+
+/// `println!` for problems, which print even under `--quiet` (to stderr
+/// under `--dry-run-json`).
+macro_rules! problem {
+    ($($arg:tt)*) => {
+        if $crate::console::json_stdout() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use problem;
+
+/// Printing handle shared by the conversion and animation paths.
+///
+/// While a progress bar is on screen every line has to go through
+/// `ProgressBar::println` (or `MultiProgress::println` under `--show-active`),
+/// otherwise the bars get torn apart. When the bars are hidden (non-TTY) or
+/// absent, messages go straight to stdout.
+#[derive(Clone)]
+pub struct Console {
+    progress: Option<ProgressBar>,
+    multi: Option<MultiProgress>,
+}
+
+impl Console {
+    pub fn plain() -> Self {
+        Self { progress: None, multi: None }
+    }
+
+    pub fn with_progress(progress: ProgressBar) -> Self {
+        Self { progress: Some(progress), multi: None }
+    }
+
+    /// Console for `--show-active`: `progress` must already be part of `multi`.
+    pub fn with_multi(multi: MultiProgress, progress: ProgressBar) -> Self {
+        Self { progress: Some(progress), multi: Some(multi) }
+    }
+
+    /// Prints a status line; `--quiet` drops it.
+    pub fn println<S: AsRef<str>>(&self, msg: S) {
+        if !quiet() {
+            self.error(msg);
+        }
+    }
+
+    /// Prints an error line, even under `--quiet`.
+    pub fn error<S: AsRef<str>>(&self, msg: S) {
+        match (&self.multi, &self.progress) {
+            (Some(multi), _) if !multi.is_hidden() => {
+                let _ = multi.println(msg.as_ref());
+            }
+            (None, Some(progress)) if !progress.is_hidden() => progress.println(msg.as_ref()),
+            _ if json_stdout() => eprintln!("{}", msg.as_ref()),
+            _ => println!("{}", msg.as_ref()),
+        }
+    }
+
+    /// Runs `f` with the progress bars cleared from the screen, for output
+    /// that can't go through `println` (prompts).
+    pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        match (&self.multi, &self.progress) {
+            (Some(multi), _) => multi.suspend(f),
+            (None, Some(progress)) => progress.suspend(f),
+            _ => f(),
+        }
+    }
+
+    /// Shows `label` in the progress message; with `--show-active` it also
+    /// gets its own line with an elapsed timer until the returned guard drops.
+    pub fn start_file(&self, label: &str) -> ActiveLine {
+        if let Some(progress) = &self.progress {
+            progress.set_message(label.to_string());
+        }
+
+        let Some(multi) = &self.multi else {
+            return ActiveLine { bar: None, multi: None };
+        };
+
+        let bar = multi.add(ProgressBar::new_spinner());
+        bar.set_style(
+            ProgressStyle::default_spinner()
+                .template("  {spinner} {elapsed:>4} {wide_msg}")
+                .unwrap()
+        );
+        bar.set_message(label.to_string());
+        bar.enable_steady_tick(Duration::from_millis(250));
+
+        ActiveLine { bar: Some(bar), multi: Some(multi.clone()) }
+    }
+}
+
+/// An in-flight file's line under `--show-active`, removed when dropped.
+pub struct ActiveLine {
+    bar: Option<ProgressBar>,
+    multi: Option<MultiProgress>,
+}
+
+impl Drop for ActiveLine {
+    fn drop(&mut self) {
+        if let (Some(bar), Some(multi)) = (&self.bar, &self.multi) {
+            bar.finish_and_clear();
+            multi.remove(bar);
+        }
+    }
+}
+
+/// Stand-in for the progress bar when it is hidden (`--no-progress`, or
+/// stdout isn't a terminal): every `interval` until aborted, prints a plain
+/// status line if `print` is set and writes a `heartbeat` record to `log`,
+/// reading position and rate from the bar.
+pub fn spawn_heartbeat(
+    progress: ProgressBar,
+    errors: Arc<AtomicUsize>,
+    interval: Duration,
+    print: bool,
+    log: Option<Arc<RunLog>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let position = progress.position();
+            let length = progress.length().unwrap_or(0);
+            let errors = errors.load(Ordering::Relaxed);
+            if print {
+                println!(
+                    "processed {}/{}, {} errors, {:.0} files/s, ETA {}",
+                    position,
+                    length,
+                    errors,
+                    progress.per_sec(),
+                    format_eta(progress.eta())
+                );
+            }
+            if let Some(log) = &log {
+                log.heartbeat(position, length, errors, progress.per_sec(), progress.eta());
+            }
+        }
+    })
+}
+
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs.div_ceil(60)),
+        _ => format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::{ProgressDrawTarget, TermLike};
+    use std::sync::Mutex;
+
+    /// Just enough of a terminal for indicatif: lines of text, a cursor,
+    /// and the moves and clears it draws with.
+    #[derive(Debug, Default)]
+    struct Screen {
+        rows: Vec<String>,
+        row: usize,
+        col: usize,
+    }
+
+    impl Screen {
+        fn write(&mut self, text: &str) {
+            while self.rows.len() <= self.row {
+                self.rows.push(String::new());
+            }
+            let line = &mut self.rows[self.row];
+            let mut chars: Vec<char> = line.chars().collect();
+            for c in text.chars() {
+                if self.col < chars.len() {
+                    chars[self.col] = c;
+                } else {
+                    chars.push(c);
+                }
+                self.col += 1;
+            }
+            *line = chars.into_iter().collect();
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct Terminal(Arc<Mutex<Screen>>);
+
+    impl TermLike for Terminal {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, n: usize) -> std::io::Result<()> {
+            let mut screen = self.0.lock().unwrap();
+            screen.row = screen.row.saturating_sub(n);
+            Ok(())
+        }
+        fn move_cursor_down(&self, n: usize) -> std::io::Result<()> {
+            self.0.lock().unwrap().row += n;
+            Ok(())
+        }
+        fn move_cursor_right(&self, n: usize) -> std::io::Result<()> {
+            self.0.lock().unwrap().col += n;
+            Ok(())
+        }
+        fn move_cursor_left(&self, n: usize) -> std::io::Result<()> {
+            let mut screen = self.0.lock().unwrap();
+            screen.col = screen.col.saturating_sub(n);
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> std::io::Result<()> {
+            let mut screen = self.0.lock().unwrap();
+            screen.write(s);
+            screen.row += 1;
+            screen.col = 0;
+            Ok(())
+        }
+        fn write_str(&self, s: &str) -> std::io::Result<()> {
+            self.0.lock().unwrap().write(s);
+            Ok(())
+        }
+        fn clear_line(&self) -> std::io::Result<()> {
+            let mut screen = self.0.lock().unwrap();
+            let row = screen.row;
+            if let Some(line) = screen.rows.get_mut(row) {
+                line.clear();
+            }
+            screen.col = 0;
+            Ok(())
+        }
+        fn flush(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A visible bar drawing to a fresh `Terminal`.
+    fn visible_bar(terminal: &Terminal, length: u64) -> ProgressBar {
+        let bar = ProgressBar::with_draw_target(Some(length), ProgressDrawTarget::term_like(Box::new(terminal.clone())));
+        bar.set_style(ProgressStyle::default_bar().template("[{pos}/{len}] {msg}").unwrap());
+        bar
+    }
+
+    /// Prints `lines` lines from each of four threads while the bar moves,
+    /// then finishes it and returns what is on screen, the bar's line last.
+    fn print_over(console: &Console, bar: &ProgressBar, terminal: &Terminal, lines: usize) -> Vec<String> {
+        std::thread::scope(|scope| {
+            for worker in 0..4 {
+                let console = console.clone();
+                scope.spawn(move || {
+                    for line in 0..lines {
+                        let _active = console.start_file(&format!("file {}-{}", worker, line));
+                        if line % 2 == 0 {
+                            console.println(format!("✅ Done: worker {} line {}", worker, line));
+                        } else {
+                            console.error(format!("❌ Error in worker {} line {}", worker, line));
+                        }
+                        bar.inc(1);
+                    }
+                });
+            }
+        });
+        // Draws are rate limited; finishing draws the final position
+        bar.finish();
+        let screen = terminal.0.lock().unwrap();
+        screen.rows.iter().filter(|row| !row.is_empty()).cloned().collect()
+    }
+
+    /// Every printed line came out whole, once, and each worker's in order.
+    fn assert_whole_lines(printed: &[String], lines: usize) {
+        assert_eq!(printed.len(), 4 * lines, "{:#?}", printed);
+        for worker in 0..4 {
+            let own: Vec<&String> = printed.iter().filter(|line| line.contains(&format!("worker {} ", worker))).collect();
+            let expected: Vec<String> = (0..lines)
+                .map(|line| match line % 2 {
+                    0 => format!("✅ Done: worker {} line {}", worker, line),
+                    _ => format!("❌ Error in worker {} line {}", worker, line),
+                })
+                .collect();
+            assert_eq!(own, expected.iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn lines_print_whole_above_an_active_bar() {
+        let terminal = Terminal::default();
+        let bar = visible_bar(&terminal, 80);
+        assert!(!bar.is_hidden());
+        let console = Console::with_progress(bar.clone());
+
+        let screen = print_over(&console, &bar, &terminal, 20);
+        let (last, printed) = screen.split_last().unwrap();
+        assert!(last.starts_with("[80/80] file "), "{:?}", last);
+        assert_whole_lines(printed, 20);
+    }
+
+    #[test]
+    fn lines_print_whole_above_show_active_bars() {
+        let terminal = Terminal::default();
+        let multi = MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(terminal.clone())));
+        let bar = multi.add(ProgressBar::new(40));
+        bar.set_style(ProgressStyle::default_bar().template("[{pos}/{len}] {msg}").unwrap());
+        let console = Console::with_multi(multi, bar.clone());
+
+        let screen = print_over(&console, &bar, &terminal, 10);
+        // Each file's spinner line is gone once it finished, leaving the bar
+        let (last, printed) = screen.split_last().unwrap();
+        assert!(last.starts_with("[40/40] file "), "{:?}", last);
+        assert_whole_lines(printed, 10);
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{entry_name, read_entry};
+use crate::cli::ConvertArgs;
+use crate::utils::texture_stem;
+
+/// `dir/name.png` -> `dir/name.<hash>.png`, where `<hash>` is the first
+/// `digits` hex digits of the SHA-256 of `contents` (the final encoded file).
+pub fn hashed_path(output: &Path, contents: &Path, digits: usize) -> Result<PathBuf> {
+    let hash = content_digest(contents)?;
+    let hash = &hash[..digits.min(hash.len())];
+
+    let file_name = match output.extension() {
+        Some(extension) => format!("{}.{}.{}", texture_stem(output), hash, extension.to_string_lossy()),
+        None => format!("{}.{}", texture_stem(output), hash),
+    };
+    Ok(output.with_file_name(file_name))
+}
+
+/// Start of the stand-in `NameTemplate` renders for `{contenthash:N}`
+/// (`{contenthash-N}`, since `:` isn't allowed in Windows file names); it
+/// is replaced once the output's bytes are known.
+pub const HASH_PLACEHOLDER: &str = "{contenthash-";
+
+/// `output` with every `{contenthash-N}` in its file name replaced by the
+/// first `N` hex digits of the SHA-256 of `contents`.
+pub fn fill_hash_placeholder(output: &Path, contents: &Path) -> Result<PathBuf> {
+    let Some(name) = output.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+        return Ok(output.to_path_buf());
+    };
+    if !name.contains(HASH_PLACEHOLDER) {
+        return Ok(output.to_path_buf());
+    }
+    let hash = content_digest(contents)?;
+    let mut filled = String::new();
+    let mut rest = name.as_str();
+    while let Some(start) = rest.find(HASH_PLACEHOLDER) {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + HASH_PLACEHOLDER.len()..];
+        match after.find('}').and_then(|end| Some((end, after[..end].parse::<usize>().ok()?))) {
+            Some((end, digits)) => {
+                filled.push_str(&hash[..digits.min(hash.len())]);
+                rest = &after[end + 1..];
+            }
+            None => {
+                filled.push_str(HASH_PLACEHOLDER);
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    Ok(output.with_file_name(filled))
+}
+
+/// Hex SHA-256 of the file `contents`.
+fn content_digest(contents: &Path) -> Result<String> {
+    let bytes = std::fs::read(contents)
+        .with_context(|| format!("Failed to read {}", contents.display()))?;
+    Ok(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// Whether `existing` is a file with the same bytes as `staged`. Sizes are
+/// compared first, so `existing` is only hashed when they match.
+pub fn same_contents(staged: &Path, existing: &Path) -> Result<bool> {
+    let Ok(metadata) = std::fs::metadata(existing) else {
+        return Ok(false);
+    };
+    let staged_len = std::fs::metadata(staged)
+        .with_context(|| format!("Failed to read {}", staged.display()))?
+        .len();
+    if !metadata.is_file() || metadata.len() != staged_len {
+        return Ok(false);
+    }
+    let digest = |path: &Path| -> Result<_> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Sha256::digest(&bytes))
+    };
+    Ok(digest(staged)? == digest(existing)?)
+}
+
+/// `manifest.json` for `--hash-manifest`: each output's plain relative path
+/// mapped to its hashed one, with `/` separators and sorted keys so reruns
+/// write the same bytes. Entries of the `previous` manifest that this run
+/// didn't write again are kept.
+pub fn manifest_json(root: &Path, previous: &PreviousManifest, renamed: &[(PathBuf, PathBuf)]) -> Result<String> {
+    let mut entries = previous.entries.clone();
+    entries.extend(renamed.iter().map(|(plain, hashed)| (relative_name(root, plain), relative_name(root, hashed))));
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
+
+/// `path` relative to `root`, `/`-separated as in the manifest.
+fn relative_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The `manifest.json` an earlier `--hash-manifest` run left, so reruns can
+/// find the hashed name a planned output was written as instead of guessing.
+#[derive(Debug, Default)]
+pub struct PreviousManifest {
+    root: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+impl PreviousManifest {
+    /// Reads `json`, the manifest of the outputs under `root`.
+    pub fn parse(root: &Path, json: &str) -> Result<Self> {
+        let entries = serde_json::from_str(json).context("manifest.json is not a map of output names")?;
+        Ok(Self { root: root.to_path_buf(), entries })
+    }
+
+    /// With `--content-hash`, the `manifest.json` an earlier run left in the
+    /// output folder or in the `--output-zip` archive (whose entries are
+    /// `archived`), without the outputs that are gone since. Empty otherwise.
+    pub fn load(cli: &ConvertArgs, archived: &HashSet<String>) -> Result<Self> {
+        if cli.content_hash.is_none() {
+            return Ok(Self::default());
+        }
+        let json = match &cli.output_zip {
+            Some(zip) if archived.contains("manifest.json") => read_entry(zip, "manifest.json")?,
+            Some(_) => None,
+            None => {
+                let path = cli.output_root().join("manifest.json");
+                match path.is_file() {
+                    true => Some(std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?),
+                    false => None,
+                }
+            }
+        };
+        let Some(json) = json else {
+            return Ok(Self::default());
+        };
+        let mut manifest = Self::parse(cli.output_root(), &json)?;
+        match &cli.output_zip {
+            Some(_) => manifest.retain_existing(|hashed| archived.contains(&entry_name(hashed))),
+            None => manifest.retain_existing(Path::exists),
+        }
+        Ok(manifest)
+    }
+
+    /// The hashed output recorded for the planned (plain) `output`.
+    pub fn hashed(&self, output: &Path) -> Option<PathBuf> {
+        let hashed = self.entries.get(&relative_name(&self.root, output))?;
+        Some(hashed.split('/').fold(self.root.clone(), |path, part| path.join(part)))
+    }
+
+    /// Drops the entries whose hashed output `exists` says is gone.
+    pub fn retain_existing(&mut self, exists: impl Fn(&Path) -> bool) {
+        let root = self.root.clone();
+        self.entries.retain(|_, hashed| exists(&hashed.split('/').fold(root.clone(), |path, part| path.join(part))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previous_manifest_finds_hashed_names_and_is_carried_over() {
+        let root = Path::new("out");
+        let json = r#"{ "ui/sword.png": "ui/sword.3fa2b1c9.png", "ui/gone.png": "ui/gone.00000000.png" }"#;
+        let mut previous = PreviousManifest::parse(root, json).unwrap();
+        assert_eq!(previous.hashed(&root.join("ui").join("sword.png")), Some(root.join("ui").join("sword.3fa2b1c9.png")));
+        assert_eq!(previous.hashed(&root.join("ui").join("shield.png")), None);
+
+        previous.retain_existing(|hashed| !hashed.ends_with("gone.00000000.png"));
+        let renamed = [(root.join("ui/shield.png"), root.join("ui/shield.0badf00d.png"))];
+        let written: BTreeMap<String, String> = serde_json::from_str(&manifest_json(root, &previous, &renamed).unwrap()).unwrap();
+        assert_eq!(written.keys().collect::<Vec<_>>(), ["ui/shield.png", "ui/sword.png"]);
+    }
+
+    #[test]
+    fn a_manifest_that_is_not_a_name_map_is_an_error() {
+        assert!(PreviousManifest::parse(Path::new("out"), "[1, 2]").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// `--crop X,Y,WxH`: a rectangle in pixels of the full-resolution texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// What to do with a texture the crop rectangle doesn't fit inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CropMode {
+    /// Shrink the rectangle to the part that lies inside the texture
+    Clamp,
+    /// Skip the file with a warning
+    Skip,
+    /// Fail the file
+    Error,
+}
+
+impl CropRect {
+    /// The rectangle to cut from a `width` x `height` image: itself if it
+    /// fits, the clamped intersection under `CropMode::Clamp`, or `None`.
+    pub fn fit(&self, width: u32, height: u32, mode: CropMode) -> Option<CropRect> {
+        let fits = self.x as u64 + self.width as u64 <= width as u64
+            && self.y as u64 + self.height as u64 <= height as u64;
+        if fits {
+            return Some(*self);
+        }
+        if mode != CropMode::Clamp || self.x >= width || self.y >= height {
+            return None;
+        }
+        Some(CropRect {
+            x: self.x,
+            y: self.y,
+            width: self.width.min(width - self.x),
+            height: self.height.min(height - self.y),
+        })
+    }
+}
+
+impl CropRect {
+    /// Grows the rectangle by `padding` on every side, staying inside a
+    /// `width` x `height` image.
+    pub fn expand(&self, padding: u32, width: u32, height: u32) -> CropRect {
+        let x = self.x.saturating_sub(padding);
+        let y = self.y.saturating_sub(padding);
+        let right = (self.x + self.width).saturating_add(padding).min(width);
+        let bottom = (self.y + self.height).saturating_add(padding).min(height);
+        CropRect { x, y, width: right - x, height: bottom - y }
+    }
+}
+
+/// Bounding box of the pixels with alpha above `threshold`, or `None` if
+/// there are none.
+pub fn content_bounds(image: &RgbaImage, threshold: u8) -> Option<CropRect> {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] > threshold {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+    }
+    (left != u32::MAX).then(|| CropRect { x: left, y: top, width: right - left + 1, height: bottom - top + 1 })
+}
+
+impl std::fmt::Display for CropRect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+pub fn parse_crop(value: &str) -> Result<CropRect, String> {
+    let invalid = || format!("'{}' is not in X,Y,WxH form (e.g. 0,0,512x512)", value);
+
+    let mut parts = value.trim().splitn(3, ',');
+    let (Some(x), Some(y), Some(size)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let number = |text: &str| text.trim().parse::<u32>().map_err(|_| invalid());
+
+    let rect = CropRect { x: number(x)?, y: number(y)?, width: number(width)?, height: number(height)? };
+    if rect.width == 0 || rect.height == 0 {
+        return Err("crop width and height must be at least 1".to_string());
+    }
+    Ok(rect)
+}
+
+/// Dimensions of the PNG at `path`.
+pub fn png_dimensions(path: &Path) -> Result<(u32, u32)> {
+    image::image_dimensions(path)
+        .with_context(|| format!("Failed to read the size of {}", path.display()))
+}
+
+/// Writes `rect` of the PNG at `input` to `output`.
+pub fn crop_png(input: &Path, output: &Path, rect: CropRect) -> Result<()> {
+    let image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?;
+    image
+        .crop_imm(rect.x, rect.y, rect.width, rect.height)
+        .save(output)
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn rect(x: u32, y: u32, width: u32, height: u32) -> CropRect {
+        CropRect { x, y, width, height }
+    }
+
+    /// 10x8, transparent but for the given pixels at `alpha`.
+    fn sparse(pixels: &[(u32, u32)], alpha: u8) -> RgbaImage {
+        let mut image = RgbaImage::new(10, 8);
+        for &(x, y) in pixels {
+            image.put_pixel(x, y, image::Rgba([255, 255, 255, alpha]));
+        }
+        image
+    }
+
+    #[test]
+    fn trim_bounding_box_covers_pixels_above_the_threshold() {
+        assert_eq!(content_bounds(&sparse(&[(3, 2)], 255), 0), Some(rect(3, 2, 1, 1)));
+        assert_eq!(content_bounds(&sparse(&[(1, 6), (8, 1), (4, 4)], 255), 0), Some(rect(1, 1, 8, 6)));
+        assert_eq!(content_bounds(&sparse(&[(0, 0), (9, 7)], 255), 0), Some(rect(0, 0, 10, 8)));
+        // Alpha has to be above the threshold, not at it
+        assert_eq!(content_bounds(&sparse(&[(3, 2)], 16), 16), None);
+        assert_eq!(content_bounds(&sparse(&[(3, 2)], 17), 16), Some(rect(3, 2, 1, 1)));
+        // Nothing visible: no box, rather than a 0x0 one
+        assert_eq!(content_bounds(&RgbaImage::new(10, 8), 0), None);
+    }
+
+    #[test]
+    fn trim_padding_stays_inside_the_image() {
+        assert_eq!(rect(3, 2, 4, 4).expand(1, 10, 8), rect(2, 1, 6, 6));
+        assert_eq!(rect(1, 1, 8, 6).expand(5, 10, 8), rect(0, 0, 10, 8));
+        assert_eq!(rect(3, 2, 4, 4).expand(0, 10, 8), rect(3, 2, 4, 4));
+    }
+
+    #[test]
+    fn crop_fits_clamps_or_gives_up_per_mode() {
+        let crop = rect(4, 4, 8, 8);
+        assert_eq!(crop.fit(16, 16, CropMode::Error), Some(crop));
+        assert_eq!(crop.fit(10, 10, CropMode::Clamp), Some(rect(4, 4, 6, 6)));
+        assert_eq!(crop.fit(10, 10, CropMode::Skip), None);
+        assert_eq!(crop.fit(4, 10, CropMode::Clamp), None);
+
+        assert_eq!(parse_crop("0, 16, 512X256").unwrap(), rect(0, 16, 512, 256));
+        assert!(parse_crop("0,0,512").unwrap_err().contains("X,Y,WxH"));
+        assert!(parse_crop("0,0,0x4").unwrap_err().contains("at least 1"));
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{GenericImage, Rgba, RgbaImage};
+use std::f32::consts::PI;
+
+/// How `--cubemap-layout` lays out the six faces of a cubemap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CubemapLayout {
+    /// One image per face, suffixed _posx, _negx, _posy, _negy, _posz, _negz
+    Faces,
+    /// All faces on one 4:3 canvas as a horizontal cross
+    Cross,
+    /// A 2:1 equirectangular panorama for 360° viewers
+    Equirect,
+}
+
+/// Face suffixes in DDS storage order: +X, -X, +Y, -Y, +Z, -Z.
+pub const FACE_NAMES: [&str; 6] = ["posx", "negx", "posy", "negy", "posz", "negz"];
+
+/// Where each face goes in the horizontal cross, as (column, row):
+///
+/// ```text
+///      +Y
+///  -X  +Z  +X  -Z
+///      -Y
+/// ```
+const CROSS_CELLS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+/// The face a direction points at and the position on it, both in 0..1,
+/// following the Direct3D cube map conventions (+Y up, +Z forward).
+pub fn face_coords(direction: [f32; 3]) -> (usize, f32, f32) {
+    let [x, y, z] = direction;
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, s, t, major) = if ax >= ay && ax >= az {
+        if x > 0.0 { (0, -z, -y, ax) } else { (1, z, -y, ax) }
+    } else if ay >= az {
+        if y > 0.0 { (2, x, z, ay) } else { (3, x, -z, ay) }
+    } else if z > 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    let major = major.max(f32::MIN_POSITIVE);
+    (face, (s / major + 1.0) / 2.0, (t / major + 1.0) / 2.0)
+}
+
+/// Inverse of `face_coords`: the (unnormalized) direction through position
+/// `s`, `t` of `face`. Positions outside 0..1 continue onto the face plane,
+/// which is how bilinear taps past an edge find the neighbouring face.
+pub fn face_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    let (sc, tc) = (s * 2.0 - 1.0, t * 2.0 - 1.0);
+    match face {
+        0 => [1.0, -tc, -sc],
+        1 => [-1.0, -tc, sc],
+        2 => [sc, 1.0, tc],
+        3 => [sc, -1.0, -tc],
+        4 => [sc, -tc, 1.0],
+        _ => [-sc, -tc, -1.0],
+    }
+}
+
+/// Direction for position `u`, `v` (0..1, left to right and top to bottom)
+/// of an equirectangular panorama whose centre looks down +Z.
+pub fn equirect_direction(u: f32, v: f32) -> [f32; 3] {
+    let longitude = (u - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - v) * PI;
+    [latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos()]
+}
+
+/// Bilinear sample of the cube along `direction`. Taps that fall past a
+/// face's edge are looked up on the face they really belong to, so there
+/// are no seams between faces.
+pub fn sample(faces: &[RgbaImage; 6], direction: [f32; 3]) -> Rgba<u8> {
+    let (face, s, t) = face_coords(direction);
+    let size = faces[face].width() as f32;
+    let (px, py) = (s * size - 0.5, t * size - 0.5);
+    let (x0, y0) = (px.floor(), py.floor());
+    let (fx, fy) = (px - x0, py - y0);
+
+    let mut sum = [0f32; 4];
+    for (dx, dy, weight) in [(0.0, 0.0, (1.0 - fx) * (1.0 - fy)), (1.0, 0.0, fx * (1.0 - fy)), (0.0, 1.0, (1.0 - fx) * fy), (1.0, 1.0, fx * fy)] {
+        if weight == 0.0 {
+            continue;
+        }
+        let texel = texel(faces, face, x0 + dx, y0 + dy);
+        for (total, channel) in sum.iter_mut().zip(texel.0) {
+            *total += channel as f32 * weight;
+        }
+    }
+    Rgba(sum.map(|value| value.round().clamp(0.0, 255.0) as u8))
+}
+
+/// Texel `x`, `y` of `face`, where coordinates past the edge resolve to the
+/// adjacent face.
+fn texel(faces: &[RgbaImage; 6], face: usize, x: f32, y: f32) -> Rgba<u8> {
+    let size = faces[face].width() as f32;
+    let (face, x, y) = if x < 0.0 || y < 0.0 || x >= size || y >= size {
+        let direction = face_direction(face, (x + 0.5) / size, (y + 0.5) / size);
+        let (other, s, t) = face_coords(direction);
+        let other_size = faces[other].width() as f32;
+        (other, s * other_size - 0.5, t * other_size - 0.5)
+    } else {
+        (face, x, y)
+    };
+    let image = &faces[face];
+    let clamp = |value: f32, limit: u32| (value.round().max(0.0) as u32).min(limit - 1);
+    *image.get_pixel(clamp(x, image.width()), clamp(y, image.height()))
+}
+
+/// Resamples the cube into a `width` x `width / 2` panorama.
+pub fn equirect(faces: &[RgbaImage; 6], width: u32) -> RgbaImage {
+    let height = (width / 2).max(1);
+    RgbaImage::from_fn(width, height, |x, y| {
+        let u = (x as f32 + 0.5) / width as f32;
+        let v = (y as f32 + 0.5) / height as f32;
+        sample(faces, equirect_direction(u, v))
+    })
+}
+
+/// Places the faces on a transparent 4:3 canvas as a horizontal cross.
+pub fn cross(faces: &[RgbaImage; 6]) -> Result<RgbaImage> {
+    let size = faces[0].width();
+    let mut canvas = RgbaImage::new(size * 4, size * 3);
+    for (face, (column, row)) in faces.iter().zip(CROSS_CELLS) {
+        canvas.copy_from(face, column * size, row * size).context("Failed to place cube face")?;
+    }
+    Ok(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Six 8x8 faces, each a single colour whose red channel is 40 * face.
+    fn single_colour_faces() -> [RgbaImage; 6] {
+        std::array::from_fn(|face| RgbaImage::from_pixel(8, 8, Rgba([40 * face as u8, 0, 0, 255])))
+    }
+
+    #[test]
+    fn face_coords_and_face_direction_round_trip() {
+        for face in 0..6 {
+            for (s, t) in [(0.5, 0.5), (0.1, 0.9), (0.75, 0.25)] {
+                let (found, s2, t2) = face_coords(face_direction(face, s, t));
+                assert_eq!(found, face);
+                assert!((s2 - s).abs() < 1e-5 && (t2 - t).abs() < 1e-5, "face {} ({}, {}) -> ({}, {})", face, s, t, s2, t2);
+            }
+        }
+    }
+
+    #[test]
+    fn every_axis_samples_its_own_face() {
+        let faces = single_colour_faces();
+        let axes = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]];
+        for (face, direction) in axes.into_iter().enumerate() {
+            assert_eq!(sample(&faces, direction).0, [40 * face as u8, 0, 0, 255]);
+        }
+        // The panorama's centre looks down +Z, its top row at +Y
+        let panorama = equirect(&faces, 32);
+        assert_eq!(panorama.dimensions(), (32, 16));
+        assert_eq!(panorama.get_pixel(16, 8).0[0], 160);
+        assert_eq!(panorama.get_pixel(5, 0).0[0], 80);
+    }
+
+    #[test]
+    fn samples_on_an_edge_blend_both_faces_without_a_seam() {
+        let faces = single_colour_faces();
+        // Exactly between +X and +Z: half of each
+        assert_eq!(sample(&faces, [1.0, 0.0, 1.0]).0[0], 80);
+        // Just inside +Z, the blend shifts towards it but stays continuous
+        let inside = sample(&faces, [0.99, 0.0, 1.0]).0[0];
+        assert!((80..=160).contains(&inside), "{}", inside);
+    }
+
+    #[test]
+    fn cross_layout_puts_every_face_in_its_cell() {
+        let faces = single_colour_faces();
+        let canvas = cross(&faces).unwrap();
+        assert_eq!(canvas.dimensions(), (32, 24));
+        for (face, (column, row)) in CROSS_CELLS.into_iter().enumerate() {
+            let cut = image::imageops::crop_imm(&canvas, column * 8, row * 8, 8, 8).to_image();
+            assert_eq!(cut, faces[face], "face {}", FACE_NAMES[face]);
+        }
+        // The corners of the cross stay transparent
+        assert_eq!(canvas.get_pixel(0, 0).0[3], 0);
+        assert_eq!(canvas.get_pixel(31, 23).0[3], 0);
+    }
+}
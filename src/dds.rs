@@ -0,0 +1,359 @@
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::Path;
+
+use crate::errors::{Classified, ErrorCode};
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+/// Magic + legacy header + DX10 extension: everything needed to describe a texture.
+pub const MAX_HEADER_BYTES: usize = 4 + HEADER_SIZE + DX10_HEADER_SIZE;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_DEPTH: u32 = 0x800000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+const DX10_DIMENSION_TEXTURE3D: u32 = 4;
+
+/// How the pixel format is declared in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// DX10 extension header with an explicit DXGI_FORMAT.
+    Dxgi(u32),
+    /// Legacy FourCC code (`DXT1`, `ATI2`, ...), or a D3DFMT number stored in the FourCC slot.
+    FourCC([u8; 4]),
+    /// Legacy uncompressed layout described by bit count and channel masks.
+    Masks { bit_count: u32, r: u32, g: u32, b: u32, a: u32 },
+}
+
+/// The parts of a DDS header the converter cares about.
+#[derive(Debug, Clone)]
+pub struct DdsHeader {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub mip_count: u32,
+    pub array_size: u32,
+    pub is_cubemap: bool,
+    pub pixel_format: PixelFormat,
+    /// `None` when the header doesn't say (e.g. BC1, which may or may not use its 1-bit alpha).
+    pub has_alpha: Option<bool>,
+}
+
+impl DdsHeader {
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut bytes = Vec::with_capacity(MAX_HEADER_BYTES);
+        file.by_ref()
+            .take(MAX_HEADER_BYTES as u64)
+            .read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 4 + HEADER_SIZE {
+            return Err(Classified::error(ErrorCode::CorruptHeader, format!("File too short for a DDS header ({} bytes)", bytes.len())));
+        }
+        if &bytes[..4] != DDS_MAGIC {
+            return Err(Classified::error(ErrorCode::CorruptHeader, "Missing DDS magic"));
+        }
+
+        let field = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        // Offsets below are relative to the start of the file (magic included)
+        let header_size = field(4);
+        if header_size as usize != HEADER_SIZE {
+            return Err(Classified::error(ErrorCode::CorruptHeader, format!("Unexpected DDS header size {}", header_size)));
+        }
+
+        let flags = field(8);
+        let height = field(12);
+        let width = field(16);
+        let depth = field(24);
+        let mip_map_count = field(28);
+        let pf_flags = field(80);
+        let fourcc: [u8; 4] = bytes[84..88].try_into().unwrap();
+        let caps2 = field(112);
+
+        let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 && mip_map_count > 0 { mip_map_count } else { 1 };
+        let mut depth = if flags & DDSD_DEPTH != 0 && caps2 & DDSCAPS2_VOLUME != 0 { depth.max(1) } else { 1 };
+        let mut is_cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+        let mut array_size = 1;
+
+        let (pixel_format, has_alpha) = if pf_flags & DDPF_FOURCC != 0 && &fourcc == b"DX10" {
+            if bytes.len() < MAX_HEADER_BYTES {
+                return Err(Classified::error(ErrorCode::CorruptHeader, "DX10 header is truncated"));
+            }
+            let dxgi_format = field(128);
+            let dimension = field(132);
+            let misc_flag = field(136);
+            array_size = field(140).max(1);
+            let alpha_mode = field(144) & 0x7;
+
+            is_cubemap |= misc_flag & DX10_MISC_TEXTURECUBE != 0;
+            if dimension != DX10_DIMENSION_TEXTURE3D {
+                depth = 1;
+            }
+
+            let has_alpha = match alpha_mode {
+                1 | 2 | 4 => Some(true),
+                3 => Some(false),
+                _ => dxgi_has_alpha(dxgi_format),
+            };
+            (PixelFormat::Dxgi(dxgi_format), has_alpha)
+        } else if pf_flags & DDPF_FOURCC != 0 {
+            let has_alpha = match &fourcc {
+                b"DXT2" | b"DXT3" | b"DXT4" | b"DXT5" => Some(true),
+                b"ATI1" | b"ATI2" | b"BC4U" | b"BC4S" | b"BC5U" | b"BC5S" => Some(false),
+                _ => legacy_dxgi_equivalent(&fourcc).and_then(dxgi_has_alpha),
+            };
+            (PixelFormat::FourCC(fourcc), has_alpha)
+        } else {
+            let masks = PixelFormat::Masks {
+                bit_count: field(88),
+                r: field(92),
+                g: field(96),
+                b: field(100),
+                a: field(104),
+            };
+            let has_alpha = pf_flags & DDPF_ALPHAPIXELS != 0 && field(104) != 0;
+            (masks, Some(has_alpha))
+        };
+
+        Ok(Self { width, height, depth, mip_count, array_size, is_cubemap, pixel_format, has_alpha })
+    }
+
+    /// Bytes taken by magic and header(s); pixel data starts here.
+    pub fn data_offset(&self) -> usize {
+        match self.pixel_format {
+            PixelFormat::Dxgi(_) => MAX_HEADER_BYTES,
+            _ => 4 + HEADER_SIZE,
+        }
+    }
+
+    /// The DXGI format this texture is (or is equivalent to), if known.
+    pub fn dxgi_format(&self) -> Option<u32> {
+        match &self.pixel_format {
+            PixelFormat::Dxgi(format) => Some(*format),
+            PixelFormat::FourCC(fourcc) => legacy_dxgi_equivalent(fourcc),
+            PixelFormat::Masks { bit_count, r, g, b, a } => match (bit_count, r, g, b, a) {
+                (32, 0xff, 0xff00, 0xff0000, _) => Some(28),          // R8G8B8A8_UNORM
+                (32, 0xff0000, 0xff00, 0xff, 0xff000000) => Some(87), // B8G8R8A8_UNORM
+                (32, 0xff0000, 0xff00, 0xff, 0) => Some(88),          // B8G8R8X8_UNORM
+                (16, 0xf800, 0x7e0, 0x1f, 0) => Some(85),             // B5G6R5_UNORM
+                (16, 0x7c00, 0x3e0, 0x1f, 0x8000) => Some(86),        // B5G5R5A1_UNORM
+                (8, 0xff, 0, 0, 0) => Some(61),                       // R8_UNORM (luminance)
+                (8, 0, 0, 0, 0xff) => Some(65),                       // A8_UNORM
+                _ => None,
+            },
+        }
+    }
+
+    pub fn format_name(&self) -> String {
+        match (&self.pixel_format, self.dxgi_format()) {
+            (PixelFormat::Dxgi(_), Some(format)) => dxgi_format_name(format),
+            (PixelFormat::FourCC(fourcc), _) => {
+                if fourcc.iter().all(|c| c.is_ascii_graphic()) {
+                    String::from_utf8_lossy(fourcc).into_owned()
+                } else {
+                    format!("D3DFMT {}", u32::from_le_bytes(*fourcc))
+                }
+            }
+            (PixelFormat::Masks { bit_count, .. }, Some(format)) => {
+                format!("{} ({}-bit masks)", dxgi_format_name(format), bit_count)
+            }
+            (PixelFormat::Masks { bit_count, .. }, None) => format!("{}-bit masks", bit_count),
+            (PixelFormat::Dxgi(_), None) => "UNKNOWN".to_string(),
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.dxgi_format()
+            .map(|format| dxgi_format_name(format).ends_with("_SRGB"))
+            .unwrap_or(false)
+    }
+
+    /// Whether any channel has more than 8 bits (16-bit, float, 10-bit and
+    /// BC6H formats), so decoding to RGBA8 loses precision.
+    pub fn is_high_bit_depth(&self) -> bool {
+        let Some(format) = self.dxgi_format() else {
+            return false;
+        };
+        let name = dxgi_format_name(format);
+        if name.starts_with("BC6H") {
+            return true;
+        }
+        name.split('_')
+            .next()
+            .unwrap_or_default()
+            .split(|c: char| c.is_ascii_alphabetic())
+            .filter_map(|bits| bits.parse::<u32>().ok())
+            .any(|bits| bits > 8)
+    }
+
+    /// Width and height of mip `level`.
+    pub fn mip_dimensions(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Bytes mip `level` of one 2D surface takes in the file, or `None` for
+    /// formats whose layout isn't known.
+    pub fn mip_bytes(&self, level: u32) -> Option<usize> {
+        let (width, height) = self.mip_dimensions(level);
+        let (width, height) = (width as usize, height as usize);
+        if let Some(block_bytes) = self.dxgi_format().and_then(block_bytes) {
+            return Some(width.div_ceil(4) * height.div_ceil(4) * block_bytes);
+        }
+        let bits = match &self.pixel_format {
+            PixelFormat::Masks { bit_count, .. } => *bit_count as usize,
+            _ => self.dxgi_format().and_then(bits_per_pixel)? as usize,
+        };
+        Some((width * bits).div_ceil(8) * height)
+    }
+
+    /// Number of 2D images stored: array slices times six for cubemaps.
+    pub fn layer_count(&self) -> u32 {
+        self.array_size * if self.is_cubemap { 6 } else { 1 }
+    }
+
+    /// Size of the whole texture (all mips, layers and slices) once decoded to RGBA8.
+    pub fn estimated_uncompressed_size(&self) -> u64 {
+        let mut total = 0u64;
+        for mip in 0..self.mip_count {
+            let width = (self.width >> mip).max(1) as u64;
+            let height = (self.height >> mip).max(1) as u64;
+            let depth = (self.depth >> mip).max(1) as u64;
+            total += width * height * depth * 4;
+        }
+        total * self.layer_count() as u64
+    }
+}
+
+/// DXGI equivalent of a legacy FourCC / D3DFMT code.
+fn legacy_dxgi_equivalent(fourcc: &[u8; 4]) -> Option<u32> {
+    match fourcc {
+        b"DXT1" => Some(71),
+        b"DXT2" | b"DXT3" => Some(74),
+        b"DXT4" | b"DXT5" => Some(77),
+        b"ATI1" | b"BC4U" => Some(80),
+        b"BC4S" => Some(81),
+        b"ATI2" | b"BC5U" => Some(83),
+        b"BC5S" => Some(84),
+        _ => match u32::from_le_bytes(*fourcc) {
+            36 => Some(11),  // A16B16G16R16
+            110 => Some(13), // Q16W16V16U16
+            111 => Some(54), // R16F
+            112 => Some(34), // G16R16F
+            113 => Some(10), // A16B16G16R16F
+            114 => Some(41), // R32F
+            115 => Some(16), // G32R32F
+            116 => Some(2),  // A32B32G32R32F
+            _ => None,
+        },
+    }
+}
+
+/// Bytes per 4x4 block of the block-compressed (BC1-BC7) formats.
+fn block_bytes(format: u32) -> Option<usize> {
+    match format {
+        70..=72 | 79..=81 => Some(8),
+        73..=78 | 82..=84 | 94..=99 => Some(16),
+        _ => None,
+    }
+}
+
+/// Bits per pixel of the plain per-pixel formats, summed from the channel
+/// widths in the name (`R16G16B16A16` -> 64).
+fn bits_per_pixel(format: u32) -> Option<u32> {
+    match format {
+        // Packed depth/stencil layouts, R1, the two-pixels-in-four-bytes
+        // formats and the video formats
+        19..=22 | 44..=47 | 66 | 68 | 69 | 100.. => return None,
+        _ => {}
+    }
+    let name = dxgi_format_name(format);
+    let bits: u32 = name
+        .split('_')
+        .next()?
+        .split(|c: char| c.is_ascii_alphabetic())
+        .filter_map(|bits| bits.parse::<u32>().ok())
+        .sum();
+    (bits > 0 && bits.is_multiple_of(8)).then_some(bits)
+}
+
+fn dxgi_has_alpha(format: u32) -> Option<bool> {
+    let name = dxgi_format_name(format);
+    if name.starts_with("BC1") {
+        return None;
+    }
+    let has_alpha_channel = name.starts_with("BC2")
+        || name.starts_with("BC3")
+        || name.starts_with("BC7")
+        || name.split('_').next().map(|channels| channels.contains('A')).unwrap_or(false);
+    Some(has_alpha_channel)
+}
+
+pub fn dxgi_format_name(format: u32) -> String {
+    const NAMES: &[&str] = &[
+        "UNKNOWN", "R32G32B32A32_TYPELESS", "R32G32B32A32_FLOAT", "R32G32B32A32_UINT",
+        "R32G32B32A32_SINT", "R32G32B32_TYPELESS", "R32G32B32_FLOAT", "R32G32B32_UINT",
+        "R32G32B32_SINT", "R16G16B16A16_TYPELESS", "R16G16B16A16_FLOAT", "R16G16B16A16_UNORM",
+        "R16G16B16A16_UINT", "R16G16B16A16_SNORM", "R16G16B16A16_SINT", "R32G32_TYPELESS",
+        "R32G32_FLOAT", "R32G32_UINT", "R32G32_SINT", "R32G8X24_TYPELESS",
+        "D32_FLOAT_S8X24_UINT", "R32_FLOAT_X8X24_TYPELESS", "X32_TYPELESS_G8X24_UINT", "R10G10B10A2_TYPELESS",
+        "R10G10B10A2_UNORM", "R10G10B10A2_UINT", "R11G11B10_FLOAT", "R8G8B8A8_TYPELESS",
+        "R8G8B8A8_UNORM", "R8G8B8A8_UNORM_SRGB", "R8G8B8A8_UINT", "R8G8B8A8_SNORM",
+        "R8G8B8A8_SINT", "R16G16_TYPELESS", "R16G16_FLOAT", "R16G16_UNORM",
+        "R16G16_UINT", "R16G16_SNORM", "R16G16_SINT", "R32_TYPELESS",
+        "D32_FLOAT", "R32_FLOAT", "R32_UINT", "R32_SINT",
+        "R24G8_TYPELESS", "D24_UNORM_S8_UINT", "R24_UNORM_X8_TYPELESS", "X24_TYPELESS_G8_UINT",
+        "R8G8_TYPELESS", "R8G8_UNORM", "R8G8_UINT", "R8G8_SNORM",
+        "R8G8_SINT", "R16_TYPELESS", "R16_FLOAT", "D16_UNORM",
+        "R16_UNORM", "R16_UINT", "R16_SNORM", "R16_SINT",
+        "R8_TYPELESS", "R8_UNORM", "R8_UINT", "R8_SNORM",
+        "R8_SINT", "A8_UNORM", "R1_UNORM", "R9G9B9E5_SHAREDEXP",
+        "R8G8_B8G8_UNORM", "G8R8_G8B8_UNORM", "BC1_TYPELESS", "BC1_UNORM",
+        "BC1_UNORM_SRGB", "BC2_TYPELESS", "BC2_UNORM", "BC2_UNORM_SRGB",
+        "BC3_TYPELESS", "BC3_UNORM", "BC3_UNORM_SRGB", "BC4_TYPELESS",
+        "BC4_UNORM", "BC4_SNORM", "BC5_TYPELESS", "BC5_UNORM",
+        "BC5_SNORM", "B5G6R5_UNORM", "B5G5R5A1_UNORM", "B8G8R8A8_UNORM",
+        "B8G8R8X8_UNORM", "R10G10B10_XR_BIAS_A2_UNORM", "B8G8R8A8_TYPELESS", "B8G8R8A8_UNORM_SRGB",
+        "B8G8R8X8_TYPELESS", "B8G8R8X8_UNORM_SRGB", "BC6H_TYPELESS", "BC6H_UF16",
+        "BC6H_SF16", "BC7_TYPELESS", "BC7_UNORM", "BC7_UNORM_SRGB",
+        "AYUV", "Y410", "Y416", "NV12",
+        "P010", "P016", "420_OPAQUE", "YUY2",
+        "Y210", "Y216", "NV11", "AI44",
+        "IA44", "P8", "A8P8", "B4G4R4A4_UNORM",
+    ];
+
+    NAMES.get(format as usize)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("DXGI_FORMAT {}", format))
+}
+
+/// A DX10 DDS header (magic included) of a `width`x`height` 2D texture in
+/// DXGI `format` with `mips` levels, for tests to put pixel data after.
+#[cfg(test)]
+pub fn dx10_header(format: u32, width: u32, height: u32, mips: u32) -> Vec<u8> {
+    let mut bytes = DDS_MAGIC.to_vec();
+    for value in [HEADER_SIZE as u32, 0x1 | 0x2 | 0x4 | 0x1000 | DDSD_MIPMAPCOUNT, height, width, 0, 0, mips] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0; 44]);
+    bytes.extend_from_slice(&32u32.to_le_bytes());
+    bytes.extend_from_slice(&DDPF_FOURCC.to_le_bytes());
+    bytes.extend_from_slice(b"DX10");
+    bytes.extend_from_slice(&[0; 20]);
+    bytes.extend_from_slice(&0x1000u32.to_le_bytes());
+    bytes.extend_from_slice(&[0; 16]);
+    for value in [format, 3, 0, 1, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
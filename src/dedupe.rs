@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use crate::console::Console;
+use crate::split_dds::{find_alpha_part, find_alpha_split_parts, find_split_parts};
+
+/// How the output of a duplicate input is put in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LinkMode {
+    Hardlink,
+    Symlink,
+    Copy,
+}
+
+impl LinkMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkMode::Hardlink => "hardlink",
+            LinkMode::Symlink => "symlink",
+            LinkMode::Copy => "copy",
+        }
+    }
+}
+
+/// Inputs split into the ones to convert and the byte-identical duplicates,
+/// each paired with the earlier file whose output it will reuse.
+pub struct DedupePlan {
+    pub unique: Vec<PathBuf>,
+    pub duplicates: Vec<(PathBuf, PathBuf)>,
+}
+
+pub fn plan_dedupe(files: Vec<PathBuf>) -> Result<DedupePlan> {
+    let hashes = hash_textures(&files)?;
+    let mut first_by_hash: HashMap<[u8; 32], PathBuf> = HashMap::new();
+    let mut plan = DedupePlan { unique: Vec::new(), duplicates: Vec::new() };
+
+    for (file, hash) in files.into_iter().zip(hashes) {
+        match first_by_hash.get(&hash) {
+            Some(original) => plan.duplicates.push((file, original.clone())),
+            None => {
+                first_by_hash.insert(hash, file.clone());
+                plan.unique.push(file);
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// The files that make up a texture: the file itself, its split parts and
+/// its attached alpha texture with that one's parts.
+pub fn texture_files(file: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![file.to_path_buf()];
+    paths.extend(find_split_parts(file).into_iter().map(|(_, part)| part));
+    paths.extend(find_alpha_part(file));
+    paths.extend(find_alpha_split_parts(file).into_iter().map(|(_, part)| part));
+    paths
+}
+
+/// SHA-256 over all of `texture_files(file)`, so two textures only match if
+/// their split and alpha parts do too.
+pub fn texture_hash(file: &Path) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    for path in texture_files(file) {
+        let contents = std::fs::read(&path)
+            .with_context(|| format!("Failed to read {} for hashing", path.display()))?;
+        hasher.update(&contents);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// `texture_hash` of every file, in order, spread over the available cores.
+pub fn hash_textures(files: &[PathBuf]) -> Result<Vec<[u8; 32]>> {
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let chunk = files.len().div_ceil(workers).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk)
+            .map(|files| scope.spawn(move || files.iter().map(|file| texture_hash(file)).collect::<Result<Vec<_>>>()))
+            .collect();
+        let mut hashes = Vec::with_capacity(files.len());
+        for handle in handles {
+            hashes.extend(handle.join().map_err(|_| anyhow::anyhow!("a hashing thread panicked"))??);
+        }
+        Ok(hashes)
+    })
+}
+
+/// Puts `source` at `destination` using `mode`, falling back to a copy when
+/// the link can't be made (hardlinks across filesystems, symlinks without the
+/// privilege on Windows). Returns the method that was actually used. The
+/// link or copy is made under a temporary name and renamed over
+/// `destination`, so an existing output stays until its replacement is in
+/// place.
+pub fn materialize(source: &Path, destination: &Path, mode: LinkMode, console: &Console) -> Result<LinkMode> {
+    let linked = match mode {
+        LinkMode::Hardlink => staged_next_to(destination, |path| std::fs::hard_link(source, path)),
+        LinkMode::Symlink => std::fs::canonicalize(source).and_then(|target| staged_next_to(destination, |path| symlink_file(&target, path))),
+        LinkMode::Copy => return copy_output(source, destination),
+    };
+
+    match linked {
+        Ok(staged) => {
+            replace_with(staged, destination)?;
+            Ok(mode)
+        }
+        Err(e) => {
+            let reason = match e.kind() {
+                std::io::ErrorKind::CrossesDevices => "output is on another filesystem".to_string(),
+                std::io::ErrorKind::PermissionDenied => "not permitted; symlinks need Developer Mode or admin on Windows".to_string(),
+                _ => e.to_string(),
+            };
+            console.println(format!(
+                "⚠️  Could not {} {} ({}); copying instead",
+                mode.name(),
+                destination.display(),
+                reason
+            ));
+            copy_output(source, destination)
+        }
+    }
+}
+
+fn copy_output(source: &Path, destination: &Path) -> Result<LinkMode> {
+    let staged = staged_next_to(destination, |path| std::fs::copy(source, path).map(|_| ()))
+        .with_context(|| format!("Failed to copy {} to {}", source.display(), destination.display()))?;
+    replace_with(staged, destination)?;
+    Ok(LinkMode::Copy)
+}
+
+/// A file `make` creates at a free temporary path in `destination`'s
+/// folder; removed again if it is dropped before `replace_with`.
+fn staged_next_to(destination: &Path, make: impl FnMut(&Path) -> std::io::Result<()>) -> std::io::Result<NamedTempFile<()>> {
+    let folder = destination.parent().filter(|folder| !folder.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    tempfile::Builder::new().prefix(".dds-converter-").make_in(folder, make)
+}
+
+/// Renames `staged` over `destination`.
+fn replace_with(staged: NamedTempFile<()>, destination: &Path) -> Result<()> {
+    let temporary = staged.path().to_path_buf();
+    staged
+        .persist(destination)
+        .map_err(|e| e.error)
+        .with_context(|| format!("Failed to replace {}", destination.display()))?;
+    // Renaming a hardlink over another link to the same file succeeds
+    // without doing anything, which would leave the temporary name behind
+    if temporary.symlink_metadata().is_ok() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink_file(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_file(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn an_existing_output_is_replaced_in_one_step() {
+        for mode in [LinkMode::Hardlink, LinkMode::Symlink, LinkMode::Copy] {
+            let dir = tempfile::tempdir().unwrap();
+            let source = dir.path().join("rock.png");
+            let destination = dir.path().join("copy.png");
+            std::fs::write(&source, b"new").unwrap();
+            std::fs::write(&destination, b"old").unwrap();
+            assert_eq!(materialize(&source, &destination, mode, &Console::plain()).unwrap(), mode);
+            assert_eq!(std::fs::read(&destination).unwrap(), b"new");
+            // Again, over the link it just made
+            assert_eq!(materialize(&source, &destination, mode, &Console::plain()).unwrap(), mode);
+            assert_eq!(names(dir.path()), ["copy.png", "rock.png"], "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn a_failed_copy_keeps_the_old_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("copy.png");
+        std::fs::write(&destination, b"old").unwrap();
+        let missing = dir.path().join("missing.png");
+        assert!(materialize(&missing, &destination, LinkMode::Copy, &Console::plain()).is_err());
+        assert_eq!(std::fs::read(&destination).unwrap(), b"old");
+        assert_eq!(names(dir.path()), ["copy.png"]);
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::cli::ConvertArgs;
+use crate::dedupe::texture_files;
+use crate::split_dds::find_split_parts;
+
+/// Refuses `--delete-source` when the outputs go into the input folder
+/// itself, unless `--yes-really-delete` confirms it.
+pub fn check_delete_source(cli: &ConvertArgs) -> Result<()> {
+    if !cli.delete_source || cli.yes_really_delete || cli.output_zip.is_some() {
+        return Ok(());
+    }
+    let output = cli.output_root();
+    if same_path(&cli.input, output) {
+        anyhow::bail!(
+            "--delete-source with --output the same as --input ({}) removes the sources from the folder being converted; add --yes-really-delete if that's intended",
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Deletes `file` with its split parts and, if it was merged in
+/// (`with_alpha`), its attached alpha texture, and returns the bytes freed.
+/// Nothing is deleted when `output` is the source itself (e.g.
+/// `--format dds` into the input folder).
+pub fn delete_source(file: &Path, output: &Path, with_alpha: bool) -> Result<Option<u64>> {
+    if same_path(file, output) {
+        return Ok(None);
+    }
+    let paths: Vec<PathBuf> = if with_alpha {
+        texture_files(file)
+    } else {
+        std::iter::once(file.to_path_buf()).chain(find_split_parts(file).into_iter().map(|(_, part)| part)).collect()
+    };
+    let mut reclaimed = 0;
+    for path in paths {
+        let len = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        std::fs::remove_file(&path).with_context(|| format!("Failed to delete {}", path.display()))?;
+        reclaimed += len;
+    }
+    Ok(Some(reclaimed))
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
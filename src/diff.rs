@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use crate::console::status;
+use crate::cli::DiffArgs;
+use crate::dedupe::{hash_textures, texture_files};
+use crate::errors::ErrorCode;
+use crate::utils::find_dds_files;
+
+/// How the textures of two trees differ, by path relative to each root
+/// (with `/` separators).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TreeDiff {
+    pub old: PathBuf,
+    pub new: PathBuf,
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    /// Same contents under a different path.
+    pub renamed: Vec<Rename>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+}
+
+/// `file`'s path under `root` with `/` separators, as diff reports and
+/// `--files-from` lists write it.
+pub fn relative_key(root: &Path, file: &Path) -> String {
+    file.strip_prefix(root).unwrap_or(file).to_string_lossy().replace('\\', "/")
+}
+
+/// Total size of a texture with its split and alpha parts.
+fn texture_size(file: &Path) -> u64 {
+    texture_files(file).iter().filter_map(|path| std::fs::metadata(path).ok()).map(|meta| meta.len()).sum()
+}
+
+/// Compares the textures under `old_root` and `new_root`. Files are only
+/// hashed where the sizes can't tell them apart: same path and size, or an
+/// added and a removed file of the same size, which may be a rename.
+pub fn diff_trees(old_root: &Path, new_root: &Path, excluded: Option<&Path>) -> Result<TreeDiff> {
+    let old: Vec<(String, PathBuf, u64)> = find_dds_files(old_root, None, None)
+        .into_iter()
+        .map(|file| (relative_key(old_root, &file), file.clone(), texture_size(&file)))
+        .collect();
+    let new: Vec<(String, PathBuf, u64)> = find_dds_files(new_root, excluded, None)
+        .into_iter()
+        .map(|file| (relative_key(new_root, &file), file.clone(), texture_size(&file)))
+        .collect();
+    let old_by_key: HashMap<&str, (&PathBuf, u64)> = old.iter().map(|(key, file, size)| (key.as_str(), (file, *size))).collect();
+    let new_keys: HashSet<&str> = new.iter().map(|(key, _, _)| key.as_str()).collect();
+
+    let mut diff = TreeDiff { old: old_root.to_path_buf(), new: new_root.to_path_buf(), ..TreeDiff::default() };
+    let mut same_size = Vec::new();
+    let mut added = Vec::new();
+    for (key, file, size) in &new {
+        match old_by_key.get(key.as_str()) {
+            Some((_, old_size)) if *old_size != *size => diff.changed.push(key.clone()),
+            Some((old_file, _)) => same_size.push((key, (*old_file).clone(), file.clone())),
+            None => added.push((key, file, *size)),
+        }
+    }
+    let removed: Vec<&(String, PathBuf, u64)> = old.iter().filter(|(key, _, _)| !new_keys.contains(key.as_str())).collect();
+
+    let old_hashes = hash_textures(&same_size.iter().map(|(_, old, _)| old.clone()).collect::<Vec<_>>())?;
+    let new_hashes = hash_textures(&same_size.iter().map(|(_, _, new)| new.clone()).collect::<Vec<_>>())?;
+    for ((key, _, _), (old_hash, new_hash)) in same_size.iter().zip(old_hashes.iter().zip(&new_hashes)) {
+        if old_hash == new_hash {
+            diff.unchanged += 1;
+        } else {
+            diff.changed.push((*key).clone());
+        }
+    }
+
+    // Renames: an added and a removed file with the same contents
+    let removed_sizes: HashSet<u64> = removed.iter().map(|(_, _, size)| *size).collect();
+    let added_sizes: HashSet<u64> = added.iter().map(|(_, _, size)| *size).collect();
+    let candidates: Vec<&&(String, PathBuf, u64)> = removed.iter().filter(|(_, _, size)| added_sizes.contains(size)).collect();
+    let candidate_hashes = hash_textures(&candidates.iter().map(|(_, file, _)| file.clone()).collect::<Vec<_>>())?;
+    let mut removed_by_hash: HashMap<[u8; 32], Vec<&str>> = HashMap::new();
+    for ((key, _, _), hash) in candidates.iter().zip(candidate_hashes).rev() {
+        removed_by_hash.entry(hash).or_default().push(key.as_str());
+    }
+    let mut renamed_from = HashSet::new();
+    let added_candidates: Vec<PathBuf> = added
+        .iter()
+        .filter(|(_, _, size)| removed_sizes.contains(size))
+        .map(|(_, file, _)| (*file).clone())
+        .collect();
+    let mut added_hashes = hash_textures(&added_candidates)?.into_iter();
+    for (key, _, size) in added {
+        let from = match removed_sizes.contains(&size) {
+            true => added_hashes.next().and_then(|hash| removed_by_hash.get_mut(&hash)).and_then(Vec::pop),
+            false => None,
+        };
+        match from {
+            Some(from) => {
+                renamed_from.insert(from);
+                diff.renamed.push(Rename { from: from.to_string(), to: key.clone() });
+            }
+            None => diff.added.push(key.clone()),
+        }
+    }
+    diff.removed = removed
+        .iter()
+        .filter(|(key, _, _)| !renamed_from.contains(key.as_str()))
+        .map(|(key, _, _)| key.clone())
+        .collect();
+    diff.changed.sort();
+    Ok(diff)
+}
+
+impl TreeDiff {
+    /// Textures that need converting: added, changed and rename targets
+    /// (their output under the new path doesn't exist yet), under the new
+    /// root.
+    pub fn to_convert(&self) -> Vec<String> {
+        self.added.iter().chain(&self.changed).chain(self.renamed.iter().map(|rename| &rename.to)).cloned().collect()
+    }
+
+    /// Counts, and with `list` every path.
+    pub fn print(&self, list: bool) {
+        let section = |icon: &str, title: &str, paths: &[String]| {
+            status!("{} {}: {}", icon, title, paths.len());
+            if list {
+                for path in paths {
+                    status!("     {}", path);
+                }
+            }
+        };
+        section("🆕", "Added", &self.added);
+        section("✏️ ", "Changed", &self.changed);
+        section("🗑️ ", "Removed", &self.removed);
+        status!("🔀 Renamed: {}", self.renamed.len());
+        if list {
+            for rename in &self.renamed {
+                status!("     {} -> {}", rename.from, rename.to);
+            }
+        }
+        status!("✅ Unchanged: {}", self.unchanged);
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Reads a `--files-from` list (`-` for stdin): a diff report written by
+/// `--diff-json` / `diff --json` (its added, changed and renamed files), or one path
+/// per line, where blank lines and `#` comments are skipped. Paths are
+/// returned relative to `input` with `/` separators.
+pub fn read_files_from(path: &Path, input: &Path) -> Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).context("Failed to read the file list from stdin")?
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    };
+    if text.trim_start().starts_with('{') {
+        let diff: TreeDiff = serde_json::from_str(&text)
+            .with_context(|| format!("{} is not a diff report", path.display()))?;
+        return Ok(diff.to_convert());
+    }
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let key = relative_key(input, Path::new(line));
+            key.strip_prefix("./").map(str::to_string).unwrap_or(key)
+        })
+        .collect())
+}
+
+/// Turns `--files-from` keys into the files to convert under `input`, in
+/// list order without repeats (entries naming the same file, going by its
+/// canonical path), and the entries that can't be converted as `summary`
+/// failures.
+pub fn resolve_listed(input: &Path, keys: &[String]) -> (Vec<PathBuf>, Vec<(PathBuf, ErrorCode, String)>) {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    let mut invalid = Vec::new();
+    for key in keys {
+        let relative = Path::new(key);
+        let path = input.join(relative);
+        if !seen.insert(std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone())) {
+            continue;
+        }
+        let (code, reason) = if relative.is_absolute() || relative.components().any(|part| matches!(part, Component::ParentDir)) {
+            (ErrorCode::NotFound, "not under --input")
+        } else if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dds")) {
+            (ErrorCode::UnsupportedFormat, "not a .dds file")
+        } else if !path.is_file() {
+            (ErrorCode::NotFound, "file not found")
+        } else {
+            files.push(path);
+            continue;
+        };
+        invalid.push((path, code, format!("listed in --files-from: {}", reason)));
+    }
+    (files, invalid)
+}
+
+/// Keeps the discovered `files` whose path relative to `input` is in `keys`.
+/// Returns them and the keys that matched no file.
+pub fn filter_by_keys(files: Vec<PathBuf>, input: &Path, keys: &[String]) -> (Vec<PathBuf>, Vec<String>) {
+    let wanted: HashSet<&str> = keys.iter().map(String::as_str).collect();
+    let kept: Vec<PathBuf> = files.into_iter().filter(|file| wanted.contains(relative_key(input, file).as_str())).collect();
+    let found: HashSet<String> = kept.iter().map(|file| relative_key(input, file)).collect();
+    let missing = keys.iter().filter(|key| !found.contains(*key)).cloned().collect();
+    (kept, missing)
+}
+
+/// `diff` subcommand: report only.
+pub fn run_diff(args: &DiffArgs) -> Result<()> {
+    status!("🔍 Comparing {} -> {}", args.old.display(), args.new.display());
+    let diff = diff_trees(&args.old, &args.new, None)?;
+    diff.print(true);
+    if let Some(json) = &args.json {
+        diff.write_json(json)?;
+        status!("🧾 Diff report: {}", json.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_textures_are_converted_under_their_new_path() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+        std::fs::write(old.path().join("rock.dds"), b"rock").unwrap();
+        std::fs::write(old.path().join("tree.dds"), b"tree").unwrap();
+        std::fs::write(old.path().join("sand.dds"), b"sand").unwrap();
+        std::fs::create_dir(new.path().join("stone")).unwrap();
+        std::fs::write(new.path().join("stone/rock.dds"), b"rock").unwrap();
+        std::fs::write(new.path().join("tree.dds"), b"tre2").unwrap();
+        std::fs::write(new.path().join("leaf.dds"), b"leaf").unwrap();
+
+        let diff = diff_trees(old.path(), new.path(), None).unwrap();
+        assert_eq!(diff.added, ["leaf.dds"]);
+        assert_eq!(diff.changed, ["tree.dds"]);
+        assert_eq!(diff.removed, ["sand.dds"]);
+        assert_eq!((diff.renamed[0].from.as_str(), diff.renamed[0].to.as_str()), ("rock.dds", "stone/rock.dds"));
+        assert_eq!(diff.to_convert(), ["leaf.dds", "tree.dds", "stone/rock.dds"]);
+    }
+
+    #[test]
+    fn listed_files_are_deduplicated_by_the_file_they_name() {
+        let input = tempfile::tempdir().unwrap();
+        std::fs::create_dir(input.path().join("sub")).unwrap();
+        std::fs::write(input.path().join("sub/rock.dds"), b"rock").unwrap();
+        let keys: Vec<String> = ["sub/rock.dds", "sub//rock.dds", "sub/./rock.dds", "gone.dds", "gone.dds"].map(String::from).to_vec();
+        let (files, invalid) = resolve_listed(input.path(), &keys);
+        assert_eq!(files, [input.path().join("sub/rock.dds")]);
+        assert_eq!(invalid.len(), 1);
+
+        // Names differing in case are different files where the file system says so
+        std::fs::write(input.path().join("sub/Rock.dds"), b"Rock").unwrap();
+        let distinct = std::fs::read(input.path().join("sub/rock.dds")).unwrap() == b"rock";
+        let keys: Vec<String> = ["sub/rock.dds", "sub/Rock.dds"].map(String::from).to_vec();
+        let (files, _) = resolve_listed(input.path(), &keys);
+        assert_eq!(files.len(), if distinct { 2 } else { 1 });
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use crate::console::Console;
+use crate::info::format_bytes;
+use crate::prompt::Prompter;
+
+/// How often a paused run checks the output volume again.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Free space on the output volume at which a paused run carries on.
+const RESUME_FREE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Pauses a convert run when its output volume fills up, instead of letting
+/// every remaining file fail the same way. Each file holds `enter` while it
+/// is converted; a file that fails with `disk_full` calls `wait_for_space`,
+/// which stops new files from starting, lets the ones in flight finish, and
+/// returns once there is room again so the file can be retried.
+pub struct SpaceGate {
+    volume: PathBuf,
+    /// `--interactive`'s stdin reader, through which Enter ends a pause.
+    prompter: Option<Arc<Prompter>>,
+    /// Read-held by every file in progress, write-held while paused. The
+    /// value counts the pauses so far.
+    pauses: RwLock<u64>,
+}
+
+impl SpaceGate {
+    /// `volume` is a path on the output volume. With a `prompter`
+    /// (`--interactive`), Enter also ends a pause.
+    pub fn new(volume: &Path, prompter: Option<Arc<Prompter>>) -> Self {
+        SpaceGate { volume: volume.to_path_buf(), prompter, pauses: RwLock::new(0) }
+    }
+
+    /// Held while converting one file.
+    pub async fn enter(&self) -> RwLockReadGuard<'_, u64> {
+        self.pauses.read().await
+    }
+
+    /// Waits until the volume has room again, after a file that entered at
+    /// pause count `seen` failed with a full disk (its guard must be dropped
+    /// first). Files that failed during the same pause return as soon as it
+    /// ends; only the first one reports and polls.
+    pub async fn wait_for_space(&self, seen: u64, console: &Console) {
+        let mut pauses = self.pauses.write().await;
+        if *pauses != seen {
+            return;
+        }
+
+        let retry = if self.prompter.is_some() { "; free some space and press Enter to retry now" } else { "" };
+        console.println(format!(
+            "💾 The output volume ({}) is full: {} free. All workers are paused; checking again every {}s{}",
+            self.volume.display(),
+            self.free_text(),
+            POLL_INTERVAL.as_secs(),
+            retry
+        ));
+
+        let mut prompter = self.prompter.as_deref();
+        loop {
+            match prompter {
+                Some(reader) => match tokio::task::block_in_place(|| reader.wait_for_enter(POLL_INTERVAL)) {
+                    Ok(()) => break,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // stdin closed; keep polling
+                    Err(RecvTimeoutError::Disconnected) => prompter = None,
+                },
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+            if self.free().is_some_and(|free| free >= RESUME_FREE_BYTES) {
+                break;
+            }
+        }
+
+        console.println(format!("▶️  Resuming with {} free on {}", self.free_text(), self.volume.display()));
+        *pauses += 1;
+    }
+
+    /// Free bytes on the volume, asking for the nearest existing folder.
+    fn free(&self) -> Option<u64> {
+        self.volume.ancestors().find_map(|path| fs2::available_space(path).ok())
+    }
+
+    fn free_text(&self) -> String {
+        self.free().map(format_bytes).unwrap_or_else(|| "unknown".to_string())
+    }
+}
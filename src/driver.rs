@@ -0,0 +1,142 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::archive::ZipOutput;
+use crate::cli::ConvertArgs;
+use crate::collision::{free_name, ExistingAction};
+use crate::console::Console;
+use crate::content_hash::PreviousManifest;
+use crate::disk_space::SpaceGate;
+use crate::errors::{classify, Classified, ErrorCode};
+use crate::processor::{is_up_to_date, process_file, FileOutcome, SkipReason};
+use crate::prompt::Prompter;
+use crate::texconv::TexconvGpu;
+
+/// What the batch run and `--watch` share while converting files: where
+/// outputs go besides the output folder, the disk-full pause, and what an
+/// earlier run left.
+pub struct FileDriver {
+    pub texconv_path: PathBuf,
+    pub zip: Option<Arc<ZipOutput>>,
+    pub space_gate: Option<Arc<SpaceGate>>,
+    pub prompter: Option<Arc<Prompter>>,
+    pub manifest: Arc<PreviousManifest>,
+}
+
+/// How converting one file went.
+pub struct FileRun {
+    pub result: Result<FileOutcome>,
+    /// Where the output went: the planned path, or the free name picked by
+    /// `--on-conflict rename`.
+    pub output_path: PathBuf,
+    /// What was done about an output that already existed.
+    pub conflict: Option<ExistingAction>,
+    pub gpu_fallback: bool,
+    /// Whether the file succeeded after `--retries` attempts.
+    pub retried: bool,
+}
+
+impl FileDriver {
+    /// Converts `file` to its planned `output_path`: `--incremental` and
+    /// `--on-conflict` first, then texconv with a pause while the output
+    /// volume is full, the CPU retry after a GPU failure, and `--retries`
+    /// for transient errors.
+    pub async fn convert(&self, file: &Path, output_path: PathBuf, cli: &Arc<ConvertArgs>, console: &Console) -> FileRun {
+        let previous = previous_output(cli, &self.manifest, &output_path);
+        let up_to_date = cli.incremental && previous.as_deref().is_some_and(|previous| is_up_to_date(file, previous));
+        let found = match &self.zip {
+            Some(zip) => previous.filter(|previous| zip.contains(previous)),
+            None => previous.filter(|previous| previous.exists()),
+        };
+        let existing = match (&self.zip, &self.prompter, &found) {
+            _ if up_to_date => None,
+            (_, _, None) => None,
+            // Archive entries can only be skipped, never replaced or renamed
+            (Some(_), _, Some(_)) => Some(Ok(ExistingAction::Skip)),
+            (None, Some(prompter), Some(found)) => Some(tokio::task::block_in_place(|| {
+                prompter.existing_output(found, cli.existing_policy(), console)
+            })),
+            (None, None, Some(_)) => Some(Ok(cli.existing_policy())),
+        };
+        let found = found.unwrap_or_else(|| output_path.clone());
+        let conflict = existing.as_ref().and_then(|action| action.as_ref().ok().copied());
+        let planned_output = output_path.clone();
+        let output_path = match existing {
+            Some(Ok(ExistingAction::Rename)) => free_name(&output_path),
+            _ => output_path,
+        };
+        if cli.verbose {
+            match conflict {
+                Some(ExistingAction::Overwrite) => console.println(format!("♻️  Overwriting {}", output_path.display())),
+                Some(ExistingAction::Skip) => console.println(format!("⏭️  Skipping {}: {} already exists", file.display(), found.display())),
+                Some(ExistingAction::Rename) => console.println(format!("✏️  {} exists; writing {}", planned_output.display(), output_path.display())),
+                _ => {}
+            }
+        }
+
+        let mut file_cli = cli.clone();
+        let mut gpu_fallback = false;
+        let mut attempts = 0;
+        let result = match existing {
+            _ if up_to_date => {
+                if cli.verbose {
+                    console.println(format!("⏭️  Up to date: {}", found.display()));
+                }
+                Ok(FileOutcome::Skipped(SkipReason::UpToDate))
+            }
+            Some(Err(e)) => Err(e),
+            Some(Ok(ExistingAction::Skip)) => Ok(FileOutcome::Skipped(SkipReason::Exists)),
+            Some(Ok(ExistingAction::Error)) => Err(Classified::error(
+                ErrorCode::OutputExists,
+                format!("{} already exists (--on-conflict error)", found.display()),
+            )),
+            _ => loop {
+                let entered = match &self.space_gate {
+                    Some(gate) => Some(gate.enter().await),
+                    None => None,
+                };
+                let seen = entered.as_deref().copied().unwrap_or_default();
+                let result = process_file(file, &output_path, &self.texconv_path, self.zip.as_deref(), &file_cli, console).await;
+                drop(entered);
+                match (&result, &self.space_gate) {
+                    (Err(e), Some(gate)) if classify(e) == ErrorCode::DiskFull => gate.wait_for_space(seen, console).await,
+                    (Err(e), _) if classify(e) == ErrorCode::GpuFailure && file_cli.gpu != TexconvGpu::Cpu => {
+                        console.println(format!("⚠️  texconv failed on the GPU for {}; retrying with the software codecs", file.display()));
+                        gpu_fallback = true;
+                        file_cli = Arc::new(ConvertArgs { gpu: TexconvGpu::Cpu, ..(**cli).clone() });
+                    }
+                    (Err(e), _) if classify(e).is_transient() && attempts < cli.retries => {
+                        attempts += 1;
+                        let delay = retry_delay(attempts);
+                        console.println(format!(
+                            "🔁 {} [{}]; retrying in {:.1}s ({} of {})",
+                            file.display(), classify(e), delay.as_secs_f64(), attempts, cli.retries
+                        ));
+                        tokio::time::sleep(delay).await;
+                    }
+                    _ => break result,
+                }
+            },
+        };
+
+        let retried = attempts > 0 && result.is_ok();
+        FileRun { result, output_path, conflict, gpu_fallback, retried }
+    }
+}
+
+/// Where an earlier run's output for the planned `output` is: with
+/// `--content-hash`, the hashed name the previous manifest recorded for it.
+pub fn previous_output(cli: &ConvertArgs, manifest: &PreviousManifest, output: &Path) -> Option<PathBuf> {
+    match cli.content_hash {
+        Some(_) => manifest.hashed(output),
+        None => Some(output.to_path_buf()),
+    }
+}
+
+/// Pause before `--retries` attempt `attempt` (from 1): 0.5s, 1s, 2s, ...
+/// up to 8s.
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(500 << (attempt - 1).min(4))
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::processor::SkipReason;
+
+/// One discovered file in the `--dry-run-json` listing.
+#[derive(Debug, Serialize)]
+pub struct DryRunEntry {
+    pub input: String,
+    /// Where the file would be written (`archive.zip:entry` with
+    /// `--output-zip`); null when it is skipped before an output is planned.
+    pub output: Option<String>,
+    pub size_bytes: u64,
+    /// Why the file would not be converted; null when it would be.
+    pub skipped_reason: Option<String>,
+}
+
+impl DryRunEntry {
+    pub fn new(input: &Path, output: Option<String>, skipped_reason: Option<String>) -> Self {
+        DryRunEntry {
+            input: input.display().to_string(),
+            output,
+            size_bytes: std::fs::metadata(input).map(|metadata| metadata.len()).unwrap_or(0),
+            skipped_reason,
+        }
+    }
+}
+
+/// Entries for the files dropped by `--exclude` and the size limits, which
+/// are listed even when nothing is left to convert.
+pub fn filtered_entries(excluded: &[(PathBuf, String)], too_big_or_small: &[(PathBuf, SkipReason)]) -> Vec<DryRunEntry> {
+    let excluded = excluded
+        .iter()
+        .map(|(file, pattern)| DryRunEntry::new(file, None, Some(format!("excluded by {}", pattern))));
+    let sized = too_big_or_small
+        .iter()
+        .map(|(file, reason)| DryRunEntry::new(file, None, Some(reason.describe().to_string())));
+    excluded.chain(sized).collect()
+}
+
+/// Writes the listing to stdout as a JSON array, sorted by input path.
+pub fn print_dry_run_json(mut entries: Vec<DryRunEntry>) -> Result<()> {
+    entries.sort_by(|a, b| a.input.cmp(&b.input));
+    println!("{}", serde_json::to_string_pretty(&entries)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_glob;
+
+    #[test]
+    fn entries_serialize_with_nulls_for_missing_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("rock.dds");
+        std::fs::write(&file, [0; 200]).unwrap();
+
+        let converted = serde_json::to_value(DryRunEntry::new(&file, Some("out/rock.png".to_string()), None)).unwrap();
+        assert_eq!(
+            converted,
+            serde_json::json!({ "input": file.display().to_string(), "output": "out/rock.png", "size_bytes": 200, "skipped_reason": null })
+        );
+        let skipped = serde_json::to_value(DryRunEntry::new(&file, None, Some("up to date".to_string()))).unwrap();
+        assert_eq!((&skipped["output"], &skipped["skipped_reason"]), (&serde_json::Value::Null, &serde_json::json!("up to date")));
+    }
+
+    #[test]
+    fn unreadable_files_count_as_empty() {
+        let entry = DryRunEntry::new(Path::new("no/such/file.dds"), None, None);
+        assert_eq!(entry.size_bytes, 0);
+    }
+
+    #[test]
+    fn filtered_files_carry_the_pattern_or_the_size_limit() {
+        let pattern = parse_glob("**/lod/**").unwrap().pattern;
+        let excluded = [(PathBuf::from("in/lod/a.dds"), pattern)];
+        let sized = [(PathBuf::from("in/tiny.dds"), SkipReason::TooSmall), (PathBuf::from("in/huge.dds"), SkipReason::TooLarge)];
+        let reasons: Vec<(String, Option<String>, Option<String>)> = filtered_entries(&excluded, &sized)
+            .into_iter()
+            .map(|entry| (entry.input, entry.output, entry.skipped_reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                ("in/lod/a.dds".to_string(), None, Some("excluded by **/lod/**".to_string())),
+                ("in/tiny.dds".to_string(), None, Some("smaller than --min-size".to_string())),
+                ("in/huge.dds".to_string(), None, Some("larger than --max-size".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+/// Decodes text written by a child process (texconv, ffmpeg).
+///
+/// Console tools on Windows write in the console's OEM code page (CP850 on a
+/// German install, CP437 on an English one), so running their bytes through
+/// `from_utf8_lossy` turns every umlaut into garbage. Valid UTF-8 is taken as
+/// is; anything else is decoded with the active console code page, falling
+/// back to lossy UTF-8 when the code page is unknown.
+pub fn decode_process_output(bytes: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+
+    console_code_page()
+        .and_then(|code_page| decode_with_code_page(bytes, code_page))
+        .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Decodes `bytes` in the given Windows code page, or `None` if the code page
+/// isn't one we have a table for.
+pub fn decode_with_code_page(bytes: &[u8], code_page: u32) -> Option<String> {
+    if let Some(table) = oem_cp::code_table::DECODING_TABLE_CP_MAP.get(&(code_page as u16)) {
+        return Some(table.decode_string_lossy(bytes));
+    }
+
+    let encoding = match code_page {
+        65001 => encoding_rs::UTF_8,
+        874 => encoding_rs::WINDOWS_874,
+        932 => encoding_rs::SHIFT_JIS,
+        936 => encoding_rs::GBK,
+        949 => encoding_rs::EUC_KR,
+        950 => encoding_rs::BIG5,
+        1250 => encoding_rs::WINDOWS_1250,
+        1251 => encoding_rs::WINDOWS_1251,
+        1252 => encoding_rs::WINDOWS_1252,
+        1253 => encoding_rs::WINDOWS_1253,
+        1254 => encoding_rs::WINDOWS_1254,
+        1255 => encoding_rs::WINDOWS_1255,
+        1256 => encoding_rs::WINDOWS_1256,
+        1257 => encoding_rs::WINDOWS_1257,
+        1258 => encoding_rs::WINDOWS_1258,
+        _ => return None,
+    };
+
+    let (text, _, _) = encoding.decode(bytes);
+    Some(text.into_owned())
+}
+
+#[cfg(windows)]
+fn console_code_page() -> Option<u32> {
+    use windows_sys::Win32::Globalization::GetOEMCP;
+    use windows_sys::Win32::System::Console::GetConsoleOutputCP;
+
+    // GetConsoleOutputCP returns 0 when we have no console (e.g. launched
+    // from a GUI); child processes then fall back to the OEM code page too.
+    let code_page = unsafe { GetConsoleOutputCP() };
+    if code_page != 0 {
+        Some(code_page)
+    } else {
+        Some(unsafe { GetOEMCP() })
+    }
+}
+
+#[cfg(not(windows))]
+fn console_code_page() -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "Größe überschritten" as a German console writes it.
+    const CP850: &[u8] = b"Gr\x94\xe1e \x81berschritten";
+
+    #[test]
+    fn cp850_bytes_decode_to_umlauts() {
+        assert_eq!(decode_with_code_page(CP850, 850).unwrap(), "Größe überschritten");
+        // The same bytes read as Windows-1252 are the garbage users saw
+        assert_eq!(decode_with_code_page(CP850, 1252).unwrap(), "Gr\u{201d}áe \u{81}berschritten");
+        // Where CP437 and CP850 differ, the table for the given one is used
+        assert_eq!(decode_with_code_page(b"\xd0", 850).unwrap(), "ð");
+        assert_eq!(decode_with_code_page(b"\xd0", 437).unwrap(), "╨");
+    }
+
+    #[test]
+    fn ansi_code_pages_and_unknown_ones() {
+        assert_eq!(decode_with_code_page(b"Gr\xf6\xdfe", 1252).unwrap(), "Größe");
+        assert_eq!(decode_with_code_page(b"\xce\xf8\xe8\xe1\xea\xe0", 1251).unwrap(), "Ошибка");
+        assert_eq!(decode_with_code_page(b"caf\xc3\xa9", 65001).unwrap(), "café");
+        assert_eq!(decode_with_code_page(CP850, 12345), None);
+    }
+
+    #[test]
+    fn utf8_is_kept_and_anything_else_falls_back() {
+        assert_eq!(decode_process_output("Größe".as_bytes()), "Größe");
+        // Without a console code page (not Windows), invalid UTF-8 is decoded lossily
+        #[cfg(not(windows))]
+        assert_eq!(decode_process_output(CP850), "Gr\u{fffd}\u{fffd}e \u{fffd}berschritten");
+    }
+}
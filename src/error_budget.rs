@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Files that must finish before `--max-error-rate` is checked, so a few
+/// early failures don't stop a run.
+const RATE_WARMUP: usize = 100;
+
+pub fn parse_error_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(rate),
+        _ => Err(format!("'{}' is not a rate between 0 and 1 (e.g. 0.2)", value)),
+    }
+}
+
+/// `--max-errors` / `--max-error-rate`: counts finished and failed files
+/// across workers and trips once a limit is crossed.
+#[derive(Debug, Default)]
+pub struct ErrorBudget {
+    max_errors: Option<usize>,
+    max_rate: Option<f64>,
+    finished: AtomicUsize,
+    failed: AtomicUsize,
+    tripped: OnceLock<String>,
+}
+
+impl ErrorBudget {
+    pub fn new(max_errors: Option<usize>, max_rate: Option<f64>) -> Self {
+        ErrorBudget { max_errors, max_rate, ..ErrorBudget::default() }
+    }
+
+    /// Counts one finished file.
+    pub fn record(&self, failed: bool) {
+        let finished = self.finished.fetch_add(1, Ordering::SeqCst) + 1;
+        let failures = if failed {
+            self.failed.fetch_add(1, Ordering::SeqCst) + 1
+        } else {
+            self.failed.load(Ordering::SeqCst)
+        };
+
+        if let Some(max) = self.max_errors {
+            if failures > max {
+                let _ = self.tripped.set(format!("{} files failed, more than --max-errors {}", failures, max));
+            }
+        }
+        if let Some(max) = self.max_rate {
+            let rate = failures as f64 / finished as f64;
+            if finished >= RATE_WARMUP && rate > max {
+                let _ = self.tripped.set(format!(
+                    "{} of {} files failed ({:.0}%), more than --max-error-rate {}",
+                    failures, finished, rate * 100.0, max
+                ));
+            }
+        }
+    }
+
+    /// Why the run should stop, once a limit was crossed.
+    pub fn tripped(&self) -> Option<&str> {
+        self.tripped.get().map(String::as_str)
+    }
+}
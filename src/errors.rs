@@ -0,0 +1,259 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Stable, machine-readable failure category for a file. The string form
+/// (`as_str`) is what scripts should match on; the human message may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorCode {
+    /// The file is locked by another process; usually worth retrying.
+    SharingViolation,
+    AccessDenied,
+    NotFound,
+    DiskFull,
+    OutOfMemory,
+    /// Any other I/O failure.
+    IoError,
+    UnsupportedFormat,
+    CorruptHeader,
+    CorruptInput,
+    /// texconv's GPU path failed (device removed, hung or missing).
+    GpuFailure,
+    /// texconv exited non-zero without a recognizable reason.
+    TexconvExit { code: i32 },
+    /// texconv reported success but no output file appeared.
+    OutputMissing,
+    /// Decoding or encoding in-process (image crate, WebP) failed.
+    EncoderError,
+    /// A `--dedupe-link` duplicate whose original failed.
+    DuplicateOfFailed,
+    /// The `--crop` rectangle doesn't fit the texture (`--crop-mode error`).
+    CropOutOfBounds,
+    /// The texture has fewer mip levels than `--mip` asks for.
+    MipOutOfRange,
+    /// The output already exists (`--on-conflict error`).
+    OutputExists,
+    /// texconv or ffmpeg ran past `--timeout` and was killed.
+    Timeout,
+    /// The written output doesn't decode (`--verify`).
+    CorruptOutput,
+    Unknown,
+}
+
+impl ErrorCode {
+    /// Failures that often pass on their own, e.g. while an antivirus scans
+    /// a freshly extracted file; `--retries` tries these again.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ErrorCode::SharingViolation | ErrorCode::AccessDenied)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::SharingViolation => "sharing_violation",
+            ErrorCode::AccessDenied => "access_denied",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::DiskFull => "disk_full",
+            ErrorCode::OutOfMemory => "out_of_memory",
+            ErrorCode::IoError => "io_error",
+            ErrorCode::UnsupportedFormat => "unsupported_format",
+            ErrorCode::CorruptHeader => "corrupt_header",
+            ErrorCode::CorruptInput => "corrupt_input",
+            ErrorCode::GpuFailure => "gpu_failure",
+            ErrorCode::TexconvExit { .. } => "texconv_exit",
+            ErrorCode::OutputMissing => "output_missing",
+            ErrorCode::EncoderError => "encoder_error",
+            ErrorCode::DuplicateOfFailed => "duplicate_of_failed",
+            ErrorCode::CropOutOfBounds => "crop_out_of_bounds",
+            ErrorCode::MipOutOfRange => "mip_out_of_range",
+            ErrorCode::OutputExists => "output_exists",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::CorruptOutput => "corrupt_output",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error whose category is known where it is raised.
+#[derive(Debug)]
+pub struct Classified {
+    pub code: ErrorCode,
+    message: String,
+}
+
+impl Classified {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> anyhow::Error {
+        anyhow::Error::new(Self { code, message: message.into() })
+    }
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Classified {}
+
+/// A non-zero texconv exit, kept structured so it can be classified from the
+/// HRESULT texconv prints.
+#[derive(Debug)]
+pub struct TexconvFailure {
+    pub source: PathBuf,
+    pub exit_code: Option<i32>,
+    pub stderr: String,
+    pub stdout: String,
+}
+
+impl TexconvFailure {
+    pub fn code(&self) -> ErrorCode {
+        // texconv reports failures as e.g. "FAILED (80070020: The process cannot access the file...)"
+        let text = format!("{}\n{}", self.stderr, self.stdout).to_lowercase();
+        let hresult = |hex: &str| text.contains(hex);
+
+        // DXGI_ERROR_DEVICE_REMOVED / _HUNG / _RESET / _DRIVER_INTERNAL_ERROR,
+        // or no usable Direct3D device at all
+        if hresult("887a0005") || hresult("887a0006") || hresult("887a0007") || hresult("887a0020")
+            || text.contains("direct3d device") || text.contains("invalid gpu adapter")
+        {
+            ErrorCode::GpuFailure
+        } else if hresult("80070020") || hresult("80070021") || text.contains("being used by another process") {
+            ErrorCode::SharingViolation
+        } else if hresult("80070005") || text.contains("access is denied") {
+            ErrorCode::AccessDenied
+        } else if hresult("80070002") || hresult("80070003") {
+            ErrorCode::NotFound
+        } else if hresult("80070070") || hresult("80070027") {
+            ErrorCode::DiskFull
+        } else if hresult("8007000e") {
+            ErrorCode::OutOfMemory
+        } else if hresult("88982f50") || hresult("88982f80") || hresult("80070032") || hresult("80004001") {
+            ErrorCode::UnsupportedFormat
+        } else if hresult("88982f61") || hresult("88982f62") || hresult("8007000d") || hresult("80070057") {
+            ErrorCode::CorruptInput
+        } else {
+            ErrorCode::TexconvExit { code: self.exit_code.unwrap_or(-1) }
+        }
+    }
+}
+
+impl fmt::Display for TexconvFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "texconv failed for {}: code {}\nStderr: {}\nStdout: {}",
+            self.source.display(),
+            self.exit_code.unwrap_or(-1),
+            self.stderr,
+            self.stdout
+        )
+    }
+}
+
+impl std::error::Error for TexconvFailure {}
+
+/// Finds the category of a per-file failure by walking its cause chain.
+pub fn classify(error: &anyhow::Error) -> ErrorCode {
+    for cause in error.chain() {
+        if let Some(classified) = cause.downcast_ref::<Classified>() {
+            return classified.code;
+        }
+        if let Some(failure) = cause.downcast_ref::<TexconvFailure>() {
+            return failure.code();
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return classify_io(io);
+        }
+        if let Some(image_error) = cause.downcast_ref::<image::ImageError>() {
+            return match image_error {
+                image::ImageError::IoError(io) => classify_io(io),
+                _ => ErrorCode::EncoderError,
+            };
+        }
+    }
+    ErrorCode::Unknown
+}
+
+fn classify_io(error: &std::io::Error) -> ErrorCode {
+    // ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION have no ErrorKind of their own
+    #[cfg(windows)]
+    if matches!(error.raw_os_error(), Some(32) | Some(33)) {
+        return ErrorCode::SharingViolation;
+    }
+
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied => ErrorCode::AccessDenied,
+        std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+        std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded => ErrorCode::DiskFull,
+        std::io::ErrorKind::OutOfMemory => ErrorCode::OutOfMemory,
+        _ => ErrorCode::IoError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANNER: &str = "Microsoft (R) DirectX Texture Converter [DirectXTex] Version 2024.6.23.0\nCopyright (C) Microsoft Corp.\n\n";
+
+    /// How a texconv run that printed `stdout` (where texconv writes its
+    /// errors) and `stderr` is classified.
+    fn code(exit_code: i32, stdout: &str, stderr: &str) -> ErrorCode {
+        let failure = TexconvFailure {
+            source: PathBuf::from("rock.dds"),
+            exit_code: Some(exit_code),
+            stderr: stderr.to_string(),
+            stdout: format!("{}{}", BANNER, stdout),
+        };
+        classify(&anyhow::Error::new(failure).context("Failed to convert rock.dds"))
+    }
+
+    #[test]
+    fn texconv_output_samples_map_to_codes() {
+        let samples = [
+            ("reading rock.dds FAILED (80070020: The process cannot access the file because it is being used by another process.)", ErrorCode::SharingViolation),
+            ("reading rock.dds FAILED (80070021: The process cannot access the file because another process has locked a portion of the file.)", ErrorCode::SharingViolation),
+            ("writing out\\rock.png FAILED (80070005: Access is denied.)", ErrorCode::AccessDenied),
+            ("reading rock.dds FAILED (80070002: The system cannot find the file specified.)", ErrorCode::NotFound),
+            ("writing out\\rock.png FAILED (80070003: The system cannot find the path specified.)", ErrorCode::NotFound),
+            ("writing out\\rock.png FAILED (80070070: There is not enough space on the disk.)", ErrorCode::DiskFull),
+            ("reading rock.dds FAILED (8007000E: Not enough memory resources are available to complete this operation.)", ErrorCode::OutOfMemory),
+            ("reading rock.dds FAILED (80070032: The request is not supported.)", ErrorCode::UnsupportedFormat),
+            ("writing out\\rock.png FAILED (88982F50: The component cannot be found.)", ErrorCode::UnsupportedFormat),
+            ("reading rock.dds FAILED (8007000D: The data is invalid.)", ErrorCode::CorruptInput),
+            ("reading rock.dds FAILED (80070057: The parameter is incorrect.)", ErrorCode::CorruptInput),
+            ("reading rock.dds  compressing (BC7_UNORM) FAILED (887A0005: The GPU device instance has been suspended.)", ErrorCode::GpuFailure),
+            ("reading rock.dds  compressing (BC7_UNORM) FAILED (887a0006: The GPU will not respond to more commands.)", ErrorCode::GpuFailure),
+        ];
+        for (stdout, expected) in samples {
+            assert_eq!(code(1, stdout, ""), expected, "{}", stdout);
+        }
+    }
+
+    #[test]
+    fn stderr_is_read_too_and_unknown_failures_keep_the_exit_code() {
+        assert_eq!(code(1, "", "ERROR: Failed to create Direct3D device"), ErrorCode::GpuFailure);
+        assert_eq!(code(1, "", "ERROR: Invalid GPU adapter index (2)!"), ErrorCode::GpuFailure);
+        let unknown = code(1, "Invalid value specified with -f (R8G8B8A8_NOPE)\n", "");
+        assert_eq!(unknown, ErrorCode::TexconvExit { code: 1 });
+        assert_eq!(unknown.as_str(), "texconv_exit");
+        // A GPU failure wins over the access error it caused
+        assert_eq!(code(1, "FAILED (887a0005)\nFAILED (80070005: Access is denied.)", ""), ErrorCode::GpuFailure);
+    }
+
+    #[test]
+    fn our_own_errors_classify_through_context() {
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        assert_eq!(classify(&anyhow::Error::new(io).context("Failed to write rock.png")), ErrorCode::AccessDenied);
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(classify(&anyhow::Error::new(missing)), ErrorCode::NotFound);
+        let classified = Classified::error(ErrorCode::MipOutOfRange, "rock.dds has 3 mips");
+        assert_eq!(classify(&classified.context("outer")), ErrorCode::MipOutOfRange);
+        assert_eq!(classify(&anyhow::anyhow!("something else")), ErrorCode::Unknown);
+        assert!(ErrorCode::SharingViolation.is_transient() && !ErrorCode::UnsupportedFormat.is_transient());
+    }
+}
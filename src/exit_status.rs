@@ -0,0 +1,37 @@
+use std::process::ExitCode;
+
+/// How a run ended, as the process exit code scripts can check. A fatal
+/// error (bad arguments, texconv missing, a failed file without
+/// `--continue-on-error`, a run aborted at an `--interactive` prompt) exits
+/// with 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// Every file was converted or deliberately skipped: 0.
+    Success,
+    /// The run finished, or was stopped by `--max-errors` /
+    /// `--max-error-rate`, but some files failed: 2.
+    FileErrors,
+    /// No input files were found or left after filtering: 3.
+    NothingMatched,
+}
+
+impl RunStatus {
+    pub const FATAL: u8 = 1;
+
+    /// Name used in `--log-file` and `--report`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Success => "success",
+            RunStatus::FileErrors => "file_errors",
+            RunStatus::NothingMatched => "nothing_matched",
+        }
+    }
+
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            RunStatus::Success => ExitCode::SUCCESS,
+            RunStatus::FileErrors => ExitCode::from(2),
+            RunStatus::NothingMatched => ExitCode::from(3),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, RgbaImage};
+use serde::Serialize;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::animation::encode_webp_animation;
+use crate::cli::ExplodeArgs;
+use crate::console::Console;
+use crate::sprite::write_sprite;
+use crate::utils::texture_stem;
+use crate::webp_anim::decode_animated_webp;
+
+/// What `explode` writes for each animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExplodeTarget {
+    /// One PNG per frame plus a <name>.frames.json with the delays
+    Frames,
+    /// One atlas PNG plus a .sprite with per-cell durations, which `animate` reads back
+    Sprite,
+    /// The frames re-encoded as a new animation (--animation-format)
+    Animation,
+}
+
+const ANIMATED_EXTENSIONS: &[&str] = &["webp", "gif", "png", "apng"];
+
+/// Decodes an animated WebP, GIF or APNG into full-canvas frames and their
+/// display times in milliseconds.
+pub fn decode_animation(path: &Path) -> Result<Vec<(RgbaImage, u32)>> {
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let open = || -> Result<BufReader<std::fs::File>> {
+        Ok(BufReader::new(std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?))
+    };
+    let timed = |frame: image::Frame| {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let delay = (numerator as f64 / denominator.max(1) as f64).round() as u32;
+        (frame.into_buffer(), delay)
+    };
+    let frames = match extension.as_str() {
+        "webp" => {
+            let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            decode_animated_webp(&bytes)?
+        }
+        "gif" => GifDecoder::new(open()?)?.into_frames().collect_frames()?.into_iter().map(timed).collect(),
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(open()?)?;
+            if !decoder.is_apng() {
+                anyhow::bail!("{} is a still PNG, not an APNG", path.display());
+            }
+            decoder.apng().into_frames().collect_frames()?.into_iter().map(timed).collect()
+        }
+        _ => anyhow::bail!("{} is not an animated WebP, GIF or APNG", path.display()),
+    };
+    if frames.is_empty() {
+        anyhow::bail!("{} has no frames", path.display());
+    }
+    Ok(frames)
+}
+
+#[derive(Serialize)]
+struct FrameEntry {
+    file: String,
+    delay_ms: u32,
+}
+
+/// Writes `<dir>/<stem>_001.png`, ... and `<dir>/<stem>.frames.json`.
+fn write_frames(frames: &[(RgbaImage, u32)], dir: &Path, stem: &str, console: &Console, verbose: bool) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut entries = Vec::new();
+    for (index, (frame, delay)) in frames.iter().enumerate() {
+        let file = format!("{}_{:03}.png", stem, index + 1);
+        let path = dir.join(&file);
+        frame.save(&path).with_context(|| format!("Failed to write {}", path.display()))?;
+        if verbose {
+            console.println(format!("  {} ({} ms)", path.display(), delay));
+        }
+        entries.push(FrameEntry { file, delay_ms: *delay });
+    }
+    let manifest = dir.join(format!("{}.frames.json", stem));
+    std::fs::write(&manifest, serde_json::to_string_pretty(&entries)?)
+        .with_context(|| format!("Failed to write {}", manifest.display()))
+}
+
+/// `explode` subcommand.
+pub async fn run_explode(args: &ExplodeArgs) -> Result<()> {
+    let console = Console::plain();
+    let inputs: Vec<PathBuf> = if args.input.is_file() {
+        vec![args.input.clone()]
+    } else {
+        WalkDir::new(&args.input)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                path.is_file() && path.extension().is_some_and(|ext| ANIMATED_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+            })
+            .filter(|path| !path.starts_with(&args.output))
+            .collect()
+    };
+    if inputs.is_empty() {
+        println!("❌ No animated WebP, GIF or APNG files found!");
+        return Ok(());
+    }
+
+    let single = inputs.len() == 1;
+    let mut exploded = 0;
+    let mut failures = 0;
+    for input in &inputs {
+        let stem = args.naming.cleanup().apply(&texture_stem(input));
+        let frames = match decode_animation(input) {
+            Ok(frames) if frames.len() > 1 || single => frames,
+            // Still images in a folder are not what this is for
+            Ok(_) => {
+                if args.verbose {
+                    println!("⏭️  {}: not animated", input.display());
+                }
+                continue;
+            }
+            Err(e) => {
+                failures += 1;
+                println!("❌ Failed to decode {}: {:#}", input.display(), e);
+                continue;
+            }
+        };
+
+        let durations: Vec<u32> = frames.iter().map(|(_, delay)| *delay).collect();
+        let result = match args.to {
+            ExplodeTarget::Frames => {
+                let dir = args.output.join(&stem);
+                write_frames(&frames, &dir, &stem, &console, args.verbose).map(|_| dir)
+            }
+            ExplodeTarget::Sprite => write_sprite(&frames, &args.output, &stem, args.columns),
+            ExplodeTarget::Animation => {
+                let output = args.output.join(format!("{}.{}", stem, args.animation_format));
+                let images: Vec<RgbaImage> = frames.into_iter().map(|(frame, _)| frame).collect();
+                match std::fs::create_dir_all(&args.output).with_context(|| format!("Failed to create {}", args.output.display())) {
+                    Ok(()) => encode_webp_animation(&images, &output, &durations, false, None, &console).await.map(|_| output),
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        match result {
+            Ok(output) => {
+                exploded += 1;
+                let total: u32 = durations.iter().sum();
+                println!("✅ {}: {} frame(s), {} ms -> {}", input.display(), durations.len(), total, output.display());
+            }
+            Err(e) => {
+                failures += 1;
+                println!("❌ Failed to explode {}: {:#}", input.display(), e);
+            }
+        }
+    }
+
+    println!("📊 Exploded: {}", exploded);
+    if failures > 0 {
+        anyhow::bail!("{} animation(s) failed", failures);
+    }
+    Ok(())
+}
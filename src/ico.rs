@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::{imageops::FilterType, ColorType, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Entries this size and up are stored as PNG; smaller ones as 32-bit
+/// bitmaps, which every Windows version reads.
+const PNG_ENTRY_MIN: u32 = 64;
+
+/// `--ico-sizes`, largest first.
+#[derive(Debug, Clone)]
+pub struct IcoSizes(pub Vec<u32>);
+
+/// `--ico-sizes 16,32,256`: sizes from 1 to 256, written largest first.
+pub fn parse_ico_sizes(value: &str) -> Result<IcoSizes, String> {
+    let mut sizes = Vec::new();
+    for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let size: u32 = part
+            .parse()
+            .ok()
+            .filter(|size| (1..=256).contains(size))
+            .ok_or_else(|| format!("'{}' is not an icon size from 1 to 256", part))?;
+        if !sizes.contains(&size) {
+            sizes.push(size);
+        }
+    }
+    if sizes.is_empty() {
+        return Err("give at least one icon size, e.g. 16,32,256".to_string());
+    }
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(IcoSizes(sizes))
+}
+
+/// A bottom-up 32-bit DIB with its AND mask, as ICO files store bitmaps.
+fn bitmap_entry(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let mask_stride = width.div_ceil(32) * 4;
+    let pixel_bytes = width * height * 4;
+    let mut dib = Vec::with_capacity((40 + pixel_bytes + mask_stride * height) as usize);
+    for value in [40, width, height * 2] {
+        dib.extend_from_slice(&value.to_le_bytes());
+    }
+    dib.extend_from_slice(&1u16.to_le_bytes());
+    dib.extend_from_slice(&32u16.to_le_bytes());
+    for value in [0, pixel_bytes + mask_stride * height, 0, 0, 0, 0] {
+        dib.extend_from_slice(&value.to_le_bytes());
+    }
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            dib.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+    // Set bits mark fully transparent pixels, for readers that ignore alpha
+    for y in (0..height).rev() {
+        let mut row = vec![0u8; mask_stride as usize];
+        for x in 0..width {
+            if image.get_pixel(x, y)[3] == 0 {
+                row[(x / 8) as usize] |= 0x80 >> (x % 8);
+            }
+        }
+        dib.extend_from_slice(&row);
+    }
+    dib
+}
+
+/// Writes the RGBA PNG at `input` as a multi-size ICO at `output`.
+/// Non-square images are centred on a transparent square first. Sizes
+/// larger than the image are left out; if that leaves none, the image's
+/// own size is used. Returns the sizes written.
+pub fn write_ico(input: &Path, output: &Path, sizes: &[u32]) -> Result<Vec<u32>> {
+    let image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    let side = image.width().max(image.height());
+    let mut square = RgbaImage::new(side, side);
+    image::imageops::overlay(&mut square, &image, ((side - image.width()) / 2) as i64, ((side - image.height()) / 2) as i64);
+
+    let mut written: Vec<u32> = sizes.iter().copied().filter(|size| *size <= side).collect();
+    if written.is_empty() {
+        written.push(side.min(256));
+    }
+
+    let mut encoded = Vec::new();
+    for &size in &written {
+        let scaled = image::imageops::resize(&square, size, size, FilterType::Lanczos3);
+        let frame = if size >= PNG_ENTRY_MIN {
+            IcoFrame::as_png(scaled.as_raw(), size, size, ColorType::Rgba8)?
+        } else {
+            IcoFrame::with_encoded(bitmap_entry(&scaled), size, size, ColorType::Rgba8)?
+        };
+        encoded.push(frame);
+    }
+
+    let file = File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    IcoEncoder::new(BufWriter::new(file))
+        .encode_images(&encoded)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (width, height, bytes) of each entry in an ICO's directory.
+    fn entries(ico: &[u8]) -> Vec<(u32, u32, &[u8])> {
+        assert_eq!(ico[..4], [0, 0, 1, 0]);
+        let count = u16::from_le_bytes([ico[4], ico[5]]) as usize;
+        (0..count)
+            .map(|index| {
+                let entry = &ico[6 + index * 16..][..16];
+                let side = |byte: u8| if byte == 0 { 256 } else { byte as u32 };
+                let size = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+                let offset = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+                (side(entry[0]), side(entry[1]), &ico[offset..offset + size])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn written_icon_reads_back_with_every_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("logo.png");
+        let ico = dir.path().join("logo.ico");
+        // 80x40 orange: centred on an 80x80 square with transparent bands
+        RgbaImage::from_pixel(80, 40, image::Rgba([255, 128, 0, 255])).save(&png).unwrap();
+
+        let sizes = parse_ico_sizes("16, 256, 64, 16").unwrap();
+        assert_eq!(sizes.0, [256, 64, 16]);
+        assert_eq!(write_ico(&png, &ico, &sizes.0).unwrap(), [64, 16]);
+
+        let bytes = std::fs::read(&ico).unwrap();
+        let entries = entries(&bytes);
+        assert_eq!(entries.iter().map(|(w, h, _)| (*w, *h)).collect::<Vec<_>>(), [(64, 64), (16, 16)]);
+
+        // The large entry is a PNG
+        let large = image::load_from_memory_with_format(entries[0].2, image::ImageFormat::Png).unwrap().to_rgba8();
+        assert_eq!(large.dimensions(), (64, 64));
+        assert_eq!(large.get_pixel(32, 32).0, [255, 128, 0, 255]);
+        assert_eq!(large.get_pixel(32, 2)[3], 0);
+
+        // The small one is a bottom-up BGRA bitmap followed by its mask
+        let small = entries[1].2;
+        assert_eq!(u32::from_le_bytes(small[..4].try_into().unwrap()), 40);
+        assert_eq!(u32::from_le_bytes(small[8..12].try_into().unwrap()), 32);
+        let pixel = |x: usize, y: usize| &small[40 + ((15 - y) * 16 + x) * 4..][..4];
+        assert_eq!(pixel(8, 8), [0, 128, 255, 255]);
+        assert_eq!(pixel(8, 0)[3], 0);
+        let mask = &small[40 + 16 * 16 * 4..];
+        assert_eq!(mask.len(), 16 * 4);
+        // Top row (stored last) is transparent, the middle row opaque
+        assert_eq!(mask[15 * 4..15 * 4 + 2], [0xff, 0xff]);
+        assert_eq!(mask[8 * 4..8 * 4 + 2], [0, 0]);
+
+        // And the image crate reads the whole file
+        let decoded = image::open(&ico).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 64));
+    }
+
+    #[test]
+    fn icon_sizes_must_be_from_1_to_256() {
+        assert!(parse_ico_sizes("0").unwrap_err().contains("from 1 to 256"));
+        assert!(parse_ico_sizes("512").unwrap_err().contains("from 1 to 256"));
+        assert!(parse_ico_sizes(" , ").unwrap_err().contains("at least one"));
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::dds::DdsHeader;
+use crate::utils::find_dds_files;
+
+/// One row of `--info` output. Either the header fields or `error` are set.
+#[derive(Serialize)]
+pub struct TextureInfo {
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mips: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub array_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cubemap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub srgb: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncompressed_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TextureInfo {
+    pub fn inspect(path: &Path) -> Self {
+        match DdsHeader::read(path) {
+            Ok(header) => Self {
+                path: path.to_path_buf(),
+                format: Some(header.format_name()),
+                width: Some(header.width),
+                height: Some(header.height),
+                depth: Some(header.depth),
+                mips: Some(header.mip_count),
+                array_size: Some(header.array_size),
+                cubemap: Some(header.is_cubemap),
+                alpha: header.has_alpha,
+                srgb: Some(header.is_srgb()),
+                uncompressed_bytes: Some(header.estimated_uncompressed_size()),
+                error: None,
+            },
+            Err(e) => Self {
+                path: path.to_path_buf(),
+                format: None,
+                width: None,
+                height: None,
+                depth: None,
+                mips: None,
+                array_size: None,
+                cubemap: None,
+                alpha: None,
+                srgb: None,
+                uncompressed_bytes: None,
+                error: Some(format!("{:#}", e)),
+            },
+        }
+    }
+}
+
+/// Prints header information for one DDS file or every DDS file in a tree,
+/// without invoking texconv. Unreadable headers are reported per file.
+pub fn run_info(input: &Path, json: bool) -> Result<()> {
+    let files = if input.is_file() {
+        vec![input.to_path_buf()]
+    } else {
+        find_dds_files(input, None, None)
+    };
+
+    let rows: Vec<TextureInfo> = files.iter().map(|file| TextureInfo::inspect(file)).collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    if rows.is_empty() {
+        println!("❌ No .dds files found!");
+        return Ok(());
+    }
+
+    println!(
+        "{:<22} {:>11} {:>5} {:>5} {:>4} {:>5} {:>5} {:>10}  Path",
+        "Format", "Size", "Mips", "Array", "Cube", "Alpha", "sRGB", "Decoded"
+    );
+    for row in &rows {
+        let relative = row.path.strip_prefix(input).ok()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(&row.path);
+
+        if let Some(error) = &row.error {
+            println!("{:<22} {}  {}", "⚠️  unreadable", error, relative.display());
+            continue;
+        }
+
+        let size = match (row.width, row.height, row.depth) {
+            (Some(w), Some(h), Some(d)) if d > 1 => format!("{}x{}x{}", w, h, d),
+            (Some(w), Some(h), _) => format!("{}x{}", w, h),
+            _ => "-".to_string(),
+        };
+        let alpha = match row.alpha {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "?",
+        };
+
+        println!(
+            "{:<22} {:>11} {:>5} {:>5} {:>4} {:>5} {:>5} {:>10}  {}",
+            row.format.as_deref().unwrap_or("-"),
+            size,
+            row.mips.unwrap_or(0),
+            row.array_size.unwrap_or(0),
+            if row.cubemap == Some(true) { "yes" } else { "no" },
+            alpha,
+            if row.srgb == Some(true) { "yes" } else { "no" },
+            format_bytes(row.uncompressed_bytes.unwrap_or(0)),
+            relative.display()
+        );
+    }
+
+    let unreadable = rows.iter().filter(|row| row.error.is_some()).count();
+    println!("📊 {} file(s) inspected, {} unreadable", rows.len(), unreadable);
+
+    Ok(())
+}
+
+/// Parses a size such as `128`, `4KB`, `2MiB` or `1.5 GB`: decimal units
+/// (KB, MB, GB, TB) are powers of 1000, binary ones (KiB, MiB, GiB, TiB)
+/// powers of 1024, and a bare number or `B` is bytes. Units ignore case.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let split = trimmed.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        "tb" | "t" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(format!("'{}' has an unknown unit (use B, KB, MB, GB, KiB, MiB or GiB)", value)),
+    };
+    let bytes = match number.parse::<u64>() {
+        Ok(whole) => whole.checked_mul(multiplier),
+        Err(_) => number
+            .parse::<f64>()
+            .ok()
+            .map(|fraction| (fraction * multiplier as f64).round())
+            .filter(|bytes| bytes.is_finite() && *bytes < u64::MAX as f64)
+            .map(|bytes| bytes as u64),
+    };
+    bytes.ok_or_else(|| format!("'{}' is not a size (e.g. 128, 4KB or 2MiB)", value))
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_take_decimal_and_binary_units_in_any_case() {
+        let table = [
+            ("128", 128),
+            ("128B", 128),
+            ("4KB", 4_000),
+            ("4kb", 4_000),
+            ("4k", 4_000),
+            ("4 KB", 4_000),
+            (" 2MB ", 2_000_000),
+            ("1G", 1_000_000_000),
+            ("1TB", 1_000_000_000_000),
+            ("4KiB", 4_096),
+            ("2MiB", 2_097_152),
+            ("2mib", 2_097_152),
+            ("1GiB", 1 << 30),
+            ("1TiB", 1 << 40),
+            ("0", 0),
+        ];
+        for (value, bytes) in table {
+            assert_eq!(parse_size(value), Ok(bytes), "{}", value);
+        }
+    }
+
+    #[test]
+    fn fractional_sizes_round_to_whole_bytes() {
+        assert_eq!(parse_size("1.5KB"), Ok(1_500));
+        assert_eq!(parse_size("1.5KiB"), Ok(1_536));
+        assert_eq!(parse_size("0.5MiB"), Ok(524_288));
+        assert_eq!(parse_size("0.0001KB"), Ok(0));
+        assert_eq!(parse_size(".5K"), Ok(500));
+    }
+
+    #[test]
+    fn unknown_units_and_malformed_numbers_are_rejected() {
+        assert!(parse_size("4XB").unwrap_err().contains("unknown unit"));
+        assert!(parse_size("4KB extra").unwrap_err().contains("unknown unit"));
+        assert!(parse_size("-5").unwrap_err().contains("unknown unit"));
+        for value in ["", "KB", "1.2.3MB", "."] {
+            assert!(parse_size(value).unwrap_err().contains("is not a size"), "{}", value);
+        }
+    }
+
+    #[test]
+    fn sizes_past_u64_are_rejected() {
+        assert_eq!(parse_size("18446744073709551615"), Ok(u64::MAX));
+        assert!(parse_size("18446744073709551616").is_err());
+        assert!(parse_size("20000000TB").is_err());
+        assert!(parse_size("1e30").is_err());
+    }
+
+    #[test]
+    fn byte_counts_format_in_binary_units() {
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(2 << 20), "2.0 MiB");
+    }
+}
@@ -4,249 +4,1375 @@ mod processor;
 mod utils;
 mod animation;
 mod sprite;
+mod console;
+mod webp_anim;
+mod encoding;
+mod summary;
+mod dds;
+mod info;
+mod selftest;
+mod sidecar;
+mod dedupe;
+mod collision;
+mod archive;
+mod split_dds;
+mod alpha_merge;
+mod alpha_threshold;
+mod errors;
+mod adaptive;
+mod reproducible;
+mod metadata;
+mod compare;
+mod crop;
+mod tile;
+mod tint;
+mod adjust;
+mod watermark;
+mod composite;
+mod stitch;
+mod pot;
+mod cubemap;
+mod mips;
+mod overrides;
+mod png8;
+mod ico;
+mod quantize;
+mod content_hash;
+mod pack;
+mod unpack;
+mod diff;
+mod explode;
+mod optimize;
+mod target_size;
+mod preview;
+mod disk_space;
+mod error_budget;
+mod prompt;
+mod exit_status;
+mod name_template;
+mod watch;
+mod dry_run;
+mod run_log;
+mod report;
+mod timeout;
+mod verify;
+mod pixel_format;
+mod srgb;
+mod resize;
+mod alpha_flatten;
+mod delete_source;
+mod config;
+mod driver;
 
-use clap::Parser;
+use clap::CommandFactory;
 use anyhow::{Result, Context};
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::path::{Path, PathBuf};
 use tokio::sync::Semaphore;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
-use cli::Cli;
-use texconv::setup_texconv;
-use processor::{calculate_output_path, process_file};
-use utils::find_dds_files;
-use animation::{find_image_sequences, find_sprite_sequences, create_webp_animation, create_animation_from_sprite_sheet};
+use cli::{output_extension, print_format_table, AnimateArgs, Cli, Command, ConvertArgs, SpriteArgs};
+use console::{problem, spawn_heartbeat, status, Console};
+use exit_status::RunStatus;
+use encoding::decode_process_output;
+use summary::RunSummary;
+use errors::{classify, ErrorCode};
+use disk_space::SpaceGate;
+use driver::{previous_output, FileDriver, FileRun};
+use error_budget::ErrorBudget;
+use prompt::Prompter;
+use adaptive::{spawn_controller, AdaptiveController, Concurrency, Queueing};
+use info::{format_bytes, run_info};
+use selftest::run_self_test;
+use sidecar::{write_sidecar, SidecarSettings};
+use dedupe::plan_dedupe;
+use collision::{resolve_collisions, resolve_collisions_with, CollisionPlan, ExistingAction, ResolvedOutput};
+use archive::{entry_name, entry_names, ZipOutput};
+use std::collections::{HashMap, HashSet};
+use texconv::{command_line, run_list_gpus, setup_texconv, TexconvOptions};
+use reproducible::normalize_output;
+use metadata::supports_embedding;
+use compare::{CompareReport, CompareTarget};
+use tile::planned_grid;
+use stitch::run_stitch;
+use explode::run_explode;
+use watch::run_watch;
+use run_log::RunLog;
+use report::RunReport;
+use timeout::output_with_timeout;
+use resize::{plan_resize, ResizePlan};
+use dry_run::{filtered_entries, print_dry_run_json, DryRunEntry};
+use content_hash::{manifest_json, PreviousManifest};
+use overrides::{load_overrides, OverrideRules};
+use processor::{convert_command, find_companions, planned_output, process_duplicate, is_up_to_date, reconcile_output, FileOutcome, SkipReason};
+use utils::{case_insensitive_names, cleanup_reason, filter_by_size, filter_paths, find_dds_files, output_placement, select_items, single_file_root, texture_stem, with_suffix, NameCleanup, OutputPlacement};
+use dds::DdsHeader;
+use mips::surface_mip_suffix;
+use cubemap::{CubemapLayout, FACE_NAMES};
+use unpack::channel_name;
+use preview::run_preview;
+use delete_source::{check_delete_source, delete_source};
+use diff::{diff_trees, filter_by_keys, read_files_from, resolve_listed, run_diff};
+use animation::{plan_animation_outputs, find_image_sequences, sequence_base_name, find_sprite_sequences, find_animated_files, sprite_source, create_webp_animation, create_animation_from_sprite_sheet, reencode_animation, export_sprite_frames, AnimationSource, FrameFilters, FrameName};
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-    
-    // Handle animation mode
-    if cli.animation_mode {
-        return handle_animation_mode(&cli).await;
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(status) => status.exit_code(),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(RunStatus::FATAL)
+        }
     }
+}
+
+async fn run() -> Result<RunStatus> {
+    let cli = Cli::parse_with_legacy();
     
-    let texconv_path = setup_texconv().await?;
+    if cli.list_formats {
+        print_format_table();
+        return Ok(RunStatus::Success);
+    }
+    if cli.list_gpus {
+        run_list_gpus(cli.texconv_path.as_deref()).await?;
+        return Ok(RunStatus::Success);
+    }
     
+    let texconv = cli.texconv_path.as_deref();
+    match cli.command {
+        Some(Command::Convert(args)) => return handle_convert(*args).await,
+        Some(Command::Animate(args)) => handle_animation_mode(&args, texconv).await?,
+        Some(Command::Sprite(args)) => handle_sprite_mode(&args)?,
+        Some(Command::Explode(args)) => run_explode(&args).await?,
+        Some(Command::Stitch(args)) => run_stitch(&args, texconv).await?,
+        Some(Command::Info(args)) => run_info(&args.input, args.json)?,
+        Some(Command::Diff(args)) => run_diff(&args)?,
+        Some(Command::Preview(args)) => run_preview(&args, texconv).await?,
+        Some(Command::SelfTest) => run_self_test(texconv).await?,
+        Some(Command::Completions(args)) => Cli::print_completions(args.shell),
+        None => Cli::command().print_help()?,
+    }
+    Ok(RunStatus::Success)
+}
+
+/// Runs `convert`, then keeps watching with `--watch`, between the
+/// `--log-file` start and end records.
+async fn handle_convert(cli: ConvertArgs) -> Result<RunStatus> {
+    if cli.watch && cli.input.is_file() {
+        anyhow::bail!("--watch needs --input to be a folder");
+    }
+    let log = match &cli.log_file {
+        Some(path) => Some(Arc::new(RunLog::open(path, &cli.input, cli.output_root())?)),
+        None => None,
+    };
+    let started = Instant::now();
+    let report = cli.report.clone();
+    let mut summary = RunSummary::default();
+    let result = match cli.watch {
+        true => match convert_files(cli.clone(), log.clone(), &mut summary).await {
+            Ok(_) => run_watch(cli, log.clone(), &mut summary).await,
+            Err(e) => Err(e),
+        },
+        false => convert_files(cli, log.clone(), &mut summary).await,
+    };
+    let result = match &report {
+        Some(path) => match (RunReport::new(&summary, &result, started.elapsed()).write(path), result) {
+            (Ok(()), result) => result.inspect(|_| status!("🧾 Report: {}", path.display())),
+            (Err(e), Ok(_)) => Err(e),
+            (Err(e), Err(fatal)) => {
+                eprintln!("⚠️  {:#}", e);
+                Err(fatal)
+            }
+        },
+        None => result,
+    };
+    if let Some(log) = &log {
+        log.finish(&result);
+    }
+    result
+}
+
+/// `--delete-source` for a converted `file`. A source that can't be deleted
+/// only gets a warning; its conversion still succeeded.
+fn delete_converted_source(file: &Path, output: &Path, cli: &ConvertArgs, console: &Console, summary: &mut RunSummary) {
+    match delete_source(file, output, !cli.no_merge_alpha_part) {
+        Ok(Some(reclaimed)) => {
+            summary.deleted_sources += 1;
+            summary.reclaimed_bytes += reclaimed;
+            if cli.verbose {
+                console.println(format!("🗑️  Deleted {} ({})", file.display(), format_bytes(reclaimed)));
+            }
+        }
+        Ok(None) => console.println(format!("⚠️  {} was replaced by its own output; not deleted", file.display())),
+        Err(e) => console.error(format!("⚠️  Could not delete {}: {:#}", file.display(), e)),
+    }
+}
+
+async fn convert_files(mut cli: ConvertArgs, log: Option<Arc<RunLog>>, summary: &mut RunSummary) -> Result<RunStatus> {
+    if cli.dry_run_json {
+        cli.dry_run = true;
+        cli.quiet = true;
+    }
+    console::set_quiet(cli.quiet);
+    console::set_json_stdout(cli.dry_run_json);
+    // --retry-failed is a --files-from run over an earlier failure list
+    if let Some(list) = &cli.retry_failed {
+        cli.files_from = Some(list.clone());
+    }
     if cli.verbose {
-        println!("✅ texconv.exe extracted to: {}", texconv_path.display());
+        for (flag, variable) in &cli.from_env {
+            let value = std::env::var_os(variable).unwrap_or_default();
+            status!("🌱 {} {} (from {})", flag, value.to_string_lossy(), variable);
+        }
+        for (flag, value) in &cli.from_config {
+            status!("🌱 {} {} (from the config file)", flag, value);
+        }
     }
+    if cli.interactive && !std::io::stdin().is_terminal() {
+        anyhow::bail!("--interactive needs a terminal to ask on, but stdin isn't one; use --on-collision for unattended runs");
+    }
+    let prompter = cli.interactive.then(|| Arc::new(Prompter::new(Duration::from_secs(cli.prompt_timeout))));
+
+    // A single file is converted as if its folder were --input
+    let single_file = if cli.input.is_file() {
+        if !cli.input.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dds")) {
+            anyhow::bail!("{} is not a .dds file", cli.input.display());
+        }
+        if cli.strip_segments > 0 {
+            anyhow::bail!("--strip-segments has no folders to strip when --input is a single file");
+        }
+        if cli.files_from.is_some() {
+            anyhow::bail!("--files-from needs --input to be the folder its paths are relative to");
+        }
+        let (root, file) = single_file_root(&cli.input);
+        cli.input = root;
+        Some(file)
+    } else {
+        None
+    };
+
+    let excluded_output = match cli.compare.as_ref().or(cli.output.as_ref()) {
+        Some(_) if single_file.is_some() => None,
+        Some(output) => output_exclusion(
+            &cli.input,
+            output,
+            cli.allow_nested_output,
+            cli.output.is_some() && cli.format.eq_ignore_ascii_case("dds"),
+        )?,
+        None => None,
+    };
+
+    // --compare converts into a scratch tree that is dropped at the end
+    let compare_staging = match &cli.compare {
+        Some(_) => Some(tempfile::Builder::new().prefix("dds-converter-compare-").tempdir()
+            .context("Failed to create the comparison folder")?),
+        None => None,
+    };
+    let compare = match (&cli.compare, &compare_staging) {
+        (Some(existing), Some(staging)) => {
+            cli.output = Some(staging.path().to_path_buf());
+            Some(Arc::new(CompareTarget {
+                staging_root: staging.path().to_path_buf(),
+                existing_root: existing.clone(),
+                tolerance: cli.compare_tolerance,
+            }))
+        }
+        _ => None,
+    };
     
-    println!("🔍 Searching for DDS files in: {}", cli.input.display());
+    if cli.max_size.is_some_and(|max| max < cli.min_size) {
+        anyhow::bail!("--max-size is smaller than --min-size, so no file could be converted");
+    }
+
+    if cli.target_size.is_some() && !matches!(cli.format.as_str(), "jpg" | "webp") {
+        anyhow::bail!("--target-size only applies to jpg and webp outputs, not {}", cli.format);
+    }
+    if (cli.jpeg_quality.is_some() || cli.jpeg_subsampling.is_some()) && cli.format != "jpg" {
+        anyhow::bail!("--jpeg-quality and --jpeg-subsampling only apply to jpg outputs, not {}", cli.format);
+    }
+
+    check_delete_source(&cli)?;
+    let (rules, rule_warnings) = OverrideRules::load(cli.rules.as_deref())?;
+
+    if cli.embed_metadata && !supports_embedding(&cli.format) {
+        status!("⚠️  --embed-metadata only writes into png and jpg outputs; {} files get none", cli.format);
+    }
+
+    let texconv_path = setup_texconv(cli.texconv_path.as_deref()).await?;
     
-    let dds_files = find_dds_files(&cli.input);
+    if cli.verbose {
+        status!("✅ texconv.exe extracted to: {}", texconv_path.display());
+    }
+    
+    // Listed files are taken as they are rather than searched for
+    let mut unlisted = Vec::new();
+    let found = match (&single_file, &cli.files_from) {
+        (Some(file), _) => {
+            status!("🔍 Converting a single file: {}", file.display());
+            vec![file.clone()]
+        }
+        (None, Some(list)) => {
+            let source = if list == Path::new("-") { "stdin".to_string() } else { list.display().to_string() };
+            status!("🔍 Reading the files to convert from {}", source);
+            let (listed, invalid) = resolve_listed(&cli.input, &read_files_from(list, &cli.input)?);
+            for (path, code, reason) in &invalid {
+                problem!("❌ {} [{}]: {}", path.display(), code, reason);
+            }
+            if !invalid.is_empty() && !cli.continue_on_error && !cli.dry_run {
+                anyhow::bail!("{} file(s) listed in {} can't be converted (use --continue-on-error to convert the rest)", invalid.len(), source);
+            }
+            if listed.is_empty() {
+                problem!("❌ None of the files listed in {} can be converted!", source);
+                if cli.dry_run_json {
+                    print_dry_run_json(Vec::new())?;
+                }
+                return Ok(RunStatus::NothingMatched);
+            }
+            unlisted = invalid;
+            listed
+        }
+        (None, None) => {
+            status!("🔍 Searching for DDS files in: {}", cli.input.display());
+            find_dds_files(&cli.input, excluded_output.as_deref(), cli.selection.max_depth())
+        }
+    };
+
+    summary.found = found.len();
+    let filter = filter_paths(found, &cli.input, &cli.include, &cli.exclude);
+    if filter.not_included > 0 {
+        status!("🚫 {} file(s) matched no --include pattern", filter.not_included);
+    }
+    if !filter.excluded.is_empty() {
+        status!("🚫 {} file(s) excluded by --exclude", filter.excluded.len());
+    }
+    let (dds_files, skipped_by_size) = filter_by_size(filter.kept, cli.min_size, cli.max_size);
+    if !skipped_by_size.is_empty() {
+        status!("🚫 {} file(s) skipped for their size (--min-size / --max-size)", skipped_by_size.len());
+    }
+    let excluded_by_pattern = filter.excluded;
+    let nothing_left = || -> Result<()> {
+        if cli.dry_run_json {
+            print_dry_run_json(filtered_entries(&excluded_by_pattern, &skipped_by_size))?;
+        }
+        Ok(())
+    };
 
     if dds_files.is_empty() {
-        println!("❌ No .dds files found!");
-        return Ok(());
+        if filter.not_included + excluded_by_pattern.len() + skipped_by_size.len() > 0 {
+            status!(
+                "❌ No .dds files left after filtering: {} rejected by --include, {} by --exclude, {} by size",
+                filter.not_included,
+                excluded_by_pattern.len(),
+                skipped_by_size.len()
+            );
+        } else {
+            problem!("❌ No .dds files found!");
+        }
+        nothing_left()?;
+        return Ok(RunStatus::NothingMatched);
+    }
+
+    let dds_files = if let Some(old) = &cli.diff_against {
+        status!("🔍 Comparing against {}", old.display());
+        let diff = diff_trees(old, &cli.input, excluded_output.as_deref())?;
+        diff.print(cli.verbose);
+        if let Some(json) = &cli.diff_json {
+            diff.write_json(json)?;
+            status!("🧾 Diff report: {}", json.display());
+        }
+        let (changed, _) = filter_by_keys(dds_files, &cli.input, &diff.to_convert());
+        if changed.is_empty() {
+            status!("✅ Nothing added or changed since {}", old.display());
+            nothing_left()?;
+            return Ok(RunStatus::Success);
+        }
+        changed
+    } else {
+        dds_files
+    };
+
+    let (dds_files, selection_note) = select_items(dds_files, &cli.selection);
+
+    // With --pack only anchors are converted; the other maps feed into them.
+    // --composite likewise converts bases, with their overlays on top
+    let dds_files = match (&cli.pack, &cli.unpack, &cli.composite) {
+        (Some(spec), _, _) => {
+            let anchors: Vec<PathBuf> = dds_files.into_iter().filter(|file| spec.anchor_stem(file).is_some()).collect();
+            if anchors.is_empty() {
+                problem!("❌ No files match the first map of --pack!");
+                nothing_left()?;
+                return Ok(RunStatus::NothingMatched);
+            }
+            status!("🧬 Packing {} texture(s)", anchors.len());
+            anchors
+        }
+        (None, Some(spec), _) => {
+            let packed: Vec<PathBuf> = dds_files.into_iter().filter(|file| spec.matches(file)).collect();
+            if packed.is_empty() {
+                problem!("❌ No files match --unpack!");
+                nothing_left()?;
+                return Ok(RunStatus::NothingMatched);
+            }
+            packed
+        }
+        (None, None, Some(spec)) => {
+            let pairing = spec.pair(&dds_files);
+            for base in &pairing.bases_without_overlay {
+                status!("⚠️  {}: no overlay ({} not found); skipped", base.display(), spec.overlay_for(base).unwrap_or_default().display());
+            }
+            for overlay in &pairing.overlays_without_base {
+                status!("⚠️  {}: overlay without a base; skipped", overlay.display());
+            }
+            if pairing.pairs.is_empty() {
+                problem!("❌ No base/overlay pairs match --composite!");
+                nothing_left()?;
+                return Ok(RunStatus::NothingMatched);
+            }
+            status!("🥞 Compositing {} pair(s)", pairing.pairs.len());
+            pairing.pairs.into_iter().map(|(base, _)| base).collect()
+        }
+        (None, None, None) => dds_files,
+    };
+
+    let (overrides, override_warnings) = load_overrides(&dds_files, &cli.input, &rules)?;
+    for warning in rule_warnings.iter().chain(&override_warnings) {
+        status!("⚠️  {}", warning);
+    }
+
+    let planned = dds_files
+        .into_iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let format = overrides.get(&file).and_then(|o| o.format.as_deref()).unwrap_or(&cli.format);
+            let names = cli.naming.cleanup();
+            let output = planned_output(&cli, &file, format, index + 1, names);
+            let raw = planned_output(&cli, &file, format, index + 1, names.raw());
+            let unsanitized = planned_output(&cli, &file, format, index + 1, NameCleanup { sanitize: None, ..names });
+            if let Some(reason) = cleanup_reason(&raw, &unsanitized, &output) {
+                status!("⚠️  Renamed {} -> {} ({})", raw.display(), output.display(), reason);
+            }
+            (file, output)
+        })
+        .collect::<Vec<_>>();
+    summary.planned = planned.clone();
+    let fold_case = case_insensitive_names(cli.target_folder());
+    let collisions = match &prompter {
+        Some(prompter) => resolve_collisions_with(planned, &cli.input, fold_case, |output, inputs| {
+            prompter.collision(output, inputs, cli.collision_policy(), &Console::plain())
+        })?,
+        None => resolve_collisions(planned, &cli.input, fold_case, cli.collision_policy())?,
+    };
+
+    let (dds_files, duplicates) = match cli.dedupe_link {
+        Some(_) => {
+            let inputs = collisions.outputs.iter().map(|resolved| resolved.input.clone()).collect();
+            let plan = plan_dedupe(inputs)?;
+            (plan.unique, plan.duplicates)
+        }
+        None => (collisions.outputs.iter().map(|resolved| resolved.input.clone()).collect(), Vec::new()),
+    };
+    let resolved: HashMap<PathBuf, ResolvedOutput> = collisions.outputs
+        .iter()
+        .map(|resolved| (resolved.input.clone(), resolved.clone()))
+        .collect();
+
+    let archived = match &cli.output_zip {
+        Some(zip) if zip.is_file() => entry_names(zip)?,
+        _ => HashSet::new(),
+    };
+    let manifest = Arc::new(PreviousManifest::load(&cli, &archived)?);
+
+    if cli.dry_run_json {
+        let existing = cli.existing_policy();
+        let mut entries = filtered_entries(&excluded_by_pattern, &skipped_by_size);
+        for file in dds_files.iter().chain(duplicates.iter().map(|(duplicate, _)| duplicate)) {
+            let target = &resolved[file];
+            let output = match &cli.output_zip {
+                Some(zip) => format!("{}:{}", zip.display(), entry_name(&target.output)),
+                None => target.output.display().to_string(),
+            };
+            let previous = previous_output(&cli, &manifest, &target.output);
+            let reason = if cli.incremental && previous.as_deref().is_some_and(|previous| is_up_to_date(file, previous)) {
+                Some(SkipReason::UpToDate.describe().to_string())
+            } else if existing == ExistingAction::Skip && previous.is_some_and(|previous| match &cli.output_zip {
+                Some(_) => archived.contains(&entry_name(&previous)),
+                None => previous.exists(),
+            }) {
+                Some(SkipReason::Exists.describe().to_string())
+            } else {
+                None
+            };
+            entries.push(DryRunEntry::new(file, Some(output), reason));
+        }
+        for (file, kept) in &collisions.skipped {
+            entries.push(DryRunEntry::new(file, None, Some(format!("same output as {}", kept.display()))));
+        }
+        print_dry_run_json(entries)?;
+        return Ok(RunStatus::Success);
     }
 
     if cli.dry_run {
-        println!("🔍 Dry-run mode - files that would be processed:");
+        status!("🔍 Dry-run mode - files that would be processed:");
+        if let Some(excluded) = &excluded_output {
+            status!("  (excluding output folder {})", excluded.display());
+        }
+        if let Some(note) = &selection_note {
+            status!("  ({})", note);
+        }
+        for (file, pattern) in &excluded_by_pattern {
+            status!("  {} (excluded by {})", file.display(), pattern);
+        }
+        for (file, reason) in &skipped_by_size {
+            status!("  {} (skipped: {})", file.display(), reason.describe());
+        }
         for file in &dds_files {
-            let output_path = calculate_output_path(&file, &cli.input, &cli.output, cli.strip_segments, &cli.format);
-            println!("  {} -> {}", file.display(), output_path.display());
+            let target = &resolved[file];
+            match &cli.output_zip {
+                Some(zip) => status!("  {} -> {}:{}", file.display(), zip.display(), entry_name(&target.output)),
+                None => status!("  {} -> {}", file.display(), target.output.display()),
+            }
+            if let Some(note) = &target.note {
+                status!("    ({})", note);
+            }
+            if cli.incremental && previous_output(&cli, &manifest, &target.output).is_some_and(|previous| is_up_to_date(file, &previous)) {
+                status!("    (up to date, would be skipped)");
+            }
+            let header = DdsHeader::read(file);
+            let mut dimensions = header.as_ref().map(|header| (header.width, header.height));
+            if let (Some(level), Ok(header)) = (cli.mip, &header) {
+                let levels = header.mip_count.max(1);
+                if level < levels || cli.mip_clamp {
+                    let chosen = level.min(levels - 1);
+                    let (width, height) = header.mip_dimensions(chosen);
+                    dimensions = Ok((width, height));
+                    if chosen == level {
+                        status!("    (mip {}: {}x{})", chosen, width, height);
+                    } else {
+                        status!("    (mip {} clamped to {}: {}x{})", level, chosen, width, height);
+                    }
+                } else {
+                    status!("    (only {} mip level(s), would fail without --mip-clamp)", levels);
+                }
+            }
+            if cli.resize.is_some() || cli.max_dimension.is_some() {
+                match dimensions.map(|(width, height)| (width, height, plan_resize(width, height, &cli))) {
+                    Ok((width, height, ResizePlan::To(size))) => status!("    (resized from {}x{} to {})", width, height, size),
+                    Ok((width, height, ResizePlan::NotUpscaled(size))) => status!("    (kept at {}x{}, smaller than {}; --allow-upscale enlarges it)", width, height, size),
+                    Ok((width, height, ResizePlan::Keep)) => status!("    (kept at {}x{})", width, height),
+                    Err(_) => status!("    (size unknown: unreadable header)"),
+                }
+            }
+            if let Some(file_overrides) = overrides.get(file) {
+                status!("    (overrides from {}: {})", file_overrides.sources.join(", "), file_overrides.describe());
+            }
+            if let Some(spec) = &cli.pack {
+                for (channel, source) in ["r", "g", "b", "a"].iter().zip(spec.source_files(file)) {
+                    match source {
+                        Some(path) if path.is_file() => status!("    {} <- {}", channel, path.display()),
+                        Some(path) => status!("    {} <- {} (missing, filled)", channel, path.display()),
+                        None => {}
+                    }
+                }
+            }
+            if let Some(spec) = &cli.composite {
+                if let Some(overlay) = spec.overlay_for(file) {
+                    status!("    + {}", overlay.display());
+                }
+            }
+            if let Some(spec) = &cli.unpack {
+                for (index, suffix) in &spec.channels {
+                    status!("    + {} ({})", with_suffix(&target.output, suffix).display(), channel_name(*index));
+                }
+            }
+            if let Some(map) = &cli.tint_map {
+                for variant in map.variants_for(file) {
+                    match &variant.pattern {
+                        Some(pattern) => status!("    + {} ({}, {})", with_suffix(&target.output, &variant.suffix).display(), variant.color, pattern),
+                        None => status!("    + {} ({})", with_suffix(&target.output, &variant.suffix).display(), variant.color),
+                    }
+                }
+            }
+            if cli.all_mips {
+                match DdsHeader::read(file) {
+                    Ok(header) => {
+                        for layer in 0..header.layer_count() {
+                            for level in 0..header.mip_count {
+                                let (width, height) = header.mip_dimensions(level);
+                                status!("    + {} ({}x{})", with_suffix(&target.output, &surface_mip_suffix(&header, layer, level)).display(), width, height);
+                            }
+                        }
+                    }
+                    Err(_) => status!("    (mip levels unknown: unreadable header)"),
+                }
+            }
+            if let Some(layout) = cli.cubemap_layout {
+                if let Some(header) = DdsHeader::read(file).ok().filter(|header| header.is_cubemap) {
+                    match layout {
+                        CubemapLayout::Faces => {
+                            for name in FACE_NAMES {
+                                status!("    + {}", with_suffix(&target.output, name).display());
+                            }
+                        }
+                        CubemapLayout::Cross => status!("    (cubemap as a {}x{} cross)", header.width * 4, header.height * 3),
+                        CubemapLayout::Equirect => {
+                            let width = cli.equirect_width.unwrap_or(header.width * 4);
+                            status!("    (cubemap as a {}x{} panorama)", width, (width / 2).max(1));
+                        }
+                    }
+                }
+            }
+            if let Some(tile_size) = cli.tile {
+                let (crop, crop_mode) = match overrides.get(file) {
+                    Some(file_overrides) => (file_overrides.crop.unwrap_or(cli.crop), file_overrides.crop_mode.unwrap_or(cli.crop_mode)),
+                    None => (cli.crop, cli.crop_mode),
+                };
+                match planned_grid(file, tile_size, crop, crop_mode) {
+                    Some(grid) => status!("    ({} tile(s), {}x{} of {}px)", grid.count(), grid.columns, grid.rows, tile_size),
+                    None => status!("    (tile count unknown until decoded)"),
+                }
+            }
+            for (source, destination) in find_companions(file, &target.output, &cli.copy_companions) {
+                status!("    + {} -> {}", source.display(), destination.display());
+            }
         }
-        return Ok(());
+        for (duplicate, original) in &duplicates {
+            status!("  {} -> {} (identical to {})", duplicate.display(), resolved[duplicate].output.display(), original.display());
+        }
+        for (file, kept) in &collisions.skipped {
+            status!("  {} skipped (same output as {})", file.display(), kept.display());
+        }
+        if cli.delete_source {
+            status!("🗑️  --delete-source would delete each converted source (with its parts) once its output is written");
+        }
+        return Ok(RunStatus::Success);
     }
 
-    println!("📊 Found {} DDS files", dds_files.len());
+    match &selection_note {
+        Some(note) => status!("📊 Processing {} DDS files ({})", dds_files.len() + duplicates.len(), note),
+        None => status!("📊 Found {} DDS files", dds_files.len() + duplicates.len() + collisions.skipped.len()),
+    }
+    if !duplicates.is_empty() {
+        status!("🔗 {} duplicate(s) will reuse an identical file's output", duplicates.len());
+    }
+    if !overrides.is_empty() {
+        status!("📝 {} file(s) have per-file overrides (--rules or <name>.dds.toml)", overrides.len());
+    }
+    let renamed = collisions.outputs.iter().filter(|resolved| resolved.note.is_some()).count();
+    if renamed > 0 {
+        status!("⚠️  {} output name(s) were suffixed to avoid collisions", renamed);
+    }
+    if !collisions.skipped.is_empty() {
+        status!("⚠️  {} file(s) will be skipped because another input has the same output name", collisions.skipped.len());
+    }
     
-    let progress = ProgressBar::new(dds_files.len() as u64);
+    let total = (dds_files.len() + duplicates.len()) as u64;
+    let interactive = !cli.no_progress && !cli.quiet && std::io::stdout().is_terminal();
+    let progress = if interactive { ProgressBar::new(total) } else { ProgressBar::hidden() };
+    progress.set_length(total);
     progress.set_style(
         ProgressStyle::default_bar()
-            .template("{elapsed_precise} [{bar:50.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
+            .template("{elapsed_precise} [{bar:50.cyan/blue}] {pos}/{len} ({percent}%) {prefix}{wide_msg}")
             .unwrap()
     );
+    let error_count = Arc::new(AtomicUsize::new(0));
+    let print_heartbeat = !interactive && !cli.quiet;
+    let heartbeat = (print_heartbeat || log.is_some()).then(|| {
+        let interval = Duration::from_secs(cli.progress_interval.max(1));
+        spawn_heartbeat(progress.clone(), error_count.clone(), interval, print_heartbeat, log.clone())
+    });
 
-    let semaphore = Arc::new(Semaphore::new(cli.concurrency));
+    let console = if cli.show_active && interactive {
+        let multi = MultiProgress::new();
+        let progress = multi.add(progress.clone());
+        Console::with_multi(multi, progress)
+    } else {
+        Console::with_progress(progress.clone())
+    };
+    let zip = match &cli.output_zip {
+        Some(path) => Some(Arc::new(ZipOutput::create(path, cli.reproducible, cli.existing_policy() == ExistingAction::Skip)?)),
+        None => None,
+    };
+    let cli = Arc::new(cli);
+    let queueing = Arc::new(Queueing::default());
+    let (semaphore, controller) = match cli.concurrency {
+        Concurrency::Fixed(workers) => (Arc::new(Semaphore::new(workers)), None),
+        Concurrency::Adaptive => {
+            let max = cli.concurrency_max
+                .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4));
+            let controller = AdaptiveController::new(cli.concurrency_min, max);
+            let semaphore = Arc::new(Semaphore::new(controller.level()));
+            let handle = spawn_controller(controller, semaphore.clone(), progress.clone(), queueing.clone());
+            (semaphore, Some(handle))
+        }
+    };
+    let space_gate = (!cli.no_pause_on_full).then(|| {
+        Arc::new(SpaceGate::new(cli.target_folder(), prompter.clone()))
+    });
+    let driver = Arc::new(FileDriver {
+        texconv_path: texconv_path.clone(),
+        zip: zip.clone(),
+        space_gate: space_gate.clone(),
+        prompter: prompter.clone(),
+        manifest: manifest.clone(),
+    });
+    let budget = Arc::new(ErrorBudget::new(cli.max_errors, cli.max_error_rate));
     let mut tasks = Vec::new();
+    let mut not_started: Vec<PathBuf> = Vec::new();
 
-    for file in dds_files {
+    for (index, file) in dds_files.iter().cloned().enumerate() {
         let permit = semaphore.clone().acquire_owned().await?;
-        let texconv_path = texconv_path.clone();
-        let input_dir = cli.input.clone();
-        let output_dir = cli.output.clone();
-        let strip_segments = cli.strip_segments;
-        let verbose = cli.verbose;
-        let format = cli.format.clone();
-        let continue_on_error = cli.continue_on_error;
+        let acquired = Instant::now();
+        if budget.tripped().is_some() || prompter.as_ref().is_some_and(|prompter| prompter.aborted()) {
+            not_started = dds_files[index..].to_vec();
+            break;
+        }
+        let output_path = resolved[&file].output.clone();
+        let driver = driver.clone();
+        let error_count = error_count.clone();
+        let queueing = queueing.clone();
+        let cli = match overrides.get(&file) {
+            Some(file_overrides) => Arc::new(file_overrides.apply(&cli)),
+            None => cli.clone(),
+        };
+        let compare = compare.clone();
+        let console = console.clone();
         let progress = progress.clone();
+        let budget = budget.clone();
+        let log = log.clone();
 
         let task = tokio::spawn(async move {
             let _permit = permit;
-            let result = process_file(
-                &file,
-                &texconv_path,
-                &input_dir,
-                &output_dir,
-                strip_segments,
-                verbose,
-                &format,
-                continue_on_error,
-            ).await;
+            let label = file.strip_prefix(&cli.input).unwrap_or(&file).display().to_string();
+            let active = console.start_file(&label);
+            let started = Instant::now();
+            let (run, queued) = adaptive::queued(acquired, driver.convert(&file, output_path, &cli, &console)).await;
+            let FileRun { mut result, output_path, conflict, gpu_fallback, retried } = run;
+            if let Some(queued) = queued {
+                queueing.record(queued);
+            }
+            let mut comparison = None;
+            if let (Ok(FileOutcome::Converted { .. }), Some(compare)) = (&result, &compare) {
+                match compare.check(&output_path) {
+                    Ok(checked) => comparison = Some(checked),
+                    Err(e) => result = Err(e),
+                }
+            }
+            drop(active);
+            if let Some(log) = &log {
+                log.file(&file, &output_path, started.elapsed(), &result);
+            }
             
             progress.inc(1);
+            budget.record(result.is_err());
             
             if let Err(e) = &result {
-                progress.println(format!("❌ Error in {}: {}", file.display(), e));
+                error_count.fetch_add(1, Ordering::Relaxed);
+                console.error(format!("❌ Error in {} [{}]: {}", file.display(), classify(e), e));
             }
             
-            result
+            (file, result, comparison, gpu_fallback, retried, conflict)
         });
         
         tasks.push(task);
     }
 
-    let mut error_count = 0;
+    summary.selection_note = selection_note;
+    summary.not_included = filter.not_included;
+    summary.excluded = excluded_by_pattern.len();
+    summary.failed.extend(unlisted);
+    for (file, reason) in &skipped_by_size {
+        summary.record(file, &Ok(FileOutcome::Skipped(*reason)));
+    }
+    for (file, _) in &collisions.skipped {
+        summary.record(file, &Ok(FileOutcome::Skipped(SkipReason::Collision)));
+    }
+    let mut compare_report = CompareReport::default();
+    let mut original_outcomes = HashMap::new();
+    let mut hashed_names = Vec::new();
+    let mut pending_deletes = Vec::new();
     for task in tasks {
-        if let Err(e) = task.await? {
-            error_count += 1;
+        let (file, result, comparison, gpu_fallback, retried, conflict) = task.await?;
+        summary.record(&file, &result);
+        if gpu_fallback {
+            summary.gpu_fallbacks.push(file.clone());
+        }
+        if retried {
+            summary.retried.push(file.clone());
+        }
+        if let Ok(FileOutcome::Converted { .. }) = &result {
+            match conflict {
+                Some(ExistingAction::Overwrite) => summary.overwritten += 1,
+                Some(ExistingAction::Rename) => summary.renamed += 1,
+                _ => {}
+            }
+        }
+        compare_report.results.extend(comparison);
+        if let (true, Ok(FileOutcome::Converted { .. })) = (cli.delete_source, &result) {
+            // An archive can still fail to finish, so its sources wait for it
+            if zip.is_some() {
+                pending_deletes.push(file.clone());
+            } else {
+                delete_converted_source(&file, &resolved[&file].output, &cli, &console, summary);
+            }
+        }
+        if let Ok(FileOutcome::Converted { hashed_names: names, .. }) = &result {
+            hashed_names.extend(names.iter().cloned());
+        }
+        if !duplicates.is_empty() {
+            original_outcomes.insert(file, result.as_ref().ok().cloned());
+        }
+        if let Err(e) = result {
             if !cli.continue_on_error {
                 progress.finish_with_message("❌ Stopped due to error");
+                if let Some(zip) = &zip {
+                    let partial = zip.finish(false)?;
+                    status!("⚠️  Partial archive left at {}", partial.display());
+                }
                 return Err(e);
             }
         }
     }
 
+    let budget_tripped = budget.tripped().is_some();
+    let stop_reason = budget.tripped().map(str::to_string).or_else(|| {
+        prompter.as_ref().filter(|prompter| prompter.aborted()).map(|_| "aborted at the prompt".to_string())
+    });
+    if let Some(reason) = stop_reason {
+        progress.finish_with_message("🛑 Stopped early");
+        if let Some(heartbeat) = heartbeat {
+            heartbeat.abort();
+        }
+        if let Some(controller) = controller {
+            controller.abort();
+        }
+        if let Some(zip) = &zip {
+            let partial = zip.finish(false)?;
+            status!("⚠️  Partial archive left at {}", partial.display());
+        }
+        summary.print(cli.verbose);
+        // Files never started are retried along with the failed ones
+        not_started.extend(duplicates.iter().map(|(file, _)| file.clone()));
+        if let Some(list) = cli.failure_list_path() {
+            summary.write_failure_list(&list, &cli.input, &not_started)?;
+        }
+        println!("⚠️  Processing completed with {} error(s)", summary.failed.len());
+        status!("🛑 Stopped early: {}; {} file(s) were not attempted", reason, not_started.len());
+        // Too many failed files is still a run with file errors, not a fatal one
+        if budget_tripped {
+            return Ok(RunStatus::FileErrors);
+        }
+        anyhow::bail!("Stopped early: {}", reason);
+    }
+
+    if cli.hash_manifest {
+        write_hash_manifest(&cli, zip.as_deref(), &manifest, &hashed_names).await?;
+    }
+
+    if let Some(zip) = &zip {
+        let archive = zip.finish(true)?;
+        status!("📦 Archive written: {}", archive.display());
+        for file in &pending_deletes {
+            delete_converted_source(file, &resolved[file].output, &cli, &console, summary);
+        }
+    }
+
+    if let Some(mode) = cli.dedupe_link {
+        for (file, original) in &duplicates {
+            let original_outcome = original_outcomes.get(original).and_then(Option::as_ref);
+            let started = Instant::now();
+            let mut result = loop {
+                let result = process_duplicate(
+                    file,
+                    &resolved[file].output,
+                    &resolved[original],
+                    original_outcome,
+                    mode,
+                    &cli,
+                    &console,
+                ).await;
+                match (&result, &space_gate) {
+                    (Err(e), Some(gate)) if classify(e) == ErrorCode::DiskFull => {
+                        let seen = *gate.enter().await;
+                        gate.wait_for_space(seen, &console).await;
+                    }
+                    _ => break result,
+                }
+            };
+            if let (Ok(FileOutcome::Linked { .. }), Some(compare)) = (&result, &compare) {
+                match compare.check(&resolved[file].output) {
+                    Ok(checked) => compare_report.results.push(checked),
+                    Err(e) => result = Err(e),
+                }
+            }
+            progress.inc(1);
+            if let Some(log) = &log {
+                log.file(file, &resolved[file].output, started.elapsed(), &result);
+            }
+            summary.record(file, &result);
+            if let Err(e) = result {
+                error_count.fetch_add(1, Ordering::Relaxed);
+                console.error(format!("❌ Error in {} [{}]: {}", file.display(), classify(&e), e));
+                if !cli.continue_on_error {
+                    progress.finish_with_message("❌ Stopped due to error");
+                    return Err(e);
+                }
+            }
+        }
+    }
+
     progress.finish_with_message("✅ Processing completed!");
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.abort();
+    }
+    if let Some(controller) = controller {
+        controller.abort();
+    }
+    summary.print(cli.verbose);
+
+    if let Some(compare) = &compare {
+        let planned: Vec<PathBuf> = collisions.outputs.iter().map(|resolved| resolved.output.clone()).collect();
+        compare_report.missing = compare.find_missing(&planned, output_extension(&cli.format));
+        compare_report.results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        compare_report.print(&compare.existing_root, cli.verbose);
+    }
     
-    if error_count > 0 {
-        println!("⚠️  Processing completed with {} error(s)", error_count);
+    if let Some(list) = cli.failure_list_path() {
+        summary.write_failure_list(&list, &cli.input, &[])?;
+    }
+    if !summary.failed.is_empty() {
+        println!("⚠️  Processing completed with {} error(s)", summary.failed.len());
+    } else if summary.skipped_count() > 0 {
+        status!("🎉 Processing completed; {} file(s) were skipped", summary.skipped_count());
     } else {
-        println!("🎉 All files were processed successfully!");
+        status!("🎉 All files were processed successfully!");
     }
 
+    if let Some(compare) = &compare {
+        if compare_report.differences() > 0 {
+            anyhow::bail!("{} file(s) differ from {}", compare_report.differences(), compare.existing_root.display());
+        }
+    }
+
+    Ok(if summary.failed.is_empty() { RunStatus::Success } else { RunStatus::FileErrors })
+}
+
+/// `--hash-manifest`: writes `manifest.json` at the root of the output folder
+/// or archive.
+async fn write_hash_manifest(
+    cli: &ConvertArgs,
+    zip: Option<&ZipOutput>,
+    previous: &PreviousManifest,
+    hashed_names: &[(PathBuf, PathBuf)],
+) -> Result<()> {
+    let json = manifest_json(cli.output_root(), previous, hashed_names)?;
+    let manifest = cli.output_root().join("manifest.json");
+    match zip {
+        Some(zip) => {
+            let staging = tempfile::tempdir().context("Failed to create staging directory")?;
+            let staged = staging.path().join("manifest.json");
+            std::fs::write(&staged, json).context("Failed to write the hash manifest")?;
+            zip.add_file(&manifest, &staged).await?;
+        }
+        None => std::fs::write(&manifest, json)
+            .with_context(|| format!("Failed to write {}", manifest.display()))?,
+    }
+    status!("🗂️  Hash manifest written: {}", manifest.display());
     Ok(())
 }
 
-async fn handle_animation_mode(cli: &Cli) -> Result<()> {
-    println!("🎬 Animation mode: Converting sequences to {}", cli.animation_format.to_uppercase());
-    println!("🔍 Searching for sequences in: {}", cli.input.display());
-    
-    // First, look for sprite sheets (DDS + .sprite files)
-    let sprite_sequences = find_sprite_sequences(&cli.input)?;
-    
-    if !sprite_sequences.is_empty() {
-        println!("📊 Found {} sprite sheet(s)", sprite_sequences.len());
-        
-        // Create output directory
-        tokio::fs::create_dir_all(&cli.output).await?;
-        
-        for (dds_path, sprite_path) in sprite_sequences {
-            println!("🎞️  Processing sprite sheet: {}", dds_path.display());
-            
-            if cli.verbose {
-                println!("  DDS: {}", dds_path.display());
-                println!("  Sprite: {}", sprite_path.display());
+/// Works out whether the output folder has to be kept out of discovery so the
+/// tool never picks up its own previous outputs.
+fn output_exclusion(
+    input: &Path,
+    output: &Path,
+    allow_nested_output: bool,
+    overwrites_sources: bool,
+) -> Result<Option<PathBuf>> {
+    if allow_nested_output {
+        return Ok(None);
+    }
+
+    match output_placement(input, output) {
+        OutputPlacement::Separate => Ok(None),
+        OutputPlacement::Same => {
+            if overwrites_sources {
+                anyhow::bail!(
+                    "Output folder is the input folder and --format dds would overwrite the sources; \
+                     pick another output folder or pass --allow-nested-output"
+                );
             }
-            
-            // Generate output filename
-            let base_name = dds_path.file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("animation");
-            
-            let output_filename = format!("{}.{}", base_name, cli.animation_format);
-            let output_path = cli.output.join(output_filename);
-            
-            println!("📤 Creating: {}", output_path.display());
-            
-            create_animation_from_sprite_sheet(
-                &dds_path,
-                &sprite_path,
-                &output_path,
-                cli.frame_delay,
-                &cli.animation_format
-            )?;
+            status!("⚠️  Output folder is the same as the input folder; converted files will be written next to the sources");
+            Ok(None)
+        }
+        OutputPlacement::Nested(excluded) => {
+            status!("⚠️  Output folder is inside the input folder; excluding {} from discovery", excluded.display());
+            Ok(Some(excluded))
         }
-        
-        println!("🎉 All sprite sheet animations created successfully!");
-        return Ok(());
     }
+}
+
+async fn handle_animation_mode(cli: &AnimateArgs, texconv: Option<&Path>) -> Result<()> {
+    // A single DDS + .sprite pair is animated as if its folder were --input
+    let (input_root, single_sheet) = if cli.input.is_file() {
+        let (root, file) = single_file_root(&cli.input);
+        match sprite_source(&file) {
+            Some(source) => (root, Some(source)),
+            None => anyhow::bail!("No .sprite file found next to {}", cli.input.display()),
+        }
+    } else {
+        (cli.input.clone(), None)
+    };
+    let excluded_output = match single_sheet {
+        Some(_) => None,
+        None => output_exclusion(&cli.input, &cli.output, cli.allow_nested_output, false)?,
+    };
+    let excluded_output = excluded_output.as_deref();
+    let console = Console::plain();
+    let filters = FrameFilters {
+        tint: cli.tint,
+        watermark: cli.watermark.watermark.as_ref(),
+        watermark_margin: cli.watermark.watermark_margin,
+        watermark_min_size: cli.watermark.watermark_min_size,
+    };
+    println!("🎬 Animation mode: Converting sequences to {}", cli.animation_format.to_uppercase());
     
-    // Fallback to regular image sequences
-    let sequences = find_image_sequences(&cli.input)?;
-    
-    if sequences.is_empty() {
+    // Sprite sheets (DDS + .sprite files), GIFs/WebPs that are already
+    // animated, and numbered image sequences
+    let (sprite_sequences, animated_files, image_sequences) = match single_sheet {
+        Some(source) => {
+            println!("🔍 Animating a single sprite sheet: {}", source.sprite.display());
+            (vec![source], Vec::new(), Vec::new())
+        }
+        None => {
+            println!("🔍 Searching for sequences in: {}", cli.input.display());
+            let sheets = find_sprite_sequences(&cli.input, excluded_output, cli.selection.max_depth())?;
+            // A multi-page sheet's `effect_0.dds`, `effect_1.dds` are not a sequence
+            let pages: HashSet<PathBuf> = sheets.iter().flat_map(|sheet| sheet.pages.iter().cloned()).collect();
+            let sequences = find_image_sequences(&cli.input, excluded_output, cli.selection.max_depth())?
+                .into_iter()
+                .filter(|sequence| !sequence.iter().any(|frame| pages.contains(frame)))
+                .collect();
+            (sheets, find_animated_files(&cli.input, excluded_output, cli.selection.max_depth())?, sequences)
+        }
+    };
+
+    if sprite_sequences.is_empty() && animated_files.is_empty() && image_sequences.is_empty() {
         println!("❌ No image sequences found!");
         println!("💡 Make sure your image files follow a naming pattern like:");
         println!("   - animation_001.png, animation_002.png");
         println!("   - named_bg_1.dds, named_bg_2.dds");
         println!("   - frame1.jpg, frame2.jpg");
+        println!("   - fx_flame_01_loop.dds, fx_flame_02_loop.dds");
         return Ok(());
     }
-    
-    println!("📊 Found {} PNG sequence(s)", sequences.len());
-    
-    // Create output directory
-    tokio::fs::create_dir_all(&cli.output).await?;
-    
-    for (seq_idx, sequence) in sequences.iter().enumerate() {
-        println!("🎞️  Processing sequence {} with {} frames", seq_idx + 1, sequence.len());
-        
-        if cli.verbose {
-            for (i, file) in sequence.iter().enumerate() {
-                println!("  Frame {}: {}", i + 1, file.display());
+
+    let sources: Vec<AnimationSource> = sprite_sequences
+        .into_iter()
+        .map(AnimationSource::Sheet)
+        .chain(animated_files.into_iter().map(AnimationSource::Animated))
+        .chain(image_sequences.into_iter().map(AnimationSource::Sequence))
+        .collect();
+    let (sources, selection_note) = select_items(sources, &cli.selection);
+    let sheets = sources.iter().filter(|source| matches!(source, AnimationSource::Sheet(_))).count();
+    let animated = sources.iter().filter(|source| matches!(source, AnimationSource::Animated(_))).count();
+    let sequences = sources.len() - sheets - animated;
+    match &selection_note {
+        Some(note) => println!(
+            "📊 Processing {} sprite sheet(s), {} GIF/WebP file(s) and {} image sequence(s) ({})",
+            sheets, animated, sequences, note
+        ),
+        None => println!("📊 Found {} sprite sheet(s), {} GIF/WebP file(s) and {} image sequence(s)", sheets, animated, sequences),
+    }
+
+    if cli.verbose {
+        for source in &sources {
+            let AnimationSource::Sequence(sequence) = source else { continue };
+            let frame = FrameName::parse(&texture_stem(&sequence[0]));
+            if frame.ambiguous {
+                println!(
+                    "🔢 {}: has several numbers; frames taken as {} (the last number is the frame)",
+                    sequence[0].display(), frame.pattern()
+                );
             }
         }
-        
-        // Generate output filename based on first file in sequence
-        let first_file = &sequence[0];
-        let base_name = first_file.file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("animation");
-        
-        // Remove numeric suffix if present
-        let clean_base = if let Some(pos) = base_name.rfind('_') {
-            let (base, suffix) = base_name.split_at(pos);
-            if suffix[1..].chars().all(|c| c.is_ascii_digit()) {
-                base
-            } else {
-                base_name
+    }
+
+    // `effect.sprite` names a multi-page `effect_0.dds`, `effect_1.dds`
+    let named: Vec<(PathBuf, String)> = sources
+        .iter()
+        .map(|source| match source {
+            AnimationSource::Sheet(sheet) => (sheet.anchor().to_path_buf(), texture_stem(&sheet.sprite)),
+            AnimationSource::Animated(path) => (path.clone(), texture_stem(path)),
+            AnimationSource::Sequence(sequence) => (sequence[0].clone(), sequence_base_name(&sequence[0])),
+        })
+        .collect();
+    let layout = animation_layout(cli, &input_root, &named)?;
+    if cli.dry_run {
+        print_animation_plan(&layout);
+        return Ok(());
+    }
+    let outputs: HashMap<PathBuf, PathBuf> = layout.outputs.iter().map(|resolved| (resolved.input.clone(), resolved.output.clone())).collect();
+
+    let mut created_from_sheets = 0;
+    let mut reencoded = 0;
+    let mut created_from_sequences = 0;
+    let mut still = 0;
+    let mut failures = 0;
+    for (source, (key, _)) in sources.iter().zip(&named) {
+        let Some(output_path) = outputs.get(key) else { continue };
+        if let Some(parent) = output_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        match source {
+            AnimationSource::Sheet(source) => {
+                let dds_path = source.anchor();
+                println!("🎞️  Processing sprite sheet: {}", dds_path.display());
+
+                if cli.verbose {
+                    for page in &source.pages {
+                        println!("  DDS: {}", page.display());
+                    }
+                    println!("  Sprite: {}", source.sprite.display());
+                }
+
+                println!("📤 Creating: {}", output_path.display());
+
+                let result = create_animation_from_sprite_sheet(
+                    source,
+                    output_path,
+                    cli.frame_delay_ms(),
+                    cli.sprite_time_unit,
+                    &filters,
+                    &cli.animation_format,
+                    cli.fallback_static,
+                    cli.texconv().timeout,
+                    cli.verbose,
+                    &console,
+                )
+                .await
+                .and_then(|_| finish_animation_output(cli, output_path, &source.sources()));
+                match result {
+                    Ok(()) => created_from_sheets += 1,
+                    Err(e) => {
+                        failures += 1;
+                        println!("❌ Failed to create {}: {:#}", output_path.display(), e);
+                    }
+                }
             }
-        } else {
-            base_name
-        };
-        
-        let output_filename = format!("{}.{}", clean_base, cli.animation_format);
-        let output_path = cli.output.join(output_filename);
-        
-        println!("📤 Creating: {}", output_path.display());
-        
-        // Check if we need to convert DDS files first
-        let has_dds = sequence.iter().any(|f| {
-            f.extension().and_then(|s| s.to_str()).unwrap_or("") == "dds"
-        });
-        
-        let processed_sequence = if has_dds {
-            println!("🔄 Converting DDS files to PNG first...");
-            let texconv_path = setup_texconv().await?;
-            convert_dds_sequence_to_png(sequence, &texconv_path, &cli.output).await?
-        } else {
-            sequence.clone()
-        };
-        
-        match cli.animation_format.as_str() {
-            "webp" => {
-                create_webp_animation(&processed_sequence, &output_path, cli.frame_delay)?;
-                println!("✅ WebP animation created successfully!");
+            AnimationSource::Animated(path) => {
+                println!("🔁 Re-encoding animation: {}", path.display());
+                // Writing a WebP onto itself would truncate the source before it is read
+                let result = if output_path == path {
+                    Err(anyhow::anyhow!("the output would overwrite the source"))
+                } else {
+                    reencode_animation(path, output_path, cli.frame_delay, &filters, &cli.animation_format, cli.fallback_static, cli.texconv().timeout, &console).await
+                };
+                match result {
+                    Ok(false) => {
+                        still += 1;
+                        if cli.verbose {
+                            println!("⏭️  {}: a still image, not an animation", path.display());
+                        }
+                    }
+                    Ok(true) => match finish_animation_output(cli, output_path, std::slice::from_ref(path)) {
+                        Ok(()) => {
+                            reencoded += 1;
+                            println!("📤 Created: {}", output_path.display());
+                        }
+                        Err(e) => {
+                            failures += 1;
+                            println!("❌ Failed to create {}: {:#}", output_path.display(), e);
+                        }
+                    },
+                    Err(e) => {
+                        failures += 1;
+                        println!("❌ Failed to re-encode {}: {:#}", path.display(), e);
+                    }
+                }
             }
-            _ => {
-                println!("❌ Only WebP format is supported (with transparency)");
-                continue;
+            AnimationSource::Sequence(sequence) => {
+                println!("🎞️  Processing sequence {} with {} frames", sequence[0].display(), sequence.len());
+
+                if cli.verbose {
+                    for (i, file) in sequence.iter().enumerate() {
+                        println!("  Frame {}: {}", i + 1, file.display());
+                    }
+                }
+
+                println!("📤 Creating: {}", output_path.display());
+
+                if cli.animation_format != "webp" {
+                    failures += 1;
+                    println!("❌ Only WebP format is supported (with transparency)");
+                    continue;
+                }
+
+                // Check if we need to convert DDS files first
+                let has_dds = sequence.iter().any(|f| {
+                    f.extension().and_then(|s| s.to_str()).unwrap_or("") == "dds"
+                });
+
+                let png_dir = tempfile::Builder::new()
+                    .prefix("dds-converter-frames-")
+                    .tempdir()
+                    .context("Failed to create a temporary directory")?;
+                let processed_sequence = if has_dds {
+                    println!("🔄 Converting DDS files to PNG first...");
+                    let texconv_path = setup_texconv(texconv).await?;
+                    convert_dds_sequence_to_png(sequence, &texconv_path, &cli.texconv(), png_dir.path(), &console).await?
+                } else {
+                    sequence.clone()
+                };
+
+                let result = create_webp_animation(&processed_sequence, output_path, cli.frame_delay_ms(), &filters, cli.fallback_static, cli.texconv().timeout, &console)
+                    .await
+                    .and_then(|_| finish_animation_output(cli, output_path, sequence));
+                match result {
+                    Ok(()) => {
+                        created_from_sequences += 1;
+                        println!("✅ WebP animation created successfully!");
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        println!("❌ Failed to create {}: {:#}", output_path.display(), e);
+                    }
+                }
             }
         }
     }
-    
-    println!("🎉 All animations created successfully!");
+
+    println!(
+        "📊 Sprite sheet animations: {}, re-encoded animations: {}, image sequence animations: {}",
+        created_from_sheets, reencoded, created_from_sequences
+    );
+    if still > 0 {
+        println!("⏭️  Skipped {} still GIF/WebP file(s)", still);
+    }
+    finish_animation_run(layout.outputs.len() - still, failures, "animations", selection_note.as_deref())
+}
+
+/// Where each animation in `named` (source, base name) goes: the mirrored
+/// layout from `plan_animation_outputs`, then `--on-collision` for what
+/// still clashes. Prints sanitized names and collision skips.
+fn animation_layout(cli: &AnimateArgs, input_root: &Path, named: &[(PathBuf, String)]) -> Result<CollisionPlan> {
+    let names = cli.naming.cleanup();
+    let planned = plan_animation_outputs(named, input_root, &cli.output, &cli.animation_format, names);
+    let raw = plan_animation_outputs(named, input_root, &cli.output, &cli.animation_format, names.raw());
+    let unsanitized = plan_animation_outputs(named, input_root, &cli.output, &cli.animation_format, NameCleanup { sanitize: None, ..names });
+    for ((animation, raw), unsanitized) in planned.iter().zip(&raw).zip(&unsanitized) {
+        if let Some(reason) = cleanup_reason(&raw.output, &unsanitized.output, &animation.output) {
+            println!("⚠️  Renamed {} -> {} ({})", raw.output.display(), animation.output.display(), reason);
+        }
+    }
+    let prefixed: Vec<PathBuf> = planned.iter().filter(|animation| animation.folder_prefixed).map(|animation| animation.source.clone()).collect();
+
+    let pairs = planned.into_iter().map(|animation| (animation.source, animation.output)).collect();
+    let mut layout = resolve_collisions(pairs, input_root, case_insensitive_names(&cli.output), cli.on_collision)?;
+    for resolved in &mut layout.outputs {
+        if resolved.note.is_none() && prefixed.contains(&resolved.input) {
+            resolved.note = Some("named after its folder: another animation has the same name".to_string());
+        }
+    }
+    if !layout.skipped.is_empty() {
+        println!("⚠️  {} animation(s) will be skipped because another has the same output name", layout.skipped.len());
+    }
+    Ok(layout)
+}
+
+/// `animate --dry-run`: each animation's source and final output path.
+fn print_animation_plan(layout: &CollisionPlan) {
+    println!("🔍 Dry-run mode - animations that would be created:");
+    for resolved in &layout.outputs {
+        println!("  {} -> {}", resolved.input.display(), resolved.output.display());
+        if let Some(note) = &resolved.note {
+            println!("    ({})", note);
+        }
+    }
+    for (source, kept) in &layout.skipped {
+        println!("  {} skipped (same output as {})", source.display(), kept.display());
+    }
+}
+
+/// Post-processing for a written animation: `--reproducible` normalization,
+/// then the `--sidecar` record.
+fn finish_animation_output(cli: &AnimateArgs, output_path: &Path, sources: &[PathBuf]) -> Result<()> {
+    if cli.reproducible {
+        normalize_output(output_path)?;
+    }
+    if !cli.sidecar {
+        return Ok(());
+    }
+    let settings = SidecarSettings::Animate {
+        format: cli.animation_format.clone(),
+        frame_delay_ms: cli.frame_delay_ms(),
+        tint: cli.tint.map(|tint| tint.to_string()),
+    };
+    let sidecar_path = write_sidecar(output_path, settings, sources)?;
+    if cli.verbose {
+        println!("🧾 Sidecar: {}", sidecar_path.display());
+    }
     Ok(())
 }
 
+fn finish_animation_run(total: usize, failures: usize, what: &str, selection_note: Option<&str>) -> Result<()> {
+    if let Some(note) = selection_note {
+        println!("✂️  Partial run: {}; other inputs were not processed", note);
+    }
+    if failures > 0 {
+        anyhow::bail!("{} of {} {} could not be created", failures, total, what);
+    }
+    println!("🎉 All {} created successfully!", what);
+    Ok(())
+}
+
+fn handle_sprite_mode(cli: &SpriteArgs) -> Result<()> {
+    let console = Console::plain();
+
+    let sheets = if cli.input.is_file() {
+        match sprite_source(&cli.input) {
+            Some(source) => vec![source],
+            None => anyhow::bail!("No .sprite file found next to {}", cli.input.display()),
+        }
+    } else {
+        let excluded = output_exclusion(&cli.input, &cli.output, false, false)?;
+        find_sprite_sequences(&cli.input, excluded.as_deref(), None)?
+    };
+
+    if sheets.is_empty() {
+        println!("❌ No sprite sheets (DDS + .sprite) found!");
+        return Ok(());
+    }
+
+    println!("📊 Found {} sprite sheet(s)", sheets.len());
+
+    let mut failures = 0;
+    for source in &sheets {
+        let dds_path = source.anchor();
+        let stem = texture_stem(&source.sprite);
+        let frame_prefix = cli.naming.cleanup().apply(&stem);
+        let frame_dir = cli.output.join(&frame_prefix);
+        match export_sprite_frames(source, &frame_dir, &frame_prefix, &console) {
+            Ok(count) => println!("✅ {}: {} frame(s) -> {}", dds_path.display(), count, frame_dir.display()),
+            Err(e) => {
+                failures += 1;
+                println!("❌ Failed to extract {}: {:#}", dds_path.display(), e);
+            }
+        }
+    }
+
+    finish_animation_run(sheets.len(), failures, "sprite sheet extractions", None)
+}
+
 async fn convert_dds_sequence_to_png(
     dds_files: &[PathBuf], 
     texconv_path: &Path, 
-    temp_dir: &Path
+    options: &TexconvOptions,
+    temp_dir: &Path,
+    console: &Console,
 ) -> Result<Vec<PathBuf>> {
     let mut png_files = Vec::new();
     
@@ -255,29 +1381,27 @@ async fn convert_dds_sequence_to_png(
     tokio::fs::create_dir_all(&png_temp_dir).await?;
     
     for dds_file in dds_files {
-        let png_name = dds_file.file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("frame");
-        let png_path = png_temp_dir.join(format!("{}.png", png_name));
+        let png_path = png_temp_dir.join(format!("{}.png", texture_stem(dds_file)));
+        let staging_dir = tempfile::Builder::new()
+            .prefix(".dds-converter-")
+            .tempdir_in(&png_temp_dir)
+            .context("Failed to create staging directory")?;
         
         // Convert DDS to PNG using texconv
-        let output = std::process::Command::new(texconv_path)
-            .arg("-f")
-            .arg("R8G8B8A8_UNORM")
-            .arg("-ft")
-            .arg("png")
-            .arg("-y")
-            .arg("-o")
-            .arg(&png_temp_dir)
-            .arg(dds_file)
-            .output()
-            .context("Failed to run texconv for DDS conversion")?;
+        let mut command = convert_command(texconv_path, options, dds_file, "R8G8B8A8_UNORM", "png", staging_dir.path(), dds_file, None);
+        if options.echo {
+            console.println(format!("🔧 {}", command_line(&command)));
+        }
+        let output = output_with_timeout(&mut command, options.timeout, "texconv")
+            .await
+            .with_context(|| format!("Failed to run texconv for {}", dds_file.display()))?;
         
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = decode_process_output(&output.stderr);
             anyhow::bail!("texconv failed for {}: {}", dds_file.display(), stderr);
         }
         
+        reconcile_output(staging_dir.path(), &png_path)?;
         png_files.push(png_path);
     }
     
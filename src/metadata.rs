@@ -0,0 +1,468 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::dds::DdsHeader;
+use crate::errors::{Classified, ErrorCode};
+
+/// XMP namespace for the fields that have no standard property.
+const XMP_NAMESPACE: &str = "urn:dds-converter:provenance/1.0/";
+
+/// Keys the built-in fields use; `--metadata` can't override them.
+const RESERVED_KEYS: &[&str] = &["Software", "Source", "SourceSHA256", "PixelFormat"];
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// `--embed-metadata`: what gets written into a converted image. Nothing in
+/// here depends on when the conversion ran, so it composes with
+/// `--reproducible`.
+pub struct Provenance {
+    /// Source path relative to the input folder, `/`-separated.
+    pub source: String,
+    pub source_sha256: String,
+    pub pixel_format: Option<String>,
+    pub software: String,
+    /// `--metadata key=value` pairs.
+    pub extra: Vec<(String, String)>,
+}
+
+impl Provenance {
+    pub fn describe(file: &Path, input_dir: &Path, extra: &[(String, String)]) -> Result<Self> {
+        let contents = std::fs::read(file)
+            .with_context(|| format!("Failed to read {} for hashing", file.display()))?;
+        let relative = file.strip_prefix(input_dir).unwrap_or(file);
+
+        Ok(Self {
+            source: relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            source_sha256: format!("{:x}", Sha256::digest(&contents)),
+            pixel_format: DdsHeader::read(file).ok().map(|header| header.format_name()),
+            software: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            extra: extra.to_vec(),
+        })
+    }
+
+    /// Key/value pairs in the order they are written.
+    fn fields(&self) -> Vec<(&str, &str)> {
+        let mut fields = vec![
+            ("Software", self.software.as_str()),
+            ("Source", self.source.as_str()),
+            ("SourceSHA256", self.source_sha256.as_str()),
+        ];
+        if let Some(pixel_format) = &self.pixel_format {
+            fields.push(("PixelFormat", pixel_format));
+        }
+        fields.extend(self.extra.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+        fields
+    }
+}
+
+/// Output formats `--embed-metadata` can write into.
+pub fn supports_embedding(format: &str) -> bool {
+    matches!(format, "png" | "png8" | "jpg")
+}
+
+/// Writes `provenance` into the PNG or JPEG at `path` in place.
+pub fn embed_metadata(path: &Path, provenance: &Provenance) -> Result<()> {
+    let ext = extension(path);
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read {} to embed metadata", path.display()))?;
+
+    let embedded = match ext.as_str() {
+        "png" => embed_png(&bytes, provenance),
+        "jpg" | "jpeg" => embed_jpeg(&bytes, provenance),
+        _ => return Ok(()),
+    };
+    let embedded = embedded.ok_or_else(|| Classified::error(
+        ErrorCode::EncoderError,
+        format!("Could not embed metadata: {} is not a well-formed {} file", path.display(), ext),
+    ))?;
+
+    std::fs::write(path, embedded)
+        .with_context(|| format!("Failed to write {} with embedded metadata", path.display()))
+}
+
+/// Adds one text chunk per field right after IHDR, where readers see them
+/// without decoding the image: tEXt when the value is
+/// Latin-1 safe ASCII, iTXt (UTF-8) otherwise.
+fn embed_png(bytes: &[u8], provenance: &Provenance) -> Option<Vec<u8>> {
+    let mut rest = bytes.strip_prefix(PNG_SIGNATURE)?;
+    let mut out = PNG_SIGNATURE.to_vec();
+
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let chunk = rest.get(..12 + length)?;
+        out.extend_from_slice(chunk);
+        if &chunk[4..8] == b"IHDR" {
+            for (key, value) in provenance.fields() {
+                write_png_text(&mut out, key, value);
+            }
+        }
+        rest = &rest[12 + length..];
+    }
+
+    Some(out)
+}
+
+fn write_png_text(out: &mut Vec<u8>, key: &str, value: &str) {
+    let (kind, data): (&[u8], Vec<u8>) = if value.is_ascii() {
+        (b"tEXt", [key.as_bytes(), b"\0", value.as_bytes()].concat())
+    } else {
+        // Uncompressed, no language tag, no translated keyword
+        (b"iTXt", [key.as_bytes(), b"\0\0\0\0\0", value.as_bytes()].concat())
+    };
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(&data);
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(&data);
+    out.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// Adds an XMP APP1 segment after SOI (and after the JFIF APP0 segment, which
+/// has to come first).
+fn embed_jpeg(bytes: &[u8], provenance: &Provenance) -> Option<Vec<u8>> {
+    const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+    let rest = bytes.strip_prefix(&[0xFF, 0xD8])?;
+    let insert_at = match rest.get(..2)? {
+        [0xFF, 0xE0] => 2 + u16::from_be_bytes(rest.get(2..4)?.try_into().ok()?) as usize,
+        _ => 0,
+    };
+    let (app0, rest) = (rest.get(..insert_at)?, &rest[insert_at..]);
+
+    let payload = [XMP_HEADER, xmp_packet(provenance).as_bytes()].concat();
+    let length = u16::try_from(payload.len() + 2).ok()?;
+
+    let mut out = vec![0xFF, 0xD8];
+    out.extend_from_slice(app0);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(rest);
+    Some(out)
+}
+
+fn xmp_packet(provenance: &Provenance) -> String {
+    let mut properties = String::new();
+    for (key, value) in provenance.fields() {
+        // Software is the one field with a standard XMP property
+        let name = match key {
+            "Software" => "xmp:CreatorTool".to_string(),
+            _ => format!("prov:{}", key),
+        };
+        properties.push_str(&format!("<{name}>{}</{name}>", xml_escape(value)));
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+         <rdf:Description rdf:about=\"\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" xmlns:prov=\"{}\">\
+         {}\
+         </rdf:Description></rdf:RDF></x:xmpmeta><?xpacket end=\"r\"?>",
+        XMP_NAMESPACE, properties
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `--strip-metadata`: drops every PNG chunk, JPEG segment and WebP chunk
+/// that isn't needed to display the image (ICC profiles too, unless
+/// `keep_icc`). Returns the number of bytes saved.
+pub fn strip_metadata(path: &Path, keep_icc: bool) -> Result<u64> {
+    // Transparency and colour interpretation; everything else ancillary goes
+    const PNG_DISPLAY: &[&[u8; 4]] = &[
+        b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tRNS", b"gAMA", b"cHRM", b"sRGB", b"acTL", b"fcTL", b"fdAT",
+    ];
+    const JPEG_APP0: u8 = 0xE0;
+    const JPEG_APP2: u8 = 0xE2;
+    const JPEG_APP14: u8 = 0xEE;
+    const JPEG_APP15: u8 = 0xEF;
+    const JPEG_COM: u8 = 0xFE;
+
+    let ext = extension(path);
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read {} to strip metadata", path.display()))?;
+
+    let stripped = match ext.as_str() {
+        "png" => filter_png_chunks(&bytes, |kind| PNG_DISPLAY.contains(&kind) || (keep_icc && kind == b"iCCP")),
+        // JFIF (APP0) and Adobe (APP14) tell decoders how to read the colour planes
+        "jpg" | "jpeg" => filter_jpeg_segments(&bytes, |marker, payload| match marker {
+            JPEG_APP2 => keep_icc && payload.starts_with(b"ICC_PROFILE\0"),
+            JPEG_APP0 | JPEG_APP14 => true,
+            m if (JPEG_APP0..=JPEG_APP15).contains(&m) || m == JPEG_COM => false,
+            _ => true,
+        }),
+        "webp" => filter_webp_chunks(&bytes, |kind| match kind {
+            b"ICCP" => keep_icc,
+            b"EXIF" | b"XMP " => false,
+            _ => true,
+        }),
+        _ => return Ok(0),
+    };
+    let stripped = stripped.ok_or_else(|| Classified::error(
+        ErrorCode::EncoderError,
+        format!("Could not strip metadata: {} is not a well-formed {} file", path.display(), ext),
+    ))?;
+
+    let saved = bytes.len().saturating_sub(stripped.len()) as u64;
+    if saved > 0 {
+        std::fs::write(path, stripped)
+            .with_context(|| format!("Failed to write stripped {}", path.display()))?;
+    }
+    Ok(saved)
+}
+
+/// Lowercased extension of `path`, or an empty string.
+pub fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Rebuilds a PNG from the chunks `keep` accepts. Returns `None` if the file
+/// doesn't parse.
+pub fn filter_png_chunks(bytes: &[u8], keep: impl Fn(&[u8; 4]) -> bool) -> Option<Vec<u8>> {
+    let mut rest = bytes.strip_prefix(PNG_SIGNATURE)?;
+    let mut out = PNG_SIGNATURE.to_vec();
+
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let chunk = rest.get(..12 + length)?;
+        if keep(chunk[4..8].try_into().ok()?) {
+            out.extend_from_slice(chunk);
+        }
+        rest = &rest[12 + length..];
+    }
+
+    Some(out)
+}
+
+/// Rebuilds a JPEG from the header segments `keep` accepts (given the marker
+/// and the payload after the length); everything from the start of scan on
+/// is kept as is. Returns `None` if the file doesn't parse.
+pub fn filter_jpeg_segments(bytes: &[u8], keep: impl Fn(u8, &[u8]) -> bool) -> Option<Vec<u8>> {
+    const SOS: u8 = 0xDA;
+
+    let mut rest = bytes.strip_prefix(&[0xFF, 0xD8])?;
+    let mut out = vec![0xFF, 0xD8];
+
+    loop {
+        if *rest.first()? != 0xFF {
+            return None;
+        }
+        let marker = *rest.get(1)?;
+        if marker == 0xFF {
+            // Fill byte
+            rest = &rest[1..];
+            continue;
+        }
+        if marker == SOS {
+            out.extend_from_slice(rest);
+            return Some(out);
+        }
+
+        let length = u16::from_be_bytes(rest.get(2..4)?.try_into().ok()?) as usize;
+        // The length counts its own two bytes, so anything shorter is malformed
+        let segment = rest.get(..2 + length)?;
+        if keep(marker, segment.get(4..)?) {
+            out.extend_from_slice(segment);
+        }
+        rest = &rest[2 + length..];
+    }
+}
+
+/// Rebuilds a WebP from the chunks `keep` accepts, clearing the VP8X flags
+/// of ICC, EXIF and XMP chunks that were dropped. Returns `None` if the file
+/// doesn't parse.
+pub fn filter_webp_chunks(bytes: &[u8], keep: impl Fn(&[u8]) -> bool) -> Option<Vec<u8>> {
+    const FLAGS: &[(&[u8], u8)] = &[(b"ICCP", 0x20), (b"EXIF", 0x08), (b"XMP ", 0x04)];
+
+    if bytes.get(..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    let mut rest = &bytes[12..];
+    let mut body = b"WEBP".to_vec();
+    let mut vp8x_flags = None;
+    let mut dropped_flags = 0u8;
+
+    while !rest.is_empty() {
+        let kind = rest.get(..4)?;
+        let size = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+        let padded = size + (size & 1);
+        let chunk = rest.get(..8 + padded).or_else(|| rest.get(..8 + size))?;
+
+        if kind == b"VP8X" {
+            vp8x_flags = Some(body.len() + 8);
+        }
+        if keep(kind) {
+            body.extend_from_slice(chunk);
+        } else if let Some((_, flag)) = FLAGS.iter().find(|(name, _)| *name == kind) {
+            dropped_flags |= flag;
+        }
+        rest = &rest[chunk.len()..];
+    }
+
+    if let Some(offset) = vp8x_flags {
+        *body.get_mut(offset)? &= !dropped_flags;
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Some(out)
+}
+
+/// `--metadata key=value`. Keys become PNG keywords and XMP element names,
+/// so they are limited to ASCII letters, digits, `_` and `-`, starting with
+/// a letter.
+pub fn parse_metadata_pair(value: &str) -> Result<(String, String), String> {
+    let Some((key, text)) = value.split_once('=') else {
+        return Err(format!("'{}' is not in key=value form", value));
+    };
+    let key = key.trim();
+
+    let valid = key.len() <= 79
+        && key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!(
+            "'{}' is not a valid key (letters, digits, '_' and '-', starting with a letter)",
+            key
+        ));
+    }
+    if RESERVED_KEYS.iter().any(|reserved| reserved.eq_ignore_ascii_case(key)) {
+        return Err(format!("'{}' is written by --embed-metadata itself", key));
+    }
+    Ok((key.to_string(), text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_jpeg_segment_is_malformed() {
+        // APP1 whose length (1) is shorter than the length field itself
+        let bytes = [0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x01, 0xFF, 0xDA, 0x00];
+        assert_eq!(filter_jpeg_segments(&bytes, |_, _| true), None);
+    }
+
+    #[test]
+    fn jpeg_comment_is_dropped() {
+        let bytes = [0xFF, 0xD8, 0xFF, 0xFE, 0x00, 0x04, b'h', b'i', 0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9];
+        let filtered = filter_jpeg_segments(&bytes, |marker, _| marker != 0xFE).unwrap();
+        assert_eq!(filtered, [0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9]);
+    }
+
+    #[test]
+    fn stripped_png_decodes_to_the_same_pixels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("icon.png");
+        let image = image::RgbaImage::from_fn(8, 8, |x, y| image::Rgba([x as u8 * 30, y as u8 * 30, 128, 200]));
+        image.save(&path).unwrap();
+
+        // Put a text chunk right after IHDR
+        let bytes = std::fs::read(&path).unwrap();
+        let header_end = PNG_SIGNATURE.len() + 12 + 13;
+        let mut tagged = bytes[..header_end].to_vec();
+        write_png_text(&mut tagged, "Software", "some encoder");
+        tagged.extend_from_slice(&bytes[header_end..]);
+        std::fs::write(&path, &tagged).unwrap();
+
+        let saved = strip_metadata(&path, false).unwrap();
+        assert_eq!(saved as usize, tagged.len() - bytes.len());
+        assert_eq!(image::open(&path).unwrap().to_rgba8(), image);
+    }
+
+    fn provenance() -> Provenance {
+        Provenance {
+            source: "ui/café & co.dds".to_string(),
+            source_sha256: "ab".repeat(32),
+            pixel_format: Some("BC7_UNORM".to_string()),
+            software: "dds-converter 1.0.0".to_string(),
+            extra: vec![("Build".to_string(), "4.2 <beta>".to_string())],
+        }
+    }
+
+    #[test]
+    fn embedded_png_text_reads_back_with_a_png_decoder() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("icon.png");
+        let image = image::RgbaImage::from_pixel(4, 4, image::Rgba([10, 20, 30, 255]));
+        image.save(&path).unwrap();
+        embed_metadata(&path, &provenance()).unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        let latin1: Vec<(&str, &str)> = info
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str()))
+            .collect();
+        assert_eq!(latin1, [
+            ("Software", "dds-converter 1.0.0"),
+            ("SourceSHA256", "ab".repeat(32).as_str()),
+            ("PixelFormat", "BC7_UNORM"),
+            ("Build", "4.2 <beta>"),
+        ]);
+        // The non-ASCII path goes in an iTXt chunk
+        let utf8 = &info.utf8_text[0];
+        assert_eq!((utf8.keyword.as_str(), utf8.get_text().unwrap().as_str()), ("Source", "ui/café & co.dds"));
+
+        assert_eq!(image::open(&path).unwrap().to_rgba8(), image);
+    }
+
+    #[test]
+    fn embedded_jpeg_xmp_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("icon.jpg");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([10, 20, 30])).save(&path).unwrap();
+        embed_metadata(&path, &provenance()).unwrap();
+
+        // Walk the segments before the scan to find the XMP APP1
+        let bytes = std::fs::read(&path).unwrap();
+        let mut xmp = None;
+        let mut at = 2;
+        while bytes[at + 1] != 0xDA {
+            let length = u16::from_be_bytes([bytes[at + 2], bytes[at + 3]]) as usize;
+            let payload = &bytes[at + 4..at + 2 + length];
+            if bytes[at + 1] == 0xE1 {
+                xmp = payload.strip_prefix(b"http://ns.adobe.com/xap/1.0/\0".as_slice());
+            }
+            at += 2 + length;
+        }
+        let xmp = std::str::from_utf8(xmp.expect("no XMP segment")).unwrap();
+        assert!(xmp.contains("<xmp:CreatorTool>dds-converter 1.0.0</xmp:CreatorTool>"), "{}", xmp);
+        assert!(xmp.contains("<prov:Source>ui/café &amp; co.dds</prov:Source>"), "{}", xmp);
+        assert!(xmp.contains("<prov:PixelFormat>BC7_UNORM</prov:PixelFormat>"), "{}", xmp);
+        assert!(xmp.contains("<prov:Build>4.2 &lt;beta&gt;</prov:Build>"), "{}", xmp);
+        assert!(xmp.contains(XMP_NAMESPACE));
+        // JFIF stays the first segment, and the image still decodes
+        assert_eq!(bytes[2..4], [0xFF, 0xE0]);
+        assert_eq!(image::open(&path).unwrap().to_rgb8().dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn built_in_keys_cant_be_given_with_metadata() {
+        assert_eq!(parse_metadata_pair("Build=4.2").unwrap(), ("Build".to_string(), "4.2".to_string()));
+        assert!(parse_metadata_pair("Source=elsewhere").unwrap_err().contains("--embed-metadata itself"));
+        assert!(parse_metadata_pair("no-equals").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::cubemap::FACE_NAMES;
+use crate::dds::{DdsHeader, PixelFormat, MAX_HEADER_BYTES};
+use crate::errors::{Classified, ErrorCode};
+
+const DDSCAPS2_CUBEMAP_ALL: u32 = 0xfe00;
+const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// `_mip0`, `_mip1`, ... for `--all-mips` outputs.
+pub fn mip_suffix(level: u32) -> String {
+    format!("mip{}", level)
+}
+
+/// Names 2D surface `layer` of `header` in `--all-mips` outputs: `posx` ...
+/// `negz` for cube faces (`cube1_posx` from the second cube of an array),
+/// `slice1` for array slices, and `None` for a plain 2D texture.
+pub fn surface_name(header: &DdsHeader, layer: u32) -> Option<String> {
+    if header.layer_count() <= 1 {
+        return None;
+    }
+    if !header.is_cubemap {
+        return Some(format!("slice{}", layer));
+    }
+    let face = FACE_NAMES[layer as usize % 6];
+    Some(match header.array_size {
+        1 => face.to_string(),
+        _ => format!("cube{}_{}", layer / 6, face),
+    })
+}
+
+/// The suffix of mip `level` of surface `layer`: `mip2`, `posx_mip2`, ...
+pub fn surface_mip_suffix(header: &DdsHeader, layer: u32, level: u32) -> String {
+    match surface_name(header, layer) {
+        Some(surface) => format!("{}_{}", surface, mip_suffix(level)),
+        None => mip_suffix(level),
+    }
+}
+
+/// Writes the stored data of mip `level` of 2D surface `layer` (array slice
+/// or cube face) of the DDS in `bytes` to `output` as a plain DDS with just
+/// that one image, so texconv converts exactly what the file holds rather
+/// than a resampled copy.
+pub fn extract_surface(bytes: &[u8], header: &DdsHeader, layer: u32, level: u32, output: &Path) -> Result<()> {
+    if header.depth > 1 {
+        anyhow::bail!("volume textures can't be split into single images");
+    }
+    let level_bytes = |level: u32| {
+        header.mip_bytes(level).with_context(|| format!("the mip layout of {} isn't known", header.format_name()))
+    };
+
+    // Surfaces are stored one after another, each with its full mip chain
+    let mut chain = 0;
+    for any in 0..header.mip_count {
+        chain += level_bytes(any)?;
+    }
+    let mut offset = header.data_offset() + chain * layer as usize;
+    for smaller in 0..level {
+        offset += level_bytes(smaller)?;
+    }
+    let size = level_bytes(level)?;
+    let Some(data) = bytes.get(offset..offset + size) else {
+        return Err(Classified::error(
+            ErrorCode::CorruptHeader,
+            format!("surface {} mip {} lies past the end of the file ({} bytes)", layer, level, bytes.len()),
+        ));
+    };
+
+    let mut single = bytes[..header.data_offset()].to_vec();
+    let field = |offset: usize| u32::from_le_bytes(single[offset..offset + 4].try_into().unwrap());
+    let caps2 = field(112);
+    let dx10 = matches!(header.pixel_format, PixelFormat::Dxgi(_)) && single.len() >= MAX_HEADER_BYTES;
+    let misc = if dx10 { field(136) } else { 0 };
+    let (width, height) = header.mip_dimensions(level);
+    let mut put = |offset: usize, value: u32| single[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    put(12, height);
+    put(16, width);
+    put(20, size as u32);
+    put(28, 1);
+    put(112, caps2 & !DDSCAPS2_CUBEMAP_ALL);
+    if dx10 {
+        put(136, misc & !DX10_MISC_TEXTURECUBE);
+        put(140, 1);
+    }
+    single.extend_from_slice(data);
+
+    std::fs::write(output, single)
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::dx10_header;
+
+    /// A 16x16 R8G8B8A8 texture with 4 mips and `layers` surfaces, each
+    /// byte holding its surface and level (`layer * 16 + level`).
+    fn texture(layers: u32, cubemap: bool) -> Vec<u8> {
+        let mut bytes = dx10_header(28, 16, 16, 4);
+        if cubemap {
+            bytes[136..140].copy_from_slice(&4u32.to_le_bytes());
+            bytes[140..144].copy_from_slice(&(layers / 6).to_le_bytes());
+        } else {
+            bytes[140..144].copy_from_slice(&layers.to_le_bytes());
+        }
+        for layer in 0..layers {
+            for level in 0..4u32 {
+                let size = (16usize >> level).pow(2) * 4;
+                bytes.extend(std::iter::repeat_n((layer * 16 + level) as u8, size));
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn every_level_is_its_own_stored_data_at_halving_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = texture(1, false);
+        let header = DdsHeader::parse(&bytes).unwrap();
+        for level in 0..4 {
+            let output = dir.path().join(format!("{}.dds", surface_mip_suffix(&header, 0, level)));
+            extract_surface(&bytes, &header, 0, level, &output).unwrap();
+            let single = std::fs::read(&output).unwrap();
+            let extracted = DdsHeader::parse(&single).unwrap();
+            assert_eq!((extracted.width, extracted.height, extracted.mip_count), (16 >> level, 16 >> level, 1));
+            let data = &single[extracted.data_offset()..];
+            assert_eq!(data.len(), (16usize >> level).pow(2) * 4);
+            assert!(data.iter().all(|&byte| byte == level as u8));
+        }
+        assert_eq!(surface_mip_suffix(&header, 0, 3), "mip3");
+    }
+
+    #[test]
+    fn every_cube_face_is_extracted_not_just_the_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = texture(6, true);
+        let header = DdsHeader::parse(&bytes).unwrap();
+        assert!(header.is_cubemap);
+        for layer in 0..6 {
+            let output = dir.path().join("face.dds");
+            extract_surface(&bytes, &header, layer, 2, &output).unwrap();
+            let single = std::fs::read(&output).unwrap();
+            let extracted = DdsHeader::parse(&single).unwrap();
+            assert!(!extracted.is_cubemap);
+            assert_eq!((extracted.width, extracted.layer_count()), (4, 1));
+            assert!(single[extracted.data_offset()..].iter().all(|&byte| byte == (layer * 16 + 2) as u8));
+        }
+        assert_eq!(surface_mip_suffix(&header, 1, 0), "negx_mip0");
+    }
+
+    #[test]
+    fn surfaces_of_arrays_are_named_by_slice_or_cube() {
+        let array = DdsHeader::parse(&texture(3, false)).unwrap();
+        assert_eq!(surface_name(&array, 2).as_deref(), Some("slice2"));
+        let cubes = DdsHeader::parse(&texture(12, true)).unwrap();
+        assert_eq!(surface_name(&cubes, 10).as_deref(), Some("cube1_posz"));
+    }
+}
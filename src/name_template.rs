@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use crate::content_hash::HASH_PLACEHOLDER;
+use crate::utils::texture_stem;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Stem,
+    Ext,
+    Parent,
+    RelPath,
+    /// Zero-padded to this many digits.
+    Index(usize),
+    /// Hex digits of the output's hash.
+    ContentHash(usize),
+}
+
+/// `--name-template`: an output file name with `{stem}`, `{ext}`,
+/// `{parent}`, `{relpath}`, `{index}` and `{contenthash}` placeholders,
+/// checked when the arguments are parsed.
+#[derive(Debug, Clone)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+pub fn parse_name_template(value: &str) -> Result<NameTemplate, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or_else(|| format!("'{}' has a '{{' without a matching '}}'", value))?;
+                let name = &rest[..end];
+                chars = rest[end + 1..].chars();
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(parse_placeholder(name).ok_or_else(|| {
+                    format!("unknown placeholder {{{}}} (use {{stem}}, {{ext}}, {{parent}}, {{relpath}}, {{index}}, {{index:N}}, {{contenthash}} or {{contenthash:N}} with N from 4 to 64)", name)
+                })?);
+            }
+            '}' => return Err(format!("'{}' has a '}}' without a matching '{{'", value)),
+            '/' | '\\' => return Err(format!("'{}' must be a file name, without folders", value)),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    if !parts.iter().any(|part| matches!(part, Part::Stem | Part::RelPath | Part::Index(_))) {
+        return Err(format!("'{}' has no {{stem}}, {{relpath}} or {{index}}, so every file would get the same name", value));
+    }
+    Ok(NameTemplate { parts })
+}
+
+fn parse_placeholder(name: &str) -> Option<Part> {
+    match name {
+        "stem" => Some(Part::Stem),
+        "ext" => Some(Part::Ext),
+        "parent" => Some(Part::Parent),
+        "relpath" => Some(Part::RelPath),
+        "index" => Some(Part::Index(0)),
+        "contenthash" => Some(Part::ContentHash(8)),
+        _ => match name.split_once(':')? {
+            ("index", width) => width.parse().ok().map(Part::Index),
+            ("contenthash", digits) => digits.parse().ok().filter(|digits| (4..=64).contains(digits)).map(Part::ContentHash),
+            _ => None,
+        },
+    }
+}
+
+impl NameTemplate {
+    /// The output file name for `input` (under `input_dir`), written as
+    /// `ext`, the `index`-th file of the run counting from 1. `{parent}` is
+    /// the input's folder (the input folder itself for top-level files) and
+    /// `{relpath}` its path under `input_dir` without the extension, with
+    /// `_` for the folder separators. `{contenthash}` is left as a
+    /// `{contenthash-N}` stand-in for `fill_hash_placeholder` to replace
+    /// once the output is encoded.
+    pub fn render(&self, input: &Path, input_dir: &Path, ext: &str, index: usize) -> String {
+        let relative = input.strip_prefix(input_dir).unwrap_or(input);
+        let folder_name = |path: &Path| path.file_name().map(|name| name.to_string_lossy().into_owned());
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Stem => name.push_str(&texture_stem(input)),
+                Part::Ext => name.push_str(ext),
+                Part::Parent => {
+                    let parent = relative.parent().and_then(folder_name).or_else(|| folder_name(input_dir));
+                    name.push_str(&parent.unwrap_or_default());
+                }
+                Part::RelPath => {
+                    let mut folders: Vec<String> = relative
+                        .parent()
+                        .map(|parent| parent.iter().map(|part| part.to_string_lossy().into_owned()).collect())
+                        .unwrap_or_default();
+                    folders.push(texture_stem(input));
+                    name.push_str(&folders.join("_"));
+                }
+                Part::Index(width) => name.push_str(&format!("{:0width$}", index, width = *width)),
+                Part::ContentHash(digits) => name.push_str(&format!("{}{}}}", HASH_PLACEHOLDER, digits)),
+            }
+        }
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, input: &str) -> String {
+        parse_name_template(template).unwrap().render(Path::new(input), Path::new("in"), "png", 7)
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        assert_eq!(render("{parent}_{stem}.{ext}", "in/rock/diffuse.dds"), "rock_diffuse.png");
+        assert_eq!(render("{relpath}.{ext}", "in/a/b/c.dds"), "a_b_c.png");
+        assert_eq!(render("{index:3}_{stem}.{ext}", "in/c.dds"), "007_c.png");
+        assert_eq!(render("{stem}.{contenthash}.{ext}", "in/c.dds"), "c.{contenthash-8}.png");
+        assert_eq!(render("{stem}.{contenthash:12}.{ext}", "in/c.dds"), "c.{contenthash-12}.png");
+    }
+
+    #[test]
+    fn rejects_bad_placeholders() {
+        assert!(parse_name_template("{name}.{ext}").is_err());
+        assert!(parse_name_template("{stem}.{contenthash:2}").is_err());
+        assert!(parse_name_template("{stem}.{contenthash:x}").is_err());
+        assert!(parse_name_template("{ext}").is_err());
+        assert!(parse_name_template("a/{stem}").is_err());
+    }
+
+    #[test]
+    fn content_hash_is_filled_from_the_output_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let staged = dir.path().join("staged.png");
+        std::fs::write(&staged, b"abc").unwrap();
+        let planned = dir.path().join(render("{stem}.{contenthash}.{ext}", "in/sword_icon.dds"));
+
+        let filled = crate::content_hash::fill_hash_placeholder(&planned, &staged).unwrap();
+        // SHA-256("abc") starts with ba7816bf
+        assert_eq!(filled, dir.path().join("sword_icon.ba7816bf.png"));
+    }
+}
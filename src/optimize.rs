@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use oxipng::{Deflaters, Options};
+use std::num::NonZeroU8;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// PNGs up to this size get zopfli at `--optimize 2` and up; on bigger
+/// ones it takes many seconds for a percent or two.
+const ZOPFLI_MAX_BYTES: u64 = 256 * 1024;
+
+/// Most of zopfli's gain for a third of the time of oxipng's default 15.
+const ZOPFLI_ITERATIONS: NonZeroU8 = NonZeroU8::new(5).unwrap();
+
+/// Per-run `--optimize` totals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptimizeStats {
+    pub files: u64,
+    /// PNGs over `--optimize-max-size` that were left as they were.
+    pub skipped: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub elapsed: Duration,
+}
+
+impl std::ops::AddAssign for OptimizeStats {
+    fn add_assign(&mut self, other: Self) {
+        self.files += other.files;
+        self.skipped += other.skipped;
+        self.bytes_before += other.bytes_before;
+        self.bytes_after += other.bytes_after;
+        self.elapsed += other.elapsed;
+    }
+}
+
+impl OptimizeStats {
+    pub fn saved(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// oxipng settings for `--optimize LEVEL` on a PNG of `size` bytes:
+/// 1 tries the row filters and recompresses (oxipng's `-o 2`), 2 switches
+/// to zopfli for small files, 3 also reduces colour type, bit depth and
+/// palette where that loses nothing.
+fn options(level: u8, size: u64) -> Options {
+    let mut options = Options::from_preset(2);
+    let reduce = level >= 3;
+    options.bit_depth_reduction = reduce;
+    options.color_type_reduction = reduce;
+    options.palette_reduction = reduce;
+    options.grayscale_reduction = reduce;
+    if level >= 2 && size <= ZOPFLI_MAX_BYTES {
+        options.deflate = Deflaters::Zopfli { iterations: ZOPFLI_ITERATIONS };
+    }
+    options
+}
+
+/// Recompresses `path` in place if it is a PNG and the result is smaller.
+/// At level 2 and up, PNGs over `max_size` bytes are skipped. Other files
+/// are left alone and count for nothing.
+pub fn optimize_png(path: &Path, level: u8, max_size: u64) -> Result<OptimizeStats> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Ok(OptimizeStats::default());
+    }
+    let size = data.len() as u64;
+    if level >= 2 && size > max_size {
+        return Ok(OptimizeStats { skipped: 1, ..OptimizeStats::default() });
+    }
+
+    // A palette's own bytes can outweigh what it saves on small images, so
+    // level 3 also tries without the reductions
+    let levels: &[u8] = if level >= 3 { &[2, 3] } else { &[level] };
+    let started = Instant::now();
+    let mut smallest: Option<Vec<u8>> = None;
+    for &try_level in levels {
+        let optimized = oxipng::optimize_from_memory(&data, &options(try_level, size))
+            .with_context(|| format!("Failed to optimize {}", path.display()))?;
+        if optimized.len() < smallest.as_ref().map_or(data.len(), Vec::len) {
+            smallest = Some(optimized);
+        }
+    }
+    let bytes_after = match smallest {
+        Some(optimized) => {
+            std::fs::write(path, &optimized).with_context(|| format!("Failed to write {}", path.display()))?;
+            optimized.len() as u64
+        }
+        None => size,
+    };
+    Ok(OptimizeStats { files: 1, skipped: 0, bytes_before: size, bytes_after, elapsed: started.elapsed() })
+}
@@ -0,0 +1,449 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{edit_distance, parse_output_format, ConvertArgs};
+use crate::crop::{parse_crop, CropMode, CropRect};
+use crate::diff::relative_key;
+use crate::quantize::Dither;
+use crate::resize::{parse_dimensions, Size};
+use crate::srgb::SrgbMode;
+use crate::texconv::parse_swizzle;
+use crate::utils::{parse_glob, GlobPattern};
+
+/// Keys a `<name>.dds.toml` file or a `--rules` rule can set.
+const KEYS: &[&str] = &[
+    "format", "quality", "swizzle", "resize", "srgb", "crop", "crop_mode", "trim", "alpha_threshold", "dither", "pad_pot",
+];
+
+/// Options set for one texture over the command line, by the `--rules`
+/// that match it and then its own `<name>.dds.toml`.
+#[derive(Debug, Clone, Default)]
+pub struct FileOverrides {
+    pub format: Option<String>,
+    pub quality: Option<u8>,
+    pub swizzle: Option<String>,
+    /// `Some(None)` for `resize = "none"`, which turns `--resize` and
+    /// `--max-dimension` off.
+    pub resize: Option<Option<Size>>,
+    pub srgb: Option<SrgbMode>,
+    /// `Some(None)` for `crop = "none"`, which turns a `--crop` off.
+    pub crop: Option<Option<CropRect>>,
+    pub crop_mode: Option<CropMode>,
+    pub trim: Option<bool>,
+    pub alpha_threshold: Option<u8>,
+    pub dither: Option<Dither>,
+    pub pad_pot: Option<bool>,
+    /// Where the overrides came from, for `--dry-run`: rules and the
+    /// override file, in the order they were layered.
+    pub sources: Vec<String>,
+}
+
+impl FileOverrides {
+    /// These overrides with every key `over` sets replaced by its value.
+    fn layer(&mut self, over: &FileOverrides) {
+        fn set<T: Clone>(value: &mut Option<T>, over: &Option<T>) {
+            if over.is_some() {
+                value.clone_from(over);
+            }
+        }
+        set(&mut self.format, &over.format);
+        set(&mut self.quality, &over.quality);
+        set(&mut self.swizzle, &over.swizzle);
+        set(&mut self.resize, &over.resize);
+        set(&mut self.srgb, &over.srgb);
+        set(&mut self.crop, &over.crop);
+        set(&mut self.crop_mode, &over.crop_mode);
+        set(&mut self.trim, &over.trim);
+        set(&mut self.alpha_threshold, &over.alpha_threshold);
+        set(&mut self.dither, &over.dither);
+        set(&mut self.pad_pot, &over.pad_pot);
+        self.sources.extend(over.sources.iter().cloned());
+    }
+
+    /// `cli` with these overrides applied.
+    pub fn apply(&self, cli: &ConvertArgs) -> ConvertArgs {
+        let mut effective = cli.clone();
+        if let Some(format) = &self.format {
+            effective.format = format.clone();
+        }
+        if let Some(quality) = self.quality {
+            // A file's own quality takes the place of the --target-size search
+            effective.jpeg_quality = Some(quality);
+            effective.target_size = None;
+        }
+        if let Some(swizzle) = &self.swizzle {
+            effective.swizzle = Some(swizzle.clone());
+        }
+        if let Some(resize) = self.resize {
+            effective.resize = resize;
+            effective.max_dimension = None;
+        }
+        if let Some(srgb) = self.srgb {
+            effective.srgb = srgb;
+        }
+        if let Some(crop) = self.crop {
+            effective.crop = crop;
+        }
+        if let Some(crop_mode) = self.crop_mode {
+            effective.crop_mode = crop_mode;
+        }
+        if let Some(trim) = self.trim {
+            effective.trim = trim;
+        }
+        if let Some(threshold) = self.alpha_threshold {
+            effective.alpha_threshold = Some(threshold);
+        }
+        if let Some(dither) = self.dither {
+            effective.dither = dither;
+        }
+        if let Some(pad_pot) = self.pad_pot {
+            effective.pad_pot = pad_pot;
+        }
+        effective
+    }
+
+    /// `format=jpg, trim=true`, for `--dry-run`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(format) = &self.format {
+            parts.push(format!("format={}", format));
+        }
+        if let Some(quality) = self.quality {
+            parts.push(format!("quality={}", quality));
+        }
+        if let Some(swizzle) = &self.swizzle {
+            parts.push(format!("swizzle={}", swizzle));
+        }
+        match self.resize {
+            Some(Some(size)) => parts.push(format!("resize={}", size)),
+            Some(None) => parts.push("resize=none".to_string()),
+            None => {}
+        }
+        if let Some(srgb) = self.srgb {
+            parts.push(format!("srgb={}", value_name(srgb)));
+        }
+        match self.crop {
+            Some(Some(rect)) => parts.push(format!("crop={}", rect)),
+            Some(None) => parts.push("crop=none".to_string()),
+            None => {}
+        }
+        if let Some(crop_mode) = self.crop_mode {
+            parts.push(format!("crop_mode={}", value_name(crop_mode)));
+        }
+        if let Some(trim) = self.trim {
+            parts.push(format!("trim={}", trim));
+        }
+        if let Some(threshold) = self.alpha_threshold {
+            parts.push(format!("alpha_threshold={}", threshold));
+        }
+        if let Some(dither) = self.dither {
+            parts.push(format!("dither={}", value_name(dither)));
+        }
+        if let Some(pad_pot) = self.pad_pot {
+            parts.push(format!("pad_pot={}", pad_pot));
+        }
+        parts.join(", ")
+    }
+}
+
+/// `texture.dds` -> `texture.dds.toml`
+fn overrides_path(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".toml");
+    PathBuf::from(name)
+}
+
+/// `--rules`: overrides for every texture whose path (relative to
+/// `--input`) matches a rule's `match` glob. Later rules win over earlier
+/// ones; a texture's own `<name>.dds.toml` wins over all of them.
+#[derive(Debug, Default)]
+pub struct OverrideRules {
+    rules: Vec<Rule>,
+}
+
+type Rule = (GlobPattern, FileOverrides);
+
+impl OverrideRules {
+    /// Reads the `--rules` file, if any. Unknown keys are returned as
+    /// warnings.
+    pub fn load(path: Option<&Path>) -> Result<(Self, Vec<String>)> {
+        let Some(path) = path else {
+            return Ok((Self::default(), Vec::new()));
+        };
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let (rules, warnings) = parse_rules(&text, &path.display().to_string()).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        let warnings = warnings.into_iter().map(|warning| format!("{}: {}", path.display(), warning)).collect();
+        Ok((OverrideRules { rules }, warnings))
+    }
+
+    /// The rules matching `file`, layered in order.
+    fn matching(&self, file: &Path, input: &Path) -> Option<FileOverrides> {
+        let relative = relative_key(input, file);
+        let mut matched = None;
+        for (pattern, rule) in &self.rules {
+            if pattern.matches(&relative) {
+                matched.get_or_insert_with(FileOverrides::default).layer(rule);
+            }
+        }
+        matched
+    }
+}
+
+/// The overrides for `files`: the `rules` matching each, then the override
+/// file next to it. Files with neither are left out; an unreadable or
+/// invalid override file is an error. Unknown keys are reported as
+/// warnings, with a suggestion where one is close.
+pub fn load_overrides(files: &[PathBuf], input: &Path, rules: &OverrideRules) -> Result<(HashMap<PathBuf, FileOverrides>, Vec<String>)> {
+    let mut overrides = HashMap::new();
+    let mut warnings = Vec::new();
+    for file in files {
+        let mut file_overrides = rules.matching(file, input);
+        let path = overrides_path(file);
+        if path.is_file() {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let (mut parsed, unknown) = parse_overrides(&text)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            warnings.extend(unknown.into_iter().map(|warning| format!("{}: {}", path.display(), warning)));
+            parsed.sources.push(path.display().to_string());
+            file_overrides.get_or_insert_with(FileOverrides::default).layer(&parsed);
+        }
+        if let Some(file_overrides) = file_overrides {
+            overrides.insert(file.clone(), file_overrides);
+        }
+    }
+    Ok((overrides, warnings))
+}
+
+/// Parses a `--rules` file: `[[rule]]` tables, each with a `match` glob
+/// and override keys. `name` labels the rules in `FileOverrides::sources`.
+pub fn parse_rules(text: &str, name: &str) -> Result<(Vec<Rule>, Vec<String>), String> {
+    let value: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let table = value.as_table().ok_or("expected [[rule]] tables")?;
+    if let Some(key) = table.keys().find(|key| *key != "rule") {
+        return Err(format!("unexpected '{}'; every rule goes in a [[rule]] table", key));
+    }
+    let Some(list) = table.get("rule") else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let list = list.as_array().ok_or("'rule' must be [[rule]] tables")?;
+
+    let mut rules = Vec::new();
+    let mut warnings = Vec::new();
+    for (index, rule) in list.iter().enumerate() {
+        let number = index + 1;
+        let mut rule = rule.as_table().cloned().ok_or_else(|| format!("rule {} is not a table", number))?;
+        let pattern = match rule.remove("match") {
+            Some(toml::Value::String(pattern)) => parse_glob(&pattern).map_err(|e| format!("rule {}: 'match': {}", number, e))?,
+            Some(_) => return Err(format!("rule {}: 'match' must be a glob string", number)),
+            None => return Err(format!("rule {} has no 'match' glob", number)),
+        };
+        let (mut overrides, unknown) = parse_table(&rule).map_err(|e| format!("rule {}: {}", number, e))?;
+        warnings.extend(unknown.into_iter().map(|warning| format!("rule {}: {}", number, warning)));
+        overrides.sources.push(format!("{} rule {} ({})", name, number, pattern.pattern));
+        rules.push((pattern, overrides));
+    }
+    Ok((rules, warnings))
+}
+
+/// Parses the contents of an override file. Returns the overrides and a
+/// warning for every key that isn't one of `KEYS`.
+pub fn parse_overrides(text: &str) -> Result<(FileOverrides, Vec<String>), String> {
+    let value: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    parse_table(value.as_table().ok_or("expected a table of options")?)
+}
+
+/// The override keys of `table`, as in an override file.
+fn parse_table(table: &toml::value::Table) -> Result<(FileOverrides, Vec<String>), String> {
+    let mut overrides = FileOverrides::default();
+    let mut warnings = Vec::new();
+    for (key, value) in table {
+        let invalid = |expected: &str| format!("'{}' must be {}", key, expected);
+        match key.as_str() {
+            "format" => {
+                let format = value.as_str().ok_or_else(|| invalid("a string"))?;
+                overrides.format = Some(parse_output_format(format)?);
+            }
+            "quality" => {
+                let quality = value.as_integer().and_then(|n| u8::try_from(n).ok()).filter(|n| (1..=100).contains(n));
+                overrides.quality = Some(quality.ok_or_else(|| invalid("a number from 1 to 100"))?);
+            }
+            "swizzle" => {
+                let swizzle = value.as_str().ok_or_else(|| invalid("a string like \"bgra\""))?;
+                overrides.swizzle = Some(parse_swizzle(swizzle)?);
+            }
+            "resize" => {
+                let resize = value.as_str().ok_or_else(|| invalid("a string like \"512x512\" or \"none\""))?;
+                overrides.resize = Some(match resize.trim() {
+                    "none" => None,
+                    size => Some(parse_dimensions(size)?),
+                });
+            }
+            "srgb" => overrides.srgb = Some(parse_value_enum(key, value)?),
+            "crop" => {
+                let crop = value.as_str().ok_or_else(|| invalid("a string like \"0,0,512x512\" or \"none\""))?;
+                overrides.crop = Some(match crop.trim() {
+                    "none" => None,
+                    rect => Some(parse_crop(rect)?),
+                });
+            }
+            "crop_mode" => overrides.crop_mode = Some(parse_value_enum(key, value)?),
+            "trim" => overrides.trim = Some(value.as_bool().ok_or_else(|| invalid("true or false"))?),
+            "alpha_threshold" => {
+                let threshold = value.as_integer().and_then(|n| u8::try_from(n).ok());
+                overrides.alpha_threshold = Some(threshold.ok_or_else(|| invalid("a number from 0 to 255"))?);
+            }
+            "dither" => overrides.dither = Some(parse_value_enum(key, value)?),
+            "pad_pot" => overrides.pad_pot = Some(value.as_bool().ok_or_else(|| invalid("true or false"))?),
+            unknown => {
+                let suggestion = KEYS
+                    .iter()
+                    .map(|known| (edit_distance(unknown, known), known))
+                    .filter(|(distance, _)| *distance <= 2)
+                    .min_by_key(|(distance, _)| *distance)
+                    .map(|(_, known)| format!(" (did you mean '{}'?)", known))
+                    .unwrap_or_default();
+                warnings.push(format!("unknown option '{}' ignored{}", unknown, suggestion));
+            }
+        }
+    }
+    Ok((overrides, warnings))
+}
+
+fn parse_value_enum<T: ValueEnum>(key: &str, value: &toml::Value) -> Result<T, String> {
+    let choices = || {
+        T::value_variants()
+            .iter()
+            .map(|variant| value_name(variant.clone()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    value
+        .as_str()
+        .and_then(|text| T::from_str(text, true).ok())
+        .ok_or_else(|| format!("'{}' must be one of: {}", key, choices()))
+}
+
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use std::ffi::OsString;
+
+    fn convert_args(args: &[&str]) -> ConvertArgs {
+        let argv = ["dds-converter", "convert", "-i", "in", "-o", "out"].iter().chain(args).map(OsString::from).collect();
+        match Cli::try_parse_args(argv, None).unwrap().command {
+            Some(Command::Convert(args)) => *args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn parses_every_key() {
+        let text = r#"
+            format = "jpeg"
+            quality = 75
+            swizzle = "BGRA"
+            resize = "256x128"
+            srgb = "linear"
+            crop = "0,0,64x64"
+            crop_mode = "clamp"
+            trim = true
+            alpha_threshold = 128
+            dither = "ordered"
+            pad_pot = false
+        "#;
+        let (overrides, warnings) = parse_overrides(text).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(overrides.format.as_deref(), Some("jpg"));
+        assert_eq!(overrides.quality, Some(75));
+        assert_eq!(overrides.swizzle.as_deref(), Some("bgra"));
+        assert_eq!(overrides.resize, Some(Some(Size { width: 256, height: 128 })));
+        assert_eq!(overrides.srgb, Some(SrgbMode::Linear));
+        assert_eq!(overrides.crop_mode, Some(CropMode::Clamp));
+        assert_eq!(overrides.trim, Some(true));
+        assert_eq!(overrides.alpha_threshold, Some(128));
+        assert_eq!(overrides.dither, Some(Dither::Ordered));
+        assert_eq!(overrides.pad_pot, Some(false));
+        assert_eq!(
+            overrides.describe(),
+            "format=jpg, quality=75, swizzle=bgra, resize=256x128, srgb=linear, crop=0,0,64x64, crop_mode=clamp, trim=true, alpha_threshold=128, dither=ordered, pad_pot=false"
+        );
+    }
+
+    #[test]
+    fn none_turns_resize_and_crop_off() {
+        let (overrides, _) = parse_overrides("resize = \"none\"\ncrop = \"none\"").unwrap();
+        assert_eq!(overrides.resize, Some(None));
+        assert_eq!(overrides.crop, Some(None));
+        let effective = overrides.apply(&convert_args(&["--max-dimension", "512"]));
+        assert_eq!(effective.resize, None);
+        assert_eq!(effective.max_dimension, None);
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        for text in ["quality = 0", "quality = 101", "quality = \"high\"", "swizzle = \"rgbx\"", "swizzle = \"rgbar\"", "resize = \"big\"", "srgb = \"gamma\""] {
+            assert!(parse_overrides(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_warn_with_a_suggestion() {
+        let (_, warnings) = parse_overrides("qualty = 80\nflavour = 1").unwrap();
+        assert_eq!(warnings, ["unknown option 'flavour' ignored", "unknown option 'qualty' ignored (did you mean 'quality'?)"]);
+    }
+
+    #[test]
+    fn rules_need_a_match_glob() {
+        assert!(parse_rules("[[rule]]\nformat = \"jpg\"", "rules.toml").unwrap_err().contains("no 'match'"));
+        assert!(parse_rules("format = \"jpg\"", "rules.toml").unwrap_err().contains("[[rule]]"));
+        let (rules, warnings) = parse_rules("[[rule]]\nmatch = \"*_ddna.dds\"\nsrgb = \"linear\"\ncolour = 1", "rules.toml").unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(warnings, ["rule 1: unknown option 'colour' ignored"]);
+        assert_eq!(rules[0].1.sources, ["rules.toml rule 1 (*_ddna.dds)"]);
+    }
+
+    #[test]
+    fn sidecar_wins_over_rules_which_win_over_the_command_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path();
+        let rock = input.join("rock/rock_diff.dds");
+        let sand = input.join("sand_diff.dds");
+        std::fs::create_dir_all(rock.parent().unwrap()).unwrap();
+        std::fs::write(overrides_path(&rock), "quality = 95").unwrap();
+
+        let (rules, _) = parse_rules(
+            r#"
+            [[rule]]
+            match = "*_diff.dds"
+            format = "jpg"
+            quality = 60
+            [[rule]]
+            match = "rock/**"
+            quality = 70
+            resize = "none"
+            "#,
+            "rules.toml",
+        )
+        .unwrap();
+        let rules = OverrideRules { rules };
+        let (overrides, _) = load_overrides(&[rock.clone(), sand.clone()], input, &rules).unwrap();
+        let cli = convert_args(&["--format", "png", "--resize", "64x64", "--srgb", "force"]);
+
+        let rock_cli = overrides[&rock].apply(&cli);
+        assert_eq!((rock_cli.format.as_str(), rock_cli.jpeg_quality, rock_cli.resize), ("jpg", Some(95), None));
+        assert_eq!(rock_cli.srgb, SrgbMode::Force);
+        assert_eq!(overrides[&rock].sources.len(), 3);
+
+        let sand_cli = overrides[&sand].apply(&cli);
+        assert_eq!((sand_cli.format.as_str(), sand_cli.jpeg_quality), ("jpg", Some(60)));
+        assert_eq!(sand_cli.resize, Some(Size { width: 64, height: 64 }));
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::{Context, Result};
+use image::{imageops::FilterType, Rgba, RgbaImage};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const CHANNELS: [&str; 4] = ["r", "g", "b", "a"];
+
+/// Where one output channel of `--pack` comes from.
+#[derive(Debug, Clone)]
+pub enum ChannelSource {
+    /// A channel of the file whose name matches `pattern` (one `*`, which
+    /// stands for the shared stem).
+    File { pattern: String, regex: Regex, channel: usize },
+    Constant(u8),
+}
+
+/// `--pack r=*_ao.dds,g=*_r.dds,b=*_m.dds,a=white[,out=*_orm]`
+#[derive(Debug, Clone)]
+pub struct PackSpec {
+    /// Sources for R, G, B and A.
+    pub channels: [ChannelSource; 4],
+    /// Output name, with `*` replaced by the stem.
+    pub output: String,
+}
+
+pub fn parse_pack_spec(value: &str) -> Result<PackSpec, String> {
+    let mut channels: [Option<ChannelSource>; 4] = Default::default();
+    let mut output = None;
+
+    for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (key, source) = part
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' should be CHANNEL=SOURCE (e.g. r=*_ao.dds)", part))?;
+        let (key, source) = (key.trim().to_lowercase(), source.trim());
+        if key == "out" {
+            if source.matches('*').count() != 1 || source.contains(['/', '\\']) {
+                return Err(format!("out={} needs exactly one '*' and no folders", source));
+            }
+            output = Some(source.to_string());
+            continue;
+        }
+        let index = CHANNELS
+            .iter()
+            .position(|channel| *channel == key)
+            .ok_or_else(|| format!("unknown channel '{}'; use r, g, b, a or out", key))?;
+        if channels[index].is_some() {
+            return Err(format!("channel '{}' is given twice", key));
+        }
+        channels[index] = Some(parse_source(source)?);
+    }
+
+    if !channels.iter().flatten().any(|source| matches!(source, ChannelSource::File { .. })) {
+        return Err("at least one channel must come from a file, e.g. r=*_ao.dds".to_string());
+    }
+    let [r, g, b, a] = channels;
+    Ok(PackSpec {
+        channels: [
+            r.unwrap_or(ChannelSource::Constant(0)),
+            g.unwrap_or(ChannelSource::Constant(0)),
+            b.unwrap_or(ChannelSource::Constant(0)),
+            a.unwrap_or(ChannelSource::Constant(255)),
+        ],
+        output: output.unwrap_or_else(|| "*_packed".to_string()),
+    })
+}
+
+/// `0`/`black`, `1`/`white`, `#80`/`0x80`, or `PATTERN[:r|g|b|a]`.
+fn parse_source(source: &str) -> Result<ChannelSource, String> {
+    match source.to_lowercase().as_str() {
+        "0" | "black" => return Ok(ChannelSource::Constant(0)),
+        "1" | "white" => return Ok(ChannelSource::Constant(255)),
+        _ => {}
+    }
+    if let Some(hex) = source.strip_prefix('#').or_else(|| source.strip_prefix("0x")) {
+        return u8::from_str_radix(hex, 16)
+            .map(ChannelSource::Constant)
+            .map_err(|_| format!("'{}' is not a hex value from #00 to #ff", source));
+    }
+
+    let (pattern, channel) = match source.rsplit_once(':') {
+        Some((pattern, channel)) => {
+            let channel = CHANNELS
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(channel))
+                .ok_or_else(|| format!("unknown source channel ':{}' in '{}'; use :r, :g, :b or :a", channel, source))?;
+            (pattern, channel)
+        }
+        None => (source, 0),
+    };
+    if pattern.matches('*').count() != 1 {
+        return Err(format!("'{}' needs exactly one '*' standing for the shared name (e.g. *_ao.dds)", pattern));
+    }
+    if pattern.contains(['/', '\\']) {
+        return Err(format!("'{}' must be a file name pattern, without folders", pattern));
+    }
+    let (before, after) = pattern.split_once('*').unwrap_or_default();
+    let regex = Regex::new(&format!("(?i)^{}(.+){}$", regex::escape(before), regex::escape(after)))
+        .map_err(|e| e.to_string())?;
+    Ok(ChannelSource::File { pattern: pattern.to_string(), regex, channel })
+}
+
+impl PackSpec {
+    fn file_sources(&self) -> impl Iterator<Item = (&str, &Regex)> {
+        self.channels.iter().filter_map(|source| match source {
+            ChannelSource::File { pattern, regex, .. } => Some((pattern.as_str(), regex)),
+            ChannelSource::Constant(_) => None,
+        })
+    }
+
+    /// The shared stem if `file` is an anchor, i.e. matches the first
+    /// file pattern of the spec.
+    pub fn anchor_stem(&self, file: &Path) -> Option<String> {
+        let (_, regex) = self.file_sources().next()?;
+        let name = file.file_name()?.to_string_lossy();
+        regex.captures(&name).map(|captures| captures[1].to_string())
+    }
+
+    /// Path `pattern` gives for `stem`, next to `anchor`.
+    fn source_path(pattern: &str, anchor: &Path, stem: &str) -> PathBuf {
+        anchor.with_file_name(pattern.replacen('*', stem, 1))
+    }
+
+    /// Where the packed result for `anchor` would sit if it were an input:
+    /// next to it, named after `out=` with a `.dds` extension, for the usual
+    /// output path mapping.
+    pub fn output_input_path(&self, anchor: &Path) -> Option<PathBuf> {
+        let stem = self.anchor_stem(anchor)?;
+        Some(anchor.with_file_name(format!("{}.dds", self.output.replacen('*', &stem, 1))))
+    }
+
+    /// The map files for `anchor`, per output channel; `None` for constants.
+    pub fn source_files(&self, anchor: &Path) -> [Option<PathBuf>; 4] {
+        let stem = self.anchor_stem(anchor).unwrap_or_default();
+        std::array::from_fn(|index| match &self.channels[index] {
+            ChannelSource::File { pattern, regex, .. } => {
+                // The anchor's own pattern keeps its exact name, whatever its case
+                let name = anchor.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                match regex.captures(&name) {
+                    Some(captures) if captures[1] == *stem => Some(anchor.to_path_buf()),
+                    _ => Some(Self::source_path(pattern, anchor, &stem)),
+                }
+            }
+            ChannelSource::Constant(_) => None,
+        })
+    }
+}
+
+/// Builds the packed image from the decoded maps (keyed by source file);
+/// `sources` is `None` where a map is missing.
+/// Maps smaller or larger than the largest one by a power of two are
+/// resized to it, with a warning per map; any other size mismatch is an
+/// error. Returns the image and the warnings.
+pub fn pack_channels(spec: &PackSpec, sources: &[Option<PathBuf>; 4], decoded: &HashMap<PathBuf, RgbaImage>) -> Result<(RgbaImage, Vec<String>)> {
+    let (width, height) = decoded
+        .values()
+        .map(|image| image.dimensions())
+        .max_by_key(|(width, height)| *width as u64 * *height as u64)
+        .context("no maps to pack")?;
+
+    let mut warnings = Vec::new();
+    let mut resized: HashMap<&PathBuf, RgbaImage> = HashMap::new();
+    for (path, image) in decoded {
+        let (w, h) = image.dimensions();
+        if (w, h) == (width, height) {
+            continue;
+        }
+        let factor = width / w.max(1);
+        let same_factor = factor.is_power_of_two() && w * factor == width && h * factor == height;
+        if !same_factor {
+            anyhow::bail!("{} is {}x{}, which doesn't match the {}x{} of the other maps", path.display(), w, h, width, height);
+        }
+        warnings.push(format!("{} is {}x{}; resized to {}x{}", path.display(), w, h, width, height));
+        resized.insert(path, image::imageops::resize(image, width, height, FilterType::Triangle));
+    }
+
+    let mut packed = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    for (index, source) in spec.channels.iter().enumerate() {
+        match (source, &sources[index]) {
+            (ChannelSource::File { channel, .. }, Some(path)) => {
+                let image = resized.get(path).or_else(|| decoded.get(path)).context("map was not decoded")?;
+                for (target, pixel) in packed.pixels_mut().zip(image.pixels()) {
+                    target[index] = pixel[*channel];
+                }
+            }
+            (ChannelSource::Constant(value), _) => packed.pixels_mut().for_each(|pixel| pixel[index] = *value),
+            // A missing map: black, or opaque for alpha
+            (_, None) => {
+                let value = if index == 3 { 255 } else { 0 };
+                packed.pixels_mut().for_each(|pixel| pixel[index] = value);
+            }
+        }
+    }
+    Ok((packed, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_full_spec_parses_files_constants_and_the_output_name() {
+        let spec = parse_pack_spec("r=*_ao.dds, g=*_r.dds:g, b=#80, a=white, out=*_orm").unwrap();
+        assert!(matches!(&spec.channels[0], ChannelSource::File { pattern, channel: 0, .. } if pattern == "*_ao.dds"));
+        assert!(matches!(&spec.channels[1], ChannelSource::File { channel: 1, .. }));
+        assert!(matches!(spec.channels[2], ChannelSource::Constant(0x80)));
+        assert!(matches!(spec.channels[3], ChannelSource::Constant(255)));
+        assert_eq!(spec.output, "*_orm");
+
+        let anchor = Path::new("maps/Rock_AO.dds");
+        assert_eq!(spec.anchor_stem(anchor).as_deref(), Some("Rock"));
+        assert_eq!(spec.output_input_path(anchor), Some(PathBuf::from("maps/Rock_orm.dds")));
+        let [r, g, b, a] = spec.source_files(anchor);
+        assert_eq!((r, g), (Some(anchor.to_path_buf()), Some(PathBuf::from("maps/Rock_r.dds"))));
+        assert_eq!((b, a), (None, None));
+    }
+
+    #[test]
+    fn channels_left_out_are_black_with_opaque_alpha() {
+        let spec = parse_pack_spec("g=*_r.dds").unwrap();
+        assert!(matches!(spec.channels[0], ChannelSource::Constant(0)));
+        assert!(matches!(spec.channels[2], ChannelSource::Constant(0)));
+        assert!(matches!(spec.channels[3], ChannelSource::Constant(255)));
+        assert_eq!(spec.output, "*_packed");
+        assert!(matches!(parse_pack_spec("r=*_ao.dds,b=0x1f").unwrap().channels[2], ChannelSource::Constant(0x1f)));
+        assert!(matches!(parse_pack_spec("r=*_ao.dds,b=black").unwrap().channels[2], ChannelSource::Constant(0)));
+    }
+
+    #[test]
+    fn bad_specs_say_what_is_wrong() {
+        let error = |spec: &str| parse_pack_spec(spec).unwrap_err();
+        assert!(error("r").contains("CHANNEL=SOURCE"));
+        assert!(error("x=*_ao.dds").contains("unknown channel 'x'"));
+        assert!(error("r=*_ao.dds,r=*_b.dds").contains("given twice"));
+        assert!(error("r=white,g=black").contains("at least one channel must come from a file"));
+        assert!(error("r=ao.dds").contains("exactly one '*'"));
+        assert!(error("r=*_*.dds").contains("exactly one '*'"));
+        assert!(error("r=maps/*_ao.dds").contains("without folders"));
+        assert!(error("r=*_ao.dds:q").contains("unknown source channel ':q'"));
+        assert!(error("r=#1g0").contains("not a hex value"));
+        assert!(error("r=*_ao.dds,out=packed").contains("out=packed needs exactly one '*'"));
+    }
+
+    #[test]
+    fn maps_are_packed_into_their_channels() {
+        let spec = parse_pack_spec("r=*_ao.dds,g=*_m.dds:a,a=#40").unwrap();
+        let (ao, metal) = (PathBuf::from("rock_ao.dds"), PathBuf::from("rock_m.dds"));
+        let decoded = HashMap::from([
+            (ao.clone(), RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 40]))),
+            // Half the size: resized up with a warning
+            (metal.clone(), RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 200]))),
+        ]);
+        let (packed, warnings) = pack_channels(&spec, &[Some(ao), Some(metal), None, None], &decoded).unwrap();
+        assert_eq!(packed.dimensions(), (4, 4));
+        assert_eq!(packed.get_pixel(3, 3).0, [10, 200, 0, 0x40]);
+        assert_eq!(warnings.len(), 1);
+    }
+}
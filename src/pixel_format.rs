@@ -0,0 +1,117 @@
+use crate::dds::{dxgi_format_name, DdsHeader};
+
+/// What texconv decodes to when nothing else asks for a format.
+pub const DEFAULT_PIXEL_FORMAT: &str = "R8G8B8A8_UNORM";
+
+/// `--pixel-format`: the DXGI format texconv writes the output in.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PixelFormatChoice {
+    /// Picked from the source texture by `pick_pixel_format`.
+    #[default]
+    Auto,
+    Fixed(String),
+}
+
+pub fn parse_pixel_format(value: &str) -> Result<PixelFormatChoice, String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(PixelFormatChoice::Auto);
+    }
+    let wanted = value.trim().to_uppercase();
+    let wanted = wanted.strip_prefix("DXGI_FORMAT_").unwrap_or(&wanted);
+    (1..=191)
+        .map(dxgi_format_name)
+        .find(|name| name == wanted)
+        .map(PixelFormatChoice::Fixed)
+        .ok_or_else(|| format!("'{}' is not a DXGI format name (e.g. R8G8B8A8_UNORM, R16G16B16A16_FLOAT) or 'auto'", value))
+}
+
+/// The `-f` value for converting a texture with `header` to `output_format`:
+/// float for HDR sources when the output can hold it, `R8_UNORM` for
+/// single-channel sources, and RGBA8 for everything else (or when the
+/// header couldn't be read).
+pub fn pick_pixel_format(header: Option<&DdsHeader>, output_format: &str) -> &'static str {
+    let Some(name) = header.and_then(DdsHeader::dxgi_format).map(dxgi_format_name) else {
+        return DEFAULT_PIXEL_FORMAT;
+    };
+    match source_kind(&name) {
+        SourceKind::Float32 if holds_float(output_format) => "R32G32B32A32_FLOAT",
+        SourceKind::Float16 if holds_float(output_format) => "R16G16B16A16_FLOAT",
+        SourceKind::SingleChannel => "R8_UNORM",
+        _ => DEFAULT_PIXEL_FORMAT,
+    }
+}
+
+enum SourceKind {
+    Float32,
+    Float16,
+    SingleChannel,
+    Other,
+}
+
+fn source_kind(name: &str) -> SourceKind {
+    if name.ends_with("_FLOAT") || name.starts_with("BC6H") || name == "R9G9B9E5_SHAREDEXP" {
+        return if name.starts_with("R32") || name.starts_with("D32") { SourceKind::Float32 } else { SourceKind::Float16 };
+    }
+    match name {
+        "BC4_UNORM" | "BC4_SNORM" | "R8_UNORM" | "R8_SNORM" => SourceKind::SingleChannel,
+        _ => SourceKind::Other,
+    }
+}
+
+/// Output formats texconv can write float pixels to.
+fn holds_float(output_format: &str) -> bool {
+    matches!(output_format, "dds" | "hdr" | "tif")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::dx10_header;
+
+    /// The pick for a DX10 header in DXGI `format`.
+    fn pick(format: u32, output_format: &str) -> &'static str {
+        let header = DdsHeader::parse(&dx10_header(format, 4, 4, 1)).unwrap();
+        pick_pixel_format(Some(&header), output_format)
+    }
+
+    /// The pick for a legacy header with `fourcc` in place of the DX10 one.
+    fn pick_legacy(fourcc: &[u8; 4], output_format: &str) -> &'static str {
+        let mut bytes = dx10_header(0, 4, 4, 1);
+        bytes.truncate(128);
+        bytes[84..88].copy_from_slice(fourcc);
+        pick_pixel_format(Some(&DdsHeader::parse(&bytes).unwrap()), output_format)
+    }
+
+    #[test]
+    fn hdr_sources_stay_float_where_the_output_can_hold_it() {
+        assert_eq!(pick(95, "tif"), "R16G16B16A16_FLOAT"); // BC6H_UF16
+        assert_eq!(pick(96, "hdr"), "R16G16B16A16_FLOAT"); // BC6H_SF16
+        assert_eq!(pick(2, "dds"), "R32G32B32A32_FLOAT"); // R32G32B32A32_FLOAT
+        assert_eq!(pick_legacy(&113u32.to_le_bytes(), "tif"), "R16G16B16A16_FLOAT"); // A16B16G16R16F
+        assert_eq!(pick(95, "png"), DEFAULT_PIXEL_FORMAT);
+    }
+
+    #[test]
+    fn single_channel_sources_decode_to_r8() {
+        assert_eq!(pick(80, "png"), "R8_UNORM"); // BC4_UNORM
+        assert_eq!(pick(81, "png"), "R8_UNORM"); // BC4_SNORM
+        assert_eq!(pick_legacy(b"ATI1", "png"), "R8_UNORM");
+        assert_eq!(pick_legacy(b"BC4U", "tga"), "R8_UNORM");
+    }
+
+    #[test]
+    fn colour_and_srgb_sources_use_the_default() {
+        assert_eq!(pick(98, "png"), DEFAULT_PIXEL_FORMAT); // BC7_UNORM
+        assert_eq!(pick(99, "png"), DEFAULT_PIXEL_FORMAT); // BC7_UNORM_SRGB
+        assert_eq!(pick(29, "tif"), DEFAULT_PIXEL_FORMAT); // R8G8B8A8_UNORM_SRGB
+        assert_eq!(pick_legacy(b"DXT5", "png"), DEFAULT_PIXEL_FORMAT);
+        assert_eq!(pick_pixel_format(None, "png"), DEFAULT_PIXEL_FORMAT);
+    }
+
+    #[test]
+    fn pixel_format_names_are_checked() {
+        assert_eq!(parse_pixel_format("auto"), Ok(PixelFormatChoice::Auto));
+        assert_eq!(parse_pixel_format("dxgi_format_r16g16b16a16_float"), Ok(PixelFormatChoice::Fixed("R16G16B16A16_FLOAT".to_string())));
+        assert!(parse_pixel_format("RGBA8").is_err());
+    }
+}
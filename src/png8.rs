@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::quantize::{quantize_palette, Dither, IndexedImage};
+
+/// Above this RMSE (0-255 scale) an image is considered to need more than
+/// 256 colours: `--format png8` warns, or keeps PNG-32 with `--png8-fallback`.
+pub const PNG8_MAX_RMSE: f64 = 3.0;
+
+/// What `--format png8` did with one image.
+pub enum Png8Result {
+    /// Written as an indexed PNG; `rmse` is the quantization error.
+    Indexed { colors: usize, rmse: f64 },
+    /// Quantization loses too much; the RGBA PNG was kept as it is.
+    KeptRgba { rmse: f64 },
+}
+
+/// Per-run `--format png8` totals: the indexed outputs' size against their
+/// RGBA intermediates (an estimate of the PNG-32 size), and how many images
+/// fell back to PNG-32.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PaletteStats {
+    pub indexed_bytes: u64,
+    pub rgba_bytes: u64,
+    pub fallbacks: u64,
+}
+
+impl std::ops::AddAssign for PaletteStats {
+    fn add_assign(&mut self, other: Self) {
+        self.indexed_bytes += other.indexed_bytes;
+        self.rgba_bytes += other.rgba_bytes;
+        self.fallbacks += other.fallbacks;
+    }
+}
+
+/// Converts the RGBA PNG at `input` into an indexed PNG at `output`. With
+/// `fallback`, images whose quantization error is over `PNG8_MAX_RMSE` are
+/// copied unchanged instead.
+pub fn write_png8(input: &Path, output: &Path, dither: Dither, fallback: bool) -> Result<(Png8Result, PaletteStats)> {
+    let image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    let rgba_bytes = std::fs::metadata(input)?.len();
+
+    let indexed = quantize_palette(&image, dither);
+    if fallback && !indexed.exact && indexed.rmse > PNG8_MAX_RMSE {
+        std::fs::copy(input, output)
+            .with_context(|| format!("Failed to write {}", output.display()))?;
+        let stats = PaletteStats { fallbacks: 1, ..PaletteStats::default() };
+        return Ok((Png8Result::KeptRgba { rmse: indexed.rmse }, stats));
+    }
+
+    encode_indexed(&indexed, output)?;
+    let stats = PaletteStats {
+        indexed_bytes: std::fs::metadata(output)?.len(),
+        rgba_bytes,
+        fallbacks: 0,
+    };
+    Ok((Png8Result::Indexed { colors: indexed.palette.len(), rmse: indexed.rmse }, stats))
+}
+
+/// Writes `image` as a palette PNG at the smallest bit depth its palette
+/// fits, with a `tRNS` chunk when any entry isn't opaque.
+fn encode_indexed(image: &IndexedImage, output: &Path) -> Result<()> {
+    let (bit_depth, bits) = match image.palette.len() {
+        0..=2 => (png::BitDepth::One, 1),
+        3..=4 => (png::BitDepth::Two, 2),
+        5..=16 => (png::BitDepth::Four, 4),
+        _ => (png::BitDepth::Eight, 8),
+    };
+
+    let file = File::create(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(bit_depth);
+    encoder.set_compression(png::Compression::Best);
+    encoder.set_filter(png::FilterType::NoFilter);
+    encoder.set_palette(image.palette.iter().flat_map(|entry| [entry[0], entry[1], entry[2]]).collect::<Vec<u8>>());
+    let alpha: Vec<u8> = image.palette.iter().map(|entry| entry[3]).collect();
+    if let Some(last) = alpha.iter().rposition(|value| *value != 255) {
+        encoder.set_trns(alpha[..=last].to_vec());
+    }
+
+    // Pack indices MSB-first into rows padded to whole bytes
+    let per_byte = 8 / bits;
+    let row_bytes = (image.width as usize).div_ceil(per_byte);
+    let mut data = vec![0u8; row_bytes * image.height as usize];
+    for (row, indices) in image.indices.chunks(image.width.max(1) as usize).enumerate() {
+        for (column, index) in indices.iter().enumerate() {
+            let shift = 8 - bits * (column % per_byte + 1);
+            data[row * row_bytes + column / per_byte] |= index << shift;
+        }
+    }
+
+    let mut writer = encoder.write_header()
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    writer.write_image_data(&data)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    writer.finish()
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn indexed_output_decodes_to_the_source_colours() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("icon.png");
+        let output = dir.path().join("icon8.png");
+        // 5 colours, so 4-bit indices packed two to a byte, over an odd width
+        let colors = [[0, 0, 0, 0], [255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128], [9, 9, 9, 255]];
+        let source = RgbaImage::from_fn(5, 3, |x, y| Rgba(colors[((x + y) % 5) as usize]));
+        source.save(&input).unwrap();
+
+        let (result, stats) = write_png8(&input, &output, Dither::None, false).unwrap();
+        assert!(matches!(result, Png8Result::Indexed { colors: 5, .. }));
+        assert!(stats.indexed_bytes > 0);
+        assert_eq!(image::open(&output).unwrap().to_rgba8(), source);
+    }
+}
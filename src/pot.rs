@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{GenericImage, RgbaImage};
+use std::path::Path;
+
+use crate::crop::CropRect;
+
+/// Where `--pad-pot` puts the original pixels inside the padded image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PotAlign {
+    /// Centered, with any odd pixel of padding on the right/bottom
+    Center,
+    /// In the top-left corner, padding only on the right and bottom
+    TopLeft,
+}
+
+/// Size `width` x `height` grows to and where the original content sits in
+/// it, or `None` if both sides already are powers of two.
+pub fn pot_layout(width: u32, height: u32, align: PotAlign) -> Option<(u32, u32, CropRect)> {
+    let (pot_width, pot_height) = (width.next_power_of_two(), height.next_power_of_two());
+    if (pot_width, pot_height) == (width, height) {
+        return None;
+    }
+
+    let (x, y) = match align {
+        PotAlign::Center => ((pot_width - width) / 2, (pot_height - height) / 2),
+        PotAlign::TopLeft => (0, 0),
+    };
+    Some((pot_width, pot_height, CropRect { x, y, width, height }))
+}
+
+/// Writes the PNG at `input` padded with transparency to power-of-two sides
+/// to `output`. Returns where the original content is, or `None` (and writes
+/// nothing) if it already was power-of-two.
+pub fn pad_png_to_pot(input: &Path, output: &Path, align: PotAlign) -> Result<Option<CropRect>> {
+    let image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    let Some((width, height, content)) = pot_layout(image.width(), image.height(), align) else {
+        return Ok(None);
+    };
+
+    let mut padded = RgbaImage::new(width, height);
+    padded.copy_from(&image, content.x, content.y).context("Failed to pad image")?;
+    padded.save(output)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(Some(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_of_two_sides_are_left_alone() {
+        for (width, height) in [(1, 1), (256, 256), (512, 64), (1, 1024)] {
+            assert_eq!(pot_layout(width, height, PotAlign::Center), None, "{}x{}", width, height);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.png");
+        let output = dir.path().join("out.png");
+        RgbaImage::new(64, 16).save(&input).unwrap();
+        assert_eq!(pad_png_to_pot(&input, &output, PotAlign::Center).unwrap(), None);
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn alignment_places_the_content() {
+        let center = pot_layout(374, 112, PotAlign::Center).unwrap();
+        assert_eq!(center, (512, 128, CropRect { x: 69, y: 8, width: 374, height: 112 }));
+        let top_left = pot_layout(374, 112, PotAlign::TopLeft).unwrap();
+        assert_eq!(top_left, (512, 128, CropRect { x: 0, y: 0, width: 374, height: 112 }));
+        // Only one side needs padding; the odd pixel goes right
+        assert_eq!(pot_layout(3, 4, PotAlign::Center).unwrap(), (4, 4, CropRect { x: 0, y: 0, width: 3, height: 4 }));
+        assert_eq!(pot_layout(5, 4, PotAlign::Center).unwrap(), (8, 4, CropRect { x: 1, y: 0, width: 5, height: 4 }));
+    }
+
+    #[test]
+    fn padding_is_transparent_around_the_original_pixels() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.png");
+        let output = dir.path().join("out.png");
+        RgbaImage::from_pixel(6, 3, image::Rgba([10, 20, 30, 255])).save(&input).unwrap();
+
+        let content = pad_png_to_pot(&input, &output, PotAlign::Center).unwrap().unwrap();
+        assert_eq!(content, CropRect { x: 1, y: 0, width: 6, height: 3 });
+        let padded = image::open(&output).unwrap().to_rgba8();
+        assert_eq!(padded.dimensions(), (8, 4));
+        for (x, y, pixel) in padded.enumerate_pixels() {
+            let inside = (1..7).contains(&x) && y < 3;
+            let expected = if inside { [10, 20, 30, 255] } else { [0, 0, 0, 0] };
+            assert_eq!(pixel.0, expected, "({}, {})", x, y);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{imageops::FilterType, RgbaImage};
+use std::fmt::Write as _;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::PreviewArgs;
+use crate::console::Console;
+use crate::dds::DdsHeader;
+use crate::processor::decode_to_png;
+use crate::texconv::{setup_texconv, TexconvOptions};
+use crate::utils::find_dds_files;
+
+/// Which escape codes `preview` draws with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PreviewColors {
+    /// Truecolor if COLORTERM says the terminal has it, 256 colours otherwise
+    Auto,
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+}
+
+impl PreviewColors {
+    /// `Auto` resolved from the environment.
+    pub fn resolve(self) -> PreviewColors {
+        match self {
+            PreviewColors::Auto => {
+                let colorterm = std::env::var("COLORTERM").unwrap_or_default().to_lowercase();
+                if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+                    PreviewColors::Truecolor
+                } else {
+                    PreviewColors::Ansi256
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Side of one checkerboard square behind transparent pixels, in preview
+/// pixels (half a character cell each).
+const CHECKER: u32 = 4;
+
+/// The pixel over the light/dark checkerboard.
+fn over_checker(pixel: [u8; 4], x: u32, y: u32) -> [u8; 3] {
+    let background: u32 = if (x / CHECKER + y / CHECKER).is_multiple_of(2) { 0x99 } else { 0x66 };
+    let alpha = pixel[3] as u32;
+    std::array::from_fn(|channel| ((pixel[channel] as u32 * alpha + background * (255 - alpha) + 127) / 255) as u8)
+}
+
+/// Nearest entry of the xterm 256-colour palette: the 6x6x6 cube or the
+/// 24-step gray ramp.
+fn ansi256(rgb: [u8; 3]) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |value: u8| (0..6).min_by_key(|&index| (LEVELS[index] as i32 - value as i32).abs()).unwrap_or(0);
+    let cube = rgb.map(nearest_level);
+    let cube_rgb = cube.map(|index| LEVELS[index]);
+    let average = rgb.iter().map(|&value| value as u32).sum::<u32>() / 3;
+    let gray_index = (average.saturating_sub(8) / 10).min(23) as u8;
+    let gray = 8 + gray_index * 10;
+    let distance = |other: [u8; 3]| rgb.iter().zip(other).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+    if distance([gray; 3]) < distance(cube_rgb) {
+        232 + gray_index
+    } else {
+        16 + 36 * cube[0] as u8 + 6 * cube[1] as u8 + cube[2] as u8
+    }
+}
+
+fn push_color(out: &mut String, rgb: [u8; 3], background: bool, colors: PreviewColors) {
+    let layer = if background { 48 } else { 38 };
+    match colors {
+        PreviewColors::Ansi256 => {
+            let _ = write!(out, "\x1b[{};5;{}m", layer, ansi256(rgb));
+        }
+        _ => {
+            let _ = write!(out, "\x1b[{};2;{};{};{}m", layer, rgb[0], rgb[1], rgb[2]);
+        }
+    }
+}
+
+/// Renders `image` `width` characters wide with upper half blocks: each
+/// character is two pixels, the top one as the foreground colour and the
+/// bottom one as the background. Transparent parts show a checkerboard.
+/// `colors` must be resolved (not `Auto`).
+pub fn render(image: &RgbaImage, width: u32, colors: PreviewColors) -> String {
+    let width = width.clamp(1, image.width().max(1));
+    let height = ((image.height() as u64 * width as u64 / image.width().max(1) as u64) as u32).max(1);
+    let scaled = image::imageops::resize(image, width, height + height % 2, FilterType::Triangle);
+
+    let mut out = String::new();
+    for row in (0..scaled.height()).step_by(2) {
+        for x in 0..width {
+            push_color(&mut out, over_checker(scaled.get_pixel(x, row).0, x, row), false, colors);
+            push_color(&mut out, over_checker(scaled.get_pixel(x, row + 1).0, x, row + 1), true, colors);
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// Decodes `path` with the built-in decoder (plain images and BC1-BC3 DDS)
+/// or, failing that, with texconv, which is set up on first use.
+async fn decode(path: &Path, texconv: &mut Option<PathBuf>, custom: Option<&Path>, work_dir: &Path) -> Result<RgbaImage> {
+    if let Ok(image) = image::open(path) {
+        return Ok(image.to_rgba8());
+    }
+    let texconv_path = match texconv {
+        Some(path) => path.clone(),
+        None => texconv.insert(setup_texconv(custom).await?).clone(),
+    };
+    let png = decode_to_png(path, &texconv_path, &TexconvOptions::default(), work_dir, false, &Console::plain()).await?;
+    Ok(image::open(&png).with_context(|| format!("Failed to open {}", png.display()))?.to_rgba8())
+}
+
+/// Terminal width from COLUMNS, or 80.
+fn terminal_columns() -> u32 {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.trim().parse().ok()).unwrap_or(80)
+}
+
+/// Waits for Enter between gallery pages; `false` once the user quits.
+fn next_page(shown: usize, total: usize) -> Result<bool> {
+    print!("-- {}/{} shown; Enter for more, q to quit -- ", shown, total);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(!answer.trim().eq_ignore_ascii_case("q"))
+}
+
+/// `preview` subcommand.
+pub async fn run_preview(args: &PreviewArgs, custom: Option<&Path>) -> Result<()> {
+    let files = if args.input.is_file() { vec![args.input.clone()] } else { find_dds_files(&args.input, None, None) };
+    if files.is_empty() {
+        println!("❌ No .dds files found in {}", args.input.display());
+        return Ok(());
+    }
+    let colors = args.colors.resolve();
+    let width = args.preview_width.unwrap_or_else(|| terminal_columns().saturating_sub(1).max(1));
+    let paged = files.len() > 1 && std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+
+    let mut texconv = None;
+    for (index, file) in files.iter().enumerate() {
+        if paged && index > 0 && index.is_multiple_of(args.page_size) && !next_page(index, files.len())? {
+            break;
+        }
+        let details = DdsHeader::read(file)
+            .map(|header| format!(" ({}x{}, {})", header.width, header.height, header.format_name()))
+            .unwrap_or_default();
+        println!("🖼️  {}{}", file.display(), details);
+
+        let work_dir = tempfile::Builder::new()
+            .prefix("dds-converter-preview-")
+            .tempdir()
+            .context("Failed to create a temporary directory")?;
+        match decode(file, &mut texconv, custom, work_dir.path()).await {
+            Ok(image) => print!("{}", render(&image, width, colors)),
+            Err(e) => println!("❌ Failed to decode {}: {:#}", file.display(), e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// A `width`x2 image: `top` over `bottom`.
+    fn two_rows(width: u32, top: [u8; 4], bottom: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_fn(width, 2, |_, y| Rgba(if y == 0 { top } else { bottom }))
+    }
+
+    #[test]
+    fn truecolor_draws_top_pixels_as_foreground_and_bottom_as_background() {
+        let image = two_rows(2, [255, 0, 0, 255], [0, 0, 255, 255]);
+        let cell = "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀";
+        assert_eq!(render(&image, 2, PreviewColors::Truecolor), format!("{cell}{cell}\x1b[0m\n"));
+    }
+
+    #[test]
+    fn ansi256_picks_the_nearest_cube_or_gray_entry() {
+        let image = two_rows(1, [255, 0, 0, 255], [128, 128, 128, 255]);
+        assert_eq!(render(&image, 1, PreviewColors::Ansi256), "\x1b[38;5;196m\x1b[48;5;244m▀\x1b[0m\n");
+        assert_eq!(ansi256([0, 0, 255]), 21);
+        assert_eq!(ansi256([0, 0, 0]), 16);
+        assert_eq!(ansi256([255, 255, 255]), 231);
+    }
+
+    #[test]
+    fn transparent_pixels_show_the_checkerboard() {
+        let image = two_rows(8, [0, 0, 0, 0], [0, 0, 0, 0]);
+        let rendered = render(&image, 8, PreviewColors::Truecolor);
+        let light = "\x1b[38;2;153;153;153m\x1b[48;2;153;153;153m▀";
+        let dark = "\x1b[38;2;102;102;102m\x1b[48;2;102;102;102m▀";
+        assert_eq!(rendered, format!("{}{}\x1b[0m\n", light.repeat(4), dark.repeat(4)));
+        // Half transparent white lands between white and the square
+        assert_eq!(over_checker([255, 255, 255, 128], 0, 0), [204, 204, 204]);
+    }
+
+    #[test]
+    fn odd_heights_pad_the_last_row_and_width_never_upscales() {
+        let image = RgbaImage::from_pixel(3, 3, Rgba([0, 255, 0, 255]));
+        let rendered = render(&image, 40, PreviewColors::Truecolor);
+        assert_eq!(rendered.lines().count(), 2);
+        assert!(rendered.lines().all(|line| line.matches('▀').count() == 3), "{:?}", rendered);
+    }
+
+    #[test]
+    fn downsampling_keeps_the_aspect_ratio() {
+        let image = RgbaImage::from_pixel(64, 32, Rgba([0, 0, 0, 255]));
+        let rendered = render(&image, 16, PreviewColors::Ansi256);
+        // 16x8 pixels is four rows of half blocks
+        assert_eq!(rendered.lines().count(), 4);
+        assert!(rendered.lines().all(|line| line.matches('▀').count() == 16));
+    }
+}
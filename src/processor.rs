@@ -1,14 +1,64 @@
 use anyhow::{Result, Context};
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
 use tokio::fs;
 
+use crate::archive::ZipOutput;
+use crate::cli::{built_in_format, output_extension, ConvertArgs};
+use crate::console::Console;
+use crate::cubemap::{cross, equirect, CubemapLayout, FACE_NAMES};
+use crate::crop::{content_bounds, crop_png, png_dimensions, CropMode, CropRect};
+use crate::collision::ResolvedOutput;
+use crate::dds::DdsHeader;
+use crate::dedupe::{materialize, texture_files, LinkMode};
+use crate::encoding::decode_process_output;
+use crate::ico::write_ico;
+use crate::info::format_bytes;
+use crate::optimize::{optimize_png, OptimizeStats};
+use crate::png8::{write_png8, PaletteStats, Png8Result, PNG8_MAX_RMSE};
+use crate::pack::{pack_channels, PackSpec};
+use crate::resize::{plan_resize, ResizePlan, Size};
+use crate::pixel_format::{pick_pixel_format, PixelFormatChoice, DEFAULT_PIXEL_FORMAT};
+use crate::pot::pad_png_to_pot;
+use crate::unpack::{channel_name, constant_value, extract_channel, UnpackSpec};
+use crate::quantize::{quantize_png, Dither};
+use crate::errors::{Classified, ErrorCode, TexconvFailure};
+use crate::alpha_merge::composite_alpha;
+use crate::adjust::adjust_png;
+use crate::composite::{composite_layers, CompositeSpec};
+use crate::alpha_threshold::threshold_png;
+use crate::alpha_flatten::{flatten_alpha_in_place, AlphaMode, AlphaRewrite};
+use crate::tint::{tint_png, Tint, TintVariant};
+use crate::watermark::watermark_png;
+use crate::srgb::srgb_flag;
+use crate::split_dds::{find_alpha_part, find_alpha_split_parts, find_split_parts, merge_split_dds};
+use crate::mips::{extract_surface, surface_mip_suffix};
+use crate::metadata::{embed_metadata, strip_metadata, Provenance};
+use crate::content_hash::{fill_hash_placeholder, hashed_path, same_contents};
+use crate::reproducible::normalize_output;
+use crate::sidecar::{write_sidecar, SidecarSettings};
+use crate::target_size::encode_lossy;
+use crate::texconv::{command_line, texconv_command, TexconvOptions};
+use crate::timeout::output_with_timeout;
+use crate::tile::{manifest_path, write_tiles, TileEntry, TileGrid, TileManifest};
+use crate::utils::{dds_stem, texture_stem, with_suffix, NameCleanup};
+use crate::verify::verify_output;
+
+/// Output path for `input_path`, mirroring its place under `input_dir`.
+/// With `sanitize` set, every created folder and file name is made valid on
+/// Windows, using it as the replacement for invalid characters. `affixes`
+/// are the `--prefix` and `--suffix` put around the file's stem.
+#[allow(clippy::too_many_arguments)]
 pub fn calculate_output_path(
     input_path: &Path, 
     input_dir: &Path, 
     output_dir: &Path, 
     strip_segments: usize, 
-    format: &str
+    format: &str,
+    names: NameCleanup,
+    file_name: Option<&str>,
+    affixes: (&str, &str),
 ) -> PathBuf {
     // Get the relative path from input directory to the file
     let relative_path = input_path.strip_prefix(input_dir).unwrap_or(input_path);
@@ -20,84 +70,1572 @@ pub fn calculate_output_path(
     } else {
         &path_components[..]
     };
+    let clean = |name: String| names.apply(&name);
     
     // Build the output path maintaining the directory structure
     let mut result_path = output_dir.to_path_buf();
-    for component in components_to_use {
-        result_path.push(component);
+    if let Some((_, folders)) = components_to_use.split_last() {
+        for component in folders {
+            match component {
+                Component::Normal(name) => result_path.push(clean(name.to_string_lossy().into_owned())),
+                other => result_path.push(other),
+            }
+        }
     }
     
-    // Change the extension to the target format
-    result_path.with_extension(format)
+    // Replace only the final `.dds` (any case), keeping every other dot;
+    // other names (animations) lose only their final extension. A
+    // `--name-template` name replaces it altogether. `--prefix` and
+    // `--suffix` go around that stem.
+    let (prefix, suffix) = affixes;
+    let name = file_name.map(str::to_string).unwrap_or_else(|| format!("{}{}{}.{}", prefix, dds_stem(input_path), suffix, format));
+    result_path.push(clean(name));
+    result_path
+}
+
+/// Output path for `file` written as `format`, with `--pack` / `--unpack`
+/// naming, `--flatten` or `--strip-segments`, `--prefix` / `--suffix`, and
+/// `--name-template` for the `index`-th file of the run (from 1).
+pub fn planned_output(cli: &ConvertArgs, file: &Path, format: &str, index: usize, names: NameCleanup) -> PathBuf {
+    let extension = output_extension(format);
+    let source = cli.pack.as_ref().and_then(|spec| spec.output_input_path(file))
+        .or_else(|| cli.unpack.as_ref().and_then(|spec| spec.output_input_path(file)))
+        .unwrap_or_else(|| file.to_path_buf());
+    // Relative to its own folder, a file keeps only its name
+    let source_root = if cli.flatten { source.parent().unwrap_or(&cli.input) } else { &cli.input };
+    let file_name = cli.name_template.as_ref().map(|template| template.render(&source, &cli.input, extension, index));
+    let affixes = (cli.prefix.as_deref().unwrap_or_default(), cli.suffix.as_deref().unwrap_or_default());
+    calculate_output_path(&source, source_root, cli.output_root(), cli.strip_segments, extension, names, file_name.as_deref(), affixes)
+}
+
+/// `--incremental`: whether `output_path` is a non-empty file modified no
+/// earlier than any part of `input` (split and alpha parts included). Equal
+/// times count as up to date, for file systems that store coarse ones.
+pub fn is_up_to_date(input: &Path, output_path: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let Ok(output) = std::fs::metadata(output_path) else {
+        return false;
+    };
+    let Some(written) = output.modified().ok().filter(|_| output.is_file() && output.len() > 0) else {
+        return false;
+    };
+    texture_files(input).iter().all(|part| modified(part).is_some_and(|changed| changed <= written))
+}
+
+/// Moves the file texconv wrote into `staging_dir` to `output_path`.
+///
+/// texconv names its output after the input on its own terms, and not every
+/// version agrees on where the stem ends for multi-dot names. Converting into
+/// an empty staging directory and picking up whatever appeared there avoids
+/// having to predict that name.
+pub fn reconcile_output(staging_dir: &Path, output_path: &Path) -> Result<()> {
+    let actual = produced_file(staging_dir, output_path)?;
+    move_into_place(&actual, output_path)
+}
+
+/// Renames `staged` to `output_path`, copying when they are on different volumes.
+fn move_into_place(staged: &Path, output_path: &Path) -> Result<()> {
+    if std::fs::rename(staged, output_path).is_err() {
+        std::fs::copy(staged, output_path)
+            .with_context(|| format!("Failed to move converted file to {}", output_path.display()))?;
+    }
+    Ok(())
+}
+
+/// Moves `staged` to `output_path`, unless `--write-if-changed` finds those
+/// exact bytes already there; then `staged` is dropped and the existing file
+/// keeps its mtime. Returns whether `output_path` was written.
+fn place_output(staged: &Path, output_path: &Path, cli: &ConvertArgs, console: &Console) -> Result<bool> {
+    let written = if cli.write_if_changed && same_contents(staged, output_path)? {
+        if cli.verbose {
+            console.println(format!("💤 Unchanged: {}", output_path.display()));
+        }
+        false
+    } else {
+        move_into_place(staged, output_path)?;
+        true
+    };
+    // A bad output is removed, so a later `--skip-existing` run converts it again
+    if let Err(e) = verify(output_path, cli, console) {
+        let _ = std::fs::remove_file(output_path);
+        return Err(e);
+    }
+    Ok(written)
+}
+
+/// `--verify`, with its time shown under `--verbose` since it reads the
+/// output back.
+fn verify(path: &Path, cli: &ConvertArgs, console: &Console) -> Result<()> {
+    if !cli.verify {
+        return Ok(());
+    }
+    let started = Instant::now();
+    verify_output(path)?;
+    if cli.verbose {
+        console.println(format!("🔍 Verified {} in {:.1}ms", path.display(), started.elapsed().as_secs_f64() * 1000.0));
+    }
+    Ok(())
+}
+
+/// The file texconv wrote into `staging_dir` for `output_path`.
+fn produced_file(staging_dir: &Path, output_path: &Path) -> Result<PathBuf> {
+    let mut produced: Vec<PathBuf> = std::fs::read_dir(staging_dir)
+        .context("Failed to read texconv staging directory")?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    produced.sort();
+
+    if produced.is_empty() {
+        return Err(Classified::error(
+            ErrorCode::OutputMissing,
+            format!("texconv reported success but wrote no output for {}", output_path.display()),
+        ));
+    }
+    Ok(produced.swap_remove(0))
+}
+
+/// Same-stem files next to `file_path` with one of `extensions`, paired with
+/// where they go next to `output_path` (renamed along with it if the output
+/// got a collision suffix).
+pub fn find_companions(file_path: &Path, output_path: &Path, extensions: &[String]) -> Vec<(PathBuf, PathBuf)> {
+    let stem = texture_stem(file_path);
+    let output_stem = texture_stem(output_path);
+    extensions
+        .iter()
+        .map(|ext| (
+            file_path.with_file_name(format!("{}.{}", stem, ext)),
+            output_path.with_file_name(format!("{}.{}", output_stem, ext)),
+        ))
+        .filter(|(source, _)| source.is_file())
+        .collect()
+}
+
+/// `--copy-companions` files copied, and ones already there with the same
+/// bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompanionStats {
+    pub copied: usize,
+    pub identical: usize,
+}
+
+impl std::ops::AddAssign for CompanionStats {
+    fn add_assign(&mut self, other: Self) {
+        self.copied += other.copied;
+        self.identical += other.identical;
+    }
+}
+
+/// Bytes in and out for one converted input: the `.dds` with its split and
+/// alpha parts, and every output written for it.
+#[derive(Debug, Clone, Default)]
+pub struct SizeStats {
+    /// Output format, e.g. `png`.
+    pub format: String,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl SizeStats {
+    fn new(file_path: &Path, cli: &ConvertArgs, output_bytes: u64) -> Self {
+        let input_bytes = texture_files(file_path).iter().map(|path| file_size(path)).sum();
+        SizeStats { format: cli.format.clone(), input_bytes, output_bytes }
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
 }
 
+/// What happened to a single input file.
+#[derive(Debug, Clone)]
+pub enum FileOutcome {
+    /// Converted, with the number of companion files copied alongside, the
+    /// bytes `--strip-metadata` removed, what trimming and padding did and,
+    /// with `--content-hash`, each planned output path and its hashed name,
+    /// the `--format png8` sizes, what `--optimize` saved and whether
+    /// `--write-if-changed` kept every existing output as it was, plus the
+    /// input and output sizes.
+    Converted {
+        companions: CompanionStats,
+        metadata_stripped: u64,
+        geometry: OutputGeometry,
+        hashed_names: Vec<(PathBuf, PathBuf)>,
+        palette: PaletteStats,
+        optimized: OptimizeStats,
+        unchanged: bool,
+        sizes: SizeStats,
+    },
+    /// Byte-identical to an earlier input; its output was reused via `method`.
+    Linked { method: LinkMode, companions: CompanionStats },
+    Skipped(SkipReason),
+}
+
+/// Per-file results of the pixel steps, recorded in the sidecar.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputGeometry {
+    /// `--trim`: the kept rectangle of the decoded (and cropped) image.
+    pub trim: Option<CropRect>,
+    /// `--pad-pot`: where the original pixels are in the padded image.
+    pub content_rect: Option<CropRect>,
+    /// `--dither`: set when the tool reduced a high-bit-depth decode itself.
+    pub dither: Option<Dither>,
+    /// `--tint` or the `--tint-map` entry the output was multiplied by.
+    pub tint: Option<Tint>,
+    /// Quality of a built-in jpg or webp encode.
+    pub quality: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    TooSmall,
+    TooLarge,
+    Collision,
+    CropOutOfBounds,
+    /// The output already existed (`--on-conflict skip`, `--skip-existing`
+    /// or an `--interactive` answer).
+    Exists,
+    /// `--incremental`: the output is newer than the input.
+    UpToDate,
+}
+
+impl SkipReason {
+    /// Name used in `--report`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::TooSmall => "too_small",
+            SkipReason::TooLarge => "too_large",
+            SkipReason::Collision => "collision",
+            SkipReason::CropOutOfBounds => "crop_out_of_bounds",
+            SkipReason::Exists => "exists",
+            SkipReason::UpToDate => "up_to_date",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SkipReason::TooSmall => "smaller than --min-size",
+            SkipReason::TooLarge => "larger than --max-size",
+            SkipReason::Collision => "output name taken by another input",
+            SkipReason::CropOutOfBounds => "smaller than the --crop region",
+            SkipReason::Exists => "output already exists",
+            SkipReason::UpToDate => "up to date",
+        }
+    }
+}
+
+/// Converts `file_path` to `output_path`. With `zip` set, `output_path` is
+/// the entry's path inside the archive.
 pub async fn process_file(
     file_path: &Path,
+    output_path: &Path,
     texconv_path: &Path,
-    input_dir: &Path,
-    output_dir: &Path,
-    strip_segments: usize,
-    verbose: bool,
-    format: &str,
-    continue_on_error: bool,
-) -> Result<()> {
-    let metadata = fs::metadata(file_path).await
-        .context("Failed to read file metadata")?;
-    
-    if metadata.len() < 128 {
-        if verbose {
-            println!("⚠️  Skipping very small file: {}", file_path.display());
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let staging_parent = match zip {
+        Some(zip) => zip.staging_dir(),
+        None => {
+            let parent = output_path.parent().unwrap();
+            fs::create_dir_all(parent).await
+                .context("Failed to create output directory")?;
+            parent
         }
-        return Ok(());
+    };
+
+    if cli.verbose {
+        console.println(format!("🔄 Processing: {} -> {}",
+                file_path.display(), output_path.display()));
     }
 
-    let output_path = calculate_output_path(file_path, input_dir, output_dir, strip_segments, format);
-    
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent).await
-            .context("Failed to create output directory")?;
+    // texconv writes into `out` on its own; merged inputs and alpha
+    // intermediates live next to it
+    let staging_dir = tempfile::Builder::new()
+        .prefix(".dds-converter-")
+        .tempdir_in(staging_parent)
+        .context("Failed to create staging directory")?;
+    let texconv_out = staging_dir.path().join("out");
+    std::fs::create_dir(&texconv_out).context("Failed to create staging directory")?;
+
+    if let Some(spec) = &cli.pack {
+        return write_packed_output(file_path, spec, output_path, staging_dir.path(), texconv_path, zip, cli, console).await;
     }
+    if let Some(spec) = &cli.composite {
+        return write_composited_output(file_path, spec, output_path, staging_dir.path(), texconv_path, zip, cli, console).await;
+    }
+    if let Some(spec) = &cli.unpack {
+        return write_unpacked_outputs(file_path, spec, output_path, staging_dir.path(), texconv_path, zip, cli, console).await;
+    }
+    let texconv_input = prepare_input(file_path, staging_dir.path(), cli.verbose, console)?;
+    let texconv_input = match cli.mip {
+        Some(level) => select_mip(file_path, &texconv_input, level, staging_dir.path(), cli, console)?,
+        None => texconv_input,
+    };
+    if cli.all_mips {
+        return write_mip_outputs(file_path, &texconv_input, output_path, staging_dir.path(), texconv_path, zip, cli, console).await;
+    }
+    if let Some(layout) = cli.cubemap_layout {
+        if DdsHeader::read(&texconv_input).is_ok_and(|header| header.is_cubemap) {
+            return write_cubemap_output(file_path, &texconv_input, output_path, layout, staging_dir.path(), texconv_path, zip, cli, console).await;
+        }
+    }
+    let size = resize_target(file_path, &texconv_input, cli, console);
+    // The alpha part is stored at full size; scale it down to the chosen mip
+    let alpha_size = size.or_else(|| {
+        cli.mip?;
+        DdsHeader::read(&texconv_input).ok().map(|header| Size { width: header.width, height: header.height })
+    });
+    let alpha_part = if cli.no_merge_alpha_part { None } else { find_alpha_part(file_path) };
+    let tint_variants = cli.tint_map.as_ref().map(|map| map.variants_for(file_path)).unwrap_or_default();
 
-    if verbose {
-        println!("🔄 Processing: {} -> {}", 
-                file_path.display(), output_path.display());
+    // `--dither` only matters where texconv would otherwise drop the extra
+    // bits of a high-bit-depth texture on its own
+    let high_bit_depth = || DdsHeader::read(file_path).is_ok_and(|header| header.is_high_bit_depth());
+    let dither = (alpha_part.is_none() && cli.dither != Dither::None && high_bit_depth()).then_some(cli.dither);
+
+    // Alpha merging, thresholding, tinting, colour adjustment, cropping, trimming, padding and tiling work on an RGBA
+    // PNG, which then goes through texconv a second time unless PNG is the
+    // target anyway
+    let mut intermediate = match &alpha_part {
+        Some(alpha_path) => {
+            let composited = merge_alpha_part(file_path, &texconv_input, alpha_path, texconv_path, &cli.texconv(), alpha_size, staging_dir.path(), console).await?;
+            if cli.verbose {
+                console.println(format!("🎭 Merged alpha from {}", alpha_path.display()));
+            }
+            Some(composited)
+        }
+        None if dither.is_some() => {
+            let decoded_dir = staging_dir.path().join("decoded");
+            std::fs::create_dir_all(&decoded_dir).context("Failed to create decode directory")?;
+            run_texconv_as(texconv_path, &cli.texconv(), &texconv_input, "R16G16B16A16_UNORM", "png", &decoded_dir, file_path, size, console).await?;
+            let wide = produced_file(&decoded_dir, file_path)?;
+            let narrowed = staging_dir.path().join(format!("{}.png", texture_stem(file_path)));
+            quantize_png(&wide, &narrowed, cli.dither)?;
+            Some(narrowed)
+        }
+        None if built_in_format(&cli.format) || lossy_built_in(cli) || cli.alpha_threshold.is_some() || cli.tint.is_some() || cli.adjust.is_some() || !tint_variants.is_empty()
+            || cli.watermark.watermark.is_some()
+            || cli.crop.is_some() || cli.trim || cli.pad_pot || cli.tile.is_some() => {
+            let decoded_dir = staging_dir.path().join("decoded");
+            std::fs::create_dir_all(&decoded_dir).context("Failed to create decode directory")?;
+            run_texconv_as(texconv_path, &cli.texconv(), &texconv_input, DEFAULT_PIXEL_FORMAT, "png", &decoded_dir, file_path, size, console).await?;
+            Some(produced_file(&decoded_dir, file_path)?)
+        }
+        None => None,
+    };
+
+    if let (Some(threshold), Some(png)) = (cli.alpha_threshold, &intermediate) {
+        let thresholded_dir = staging_dir.path().join("thresholded");
+        std::fs::create_dir_all(&thresholded_dir).context("Failed to create threshold directory")?;
+        let thresholded = thresholded_dir.join(format!("{}.png", texture_stem(file_path)));
+        threshold_png(png, &thresholded, threshold, cli.alpha_dither)?;
+        intermediate = Some(thresholded);
+    }
+
+    if let (Some(tint), Some(png)) = (cli.tint, &intermediate) {
+        let tinted_dir = staging_dir.path().join("tinted");
+        std::fs::create_dir_all(&tinted_dir).context("Failed to create tint directory")?;
+        let tinted = tinted_dir.join(format!("{}.png", texture_stem(file_path)));
+        tint_png(png, &tinted, tint)?;
+        intermediate = Some(tinted);
+    }
+
+    if let (Some(chain), Some(png)) = (&cli.adjust, &intermediate) {
+        let adjusted_dir = staging_dir.path().join("adjusted");
+        std::fs::create_dir_all(&adjusted_dir).context("Failed to create adjustment directory")?;
+        let adjusted = adjusted_dir.join(format!("{}.png", texture_stem(file_path)));
+        adjust_png(png, &adjusted, chain)?;
+        intermediate = Some(adjusted);
+    }
+
+    if let (Some(rect), Some(png)) = (cli.crop, &intermediate) {
+        match crop_intermediate(file_path, png, rect, staging_dir.path(), cli, console)? {
+            Some(cropped) => intermediate = Some(cropped),
+            None => return Ok(FileOutcome::Skipped(SkipReason::CropOutOfBounds)),
+        }
+    }
+
+    let mut geometry = OutputGeometry { dither, tint: cli.tint, ..OutputGeometry::default() };
+    if let (true, Some(png)) = (cli.trim, &intermediate) {
+        if let Some((trimmed, rect)) = trim_intermediate(file_path, png, staging_dir.path(), cli, console)? {
+            intermediate = Some(trimmed);
+            geometry.trim = Some(rect);
+        }
+    }
+
+    if let (true, Some(png)) = (cli.pad_pot, &intermediate) {
+        let padded_dir = staging_dir.path().join("padded");
+        std::fs::create_dir_all(&padded_dir).context("Failed to create padding directory")?;
+        let padded = padded_dir.join(format!("{}.png", texture_stem(file_path)));
+        if let Some(rect) = pad_png_to_pot(png, &padded, cli.pot_align)? {
+            if cli.verbose {
+                let (width, height) = png_dimensions(&padded)?;
+                console.println(format!("📐 Padded {} from {}x{} to {}x{}", file_path.display(), rect.width, rect.height, width, height));
+            }
+            intermediate = Some(padded);
+            geometry.content_rect = Some(rect);
+        }
+    }
+
+    if let (false, Some(png)) = (tint_variants.is_empty(), &intermediate) {
+        return write_tint_variants(file_path, png, output_path, &tint_variants, geometry, staging_dir.path(), texconv_path, zip, cli, console).await;
     }
 
-    let output = Command::new(texconv_path)
+    if let (Some(tile_size), Some(png)) = (cli.tile, &intermediate) {
+        return write_tiled_output(file_path, png, output_path, tile_size, geometry, staging_dir.path(), texconv_path, zip, cli, console).await;
+    }
+
+    if let Some(png) = &intermediate {
+        intermediate = Some(watermark_intermediate(file_path, png, &staging_dir.path().join("watermarked"), cli, console)?);
+    }
+
+    if let Some(png) = &intermediate {
+        intermediate = Some(flatten_intermediate(file_path, png, &staging_dir.path().join("flattened"), cli, console)?);
+    }
+
+    let mut palette = PaletteStats::default();
+    match intermediate {
+        None => {
+            let pixel_format = output_pixel_format(file_path, DdsHeader::read(&texconv_input).ok().as_ref(), cli, console);
+            run_texconv_as(texconv_path, &cli.texconv(), &texconv_input, &pixel_format, &cli.format, &texconv_out, file_path, size, console).await?
+        }
+        Some(png) if cli.format == "png8" => {
+            let staged = texconv_out.join(format!("{}.png", texture_stem(file_path)));
+            palette = png8_output(file_path, &png, &staged, cli, console)?;
+        }
+        Some(png) if cli.format == "ico" => {
+            let staged = texconv_out.join(format!("{}.ico", texture_stem(file_path)));
+            ico_output(file_path, &png, &staged, cli, console)?;
+        }
+        Some(png) if lossy_built_in(cli) => {
+            let staged = texconv_out.join(format!("{}.{}", texture_stem(file_path), cli.format));
+            geometry.quality = Some(lossy_output(file_path, &png, &staged, cli, console)?);
+        }
+        Some(png) if cli.format.eq_ignore_ascii_case("png") => {
+            std::fs::copy(&png, texconv_out.join(png.file_name().unwrap_or_default()))
+                .context("Failed to stage the intermediate image")?;
+        }
+        Some(png) => run_texconv(texconv_path, &cli.texconv(), &png, &cli.format, &texconv_out, file_path, console).await?,
+    }
+
+    let produced = produced_file(&texconv_out, output_path)?;
+    if !built_in_format(&cli.format) && !lossy_built_in(cli) {
+        flatten_output_alpha(file_path, &produced, cli, console)?;
+    }
+    let optimized = optimize_output(file_path, &produced, cli, console)?;
+    let metadata_stripped = rewrite_metadata(file_path, &produced, cli)?;
+    let final_path = final_output_path(output_path, &produced, cli)?;
+    let sizes = SizeStats::new(file_path, cli, file_size(&produced));
+    let mut unchanged = false;
+    let companions = match zip {
+        Some(zip) => {
+            verify(&produced, cli, console)?;
+            zip.add_file(&final_path, &produced).await?;
+            CompanionStats::default()
+        }
+        None => {
+            unchanged = !place_output(&produced, &final_path, cli, console)?;
+            finish_output(file_path, &final_path, geometry, cli, console).await?
+        }
+    };
+    let hashed_names = if final_path != output_path { vec![(output_path.to_path_buf(), final_path.clone())] } else { Vec::new() };
+
+    if cli.verbose {
+        console.println(format!("✅ Done: {}", final_path.display()));
+    }
+
+    Ok(FileOutcome::Converted { companions, metadata_stripped, geometry, hashed_names, palette, optimized, unchanged, sizes })
+}
+
+/// `--format png8`: writes the decoded `png` to `staged` as an indexed PNG,
+/// warning about images that need more than 256 colours.
+fn png8_output(file_path: &Path, png: &Path, staged: &Path, cli: &ConvertArgs, console: &Console) -> Result<PaletteStats> {
+    let (result, stats) = write_png8(png, staged, cli.dither, cli.png8_fallback)?;
+    match result {
+        Png8Result::KeptRgba { rmse } => console.println(format!(
+            "⚠️  {} needs more than 256 colours (RMSE {:.1}); kept as PNG-32",
+            file_path.display(), rmse
+        )),
+        Png8Result::Indexed { rmse, .. } if rmse > PNG8_MAX_RMSE => console.println(format!(
+            "⚠️  {} lost detail in 256 colours (RMSE {:.1}); pass --png8-fallback to keep such images as PNG-32",
+            file_path.display(), rmse
+        )),
+        Png8Result::Indexed { colors, rmse } if cli.verbose => {
+            console.println(format!("🎨 {}: {} colours (RMSE {:.1})", file_path.display(), colors, rmse));
+        }
+        Png8Result::Indexed { .. } => {}
+    }
+    Ok(stats)
+}
+
+/// `--watermark` on the final RGBA `png` of an output, stamped into
+/// `work_dir`. Returns the PNG to encode.
+fn watermark_intermediate(file_path: &Path, png: &Path, work_dir: &Path, cli: &ConvertArgs, console: &Console) -> Result<PathBuf> {
+    let Some(watermark) = &cli.watermark.watermark else {
+        return Ok(png.to_path_buf());
+    };
+    match watermark_png(png, work_dir, watermark, cli.watermark.watermark_margin, cli.watermark.watermark_min_size)? {
+        Some(stamped) => Ok(stamped),
+        None => {
+            if cli.verbose {
+                console.println(format!("⏭️  {}: smaller than --watermark-min-size; not watermarked", file_path.display()));
+            }
+            Ok(png.to_path_buf())
+        }
+    }
+}
+
+/// `--format ico`: writes the decoded `png` to `staged` as a multi-size
+/// icon, warning when the image is too small for the largest size.
+fn ico_output(file_path: &Path, png: &Path, staged: &Path, cli: &ConvertArgs, console: &Console) -> Result<()> {
+    let sizes = write_ico(png, staged, &cli.ico_sizes.0)?;
+    let largest = cli.ico_sizes.0.first().copied().unwrap_or_default();
+    if sizes.first().is_some_and(|size| *size < largest) {
+        console.println(format!(
+            "⚠️  {} is smaller than {}px; the icon only goes up to {}px",
+            file_path.display(), largest, sizes[0]
+        ));
+    }
+    if cli.verbose {
+        let list: Vec<String> = sizes.iter().map(|size| size.to_string()).collect();
+        console.println(format!("🪟 {}: icon sizes {}", file_path.display(), list.join(", ")));
+    }
+    Ok(())
+}
+
+/// Whether the tool encodes the output itself at a chosen quality: always
+/// for webp, and for jpg when `--target-size` needs the quality search.
+fn lossy_built_in(cli: &ConvertArgs) -> bool {
+    cli.format == "webp" || (cli.format == "jpg" && (cli.target_size.is_some() || cli.jpeg_quality.is_some() || cli.jpeg_subsampling.is_some()))
+}
+
+/// Encodes the decoded `png` to `staged` as jpg or webp, searching for a
+/// quality that meets `--target-size` or using `--jpeg-quality`. Returns
+/// the quality used.
+fn lossy_output(file_path: &Path, png: &Path, staged: &Path, cli: &ConvertArgs, console: &Console) -> Result<u8> {
+    let search = encode_lossy(png, staged, &cli.format, cli.target_size, cli.jpeg_quality, cli.jpeg_subsampling.unwrap_or_default())?;
+    let size = format_bytes(search.bytes.len() as u64);
+    if !search.fits {
+        console.println(format!(
+            "⚠️  {} is {} even at quality {}, over --target-size {}",
+            file_path.display(), size, search.quality, format_bytes(cli.target_size.unwrap_or_default())
+        ));
+    } else if cli.verbose && cli.target_size.is_some() {
+        console.println(format!("🎯 {}: quality {} ({}, {} attempt(s))", file_path.display(), search.quality, size, search.attempts));
+    }
+    Ok(search.quality)
+}
+
+/// `--optimize` on a finished output, before its metadata is rewritten and
+/// its name hashed.
+fn optimize_output(file_path: &Path, converted: &Path, cli: &ConvertArgs, console: &Console) -> Result<OptimizeStats> {
+    let Some(level) = cli.optimize else {
+        return Ok(OptimizeStats::default());
+    };
+    let stats = optimize_png(converted, level, cli.optimize_max_size)?;
+    if cli.verbose && stats.files > 0 {
+        console.println(format!(
+            "🗜️  {}: {} -> {} in {:.2}s",
+            file_path.display(), format_bytes(stats.bytes_before), format_bytes(stats.bytes_after), stats.elapsed.as_secs_f64()
+        ));
+    } else if cli.verbose && stats.skipped > 0 {
+        console.println(format!("⏭️  {}: larger than --optimize-max-size; not optimized", file_path.display()));
+    }
+    Ok(stats)
+}
+
+/// `--alpha` on the image at `path`, in place. Returns whether it was
+/// rewritten; formats the image crate can't re-encode keep their alpha,
+/// with a warning.
+fn flatten_output_alpha(file_path: &Path, path: &Path, cli: &ConvertArgs, console: &Console) -> Result<bool> {
+    match flatten_alpha_in_place(path, cli.alpha)? {
+        AlphaRewrite::Rewritten => {
+            if cli.verbose {
+                let how = if cli.alpha == AlphaMode::Strip { "Stripped alpha".to_string() } else { format!("Flattened alpha onto {}", cli.alpha.name()) };
+                console.println(format!("🫥 {}: {}", file_path.display(), how));
+            }
+            Ok(true)
+        }
+        AlphaRewrite::Unsupported => {
+            console.println(format!(
+                "⚠️  {}: --alpha {} can't rewrite {} outputs; alpha kept",
+                file_path.display(), cli.alpha.name(), cli.format
+            ));
+            Ok(false)
+        }
+        AlphaRewrite::Unchanged => Ok(false),
+    }
+}
+
+/// `--alpha` for the encoders the tool runs itself, whose outputs can't be
+/// re-encoded after the fact: a copy of the decoded `png` in `work_dir`
+/// without alpha. Other formats get `png` back and are flattened once
+/// written.
+fn flatten_intermediate(file_path: &Path, png: &Path, work_dir: &Path, cli: &ConvertArgs, console: &Console) -> Result<PathBuf> {
+    if cli.alpha == AlphaMode::Keep || !(built_in_format(&cli.format) || lossy_built_in(cli)) {
+        return Ok(png.to_path_buf());
+    }
+    std::fs::create_dir_all(work_dir).context("Failed to create alpha directory")?;
+    let flattened = work_dir.join(png.file_name().unwrap_or_default());
+    std::fs::copy(png, &flattened).context("Failed to stage the intermediate image")?;
+    match flatten_output_alpha(file_path, &flattened, cli, console)? {
+        true => Ok(flattened),
+        false => Ok(png.to_path_buf()),
+    }
+}
+
+/// Where the finished `staged` file for `output_path` goes: `output_path`
+/// with a `{contenthash}` from `--name-template` filled in, and with
+/// `--content-hash` the hash inserted before the extension.
+fn final_output_path(output_path: &Path, staged: &Path, cli: &ConvertArgs) -> Result<PathBuf> {
+    let output_path = fill_hash_placeholder(output_path, staged)?;
+    match cli.content_hash {
+        Some(digits) => hashed_path(&output_path, staged, digits as usize),
+        None => Ok(output_path),
+    }
+}
+
+/// `--strip-metadata`, `--reproducible` stripping, then `--embed-metadata`,
+/// on a converted file (stripping first, since it would remove the embedded
+/// text again). Returns the bytes `--strip-metadata` saved.
+fn rewrite_metadata(file_path: &Path, converted: &Path, cli: &ConvertArgs) -> Result<u64> {
+    let stripped = if cli.strip_metadata { strip_metadata(converted, cli.keep_icc)? } else { 0 };
+    if cli.reproducible {
+        normalize_output(converted)?;
+    }
+    if cli.embed_metadata {
+        let provenance = Provenance::describe(file_path, &cli.input, &cli.metadata)?;
+        embed_metadata(converted, &provenance)?;
+    }
+    Ok(stripped)
+}
+
+/// Runs texconv on `input`, writing a `format` file into `out_dir`.
+/// `source` is the original input, for error messages.
+async fn run_texconv(texconv_path: &Path, options: &TexconvOptions, input: &Path, format: &str, out_dir: &Path, source: &Path, console: &Console) -> Result<()> {
+    run_texconv_as(texconv_path, options, input, DEFAULT_PIXEL_FORMAT, format, out_dir, source, None, console).await
+}
+
+/// The `-f` value for texconv's direct conversion of `file_path` (with
+/// `header`) to the output format: `--pixel-format`, or one that suits the
+/// source.
+fn output_pixel_format(file_path: &Path, header: Option<&DdsHeader>, cli: &ConvertArgs, console: &Console) -> String {
+    let pixel_format = match &cli.pixel_format {
+        PixelFormatChoice::Fixed(name) => return name.clone(),
+        PixelFormatChoice::Auto => pick_pixel_format(header, &cli.format),
+    };
+    if cli.verbose && pixel_format != DEFAULT_PIXEL_FORMAT {
+        let source = header.map_or_else(|| "unknown".to_string(), DdsHeader::format_name);
+        console.println(format!("🎨 {}: converting as {} ({} source)", file_path.display(), pixel_format, source));
+    }
+    pixel_format.to_string()
+}
+
+/// Cuts `--mip` `level` out of `input` (the texture, or its merged split
+/// parts) as a single-level DDS. Levels count from the full-size image,
+/// also when the merged parts lack the top levels.
+fn select_mip(file_path: &Path, input: &Path, level: u32, work_dir: &Path, cli: &ConvertArgs, console: &Console) -> Result<PathBuf> {
+    let bytes = std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let header = DdsHeader::parse(&bytes).with_context(|| format!("Failed to read the DDS header of {}", input.display()))?;
+    let dropped = DdsHeader::read(file_path)
+        .ok()
+        .filter(|full| full.width > header.width && header.width > 0)
+        .map_or(0, |full| (full.width / header.width).trailing_zeros());
+    let stored = dropped + header.mip_count.max(1);
+
+    let chosen = if level >= stored {
+        if !cli.mip_clamp {
+            let message = format!(
+                "{} has {} mip level(s) (0-{}), so there is no mip {}; use --mip-clamp to take the smallest instead",
+                file_path.display(), stored, stored - 1, level
+            );
+            return Err(Classified::error(ErrorCode::MipOutOfRange, message));
+        }
+        stored - 1
+    } else if level < dropped {
+        if !cli.mip_clamp {
+            let message = format!("{}: mip {} is missing from its incomplete split parts; use --mip-clamp to take the largest stored level", file_path.display(), level);
+            return Err(Classified::error(ErrorCode::MipOutOfRange, message));
+        }
+        dropped
+    } else {
+        level
+    };
+
+    let local = chosen - dropped;
+    let (width, height) = header.mip_dimensions(local);
+    if cli.verbose {
+        let clamped = if chosen == level { String::new() } else { format!(", clamped from mip {}", level) };
+        console.println(format!("🪜 Using mip {} ({}x{}{}) of {}", chosen, width, height, clamped, file_path.display()));
+    }
+    if local == 0 && header.mip_count <= 1 && header.array_size <= 1 && !header.is_cubemap {
+        return Ok(input.to_path_buf());
+    }
+
+    let mip_dir = work_dir.join("mip");
+    std::fs::create_dir_all(&mip_dir).context("Failed to create mip directory")?;
+    let output = mip_dir.join(file_path.file_name().unwrap_or_default());
+    extract_surface(&bytes, &header, 0, local, &output)?;
+    Ok(output)
+}
+
+/// The size `--resize` / `--max-dimension` give the texture at `input`, or
+/// `None` to keep its own.
+fn resize_target(file_path: &Path, input: &Path, cli: &ConvertArgs, console: &Console) -> Option<Size> {
+    if cli.resize.is_none() && cli.max_dimension.is_none() {
+        return None;
+    }
+    let header = DdsHeader::read(input).ok()?;
+    match plan_resize(header.width, header.height, cli) {
+        ResizePlan::To(size) => {
+            if cli.verbose {
+                console.println(format!("📐 Resizing {} from {}x{} to {}", file_path.display(), header.width, header.height, size));
+            }
+            Some(size)
+        }
+        ResizePlan::NotUpscaled(size) => {
+            console.println(format!(
+                "⚠️  {} is {}x{}, smaller than {}; kept at its size (use --allow-upscale to enlarge it)",
+                file_path.display(), header.width, header.height, size
+            ));
+            None
+        }
+        ResizePlan::Keep => None,
+    }
+}
+
+/// The texconv command line for converting `input` into `out_dir`, with
+/// the `--srgb` flag for the original texture `source`, resized to `size`
+/// and, when `input` is a DDS, swizzled per `--swizzle`.
+/// The `--texconv-arg` switches come after the built-in ones, so they win.
+#[allow(clippy::too_many_arguments)]
+pub fn convert_command(texconv_path: &Path, options: &TexconvOptions, input: &Path, pixel_format: &str, format: &str, out_dir: &Path, source: &Path, size: Option<Size>) -> tokio::process::Command {
+    let mut command = texconv_command(texconv_path, options);
+    command
         .arg("-f")
-        .arg("R8G8B8A8_UNORM")
+        .arg(pixel_format)
         .arg("-ft")
         .arg(format)
         .arg("-y")  // Overwrite existing files
         .arg("-o")
-        .arg(output_path.parent().unwrap())
-        .arg(file_path)
-        .output()
-        .context("Failed to run texconv")?;
+        .arg(out_dir);
+    if let Some(size) = size {
+        command.arg("-w").arg(size.width.to_string()).arg("-h").arg(size.height.to_string());
+    }
+    command.args(srgb_flag(source, pixel_format, options.srgb));
+    if let Some(swizzle) = options.swizzle.as_ref().filter(|_| input.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dds"))) {
+        command.arg("-swizzle").arg(swizzle);
+    }
+    command.args(&options.extra_args);
+    command.arg(input);
+    command
+}
+
+/// `run_texconv` with an explicit DXGI pixel format to decode to and
+/// `--resize` / `--max-dimension` size. The command line is printed first
+/// under `--verbose`.
+#[allow(clippy::too_many_arguments)]
+async fn run_texconv_as(texconv_path: &Path, options: &TexconvOptions, input: &Path, pixel_format: &str, format: &str, out_dir: &Path, source: &Path, size: Option<Size>, console: &Console) -> Result<()> {
+    let mut command = convert_command(texconv_path, options, input, pixel_format, format, out_dir, source, size);
+    if options.echo {
+        console.println(format!("🔧 {}", command_line(&command)));
+    }
+    let output = output_with_timeout(&mut command, options.timeout, "texconv").await?;
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        
-        let error_msg = format!(
-            "texconv failed for {}: code {}\nStderr: {}\nStdout: {}",
+        return Err(TexconvFailure {
+            source: source.to_path_buf(),
+            exit_code: output.status.code(),
+            stderr: decode_process_output(&output.stderr),
+            stdout: decode_process_output(&output.stdout),
+        }.into());
+    }
+
+    Ok(())
+}
+
+/// The file to hand to texconv: `file_path` itself, or for a split texture
+/// with `.dds.N` parts, a merged copy written into `work_dir`.
+fn prepare_input(
+    file_path: &Path,
+    work_dir: &Path,
+    verbose: bool,
+    console: &Console,
+) -> Result<PathBuf> {
+    let parts = find_split_parts(file_path);
+    if parts.is_empty() {
+        return Ok(file_path.to_path_buf());
+    }
+
+    let merged_dir = work_dir.join("merged");
+    std::fs::create_dir_all(&merged_dir).context("Failed to create merge directory")?;
+    let merged = merge_split_dds(file_path, &parts, &merged_dir.join(file_path.file_name().unwrap_or_default()))?;
+
+    if merged.dropped_levels > 0 {
+        let missing: Vec<String> = merged.missing_parts.iter().map(|number| format!(".{}", number)).collect();
+        console.println(format!(
+            "⚠️  {}: split part(s) {} missing; converting without the top {} mip level(s)",
             file_path.display(),
-            output.status.code().unwrap_or(-1),
-            stderr,
-            stdout
-        );
-        
-        if continue_on_error {
-            println!("⚠️  {}", error_msg);
-            return Ok(());
-        } else {
-            anyhow::bail!(error_msg);
+            missing.join(", "),
+            merged.dropped_levels
+        ));
+    }
+    if verbose {
+        console.println(format!("🧩 Merged {} split part(s) into {}", merged.parts_used, file_path.display()));
+    }
+
+    Ok(merged.path)
+}
+
+/// Decodes `file_path` (with its split parts merged) to an RGBA PNG inside
+/// `work_dir`.
+pub async fn decode_to_png(file_path: &Path, texconv_path: &Path, options: &TexconvOptions, work_dir: &Path, verbose: bool, console: &Console) -> Result<PathBuf> {
+    let input = prepare_input(file_path, work_dir, verbose, console)?;
+    let decoded_dir = work_dir.join("decoded");
+    std::fs::create_dir_all(&decoded_dir).context("Failed to create decode directory")?;
+    run_texconv(texconv_path, options, &input, "png", &decoded_dir, file_path, console).await?;
+    produced_file(&decoded_dir, file_path)
+}
+
+/// Converts the color texture and its attached `.dds.a` alpha texture to PNG
+/// and composites them, returning the composited PNG inside `work_dir`.
+#[allow(clippy::too_many_arguments)]
+async fn merge_alpha_part(
+    file_path: &Path,
+    color_input: &Path,
+    alpha_path: &Path,
+    texconv_path: &Path,
+    options: &TexconvOptions,
+    size: Option<Size>,
+    work_dir: &Path,
+    console: &Console,
+) -> Result<PathBuf> {
+    let color_dir = work_dir.join("color");
+    let alpha_dir = work_dir.join("alpha");
+    std::fs::create_dir_all(&color_dir).context("Failed to create alpha merge directory")?;
+    std::fs::create_dir_all(&alpha_dir).context("Failed to create alpha merge directory")?;
+
+    // texconv picks its reader by extension, so the alpha texture needs a .dds name
+    let alpha_input = work_dir.join("alpha.dds");
+    let alpha_parts = find_alpha_split_parts(file_path);
+    if alpha_parts.is_empty() {
+        std::fs::copy(alpha_path, &alpha_input).context("Failed to stage the alpha part")?;
+    } else {
+        merge_split_dds(alpha_path, &alpha_parts, &alpha_input)?;
+    }
+
+    run_texconv_as(texconv_path, options, color_input, DEFAULT_PIXEL_FORMAT, "png", &color_dir, file_path, size, console).await?;
+    run_texconv_as(texconv_path, options, &alpha_input, DEFAULT_PIXEL_FORMAT, "png", &alpha_dir, alpha_path, size, console).await?;
+
+    let composited = work_dir.join(format!("{}.png", texture_stem(file_path)));
+    composite_alpha(
+        &produced_file(&color_dir, file_path)?,
+        &produced_file(&alpha_dir, alpha_path)?,
+        &composited,
+        console,
+    )?;
+    Ok(composited)
+}
+
+/// Cuts `rect` out of the decoded `png`, fitted per `--crop-mode`.
+/// Returns the cropped PNG inside `work_dir`, or `None` to skip the file.
+fn crop_intermediate(
+    file_path: &Path,
+    png: &Path,
+    rect: CropRect,
+    work_dir: &Path,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<Option<PathBuf>> {
+    let (width, height) = png_dimensions(png)?;
+    let Some(fitted) = rect.fit(width, height, cli.crop_mode) else {
+        let message = format!("{} is {}x{}, too small for --crop {}", file_path.display(), width, height, rect);
+        if cli.crop_mode == CropMode::Error {
+            return Err(Classified::error(ErrorCode::CropOutOfBounds, message));
         }
+        console.println(format!("⚠️  Skipping {}", message));
+        return Ok(None);
+    };
+
+    let cropped_dir = work_dir.join("cropped");
+    std::fs::create_dir_all(&cropped_dir).context("Failed to create crop directory")?;
+    let cropped = cropped_dir.join(format!("{}.png", texture_stem(file_path)));
+    crop_png(png, &cropped, fitted)?;
+
+    if cli.verbose {
+        let clamped = if fitted != rect { " (clamped)" } else { "" };
+        console.println(format!("✂️  Cropped {} to {}x{} at {},{}{}", file_path.display(), fitted.width, fitted.height, fitted.x, fitted.y, clamped));
     }
+    Ok(Some(cropped))
+}
 
-    if verbose {
-        println!("✅ Done: {}", output_path.display());
+/// `--trim`: cuts the decoded `png` down to its non-transparent pixels plus
+/// `--trim-padding`. Returns the trimmed PNG inside `work_dir` and the kept
+/// rectangle, or `None` for a fully transparent image, which stays as it is.
+fn trim_intermediate(
+    file_path: &Path,
+    png: &Path,
+    work_dir: &Path,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<Option<(PathBuf, CropRect)>> {
+    let image = image::open(png)
+        .with_context(|| format!("Failed to open {}", png.display()))?
+        .to_rgba8();
+    let Some(bounds) = content_bounds(&image, cli.trim_threshold) else {
+        console.println(format!("⚠️  {} is fully transparent; not trimmed", file_path.display()));
+        return Ok(None);
+    };
+    let rect = bounds.expand(cli.trim_padding, image.width(), image.height());
+
+    let trimmed_dir = work_dir.join("trimmed");
+    std::fs::create_dir_all(&trimmed_dir).context("Failed to create trim directory")?;
+    let trimmed = trimmed_dir.join(format!("{}.png", texture_stem(file_path)));
+    image::imageops::crop_imm(&image, rect.x, rect.y, rect.width, rect.height)
+        .to_image()
+        .save(&trimmed)
+        .with_context(|| format!("Failed to write {}", trimmed.display()))?;
+
+    if cli.verbose {
+        console.println(format!(
+            "✂️  Trimmed {} from {}x{} to {}x{} at {},{}",
+            file_path.display(), image.width(), image.height(), rect.width, rect.height, rect.x, rect.y
+        ));
     }
+    Ok(Some((trimmed, rect)))
+}
+
+/// Extras that accompany every output of `file_path`: the `--sidecar` JSON
+/// and `--copy-companions` files. Returns what happened to the companions.
+pub async fn finish_output(
+    file_path: &Path,
+    output_path: &Path,
+    geometry: OutputGeometry,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<CompanionStats> {
+    write_convert_sidecar(file_path, output_path, geometry, cli, console)?;
+    copy_companions(file_path, output_path, cli, console).await
+}
 
+fn write_convert_sidecar(
+    file_path: &Path,
+    output_path: &Path,
+    geometry: OutputGeometry,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<()> {
+    if !cli.sidecar {
+        return Ok(());
+    }
+    let settings = SidecarSettings::Convert {
+        format: cli.format.clone(),
+        decode_format: if geometry.dither.is_some() { "R16G16B16A16_UNORM" } else { "R8G8B8A8_UNORM" }.to_string(),
+        dither: geometry.dither,
+        alpha_threshold: cli.alpha_threshold,
+        alpha_dither: cli.alpha_dither,
+        crop: cli.crop,
+        trim: geometry.trim,
+        content_rect: geometry.content_rect,
+        tint: geometry.tint.map(|tint| tint.to_string()),
+        adjust: cli.adjust.as_ref().map(|chain| chain.to_string()),
+        quality: geometry.quality,
+    };
+    let sidecar_path = write_sidecar(output_path, settings, &[file_path.to_path_buf()])?;
+    if cli.verbose {
+        console.println(format!("🧾 Sidecar: {}", sidecar_path.display()));
+    }
     Ok(())
 }
+
+async fn copy_companions(file_path: &Path, output_path: &Path, cli: &ConvertArgs, console: &Console) -> Result<CompanionStats> {
+    let mut stats = CompanionStats::default();
+    for (source, destination) in find_companions(file_path, output_path, &cli.copy_companions) {
+        if same_contents(&source, &destination)? {
+            stats.identical += 1;
+            continue;
+        }
+        fs::copy(&source, &destination).await
+            .with_context(|| format!("Failed to copy companion {} to {}", source.display(), destination.display()))?;
+        if cli.verbose {
+            console.println(format!("📎 Companion: {}", destination.display()));
+        }
+        stats.copied += 1;
+    }
+
+    Ok(stats)
+}
+
+/// Running totals for an input that produces several outputs (`--tile`,
+/// `--all-mips`, cubemap faces).
+#[derive(Default)]
+struct OutputSet {
+    metadata_stripped: u64,
+    hashed_names: Vec<(PathBuf, PathBuf)>,
+    palette: PaletteStats,
+    optimized: OptimizeStats,
+    /// Quality of the last built-in jpg or webp encode, for its sidecar.
+    quality: Option<u8>,
+    /// Outputs written, and ones `--write-if-changed` left as they were.
+    written: usize,
+    kept: usize,
+    output_bytes: u64,
+}
+
+impl OutputSet {
+    /// Encodes the RGBA `png` to the output format inside the empty
+    /// `work_dir` and returns the encoded file.
+    #[allow(clippy::too_many_arguments)]
+    async fn encode_png(
+        &mut self,
+        file_path: &Path,
+        png: &Path,
+        planned: &Path,
+        work_dir: &Path,
+        texconv_path: &Path,
+        cli: &ConvertArgs,
+        console: &Console,
+    ) -> Result<PathBuf> {
+        let png = &watermark_intermediate(file_path, png, &work_dir.join("watermarked"), cli, console)?;
+        let png = &flatten_intermediate(file_path, png, &work_dir.join("flattened"), cli, console)?;
+        if cli.format.eq_ignore_ascii_case("png") {
+            return Ok(png.to_path_buf());
+        }
+        std::fs::create_dir_all(work_dir).context("Failed to create staging directory")?;
+        if cli.format == "png8" {
+            let staged = work_dir.join(format!("{}.png", texture_stem(planned)));
+            self.palette += png8_output(file_path, png, &staged, cli, console)?;
+            return Ok(staged);
+        }
+        if cli.format == "ico" {
+            let staged = work_dir.join(format!("{}.ico", texture_stem(planned)));
+            ico_output(file_path, png, &staged, cli, console)?;
+            return Ok(staged);
+        }
+        if lossy_built_in(cli) {
+            let staged = work_dir.join(format!("{}.{}", texture_stem(planned), cli.format));
+            self.quality = Some(lossy_output(file_path, png, &staged, cli, console)?);
+            return Ok(staged);
+        }
+        run_texconv(texconv_path, &cli.texconv(), png, &cli.format, work_dir, file_path, console).await?;
+        produced_file(work_dir, planned)
+    }
+
+    /// Applies `--alpha` to `converted`, optimizes it and rewrites its
+    /// metadata, names it (`--content-hash`) and puts it at `planned` or
+    /// into the archive. Returns where it went.
+    #[allow(clippy::too_many_arguments)]
+    async fn place(
+        &mut self,
+        file_path: &Path,
+        converted: &Path,
+        planned: &Path,
+        geometry: OutputGeometry,
+        zip: Option<&ZipOutput>,
+        cli: &ConvertArgs,
+        console: &Console,
+    ) -> Result<PathBuf> {
+        let geometry = OutputGeometry { quality: self.quality.take().or(geometry.quality), ..geometry };
+        if !built_in_format(&cli.format) && !lossy_built_in(cli) {
+            flatten_output_alpha(file_path, converted, cli, console)?;
+        }
+        self.optimized += optimize_output(file_path, converted, cli, console)?;
+        self.metadata_stripped += rewrite_metadata(file_path, converted, cli)?;
+        let final_path = final_output_path(planned, converted, cli)?;
+        self.output_bytes += file_size(converted);
+        match zip {
+            Some(zip) => {
+                verify(converted, cli, console)?;
+                zip.add_file(&final_path, converted).await?;
+            }
+            None => {
+                if place_output(converted, &final_path, cli, console)? {
+                    self.written += 1;
+                } else {
+                    self.kept += 1;
+                }
+                write_convert_sidecar(file_path, &final_path, geometry, cli, console)?;
+            }
+        }
+        if final_path != planned {
+            self.hashed_names.push((planned.to_path_buf(), final_path.clone()));
+        }
+        Ok(final_path)
+    }
+
+    /// The outcome, after copying companions once under the plain name.
+    async fn finish(
+        self,
+        file_path: &Path,
+        output_path: &Path,
+        geometry: OutputGeometry,
+        zip: Option<&ZipOutput>,
+        cli: &ConvertArgs,
+        console: &Console,
+    ) -> Result<FileOutcome> {
+        let companions = match zip {
+            Some(_) => CompanionStats::default(),
+            None => copy_companions(file_path, output_path, cli, console).await?,
+        };
+        Ok(FileOutcome::Converted {
+            companions,
+            metadata_stripped: self.metadata_stripped,
+            geometry,
+            hashed_names: self.hashed_names,
+            palette: self.palette,
+            optimized: self.optimized,
+            unchanged: self.written == 0 && self.kept > 0,
+            sizes: SizeStats::new(file_path, cli, self.output_bytes),
+        })
+    }
+}
+
+/// `--all-mips`: converts each stored mip level of every surface of `input`
+/// on its own and puts it at `output_path` with a `_mipN` suffix (`_posx_mipN`,
+/// `_slice1_mipN`, ... for cubemaps and arrays). Companions are copied once,
+/// under the plain name.
+#[allow(clippy::too_many_arguments)]
+async fn write_mip_outputs(
+    file_path: &Path,
+    input: &Path,
+    output_path: &Path,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let bytes = std::fs::read(input)
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let header = DdsHeader::parse(&bytes)?;
+    let stem = texture_stem(file_path);
+
+    let mut outputs = OutputSet::default();
+    for layer in 0..header.layer_count() {
+        for level in 0..header.mip_count {
+            let suffix = surface_mip_suffix(&header, layer, level);
+            let level_dir = work_dir.join(&suffix);
+            std::fs::create_dir_all(&level_dir).context("Failed to create mip directory")?;
+            let mip_dds = level_dir.join(format!("{}.dds", stem));
+            extract_surface(&bytes, &header, layer, level, &mip_dds)?;
+
+            let mip_output = with_suffix(output_path, &suffix);
+            let converted = if built_in_format(&cli.format) || lossy_built_in(cli) || cli.watermark.watermark.is_some() {
+                let png = decode_to_png(&mip_dds, texconv_path, &cli.texconv(), &level_dir, false, console).await?;
+                outputs.encode_png(file_path, &png, &mip_output, &level_dir.join("out"), texconv_path, cli, console).await?
+            } else {
+                let level_out = level_dir.join("out");
+                std::fs::create_dir_all(&level_out).context("Failed to create mip directory")?;
+                let pixel_format = output_pixel_format(file_path, Some(&header), cli, console);
+                run_texconv_as(texconv_path, &cli.texconv(), &mip_dds, &pixel_format, &cli.format, &level_out, file_path, None, console).await?;
+                produced_file(&level_out, &mip_output)?
+            };
+
+            let final_path = outputs.place(file_path, &converted, &mip_output, OutputGeometry::default(), zip, cli, console).await?;
+            if cli.verbose {
+                let (width, height) = header.mip_dimensions(level);
+                console.println(format!("🪜 Mip {} ({}x{}): {}", level, width, height, final_path.display()));
+            }
+        }
+    }
+
+    outputs.finish(file_path, output_path, OutputGeometry::default(), zip, cli, console).await
+}
+
+/// `--cubemap-layout`: decodes the six faces of the first cube in `input`
+/// and writes them as separate `_posx` ... `_negz` outputs, a horizontal
+/// cross or an equirectangular panorama.
+#[allow(clippy::too_many_arguments)]
+async fn write_cubemap_output(
+    file_path: &Path,
+    input: &Path,
+    output_path: &Path,
+    layout: CubemapLayout,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let bytes = std::fs::read(input)
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let header = DdsHeader::parse(&bytes)?;
+    let stem = texture_stem(file_path);
+
+    let mut pngs = Vec::new();
+    for (face, name) in FACE_NAMES.iter().enumerate() {
+        let face_dir = work_dir.join(name);
+        std::fs::create_dir_all(&face_dir).context("Failed to create cube face directory")?;
+        let face_dds = face_dir.join(format!("{}.dds", stem));
+        extract_surface(&bytes, &header, face as u32, 0, &face_dds)?;
+        pngs.push(decode_to_png(&face_dds, texconv_path, &cli.texconv(), &face_dir, false, console).await?);
+    }
+
+    let mut outputs = OutputSet::default();
+    if layout == CubemapLayout::Faces {
+        for (png, name) in pngs.iter().zip(FACE_NAMES) {
+            let face_output = with_suffix(output_path, name);
+            let converted = outputs.encode_png(file_path, png, &face_output, &work_dir.join(name).join("out"), texconv_path, cli, console).await?;
+            outputs.place(file_path, &converted, &face_output, OutputGeometry::default(), zip, cli, console).await?;
+        }
+    } else {
+        let mut faces = Vec::new();
+        for png in &pngs {
+            faces.push(image::open(png).with_context(|| format!("Failed to open {}", png.display()))?.to_rgba8());
+        }
+        let faces: [image::RgbaImage; 6] = faces.try_into().map_err(|_| anyhow::anyhow!("expected six cube faces"))?;
+        let canvas = match layout {
+            CubemapLayout::Equirect => equirect(&faces, cli.equirect_width.unwrap_or(faces[0].width() * 4)),
+            _ => cross(&faces)?,
+        };
+        let canvas_png = work_dir.join(format!("{}.png", stem));
+        canvas.save(&canvas_png)
+            .with_context(|| format!("Failed to write {}", canvas_png.display()))?;
+        let converted = outputs.encode_png(file_path, &canvas_png, output_path, &work_dir.join("cube-out"), texconv_path, cli, console).await?;
+        outputs.place(file_path, &converted, output_path, OutputGeometry::default(), zip, cli, console).await?;
+    }
+
+    if cli.verbose {
+        console.println(format!("🧊 {}: cubemap of {}x{} faces as {:?}", file_path.display(), header.width, header.height, layout));
+    }
+    outputs.finish(file_path, output_path, OutputGeometry::default(), zip, cli, console).await
+}
+
+/// `--pack`: decodes the maps `spec` names for the anchor `file_path`,
+/// packs their channels into one image and writes it to `output_path`.
+/// Maps that don't exist fill their channel with black (opaque for alpha).
+#[allow(clippy::too_many_arguments)]
+async fn write_packed_output(
+    file_path: &Path,
+    spec: &PackSpec,
+    output_path: &Path,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let mut sources = spec.source_files(file_path);
+    let mut decoded = HashMap::new();
+    for source in sources.iter_mut() {
+        let Some(path) = source.clone() else { continue };
+        if decoded.contains_key(&path) {
+            continue;
+        }
+        if !path.is_file() {
+            console.println(format!("⚠️  {}: {} not found; its channel is filled", file_path.display(), path.display()));
+            *source = None;
+            continue;
+        }
+        let map_dir = work_dir.join(format!("map-{}", decoded.len()));
+        std::fs::create_dir_all(&map_dir).context("Failed to create decode directory")?;
+        let png = decode_to_png(&path, texconv_path, &cli.texconv(), &map_dir, cli.verbose, console).await?;
+        let image = image::open(&png).with_context(|| format!("Failed to open {}", png.display()))?.to_rgba8();
+        decoded.insert(path, image);
+    }
+
+    let (packed, warnings) = pack_channels(spec, &sources, &decoded)?;
+    for warning in warnings {
+        console.println(format!("⚠️  {}", warning));
+    }
+    let packed_png = work_dir.join(format!("{}.png", texture_stem(output_path)));
+    packed.save(&packed_png)
+        .with_context(|| format!("Failed to write {}", packed_png.display()))?;
+
+    let mut outputs = OutputSet::default();
+    let converted = outputs.encode_png(file_path, &packed_png, output_path, &work_dir.join("out"), texconv_path, cli, console).await?;
+    outputs.place(file_path, &converted, output_path, OutputGeometry::default(), zip, cli, console).await?;
+    if cli.verbose {
+        console.println(format!("🧬 {}: packed {} map(s) into {}x{}", file_path.display(), decoded.len(), packed.width(), packed.height()));
+    }
+    outputs.finish(file_path, output_path, OutputGeometry::default(), zip, cli, console).await
+}
+
+/// `--composite`: decodes the base `file_path` and its overlay, tints each
+/// if the spec says so, and writes the overlay laid over the base to
+/// `output_path`.
+#[allow(clippy::too_many_arguments)]
+async fn write_composited_output(
+    file_path: &Path,
+    spec: &CompositeSpec,
+    output_path: &Path,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let overlay_path = spec.overlay_for(file_path).context("not a --composite base")?;
+    let mut layers = Vec::new();
+    for (index, (path, layer)) in [(file_path, &spec.base), (overlay_path.as_path(), &spec.overlay)].into_iter().enumerate() {
+        let layer_dir = work_dir.join(format!("layer-{}", index));
+        std::fs::create_dir_all(&layer_dir).context("Failed to create decode directory")?;
+        let png = decode_to_png(path, texconv_path, &cli.texconv(), &layer_dir, cli.verbose, console).await?;
+        let mut image = image::open(&png).with_context(|| format!("Failed to open {}", png.display()))?.to_rgba8();
+        if let Some(tint) = layer.tint {
+            tint.apply(&mut image);
+        }
+        layers.push(image);
+    }
+
+    let (composited, warning) = composite_layers(&layers[0], &layers[1])
+        .with_context(|| format!("Failed to composite {} over {}", overlay_path.display(), file_path.display()))?;
+    if let Some(warning) = warning {
+        console.println(format!("⚠️  {}: {}", overlay_path.display(), warning));
+    }
+    let composited_png = work_dir.join(format!("{}.png", texture_stem(output_path)));
+    composited.save(&composited_png)
+        .with_context(|| format!("Failed to write {}", composited_png.display()))?;
+
+    let mut outputs = OutputSet::default();
+    let converted = outputs.encode_png(file_path, &composited_png, output_path, &work_dir.join("out"), texconv_path, cli, console).await?;
+    outputs.place(file_path, &converted, output_path, OutputGeometry::default(), zip, cli, console).await?;
+    if cli.verbose {
+        console.println(format!("🥞 {}: {} laid over it", file_path.display(), overlay_path.display()));
+    }
+    outputs.finish(file_path, output_path, OutputGeometry::default(), zip, cli, console).await
+}
+
+/// `--unpack`: decodes `file_path` once and writes each mapped channel as a
+/// grayscale image at `output_path` with the channel's suffix. Channels that
+/// are all 0 or all 255 are skipped unless `--keep-constant-channels`.
+#[allow(clippy::too_many_arguments)]
+async fn write_unpacked_outputs(
+    file_path: &Path,
+    spec: &UnpackSpec,
+    output_path: &Path,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let png = decode_to_png(file_path, texconv_path, &cli.texconv(), work_dir, cli.verbose, console).await?;
+    let image = image::open(&png).with_context(|| format!("Failed to open {}", png.display()))?.to_rgba8();
+
+    let mut outputs = OutputSet::default();
+    for (index, suffix) in &spec.channels {
+        let channel = extract_channel(&image, *index);
+        let channel_output = with_suffix(output_path, suffix);
+        if let Some(value) = constant_value(&channel).filter(|_| !cli.keep_constant_channels) {
+            console.println(format!("⏭️  {}: {} is all {}; {} not written", file_path.display(), channel_name(*index), value, channel_output.display()));
+            continue;
+        }
+        let channel_dir = work_dir.join(format!("channel-{}", channel_name(*index)));
+        std::fs::create_dir_all(&channel_dir).context("Failed to create channel directory")?;
+        let channel_png = channel_dir.join(format!("{}.png", texture_stem(&channel_output)));
+        channel.save(&channel_png)
+            .with_context(|| format!("Failed to write {}", channel_png.display()))?;
+        let converted = outputs.encode_png(file_path, &channel_png, &channel_output, &channel_dir.join("out"), texconv_path, cli, console).await?;
+        let placed = outputs.place(file_path, &converted, &channel_output, OutputGeometry::default(), zip, cli, console).await?;
+        if cli.verbose {
+            console.println(format!("🧩 {}: {} -> {}", file_path.display(), channel_name(*index), placed.display()));
+        }
+    }
+    outputs.finish(file_path, output_path, OutputGeometry::default(), zip, cli, console).await
+}
+
+/// `--tint-map`: writes one tinted copy of the decoded `png` per matching
+/// entry, at `output_path` with the entry's suffix. Companions are copied
+/// once, under the plain name.
+#[allow(clippy::too_many_arguments)]
+async fn write_tint_variants(
+    file_path: &Path,
+    png: &Path,
+    output_path: &Path,
+    variants: &[&TintVariant],
+    geometry: OutputGeometry,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let mut outputs = OutputSet::default();
+    for variant in variants {
+        let variant_output = with_suffix(output_path, &variant.suffix);
+        let variant_dir = work_dir.join(format!("tint-{}", variant.suffix));
+        std::fs::create_dir_all(&variant_dir).context("Failed to create tint directory")?;
+        let tinted = variant_dir.join(format!("{}.png", texture_stem(&variant_output)));
+        tint_png(png, &tinted, variant.color)?;
+        let converted = outputs.encode_png(file_path, &tinted, &variant_output, &variant_dir.join("out"), texconv_path, cli, console).await?;
+        let placed = outputs.place(file_path, &converted, &variant_output, OutputGeometry { tint: Some(variant.color), ..geometry }, zip, cli, console).await?;
+        if cli.verbose {
+            console.println(format!("🎨 {}: {} {} -> {}", file_path.display(), variant.suffix, variant.color, placed.display()));
+        }
+    }
+    outputs.finish(file_path, output_path, geometry, zip, cli, console).await
+}
+
+/// `--tile`: slices the decoded `png` into tiles, converts each to the
+/// output format and puts it at `output_path` with a `_xNN_yNN` suffix.
+/// Companions are copied once, under the untiled name.
+#[allow(clippy::too_many_arguments)]
+async fn write_tiled_output(
+    file_path: &Path,
+    png: &Path,
+    output_path: &Path,
+    tile_size: u32,
+    geometry: OutputGeometry,
+    work_dir: &Path,
+    texconv_path: &Path,
+    zip: Option<&ZipOutput>,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    let (width, height) = png_dimensions(png)?;
+    let grid = TileGrid::new(width, height, tile_size);
+    let tiles = write_tiles(png, &grid, cli.tile_pad, &work_dir.join("tiles"), &texture_stem(file_path))?;
+
+    let mut outputs = OutputSet::default();
+    let mut entries = Vec::new();
+    for (index, (tile, tile_png)) in tiles.iter().enumerate() {
+        let tile_output = with_suffix(output_path, &grid.suffix(tile));
+        let tile_out = work_dir.join(format!("tile-out-{}", index));
+        let converted = outputs.encode_png(file_path, tile_png, &tile_output, &tile_out, texconv_path, cli, console).await?;
+        let final_path = outputs.place(file_path, &converted, &tile_output, geometry, zip, cli, console).await?;
+        entries.push(TileEntry {
+            file: final_path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            tile: *tile,
+        });
+    }
+
+    if cli.tile_manifest {
+        let manifest = TileManifest {
+            source: file_path.strip_prefix(&cli.input).unwrap_or(file_path).to_path_buf(),
+            width,
+            height,
+            tile_size,
+            padded: cli.tile_pad,
+            columns: grid.columns,
+            rows: grid.rows,
+            tiles: entries,
+        };
+        let json = serde_json::to_string_pretty(&manifest)?;
+        let manifest_output = manifest_path(output_path);
+        match zip {
+            Some(zip) => {
+                let staged = work_dir.join("tiles.json");
+                std::fs::write(&staged, json).context("Failed to write the tile manifest")?;
+                zip.add_file(&manifest_output, &staged).await?;
+            }
+            None => std::fs::write(&manifest_output, json)
+                .with_context(|| format!("Failed to write {}", manifest_output.display()))?,
+        }
+    }
+
+    if cli.verbose {
+        console.println(format!("🧱 {}: {} tile(s) of {}px ({}x{})", file_path.display(), grid.count(), tile_size, grid.columns, grid.rows));
+    }
+
+    outputs.finish(file_path, output_path, geometry, zip, cli, console).await
+}
+
+/// Gives a `--dedupe-link` duplicate the output of the identical file it was
+/// matched with. `original_outcome` is `None` when that file failed.
+pub async fn process_duplicate(
+    file_path: &Path,
+    output_path: &Path,
+    original: &ResolvedOutput,
+    original_outcome: Option<&FileOutcome>,
+    mode: LinkMode,
+    cli: &ConvertArgs,
+    console: &Console,
+) -> Result<FileOutcome> {
+    match original_outcome {
+        None => {
+            return Err(Classified::error(
+                ErrorCode::DuplicateOfFailed,
+                format!("identical to {}, which failed to convert", original.input.display()),
+            ));
+        }
+        Some(FileOutcome::Skipped(reason)) => return Ok(FileOutcome::Skipped(*reason)),
+        Some(_) => {}
+    }
+    let geometry = match original_outcome {
+        Some(FileOutcome::Converted { geometry, .. }) => *geometry,
+        _ => OutputGeometry::default(),
+    };
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).await
+            .context("Failed to create output directory")?;
+    }
+
+    let method = materialize(&original.output, output_path, mode, console)?;
+    // `--verify` reads through the link, so a dangling symlink or short copy
+    // fails here and is removed like any other bad output
+    if let Err(e) = verify(output_path, cli, console) {
+        let _ = std::fs::remove_file(output_path);
+        return Err(e);
+    }
+    let companions = finish_output(file_path, output_path, geometry, cli, console).await?;
+
+    if cli.verbose {
+        console.println(format!("🔗 {} ({}): {}", method.name(), original.output.display(), output_path.display()));
+    }
+
+    Ok(FileOutcome::Linked { method, companions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::classify;
+    use crate::utils::UnicodeForm;
+
+    const NAMES: NameCleanup = NameCleanup { sanitize: None, unicode: UnicodeForm::None, lowercase: false };
+
+    fn output_name(input: &str) -> PathBuf {
+        calculate_output_path(&Path::new("in").join(input), Path::new("in"), Path::new("out"), 0, "png", NAMES, None, ("", ""))
+    }
+
+    #[test]
+    fn output_path_replaces_only_the_final_dds() {
+        assert_eq!(output_name("foo.dds"), Path::new("out/foo.png"));
+        assert_eq!(output_name("foo.1.dds"), Path::new("out/foo.1.png"));
+        assert_eq!(output_name("foo.DDS"), Path::new("out/foo.png"));
+        assert_eq!(output_name("foo.dds.dds"), Path::new("out/foo.dds.png"));
+        assert_eq!(output_name("ui/loading.screen.dds"), Path::new("out/ui/loading.screen.png"));
+    }
+
+    #[test]
+    fn tricky_names_keep_every_dot_but_the_extension() {
+        // (input, output of calculate_output_path, texture_stem for animations and sequences)
+        let table = [
+            ("ui.hud.compass.dds", "ui.hud.compass.png", "ui.hud.compass"),
+            ("ui.hud.compass.dds.bak.dds", "ui.hud.compass.dds.bak.png", "ui.hud.compass.dds.bak"),
+            ("ui.hud.compass.DDS", "ui.hud.compass.png", "ui.hud.compass"),
+            ("ui.hud.compass.dds.bak", "ui.hud.compass.dds.png", "ui.hud.compass.dds"),
+            ("fire.v2.0001.dds", "fire.v2.0001.png", "fire.v2.0001"),
+            // Sniffed files without an extension keep their whole name
+            ("compass", "compass.png", "compass"),
+            // Leading dots are part of the name, not an extension
+            (".hidden.dds", ".hidden.png", ".hidden"),
+            (".dds", ".dds.png", ".dds"),
+            ("trailing.", "trailing.png", "trailing"),
+        ];
+        for (input, output, stem) in table {
+            assert_eq!(output_name(input), Path::new("out").join(output), "{}", input);
+            assert_eq!(texture_stem(&Path::new("in").join(input)), stem, "{}", input);
+        }
+    }
+
+    #[test]
+    fn reconcile_takes_whatever_texconv_named_its_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging = dir.path().join("staging");
+        std::fs::create_dir(&staging).unwrap();
+        // Some texconv versions cut the stem at the first dot
+        std::fs::write(staging.join("ui.png"), b"png").unwrap();
+        let output = dir.path().join("ui.hud.compass.png");
+        reconcile_output(&staging, &output).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"png");
+        assert!(!staging.join("ui.png").exists());
+
+        let error = reconcile_output(&staging, &output).unwrap_err();
+        assert_eq!(classify(&error), ErrorCode::OutputMissing);
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::collision::{CollisionPolicy, ExistingAction};
+use crate::console::Console;
+
+/// "Always" answers given so far.
+#[derive(Default)]
+struct Remembered {
+    existing: Option<ExistingAction>,
+    collision: Option<CollisionPolicy>,
+}
+
+/// `--interactive`: asks on the terminal how to handle outputs that already
+/// exist and output collisions, one question at a time however many workers
+/// are asking. Unanswered questions fall back to the non-interactive policy
+/// after `timeout`.
+pub struct Prompter {
+    timeout: Duration,
+    /// Lines read from stdin by a reader thread started on the first
+    /// question; a single reader keeps a late answer from being lost.
+    answers: Mutex<Option<Receiver<String>>>,
+    /// Held while a question is on screen.
+    remembered: Mutex<Remembered>,
+    aborted: AtomicBool,
+}
+
+impl Prompter {
+    pub fn new(timeout: Duration) -> Self {
+        Prompter {
+            timeout,
+            answers: Mutex::new(None),
+            remembered: Mutex::new(Remembered::default()),
+            aborted: AtomicBool::new(false),
+        }
+    }
+
+    /// Set once the user chose to abort.
+    pub fn aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
+
+    /// What to do with `output`, which already exists: `fallback` (the
+    /// `--on-conflict` policy) when unanswered. Errors when the user aborts.
+    pub fn existing_output(&self, output: &Path, fallback: ExistingAction, console: &Console) -> Result<ExistingAction> {
+        let mut remembered = self.remembered.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.aborted() {
+            anyhow::bail!("Aborted at the prompt");
+        }
+        if let Some(action) = remembered.existing {
+            return Ok(action);
+        }
+
+        let question = format!(
+            "❓ {} already exists. [o]verwrite, [s]kip, [r]ename, overwrite [a]ll, skip a[l]l or [q]uit (--on-conflict {} in {}s):",
+            output.display(),
+            value_name(fallback),
+            self.timeout.as_secs()
+        );
+        loop {
+            let Some(answer) = self.ask(&question, console) else {
+                console.println(format!("⏱️  No answer; using --on-conflict {}", value_name(fallback)));
+                return Ok(fallback);
+            };
+            let action = match answer.as_str() {
+                "o" | "overwrite" => ExistingAction::Overwrite,
+                "s" | "skip" => ExistingAction::Skip,
+                "r" | "rename" => ExistingAction::Rename,
+                "a" | "always-overwrite" => *remembered.existing.insert(ExistingAction::Overwrite),
+                "l" | "always-skip" => *remembered.existing.insert(ExistingAction::Skip),
+                "q" | "quit" | "abort" => {
+                    self.aborted.store(true, Ordering::SeqCst);
+                    anyhow::bail!("Aborted at the prompt");
+                }
+                _ => continue,
+            };
+            return Ok(action);
+        }
+    }
+
+    /// How to resolve `inputs` all mapping to `output`: `Skip` or
+    /// `SuffixCounter`, or `fallback` (the `--on-collision` policy) when
+    /// unanswered. Errors when the user aborts.
+    pub fn collision(&self, output: &Path, inputs: &[&PathBuf], fallback: CollisionPolicy, console: &Console) -> Result<CollisionPolicy> {
+        let mut remembered = self.remembered.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(policy) = remembered.collision {
+            return Ok(policy);
+        }
+
+        let mut question = format!("❓ {} inputs would write {}:", inputs.len(), output.display());
+        for input in inputs {
+            question.push_str(&format!("\n    <- {}", input.display()));
+        }
+        question.push_str(&format!(
+            "\n   [s]kip all but the first, [r]ename with _2, _3..., skip [a]ll such, rename a[l]l such or [q]uit (--on-collision {} in {}s):",
+            value_name(fallback),
+            self.timeout.as_secs()
+        ));
+        loop {
+            let Some(answer) = self.ask(&question, console) else {
+                console.println(format!("⏱️  No answer; using --on-collision {}", value_name(fallback)));
+                return Ok(fallback);
+            };
+            let policy = match answer.as_str() {
+                "s" | "skip" => CollisionPolicy::Skip,
+                "r" | "rename" => CollisionPolicy::SuffixCounter,
+                "a" | "always-skip" => *remembered.collision.insert(CollisionPolicy::Skip),
+                "l" | "always-rename" => *remembered.collision.insert(CollisionPolicy::SuffixCounter),
+                "q" | "quit" | "abort" => {
+                    self.aborted.store(true, Ordering::SeqCst);
+                    anyhow::bail!("Aborted at the prompt");
+                }
+                _ => continue,
+            };
+            return Ok(policy);
+        }
+    }
+
+    /// Waits up to `limit` for a line on stdin, through the same reader as
+    /// the questions, so a paused run can be resumed with Enter without the
+    /// two fighting over keypresses. `Disconnected` once stdin is closed.
+    pub fn wait_for_enter(&self, limit: Duration) -> Result<(), RecvTimeoutError> {
+        let mut answers = self.answers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let answers = answers.get_or_insert_with(read_lines);
+        while answers.try_recv().is_ok() {}
+        answers.recv_timeout(limit).map(|_| ())
+    }
+
+    /// Shows `question` with the progress bar out of the way and returns
+    /// the trimmed, lowercased answer, or `None` on timeout or end of input.
+    fn ask(&self, question: &str, console: &Console) -> Option<String> {
+        let mut answers = self.answers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let answers = answers.get_or_insert_with(read_lines);
+        // Whatever was typed while nobody was asking isn't an answer to this
+        while answers.try_recv().is_ok() {}
+
+        console.suspend(|| {
+            print!("{} ", question);
+            let _ = std::io::stdout().flush();
+            let answer = answers.recv_timeout(self.timeout).ok();
+            if answer.is_none() {
+                println!();
+            }
+            answer.map(|line| line.trim().to_lowercase())
+        })
+    }
+}
+
+fn value_name(value: impl ValueEnum) -> String {
+    value.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+}
+
+/// Reads stdin line by line on its own thread until end of input.
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A prompter reading from a channel instead of stdin.
+    fn prompter() -> (Prompter, mpsc::Sender<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let prompter = Prompter::new(Duration::from_secs(1));
+        *prompter.answers.lock().unwrap() = Some(receiver);
+        (prompter, sender)
+    }
+
+    #[test]
+    fn enter_during_a_pause_comes_from_the_shared_reader() {
+        let (prompter, sender) = prompter();
+        sender.send("typed earlier".to_string()).unwrap();
+        assert_eq!(prompter.wait_for_enter(Duration::from_millis(20)), Err(RecvTimeoutError::Timeout));
+
+        let typing = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            sender.send(String::new()).unwrap();
+            sender
+        });
+        assert_eq!(prompter.wait_for_enter(Duration::from_secs(5)), Ok(()));
+        drop(typing.join().unwrap());
+        assert_eq!(prompter.wait_for_enter(Duration::from_secs(5)), Err(RecvTimeoutError::Disconnected));
+    }
+}
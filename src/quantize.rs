@@ -0,0 +1,352 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 16-bit RGBA, as decoded from high-bit-depth textures for `--dither`.
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+/// How `--dither` hides banding when reducing to 8 bits per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    /// Round to the nearest value
+    None,
+    /// 4x4 Bayer pattern
+    Ordered,
+    /// Error diffusion
+    FloydSteinberg,
+}
+
+/// 4x4 Bayer matrix: the order in which the cells of each 4x4 block switch on.
+pub const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Reduces `image` to 8 bits per channel. Colour channels are dithered;
+/// alpha is always rounded so hard edges stay hard. Pixels are visited
+/// row by row, left to right, so the result only depends on the input.
+pub fn reduce_to_8bit(image: &Rgba16Image, dither: Dither) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scale = |value: u16| value as f32 * 255.0 / 65535.0;
+    let store = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    let mut output = RgbaImage::new(width, height);
+
+    match dither {
+        Dither::None | Dither::Ordered => {
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let offset = match dither {
+                    Dither::Ordered => (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0 - 0.5,
+                    _ => 0.0,
+                };
+                let [r, g, b, a] = pixel.0;
+                output.put_pixel(x, y, Rgba([
+                    store(scale(r) + offset),
+                    store(scale(g) + offset),
+                    store(scale(b) + offset),
+                    store(scale(a)),
+                ]));
+            }
+        }
+        Dither::FloydSteinberg => {
+            // Error carried into the current and next row, per colour channel
+            let row_len = width as usize + 2;
+            let mut current = vec![[0f32; 3]; row_len];
+            let mut next = vec![[0f32; 3]; row_len];
+            for y in 0..height {
+                for x in 0..width {
+                    let pixel = image.get_pixel(x, y).0;
+                    let cell = x as usize + 1;
+                    let mut quantized = [0u8; 4];
+                    for channel in 0..3 {
+                        let wanted = scale(pixel[channel]) + current[cell][channel];
+                        quantized[channel] = store(wanted);
+                        let error = wanted - quantized[channel] as f32;
+                        current[cell + 1][channel] += error * 7.0 / 16.0;
+                        next[cell - 1][channel] += error * 3.0 / 16.0;
+                        next[cell][channel] += error * 5.0 / 16.0;
+                        next[cell + 1][channel] += error / 16.0;
+                    }
+                    quantized[3] = store(scale(pixel[3]));
+                    output.put_pixel(x, y, Rgba(quantized));
+                }
+                std::mem::swap(&mut current, &mut next);
+                next.iter_mut().for_each(|error| *error = [0.0; 3]);
+            }
+        }
+    }
+    output
+}
+
+/// Writes the (16-bit) PNG at `input` to `output` as 8-bit RGBA.
+pub fn quantize_png(input: &Path, output: &Path, dither: Dither) -> Result<()> {
+    let image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba16();
+    reduce_to_8bit(&image, dither)
+        .save(output)
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+/// An image reduced to a palette of at most 256 RGBA colours.
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+    /// Whether every pixel kept its exact colour.
+    pub exact: bool,
+    /// Root-mean-square difference to the source, per channel on a 0-255 scale.
+    pub rmse: f64,
+}
+
+/// Reduces `image` to at most 256 colours by median cut. Fully transparent
+/// pixels share one palette entry (index 0); partial alpha is quantized like
+/// the colour channels. Images with few enough colours keep them exactly.
+pub fn quantize_palette(image: &RgbaImage, dither: Dither) -> IndexedImage {
+    let transparent = |pixel: [u8; 4]| if pixel[3] == 0 { [0, 0, 0, 0] } else { pixel };
+
+    let mut histogram: std::collections::BTreeMap<[u8; 4], u64> = std::collections::BTreeMap::new();
+    for pixel in image.pixels() {
+        *histogram.entry(transparent(pixel.0)).or_default() += 1;
+    }
+    let has_transparent = histogram.remove(&[0, 0, 0, 0]).is_some();
+    let budget = 256 - has_transparent as usize;
+    let colors: Vec<([u8; 4], u64)> = histogram.into_iter().collect();
+    let exact = colors.len() <= budget;
+
+    let mut palette = Vec::with_capacity(256);
+    if has_transparent {
+        palette.push([0, 0, 0, 0]);
+    }
+    if exact {
+        palette.extend(colors.iter().map(|(color, _)| *color));
+    } else {
+        palette.extend(median_cut(colors, budget));
+    }
+
+    let (width, height) = image.dimensions();
+    // In u64: width * height overflows u32 for the largest textures
+    let mut indices = vec![0u8; (width as u64 * height as u64) as usize];
+    let mut nearest_cache = std::collections::HashMap::new();
+    let mut nearest = |color: [u8; 4]| {
+        *nearest_cache.entry(color).or_insert_with(|| nearest_entry(&palette, color))
+    };
+    let mut squared_error = 0f64;
+
+    // Floyd-Steinberg error for the current and next row, per colour channel
+    let row_len = width as usize + 2;
+    let mut current = vec![[0f32; 3]; row_len];
+    let mut next = vec![[0f32; 3]; row_len];
+    for y in 0..height {
+        for x in 0..width {
+            let source = transparent(image.get_pixel(x, y).0);
+            let cell = x as usize + 1;
+            let mut wanted = [source[0] as f32, source[1] as f32, source[2] as f32];
+            match dither {
+                _ if exact || source[3] == 0 => {}
+                Dither::None => {}
+                Dither::Ordered => {
+                    let offset = (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) - 8.0;
+                    wanted.iter_mut().for_each(|channel| *channel += offset);
+                }
+                Dither::FloydSteinberg => {
+                    wanted.iter_mut().zip(current[cell]).for_each(|(channel, error)| *channel += error);
+                }
+            }
+            let store = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+            let adjusted = [store(wanted[0]), store(wanted[1]), store(wanted[2]), source[3]];
+            let index = nearest(adjusted);
+            let chosen = palette[index as usize];
+            indices[(y as u64 * width as u64 + x as u64) as usize] = index;
+
+            for channel in 0..4 {
+                squared_error += (source[channel] as f64 - chosen[channel] as f64).powi(2);
+            }
+            if dither == Dither::FloydSteinberg && !exact && source[3] != 0 {
+                for channel in 0..3 {
+                    let error = wanted[channel] - chosen[channel] as f32;
+                    current[cell + 1][channel] += error * 7.0 / 16.0;
+                    next[cell - 1][channel] += error * 3.0 / 16.0;
+                    next[cell][channel] += error * 5.0 / 16.0;
+                    next[cell + 1][channel] += error / 16.0;
+                }
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|error| *error = [0.0; 3]);
+    }
+
+    let samples = (width as f64 * height as f64 * 4.0).max(1.0);
+    IndexedImage { width, height, palette, indices, exact, rmse: (squared_error / samples).sqrt() }
+}
+
+/// Splits the weighted `colors` into at most `count` boxes, always cutting
+/// the box with the widest channel at its weighted median, and returns each
+/// box's weighted mean.
+fn median_cut(colors: Vec<([u8; 4], u64)>, count: usize) -> Vec<[u8; 4]> {
+    let widest = |colors: &[([u8; 4], u64)]| {
+        (0..4)
+            .map(|channel| {
+                let (low, high) = colors.iter().fold((255u8, 0u8), |(low, high), (color, _)| {
+                    (low.min(color[channel]), high.max(color[channel]))
+                });
+                (high.saturating_sub(low), channel)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![colors];
+    while boxes.len() < count {
+        let Some((position, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(position, colors)| {
+                let (range, channel) = widest(colors);
+                (range, std::cmp::Reverse(position), channel)
+            })
+            .max()
+            .map(|(_, std::cmp::Reverse(position), channel)| (position, channel))
+        else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(position);
+        colors.sort_by_key(|(color, _)| (color[channel], *color));
+        let total: u64 = colors.iter().map(|(_, weight)| weight).sum();
+        let mut running = 0;
+        let mut split = colors.len() - 1;
+        for (index, (_, weight)) in colors.iter().enumerate() {
+            running += weight;
+            if running * 2 >= total {
+                split = (index + 1).min(colors.len() - 1);
+                break;
+            }
+        }
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|colors| {
+            let total: u64 = colors.iter().map(|(_, weight)| weight).sum();
+            let mut mean = [0u8; 4];
+            for (channel, value) in mean.iter_mut().enumerate() {
+                let sum: u64 = colors.iter().map(|(color, weight)| color[channel] as u64 * weight).sum();
+                *value = ((sum + total / 2) / total) as u8;
+            }
+            mean
+        })
+        .collect()
+}
+
+fn nearest_entry(palette: &[[u8; 4]], color: [u8; 4]) -> u8 {
+    let distance = |entry: &[u8; 4]| -> u32 {
+        (0..4).map(|channel| (entry[channel] as i32 - color[channel] as i32).pow(2) as u32).sum()
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map(|(index, _)| index as u8)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width`x`height` image where pixel (x, y) has colour `color(x, y)`.
+    fn image(width: u32, height: u32, color: impl Fn(u32, u32) -> [u8; 4]) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba(color(x, y)))
+    }
+
+    fn lookup(indexed: &IndexedImage, x: u32, y: u32) -> [u8; 4] {
+        indexed.palette[indexed.indices[(y * indexed.width + x) as usize] as usize]
+    }
+
+    #[test]
+    fn few_colours_are_kept_exactly_in_row_order() {
+        // Wider than tall, so a row/column mix-up shows
+        let source = image(3, 2, |x, y| [x as u8 * 100, y as u8 * 200, 7, 255]);
+        let indexed = quantize_palette(&source, Dither::None);
+        assert!(indexed.exact);
+        assert_eq!(indexed.rmse, 0.0);
+        assert_eq!((indexed.width, indexed.height, indexed.indices.len()), (3, 2, 6));
+        for (x, y, pixel) in source.enumerate_pixels() {
+            assert_eq!(lookup(&indexed, x, y), pixel.0);
+        }
+    }
+
+    #[test]
+    fn transparent_pixels_share_entry_zero() {
+        let source = image(2, 2, |x, y| if x == y { [x as u8 * 90, 1, 2, 0] } else { [10, 20, 30, 128] });
+        let indexed = quantize_palette(&source, Dither::None);
+        assert_eq!(indexed.palette, [[0, 0, 0, 0], [10, 20, 30, 128]]);
+        assert_eq!(indexed.indices, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn more_than_256_colours_are_cut_down() {
+        let source = image(64, 64, |x, y| [(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8, 255]);
+        for dither in [Dither::None, Dither::Ordered, Dither::FloydSteinberg] {
+            let indexed = quantize_palette(&source, dither);
+            assert!(!indexed.exact);
+            assert_eq!(indexed.palette.len(), 256, "{:?}", dither);
+            assert!(indexed.rmse > 0.0 && indexed.rmse < 8.0, "{:?}: {}", dither, indexed.rmse);
+            // Same input, same output
+            assert_eq!(quantize_palette(&source, dither).indices, indexed.indices);
+        }
+    }
+
+    #[test]
+    fn empty_images_quantize_to_nothing() {
+        let indexed = quantize_palette(&RgbaImage::new(0, 0), Dither::FloydSteinberg);
+        assert!(indexed.indices.is_empty() && indexed.palette.is_empty());
+        assert_eq!(indexed.rmse, 0.0);
+    }
+
+    /// 256x16 16-bit grey rising from 100 to 102 (in 8-bit steps) left to
+    /// right: a smooth gradient that rounding turns into three bands.
+    fn shallow_gradient() -> Rgba16Image {
+        Rgba16Image::from_fn(256, 16, |x, _| {
+            let value = ((100.0 + 2.0 * x as f32 / 256.0) * 257.0).round() as u16;
+            Rgba([value, value, value, 65535])
+        })
+    }
+
+    /// Distinct means of the red channel over 4-column strips: how many
+    /// levels the eye sees across the gradient.
+    fn visible_levels(image: &RgbaImage) -> usize {
+        let levels: std::collections::BTreeSet<u32> = (0..image.width() / 4)
+            .map(|strip| image.enumerate_pixels().filter(|(x, _, _)| x / 4 == strip).map(|(_, _, pixel)| pixel[0] as u32).sum::<u32>())
+            .collect();
+        levels.len()
+    }
+
+    #[test]
+    fn dithering_breaks_up_banding_in_a_smooth_gradient() {
+        let source = shallow_gradient();
+        let rounded = reduce_to_8bit(&source, Dither::None);
+        assert_eq!(visible_levels(&rounded), 3);
+        for dither in [Dither::Ordered, Dither::FloydSteinberg] {
+            let dithered = reduce_to_8bit(&source, dither);
+            // Ordered reaches all 33 levels two 8-bit steps allow a 4x4 pattern
+            assert!(visible_levels(&dithered) >= 30, "{:?}: {}", dither, visible_levels(&dithered));
+            // The average stays true to the source
+            let mean = |image: &RgbaImage| image.pixels().map(|pixel| pixel[0] as f64).sum::<f64>() / (256.0 * 16.0);
+            assert!((mean(&dithered) - 101.0).abs() < 0.05, "{:?}: {}", dither, mean(&dithered));
+            // Alpha is rounded, not dithered
+            assert!(dithered.pixels().all(|pixel| pixel[3] == 255));
+            assert_eq!(reduce_to_8bit(&source, dither), dithered);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::exit_status::RunStatus;
+use crate::summary::{RunSummary, SizeTotals};
+
+#[derive(Serialize)]
+struct ConvertedFile {
+    input: String,
+    /// Converted with `--gpu cpu` after texconv failed on the GPU.
+    gpu_fallback: bool,
+}
+
+#[derive(Serialize)]
+struct LinkedFile {
+    input: String,
+    /// hardlink, symlink or copy: what was actually made, after any fallback.
+    method: &'static str,
+}
+
+#[derive(Serialize)]
+struct FailedFile {
+    input: String,
+    error_code: &'static str,
+    error: String,
+    gpu_fallback: bool,
+}
+
+#[derive(Serialize)]
+struct FormatSizes {
+    #[serde(flatten)]
+    totals: SizeTotals,
+    ratio: f64,
+}
+
+/// `--report`: the end-of-run summary as JSON.
+#[derive(Serialize)]
+pub struct RunReport {
+    /// False when a fatal error stopped the run; `error` says which.
+    complete: bool,
+    status: &'static str,
+    error: Option<String>,
+    duration_secs: f64,
+    found: usize,
+    not_included: usize,
+    excluded: usize,
+    converted: usize,
+    unchanged: usize,
+    linked: usize,
+    /// Skipped files per reason.
+    skipped: BTreeMap<&'static str, usize>,
+    files: Vec<ConvertedFile>,
+    linked_files: Vec<LinkedFile>,
+    failed: Vec<FailedFile>,
+    /// Files that succeeded after `--retries`.
+    retried: usize,
+    /// Sizes of converted files only, in total and per output format.
+    input_bytes: u64,
+    output_bytes: u64,
+    sizes: BTreeMap<String, FormatSizes>,
+    /// `--delete-source` deletions.
+    deleted_sources: usize,
+    reclaimed_bytes: u64,
+    selection_note: Option<String>,
+}
+
+impl RunReport {
+    pub fn new(summary: &RunSummary, result: &Result<RunStatus>, duration: Duration) -> Self {
+        RunReport {
+            complete: result.is_ok(),
+            status: result.as_ref().map_or("error", |status| status.as_str()),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            duration_secs: duration.as_secs_f64(),
+            found: summary.found,
+            not_included: summary.not_included,
+            excluded: summary.excluded,
+            converted: summary.converted,
+            unchanged: summary.unchanged,
+            linked: summary.linked.values().sum(),
+            skipped: summary.skipped.iter().map(|(reason, files)| (reason.as_str(), files.len())).collect(),
+            files: summary
+                .converted_files
+                .iter()
+                .map(|file| ConvertedFile { input: file.display().to_string(), gpu_fallback: summary.gpu_fallbacks.contains(file) })
+                .collect(),
+            linked_files: summary
+                .linked_files
+                .iter()
+                .map(|(file, method)| LinkedFile { input: file.display().to_string(), method: method.name() })
+                .collect(),
+            failed: summary
+                .failed
+                .iter()
+                .map(|(file, code, error)| FailedFile {
+                    input: file.display().to_string(),
+                    error_code: code.as_str(),
+                    error: error.clone(),
+                    gpu_fallback: summary.gpu_fallbacks.contains(file),
+                })
+                .collect(),
+            retried: summary.retried.len(),
+            input_bytes: summary.total_sizes().input_bytes,
+            output_bytes: summary.total_sizes().output_bytes,
+            sizes: summary
+                .sizes
+                .iter()
+                .map(|(format, totals)| (format.clone(), FormatSizes { totals: *totals, ratio: totals.ratio() }))
+                .collect(),
+            deleted_sources: summary.deleted_sources,
+            reclaimed_bytes: summary.reclaimed_bytes,
+            selection_note: summary.selection_note.clone(),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write report {}", path.display()))
+    }
+}
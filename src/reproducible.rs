@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::metadata::{extension, filter_jpeg_segments, filter_png_chunks, filter_webp_chunks};
+
+/// `--reproducible`: strips metadata that differs between runs of the same
+/// conversion (timestamps, software tags, EXIF/XMP) from a PNG, JPEG or WebP
+/// file in place. Other formats are left alone.
+pub fn normalize_output(path: &Path) -> Result<()> {
+    const PNG_VOLATILE: &[&[u8; 4]] = &[b"tIME", b"tEXt", b"zTXt", b"iTXt", b"eXIf"];
+    const JPEG_APP1: u8 = 0xE1;
+    const JPEG_APP13: u8 = 0xED;
+    const JPEG_COM: u8 = 0xFE;
+
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read {} for normalization", path.display()))?;
+
+    // Files that don't parse are left for the consumer to complain about
+    let normalized = match extension(path).as_str() {
+        "png" => filter_png_chunks(&bytes, |kind| !PNG_VOLATILE.contains(&kind)),
+        "jpg" | "jpeg" => filter_jpeg_segments(&bytes, |marker, _| !matches!(marker, JPEG_APP1 | JPEG_APP13 | JPEG_COM)),
+        "webp" => filter_webp_chunks(&bytes, |kind| !matches!(kind, b"EXIF" | b"XMP ")),
+        _ => None,
+    };
+
+    if let Some(normalized) = normalized.filter(|normalized| *normalized != bytes) {
+        std::fs::write(path, normalized)
+            .with_context(|| format!("Failed to write normalized {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use crate::cli::ConvertArgs;
+
+/// Output dimensions in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+pub fn parse_dimensions(value: &str) -> Result<Size, String> {
+    let invalid = || format!("'{}' is not in WxH form (e.g. 512x512)", value);
+    let (width, height) = value.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+    let number = |text: &str| text.trim().parse::<u32>().map_err(|_| invalid());
+    let size = Size { width: number(width)?, height: number(height)? };
+    if size.width == 0 || size.height == 0 {
+        return Err("width and height must be at least 1".to_string());
+    }
+    Ok(size)
+}
+
+/// What `--resize` / `--max-dimension` do to one texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizePlan {
+    Keep,
+    To(Size),
+    /// `--resize` asked for this larger size without `--allow-upscale`; the
+    /// texture keeps its own.
+    NotUpscaled(Size),
+}
+
+/// The plan for a `width`x`height` texture. `--max-dimension` scales the
+/// longer side to `N`, keeping the aspect ratio, and leaves smaller
+/// textures alone unless upscaling is allowed.
+pub fn plan_resize(width: u32, height: u32, cli: &ConvertArgs) -> ResizePlan {
+    let wanted = match (cli.resize, cli.max_dimension) {
+        (Some(size), _) => size,
+        (None, Some(limit)) => {
+            let longer = width.max(height);
+            if longer == 0 || (longer <= limit && !cli.allow_upscale) {
+                return ResizePlan::Keep;
+            }
+            let scale = |side: u32| ((side as u64 * limit as u64 + longer as u64 / 2) / longer as u64).max(1) as u32;
+            Size { width: scale(width), height: scale(height) }
+        }
+        (None, None) => return ResizePlan::Keep,
+    };
+
+    if wanted.width == width && wanted.height == height {
+        ResizePlan::Keep
+    } else if (wanted.width > width || wanted.height > height) && !cli.allow_upscale {
+        ResizePlan::NotUpscaled(wanted)
+    } else {
+        ResizePlan::To(wanted)
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::errors::classify;
+use crate::exit_status::RunStatus;
+use crate::processor::FileOutcome;
+
+/// `--log-file`: appends one JSON object per line for the run start, every
+/// file processed and the run end. All lines go through one writer thread,
+/// so concurrent files never interleave, and each is flushed as written.
+pub struct RunLog {
+    sender: Mutex<Option<Sender<Value>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    started: Instant,
+}
+
+impl RunLog {
+    /// Opens `path` for appending and writes the `run_start` record.
+    pub fn open(path: &Path, input: &Path, output: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        let (sender, lines) = mpsc::channel::<Value>();
+        let writer = std::thread::spawn(move || {
+            let mut file = BufWriter::new(file);
+            for line in lines {
+                let _ = writeln!(file, "{}", line).and_then(|_| file.flush());
+            }
+        });
+
+        let log = RunLog { sender: Mutex::new(Some(sender)), writer: Mutex::new(Some(writer)), started: Instant::now() };
+        let command: Vec<String> = std::env::args().collect();
+        log.write(json!({
+            "event": "run_start",
+            "input": input.display().to_string(),
+            "output": output.display().to_string(),
+            "command": command,
+        }));
+        Ok(log)
+    }
+
+    /// Records one processed file.
+    pub fn file(&self, input: &Path, output: &Path, duration: Duration, result: &Result<FileOutcome>) {
+        let mut record = json!({
+            "event": "file",
+            "input": input.display().to_string(),
+            "output": output.display().to_string(),
+            "duration_ms": duration.as_millis() as u64,
+        });
+        match result {
+            Ok(FileOutcome::Converted { .. }) => record["status"] = json!("converted"),
+            Ok(FileOutcome::Linked { method, .. }) => {
+                record["status"] = json!("linked");
+                record["method"] = json!(method.name());
+            }
+            Ok(FileOutcome::Skipped(reason)) => {
+                record["status"] = json!("skipped");
+                record["reason"] = json!(reason.describe());
+            }
+            Err(e) => {
+                record["status"] = json!("failed");
+                record["error_code"] = json!(classify(e).as_str());
+                record["error"] = json!(format!("{:#}", e));
+            }
+        }
+        self.write(record);
+    }
+
+    /// Records a progress heartbeat: files processed out of `total`, the
+    /// errors so far, the rate in files per second and the estimated time left.
+    pub fn heartbeat(&self, processed: u64, total: u64, errors: usize, rate: f64, eta: Duration) {
+        self.write(json!({
+            "event": "heartbeat",
+            "processed": processed,
+            "total": total,
+            "errors": errors,
+            "files_per_sec": (rate * 10.0).round() / 10.0,
+            "eta_s": eta.as_secs(),
+        }));
+    }
+
+    /// Writes the `run_end` record and waits until every line is on disk.
+    /// Lines sent afterwards (by files still finishing after an abort) are
+    /// dropped.
+    pub fn finish(&self, result: &Result<RunStatus>) {
+        let mut record = json!({
+            "event": "run_end",
+            "duration_ms": self.started.elapsed().as_millis() as u64,
+        });
+        match result {
+            Ok(status) => record["status"] = json!(status.as_str()),
+            Err(e) => {
+                record["status"] = json!("error");
+                record["error"] = json!(format!("{:#}", e));
+            }
+        }
+        self.write(record);
+
+        drop(self.sender.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take());
+        if let Some(writer) = self.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() {
+            let _ = writer.join();
+        }
+    }
+
+    fn write(&self, mut record: Value) {
+        record["time"] = json!(humantime::format_rfc3339_millis(SystemTime::now()).to_string());
+        if let Some(sender) = self.sender.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).as_ref() {
+            let _ = sender.send(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heartbeats_are_logged_between_start_and_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.log");
+        let log = RunLog::open(&path, Path::new("in"), Path::new("out")).unwrap();
+        log.heartbeat(1200, 40000, 3, 95.04, Duration::from_secs(360));
+        log.finish(&Ok(RunStatus::Success));
+
+        let records: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let events: Vec<&str> = records.iter().map(|record| record["event"].as_str().unwrap()).collect();
+        assert_eq!(events, ["run_start", "heartbeat", "run_end"]);
+        let heartbeat = &records[1];
+        assert_eq!((heartbeat["processed"].as_u64(), heartbeat["total"].as_u64()), (Some(1200), Some(40000)));
+        assert_eq!((heartbeat["errors"].as_u64(), heartbeat["eta_s"].as_u64()), (Some(3), Some(360)));
+        assert_eq!(heartbeat["files_per_sec"].as_f64(), Some(95.0));
+    }
+}
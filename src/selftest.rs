@@ -0,0 +1,47 @@
+use anyhow::Result;
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+
+use crate::texconv::setup_texconv;
+use crate::webp_anim::encode_animated_webp;
+
+/// Checks each external tool and built-in encoder the converter relies on and
+/// prints one line per check. Only a broken texconv is fatal, since every
+/// conversion needs it; the others just narrow down what will work.
+pub async fn run_self_test(texconv: Option<&Path>) -> Result<()> {
+    println!("🩺 Running self-test...");
+    let mut texconv_ok = true;
+
+    match setup_texconv(texconv).await {
+        Ok(path) => println!("✅ texconv: extracted and runs ({})", path.display()),
+        Err(e) => {
+            texconv_ok = false;
+            println!("❌ texconv: {:#}", e);
+        }
+    }
+
+    match std::process::Command::new("ffmpeg").arg("-version").output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            println!("✅ ffmpeg: {}", version.lines().next().unwrap_or("found"));
+        }
+        Ok(output) => println!("⚠️  ffmpeg: exited with code {:?}", output.status.code()),
+        Err(_) => println!("⚠️  ffmpeg: not found on PATH (animations will use the built-in encoder)"),
+    }
+
+    let frames: Vec<RgbaImage> = [Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 128])]
+        .into_iter()
+        .map(|color| RgbaImage::from_pixel(4, 4, color))
+        .collect();
+    match encode_animated_webp(&frames, &[100, 100], 85.0) {
+        Ok(bytes) => println!("✅ built-in WebP animation encoder: ok ({} bytes)", bytes.len()),
+        Err(e) => println!("❌ built-in WebP animation encoder: {:#}", e),
+    }
+
+    if !texconv_ok {
+        anyhow::bail!("Self-test failed: texconv is required for conversions");
+    }
+
+    println!("🎉 Self-test passed");
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::crop::CropRect;
+use crate::dds::DdsHeader;
+use crate::quantize::Dither;
+
+/// Bumped whenever a field is renamed or removed; new optional fields don't
+/// need a bump.
+pub const SIDECAR_SCHEMA_VERSION: u32 = 1;
+
+/// Provenance record written as `<output>.json` next to a converted file or
+/// animation when `--sidecar` is set.
+#[derive(Serialize, Deserialize)]
+pub struct Sidecar {
+    pub schema_version: u32,
+    pub tool: String,
+    pub tool_version: String,
+    pub output: PathBuf,
+    pub sha256: String,
+    /// Output image size, when it is a format the image crate can read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    pub settings: SidecarSettings,
+    pub sources: Vec<SidecarSource>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SidecarSettings {
+    Convert {
+        format: String,
+        decode_format: String,
+        /// How a high-bit-depth texture was reduced to 8 bits with `--dither`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dither: Option<Dither>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alpha_threshold: Option<u8>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        alpha_dither: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        crop: Option<CropRect>,
+        /// The rectangle `--trim` kept, in the decoded (and cropped) image.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trim: Option<CropRect>,
+        /// Where the original pixels are in a `--pad-pot` output.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content_rect: Option<CropRect>,
+        /// `#rrggbb` the colours were multiplied by.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tint: Option<String>,
+        /// `--adjust` chain, in the order applied.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        adjust: Option<String>,
+        /// Quality of a built-in jpg or webp encode, as `--target-size` chose it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        quality: Option<u8>,
+    },
+    Animate {
+        format: String,
+        frame_delay_ms: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tint: Option<String>,
+    },
+}
+
+/// One input that went into the output. DDS header fields are filled in for
+/// `.dds` inputs whose header could be read.
+#[derive(Serialize, Deserialize)]
+pub struct SidecarSource {
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixel_format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mips: Option<u32>,
+}
+
+impl SidecarSource {
+    pub fn describe(path: &Path) -> Self {
+        let is_dds = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("dds"))
+            .unwrap_or(false);
+        let header = if is_dds { DdsHeader::read(path).ok() } else { None };
+
+        Self {
+            path: path.to_path_buf(),
+            pixel_format: header.as_ref().map(DdsHeader::format_name),
+            width: header.as_ref().map(|h| h.width),
+            height: header.as_ref().map(|h| h.height),
+            mips: header.as_ref().map(|h| h.mip_count),
+        }
+    }
+}
+
+/// Hashes `output_path` and writes its sidecar next to it, returning the
+/// sidecar's path.
+pub fn write_sidecar(output_path: &Path, settings: SidecarSettings, sources: &[PathBuf]) -> Result<PathBuf> {
+    let contents = std::fs::read(output_path)
+        .with_context(|| format!("Failed to read {} for hashing", output_path.display()))?;
+
+    let dimensions = image::image_dimensions(output_path).ok();
+    let sidecar = Sidecar {
+        schema_version: SIDECAR_SCHEMA_VERSION,
+        tool: env!("CARGO_PKG_NAME").to_string(),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        output: output_path.to_path_buf(),
+        sha256: format!("{:x}", Sha256::digest(&contents)),
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        settings,
+        sources: sources.iter().map(|path| SidecarSource::describe(path)).collect(),
+    };
+
+    let sidecar_path = sidecar_path(output_path);
+    let json = serde_json::to_string_pretty(&sidecar)?;
+    // An identical sidecar is left alone, so unchanged outputs keep both mtimes
+    if std::fs::read(&sidecar_path).is_ok_and(|existing| existing == json.as_bytes()) {
+        return Ok(sidecar_path);
+    }
+    std::fs::write(&sidecar_path, json)
+        .with_context(|| format!("Failed to write sidecar {}", sidecar_path.display()))?;
+
+    Ok(sidecar_path)
+}
+
+/// `foo.png` -> `foo.png.json`
+pub fn sidecar_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of an uncompressed 32-bit BGRA DDS, 8x4 with 3 mips.
+    fn dds_header() -> Vec<u8> {
+        let mut bytes = b"DDS ".to_vec();
+        for value in [124, 0x2100f, 4, 8, 32, 0, 3] {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        bytes.extend_from_slice(&[0; 44]);
+        for value in [32, 0x41, 0, 32, 0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000, 0x40_1008, 0, 0, 0, 0] {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        bytes
+    }
+
+    #[test]
+    fn sidecar_round_trips_through_its_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("rock.dds");
+        std::fs::write(&source, dds_header()).unwrap();
+        let output = dir.path().join("rock.png");
+        image::RgbaImage::new(8, 4).save(&output).unwrap();
+        let settings = SidecarSettings::Convert {
+            format: "png".to_string(),
+            decode_format: "R8G8B8A8_UNORM".to_string(),
+            dither: None,
+            alpha_threshold: Some(128),
+            alpha_dither: false,
+            crop: Some(CropRect { x: 1, y: 0, width: 4, height: 4 }),
+            trim: None,
+            content_rect: None,
+            tint: Some("#ffb000".to_string()),
+            adjust: None,
+            quality: None,
+        };
+
+        let path = write_sidecar(&output, settings, std::slice::from_ref(&source)).unwrap();
+        assert_eq!(path, dir.path().join("rock.png.json"));
+        let json = std::fs::read_to_string(&path).unwrap();
+        let sidecar: Sidecar = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string_pretty(&sidecar).unwrap(), json);
+
+        assert_eq!(sidecar.schema_version, SIDECAR_SCHEMA_VERSION);
+        assert_eq!(sidecar.tool_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!((sidecar.width, sidecar.height), (Some(8), Some(4)));
+        assert_eq!(sidecar.sha256, format!("{:x}", Sha256::digest(std::fs::read(&output).unwrap())));
+        let source = &sidecar.sources[0];
+        assert_eq!((source.width, source.height, source.mips), (Some(8), Some(4), Some(3)));
+        assert!(source.pixel_format.is_some());
+
+        // Unset options are left out, and the mode tags the settings
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let settings = value["settings"].as_object().unwrap();
+        let mut keys: Vec<&str> = settings.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["alpha_threshold", "crop", "decode_format", "format", "mode", "tint"]);
+        assert_eq!(settings["mode"], "convert");
+    }
+
+    #[test]
+    fn older_sidecars_without_optional_fields_still_read() {
+        let json = r#"{
+            "schema_version": 1, "tool": "dds-converter", "tool_version": "0.1.0",
+            "output": "out/fire.webp", "sha256": "00",
+            "settings": { "mode": "animate", "format": "webp", "frame_delay_ms": 40 },
+            "sources": [{ "path": "in/fire.gif" }]
+        }"#;
+        let sidecar: Sidecar = serde_json::from_str(json).unwrap();
+        assert!(sidecar.width.is_none());
+        assert!(matches!(sidecar.settings, SidecarSettings::Animate { frame_delay_ms: 40, tint: None, .. }));
+        assert!(sidecar.sources[0].pixel_format.is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::dds::DdsHeader;
+use crate::errors::{Classified, ErrorCode};
+
+/// Streamed mip parts of a split Lumberyard/CryEngine texture: `foo.dds.1` …
+/// `foo.dds.N`, sorted by part number. The base `foo.dds` keeps the full-size
+/// header and the smallest mips; part 1 holds the next mip up and part N the
+/// largest. `.dds.1a`-style parts belong to the attached alpha texture
+/// (`foo.dds.a`) and are returned by [`find_alpha_split_parts`] instead.
+pub fn find_split_parts(dds_path: &Path) -> Vec<(u32, PathBuf)> {
+    find_parts(dds_path, "")
+}
+
+/// The attached alpha texture `foo.dds.a`, if there is one.
+pub fn find_alpha_part(dds_path: &Path) -> Option<PathBuf> {
+    let mut name = dds_path.file_name()?.to_os_string();
+    name.push(".a");
+    let alpha_path = dds_path.with_file_name(name);
+    alpha_path.is_file().then_some(alpha_path)
+}
+
+/// Split parts of the attached alpha texture: `foo.dds.1a` … `foo.dds.Na`.
+pub fn find_alpha_split_parts(dds_path: &Path) -> Vec<(u32, PathBuf)> {
+    find_parts(dds_path, "a")
+}
+
+/// Highest part number probed for: a DDS has at most 16 mip levels, and the
+/// base file keeps at least the smallest one.
+const MAX_PARTS: u32 = 15;
+
+/// Files named `<file name>.<number><suffix>` next to `dds_path`. Each
+/// candidate name is probed on its own rather than listing the folder,
+/// since this runs several times per texture and game dumps keep tens of
+/// thousands of files in one folder. Probing carries on past a missing
+/// part so gaps can be reported.
+fn find_parts(dds_path: &Path, suffix: &str) -> Vec<(u32, PathBuf)> {
+    let Some(file_name) = dds_path.file_name() else {
+        return Vec::new();
+    };
+    (1..=MAX_PARTS)
+        .filter_map(|number| {
+            let mut name = file_name.to_os_string();
+            name.push(format!(".{}{}", number, suffix));
+            let part = dds_path.with_file_name(name);
+            part.is_file().then_some((number, part))
+        })
+        .collect()
+}
+
+pub struct MergedTexture {
+    pub path: PathBuf,
+    pub parts_used: usize,
+    /// Mip levels that had to be dropped from the top because a part was missing.
+    pub dropped_levels: u32,
+    /// Numbers of the parts that are missing, including ones above the
+    /// highest part found.
+    pub missing_parts: Vec<u32>,
+}
+
+/// Reassembles a split texture into `merged_path`: header, then the parts
+/// from largest (N) to smallest (1), then the base file's own mips. If a part
+/// is missing, only the unbroken run from part 1 upwards is used and the
+/// header is shrunk to match, so the result is smaller but valid. N comes
+/// from the header and the mips the base file holds, so missing top parts
+/// count too. Cubemaps and arrays are regrouped so each surface keeps its
+/// whole mip chain together, as DDS stores them.
+pub fn merge_split_dds(dds_path: &Path, parts: &[(u32, PathBuf)], merged_path: &Path) -> Result<MergedTexture> {
+    let base = std::fs::read(dds_path)
+        .with_context(|| format!("Failed to read {}", dds_path.display()))?;
+    let header = DdsHeader::parse(&base)?;
+    let data_offset = header.data_offset();
+
+    let highest = parts.last().map(|(number, _)| *number).unwrap_or(0);
+    let expected = expected_parts(&header, base.len().saturating_sub(data_offset)).unwrap_or(0).max(highest);
+    let usable = parts
+        .iter()
+        .enumerate()
+        .take_while(|(i, (number, _))| *number as usize == i + 1)
+        .count();
+    let dropped_levels = expected.saturating_sub(usable as u32);
+    let missing_parts = (1..=expected).filter(|number| !parts.iter().any(|(part, _)| part == number)).collect();
+
+    let mut merged = base[..data_offset].to_vec();
+    if dropped_levels > 0 {
+        shrink_header(&mut merged, &header, dropped_levels)?;
+    }
+    let mut part_data = Vec::with_capacity(usable);
+    for (number, part) in parts[..usable].iter().rev() {
+        let data = std::fs::read(part)
+            .with_context(|| format!("Failed to read split part {} ({})", number, part.display()))?;
+        part_data.push((*number, data));
+    }
+    if header.layer_count() == 1 {
+        for (_, data) in &part_data {
+            merged.extend_from_slice(data);
+        }
+        merged.extend_from_slice(&base[data_offset..]);
+    } else {
+        interleave_surfaces(&mut merged, &header, expected, &part_data, &base[data_offset..])?;
+    }
+
+    std::fs::write(merged_path, merged)
+        .with_context(|| format!("Failed to write merged texture {}", merged_path.display()))?;
+
+    Ok(MergedTexture { path: merged_path.to_path_buf(), parts_used: usable, dropped_levels, missing_parts })
+}
+
+/// Appends the data of a split cubemap or texture array in DDS order: each
+/// face or slice with its full mip chain in turn. The base file and every
+/// part hold their levels for all surfaces one after another, so part N
+/// (mip `expected - N`) and the base file's tail are cut into per-surface
+/// pieces and dealt out surface by surface.
+fn interleave_surfaces(merged: &mut Vec<u8>, header: &DdsHeader, expected: u32, parts: &[(u32, Vec<u8>)], base: &[u8]) -> Result<()> {
+    let layers = header.layer_count() as usize;
+    let unknown = || Classified::error(ErrorCode::UnsupportedFormat, format!("Can't merge a split {} texture with several surfaces: its mip layout isn't known", header.format_name()));
+    let base_chain = (expected..header.mip_count).map(|level| header.mip_bytes(level)).sum::<Option<usize>>().ok_or_else(unknown)?;
+    if base.len() != base_chain * layers {
+        return Err(Classified::error(
+            ErrorCode::CorruptHeader,
+            format!("Base file of a split texture holds {} bytes of mips, but {} surfaces need {}", base.len(), layers, base_chain * layers),
+        ));
+    }
+    let mut pieces = Vec::with_capacity(parts.len());
+    for (number, data) in parts {
+        let level = expected.checked_sub(*number).ok_or_else(unknown)?;
+        let size = header.mip_bytes(level).ok_or_else(unknown)?;
+        if data.len() != size * layers {
+            return Err(Classified::error(
+                ErrorCode::CorruptHeader,
+                format!("Split part {} holds {} bytes, but mip {} of {} surfaces needs {}", number, data.len(), level, layers, size * layers),
+            ));
+        }
+        pieces.push((size, data));
+    }
+
+    for layer in 0..layers {
+        for (size, data) in &pieces {
+            merged.extend_from_slice(&data[layer * size..(layer + 1) * size]);
+        }
+        merged.extend_from_slice(&base[layer * base_chain..(layer + 1) * base_chain]);
+    }
+    Ok(())
+}
+
+/// How many split parts the texture has: its mip levels minus the ones the
+/// `base_bytes` of mip data in the base file hold, smallest first. `None`
+/// when the format's layout isn't known or the sizes don't add up.
+fn expected_parts(header: &DdsHeader, base_bytes: usize) -> Option<u32> {
+    let mut held = 0;
+    for level in (0..header.mip_count).rev() {
+        if held == base_bytes {
+            return Some(level + 1);
+        }
+        held += header.mip_bytes(level)? * header.layer_count() as usize;
+    }
+    (held == base_bytes).then_some(0)
+}
+
+/// Rewrites width, height and mip count for a texture missing its top
+/// `levels` mips.
+fn shrink_header(bytes: &mut [u8], header: &DdsHeader, levels: u32) -> Result<()> {
+    if levels >= header.mip_count {
+        return Err(Classified::error(ErrorCode::CorruptHeader, "Split texture is missing more mip levels than its header lists"));
+    }
+    let mut put = |offset: usize, value: u32| bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    put(12, (header.height >> levels).max(1));
+    put(16, (header.width >> levels).max(1));
+    put(28, header.mip_count - levels);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::dx10_header;
+
+    const R8G8B8A8_UNORM: u32 = 28;
+
+    /// A 16x16, 5-mip RGBA texture split New World style: part 2 holds the
+    /// 16x16 level, part 1 the 8x8 one and the base file the rest. Only
+    /// the `parts` listed are written.
+    fn split_texture(dir: &Path, parts: &[u32]) -> (PathBuf, Vec<(u32, PathBuf)>) {
+        split_surfaces(dir, parts, 1)
+    }
+
+    /// `split_texture` with `faces` surfaces (a cubemap for 6). Each part
+    /// and the base file hold their levels for face 0, then face 1, and so
+    /// on; every pixel is `[face, level, 0, 0]`.
+    fn split_surfaces(dir: &Path, parts: &[u32], faces: u32) -> (PathBuf, Vec<(u32, PathBuf)>) {
+        let level = |face: u32, level: u32| {
+            let side = 16usize >> level;
+            [face as u8, level as u8, 0, 0].repeat(side * side)
+        };
+        let base = dir.join("rock.dds");
+        let mut bytes = dx10_header(R8G8B8A8_UNORM, 16, 16, 5);
+        if faces == 6 {
+            // DDSCAPS2_CUBEMAP plus the DX10 TEXTURECUBE flag
+            bytes[112..116].copy_from_slice(&0xfe00u32.to_le_bytes());
+            bytes[136..140].copy_from_slice(&0x4u32.to_le_bytes());
+        }
+        for face in 0..faces {
+            for mip in 2..5 {
+                bytes.extend(level(face, mip));
+            }
+        }
+        std::fs::write(&base, bytes).unwrap();
+        for &number in parts {
+            let data: Vec<u8> = (0..faces).flat_map(|face| level(face, 2 - number)).collect();
+            std::fs::write(dir.join(format!("rock.dds.{}", number)), data).unwrap();
+        }
+        let found = find_split_parts(&base);
+        (base, found)
+    }
+
+    fn merge(parts: &[u32]) -> (MergedTexture, DdsHeader, usize) {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, found) = split_texture(dir.path(), parts);
+        let merged = merge_split_dds(&base, &found, &dir.path().join("merged.dds")).unwrap();
+        let bytes = std::fs::read(&merged.path).unwrap();
+        let header = DdsHeader::parse(&bytes).unwrap();
+        let data = bytes.len() - header.data_offset();
+        (merged, header, data)
+    }
+
+    fn chain_bytes(header: &DdsHeader) -> usize {
+        (0..header.mip_count).map(|level| header.mip_bytes(level).unwrap()).sum()
+    }
+
+    #[test]
+    fn merged_texture_takes_the_size_of_the_largest_part() {
+        let (merged, header, data) = merge(&[1, 2]);
+        assert_eq!((header.width, header.height, header.mip_count), (16, 16, 5));
+        assert_eq!(data, chain_bytes(&header));
+        assert_eq!((merged.parts_used, merged.dropped_levels), (2, 0));
+        assert!(merged.missing_parts.is_empty());
+    }
+
+    #[test]
+    fn a_missing_top_part_shrinks_the_texture() {
+        let (merged, header, data) = merge(&[1]);
+        assert_eq!((header.width, header.height, header.mip_count), (8, 8, 4));
+        assert_eq!(data, chain_bytes(&header));
+        assert_eq!(merged.dropped_levels, 1);
+        assert_eq!(merged.missing_parts, [2]);
+    }
+
+    #[test]
+    fn a_missing_middle_part_drops_everything_above_it() {
+        let (merged, header, data) = merge(&[2]);
+        assert_eq!((header.width, header.height, header.mip_count), (4, 4, 3));
+        assert_eq!(data, chain_bytes(&header));
+        assert_eq!((merged.parts_used, merged.dropped_levels), (0, 2));
+        assert_eq!(merged.missing_parts, [1]);
+    }
+
+    #[test]
+    fn cubemap_faces_each_get_their_whole_mip_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, found) = split_surfaces(dir.path(), &[1, 2], 6);
+        let merged = merge_split_dds(&base, &found, &dir.path().join("merged.dds")).unwrap();
+        let bytes = std::fs::read(&merged.path).unwrap();
+        let header = DdsHeader::parse(&bytes).unwrap();
+        assert_eq!((header.layer_count(), header.mip_count, merged.parts_used), (6, 5, 2));
+        assert_eq!(bytes.len() - header.data_offset(), chain_bytes(&header) * 6);
+
+        // Every face and level lands where extract_surface reads it
+        for face in 0..6 {
+            for level in 0..5 {
+                let surface = dir.path().join("surface.dds");
+                crate::mips::extract_surface(&bytes, &header, face, level, &surface).unwrap();
+                let single = std::fs::read(&surface).unwrap();
+                let data = &single[DdsHeader::parse(&single).unwrap().data_offset()..];
+                assert!(data.chunks(4).all(|pixel| pixel == [face as u8, level as u8, 0, 0]), "face {} mip {}", face, level);
+            }
+        }
+    }
+
+    #[test]
+    fn a_cubemap_part_of_the_wrong_size_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, found) = split_surfaces(dir.path(), &[1, 2], 6);
+        // Part 1 holding the 8x8 level of one face only
+        std::fs::write(&found[0].1, vec![0; 8 * 8 * 4]).unwrap();
+        let Err(error) = merge_split_dds(&base, &found, &dir.path().join("merged.dds")) else {
+            panic!("merged a cubemap with a short part");
+        };
+        assert_eq!(crate::errors::classify(&error), ErrorCode::CorruptHeader);
+        assert!(error.to_string().contains("Split part 1 holds 256 bytes"), "{}", error);
+    }
+}
@@ -1,18 +1,48 @@
 use anyhow::{Result, Context};
-use std::path::Path;
-use image::{DynamicImage, RgbaImage, ImageBuffer};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, GenericImage, RgbaImage, ImageBuffer};
+
+use crate::utils::texture_stem;
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct SpriteCell {
     pub top_left: (f32, f32),
     pub top_right: (f32, f32),
     pub bottom_left: (f32, f32),
     pub bottom_right: (f32, f32),
+    /// From a `duration` or `time` attribute, if the cell has one.
+    pub duration: Option<CellDuration>,
+    /// Texture page the cell is on (0 without a `page` attribute).
+    pub page: Option<usize>,
+    /// Page given by file name (`texture="effect_1.dds"`), resolved by
+    /// `SpriteSheet::assign_pages`.
+    pub texture: Option<String>,
+}
+
+/// A cell's display time as written in the sprite file.
+#[derive(Debug, Clone, Copy)]
+pub struct CellDuration {
+    pub value: f32,
+    /// Set when the value carries an `ms` or `s` suffix.
+    pub unit: Option<SpriteTimeUnit>,
+}
+
+/// Unit of sprite cell durations without a suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SpriteTimeUnit {
+    /// Seconds if every duration in the sheet is below 10, milliseconds otherwise
+    Auto,
+    Ms,
+    S,
 }
 
 #[derive(Debug)]
 pub struct SpriteSheet {
     pub cells: Vec<SpriteCell>,
+    /// Texture pages declared with `<Texture path="..."/>` or `<Page file="..."/>`.
+    pub textures: Vec<String>,
 }
 
 impl SpriteSheet {
@@ -25,6 +55,7 @@ impl SpriteSheet {
     
     pub fn from_xml_content(xml_content: &str) -> Result<Self> {
         let mut cells = Vec::new();
+        let mut textures = Vec::new();
         
         // Simple XML parsing - look for Cell elements
         for line in xml_content.lines() {
@@ -33,10 +64,13 @@ impl SpriteSheet {
                 if let Some(cell) = Self::parse_cell_line(line)? {
                     cells.push(cell);
                 }
+            } else if line.starts_with("<Texture ") || line.starts_with("<Page ") {
+                let path = ["path", "file", "name"].iter().find_map(|attr| Self::extract_attribute(line, attr));
+                textures.extend(path);
             }
         }
         
-        Ok(SpriteSheet { cells })
+        Ok(SpriteSheet { cells, textures })
     }
     
     fn parse_cell_line(line: &str) -> Result<Option<SpriteCell>> {
@@ -68,12 +102,36 @@ impl SpriteSheet {
             }
         }
         
+        let duration = match Self::extract_attribute(line, "duration").or_else(|| Self::extract_attribute(line, "time")) {
+            Some(value) => Some(Self::parse_duration(&value)?),
+            None => None,
+        };
+
+        // `page="1"`, or `texture=` with a page number or a file name
+        let mut texture = None;
+        let page = match Self::extract_attribute(line, "page") {
+            Some(page) => Some(page.trim().parse::<usize>().with_context(|| format!("Invalid page index: {}", page))?),
+            None => match Self::extract_attribute(line, "texture") {
+                Some(value) => match value.trim().parse::<usize>() {
+                    Ok(page) => Some(page),
+                    Err(_) => {
+                        texture = Some(value);
+                        None
+                    }
+                },
+                None => None,
+            },
+        };
+
         if let (Some(tl), Some(tr), Some(bl), Some(br)) = (top_left, top_right, bottom_left, bottom_right) {
             Ok(Some(SpriteCell {
                 top_left: tl,
                 top_right: tr,
                 bottom_left: bl,
                 bottom_right: br,
+                duration,
+                page,
+                texture,
             }))
         } else {
             Ok(None)
@@ -82,13 +140,32 @@ impl SpriteSheet {
     
     fn extract_attribute(line: &str, attr_name: &str) -> Option<String> {
         let pattern = format!("{}=\"", attr_name);
-        if let Some(start) = line.find(&pattern) {
-            let start = start + pattern.len();
-            if let Some(end) = line[start..].find('"') {
-                return Some(line[start..start + end].to_string());
-            }
+        // Only whole attribute names: `time` must not match inside `frameTime`
+        let start = line
+            .match_indices(&pattern)
+            .map(|(start, _)| start)
+            .find(|&start| line[..start].ends_with(char::is_whitespace))?;
+        let start = start + pattern.len();
+        let end = line[start..].find('"')?;
+        Some(line[start..start + end].to_string())
+    }
+
+    /// `100`, `100ms`, `0.1s`
+    fn parse_duration(value: &str) -> Result<CellDuration> {
+        let value = value.trim().to_lowercase();
+        let (number, unit) = if let Some(number) = value.strip_suffix("ms") {
+            (number, Some(SpriteTimeUnit::Ms))
+        } else if let Some(number) = value.strip_suffix('s') {
+            (number, Some(SpriteTimeUnit::S))
+        } else {
+            (value.as_str(), None)
+        };
+        let number: f32 = number.trim().parse()
+            .with_context(|| format!("Invalid cell duration: {}", value))?;
+        if !number.is_finite() || number < 0.0 {
+            anyhow::bail!("Invalid cell duration: {}", value);
         }
-        None
+        Ok(CellDuration { value: number, unit })
     }
     
     fn parse_coords(coords_str: &str) -> Result<(f32, f32)> {
@@ -103,24 +180,109 @@ impl SpriteSheet {
         Ok((x, y))
     }
     
-    pub fn extract_frames(&self, texture: &DynamicImage) -> Result<Vec<RgbaImage>> {
-        let rgba_texture = texture.to_rgba8();
-        let (tex_width, tex_height) = rgba_texture.dimensions();
+    /// The texture files of the sheet: the declared `<Texture>`/`<Page>`
+    /// entries (relative to the sprite file), or else `anchor` followed by
+    /// its numbered siblings for every page index the cells use
+    /// (`effect_0.dds` -> `effect_1.dds`, `effect.dds` -> `effect_1.dds`) and
+    /// any textures cells name directly.
+    pub fn page_paths(&self, anchor: &Path, sprite_path: &Path) -> Vec<PathBuf> {
+        let dir = sprite_path.parent().unwrap_or(Path::new(""));
+        if !self.textures.is_empty() {
+            return self.textures.iter().map(|texture| dir.join(texture)).collect();
+        }
+        let mut pages = vec![anchor.to_path_buf()];
+        let last = self.cells.iter().filter_map(|cell| cell.page).max().unwrap_or(0);
+        pages.extend((1..=last).map(|page| numbered_sibling(anchor, page)));
+        for name in self.cells.iter().filter_map(|cell| cell.texture.as_deref()) {
+            if find_page(&pages, name).is_none() {
+                pages.push(dir.join(name));
+            }
+        }
+        pages
+    }
+
+    /// Resolves cells that name their texture to page indices into `pages`
+    /// and checks every cell's page is in range.
+    pub fn assign_pages(&mut self, pages: &[PathBuf]) -> Result<()> {
+        for (index, cell) in self.cells.iter_mut().enumerate() {
+            if let Some(name) = &cell.texture {
+                let page = find_page(pages, name)
+                    .with_context(|| format!("Cell {} uses texture {}, which isn't one of the sheet's pages", index + 1, name))?;
+                cell.page = Some(page);
+            }
+            let page = cell.page.unwrap_or(0);
+            if page >= pages.len() {
+                anyhow::bail!("Cell {} is on page {}, but the sheet has {} texture page(s)", index + 1, page, pages.len());
+            }
+        }
+        Ok(())
+    }
+
+    /// Per-frame display times in milliseconds for the cells `extract_frames`
+    /// keeps, or `None` if no cell has a duration. Cells without one get
+    /// `default_ms`. With `SpriteTimeUnit::Auto`, values without a suffix are
+    /// seconds if all of them are below 10 (`0.05`, `1.5`) and milliseconds
+    /// otherwise. Returns the unit that was assumed for unsuffixed values.
+    pub fn frame_durations(&self, page_sizes: &[(u32, u32)], unit: SpriteTimeUnit, default_ms: u16) -> Option<(Vec<u32>, SpriteTimeUnit)> {
+        let cells: Vec<&SpriteCell> = self.cells
+            .iter()
+            .filter(|cell| page_sizes.get(cell.page.unwrap_or(0)).and_then(|size| Self::cell_rect(cell, *size)).is_some())
+            .collect();
+        if cells.iter().all(|cell| cell.duration.is_none()) {
+            return None;
+        }
+        let unit = match unit {
+            SpriteTimeUnit::Auto => {
+                let plain = || cells.iter().filter_map(|cell| cell.duration).filter(|duration| duration.unit.is_none());
+                if plain().next().is_some() && plain().all(|duration| duration.value < 10.0) {
+                    SpriteTimeUnit::S
+                } else {
+                    SpriteTimeUnit::Ms
+                }
+            }
+            unit => unit,
+        };
+        let durations = cells
+            .iter()
+            .map(|cell| match cell.duration {
+                Some(duration) => match duration.unit.unwrap_or(unit) {
+                    SpriteTimeUnit::S => (duration.value * 1000.0).round() as u32,
+                    _ => duration.value.round() as u32,
+                },
+                None => default_ms as u32,
+            })
+            .map(|ms| ms.max(1))
+            .collect();
+        Some((durations, unit))
+    }
+
+    /// Pixel rectangle (x, y, width, height) of `cell`, or `None` if it is empty.
+    fn cell_rect(cell: &SpriteCell, (tex_width, tex_height): (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+        // Convert UV coordinates to pixel coordinates
+        let x1 = (cell.top_left.0 * tex_width as f32) as u32;
+        let y1 = (cell.top_left.1 * tex_height as f32) as u32;
+        let x2 = (cell.bottom_right.0 * tex_width as f32) as u32;
+        let y2 = (cell.bottom_right.1 * tex_height as f32) as u32;
+
+        let width = x2.saturating_sub(x1);
+        let height = y2.saturating_sub(y1);
+        (width > 0 && height > 0).then_some((x1, y1, width, height))
+    }
+
+    /// Cuts every cell out of its texture page, in cell order.
+    pub fn extract_frames(&self, pages: &[DynamicImage]) -> Result<Vec<RgbaImage>> {
+        let pages: Vec<RgbaImage> = pages.iter().map(DynamicImage::to_rgba8).collect();
         let mut frames = Vec::new();
         
-        for cell in &self.cells {
-            // Convert UV coordinates to pixel coordinates
-            let x1 = (cell.top_left.0 * tex_width as f32) as u32;
-            let y1 = (cell.top_left.1 * tex_height as f32) as u32;
-            let x2 = (cell.bottom_right.0 * tex_width as f32) as u32;
-            let y2 = (cell.bottom_right.1 * tex_height as f32) as u32;
-            
-            let width = x2.saturating_sub(x1);
-            let height = y2.saturating_sub(y1);
-            
-            if width == 0 || height == 0 {
+        for (index, cell) in self.cells.iter().enumerate() {
+            let page = cell.page.unwrap_or(0);
+            let Some(rgba_texture) = pages.get(page) else {
+                anyhow::bail!("Cell {} is on page {}, but the sheet has {} texture page(s)", index + 1, page, pages.len());
+            };
+            let (tex_width, tex_height) = rgba_texture.dimensions();
+            let Some((x1, y1, width, height)) = Self::cell_rect(cell, (tex_width, tex_height)) else {
                 continue;
-            }
+            };
             
             // Extract the sub-image
             let mut frame = ImageBuffer::new(width, height);
@@ -142,4 +304,104 @@ impl SpriteSheet {
         
         Ok(frames)
     }
-}
\ No newline at end of file
+}
+
+/// Packs `frames` (image, duration in ms) into a grid atlas `<stem>.png` in
+/// `dir`, `columns` wide (square by default), with a `<stem>.sprite` that
+/// names the atlas as its texture and gives every cell its duration, so
+/// `SpriteSheet::from_xml_file` reads it back. Returns the atlas path.
+pub fn write_sprite(frames: &[(RgbaImage, u32)], dir: &Path, stem: &str, columns: Option<u32>) -> Result<PathBuf> {
+    if frames.is_empty() {
+        anyhow::bail!("No frames to pack into {}.png", stem);
+    }
+    let count = frames.len() as u32;
+    let columns = columns.unwrap_or_else(|| (count as f64).sqrt().ceil() as u32).clamp(1, count);
+    let rows = count.div_ceil(columns);
+    let cell_width = frames.iter().map(|(frame, _)| frame.width()).max().unwrap_or(1);
+    let cell_height = frames.iter().map(|(frame, _)| frame.height()).max().unwrap_or(1);
+    let (width, height) = (cell_width * columns, cell_height * rows);
+
+    let mut atlas = RgbaImage::new(width, height);
+    let atlas_name = format!("{}.png", stem);
+    let mut xml = format!("<Sprite>\n  <Texture path=\"{}\" />\n", atlas_name);
+    for (index, (frame, delay)) in frames.iter().enumerate() {
+        let (x, y) = (index as u32 % columns * cell_width, index as u32 / columns * cell_height);
+        atlas.copy_from(frame, x, y).context("Failed to place frame in the atlas")?;
+        let u = |px: u32| px as f64 / width as f64;
+        let v = |px: u32| px as f64 / height as f64;
+        let (right, bottom) = (x + frame.width(), y + frame.height());
+        xml.push_str(&format!(
+            "  <Cell topLeft=\"{},{}\" topRight=\"{},{}\" bottomLeft=\"{},{}\" bottomRight=\"{},{}\" duration=\"{}ms\" />\n",
+            u(x), v(y), u(right), v(y), u(x), v(bottom), u(right), v(bottom), delay
+        ));
+    }
+    xml.push_str("</Sprite>\n");
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let atlas_path = dir.join(&atlas_name);
+    atlas.save(&atlas_path).with_context(|| format!("Failed to write {}", atlas_path.display()))?;
+    let sprite_path = dir.join(format!("{}.sprite", stem));
+    std::fs::write(&sprite_path, xml).with_context(|| format!("Failed to write {}", sprite_path.display()))?;
+    Ok(atlas_path)
+}
+
+/// Index of the page whose file name is `name`, ignoring case.
+fn find_page(pages: &[PathBuf], name: &str) -> Option<usize> {
+    let name = Path::new(name).file_name()?.to_string_lossy().to_lowercase();
+    pages.iter().position(|page| page.file_name().is_some_and(|file| file.to_string_lossy().to_lowercase() == name))
+}
+
+/// Page `page` next to `anchor`: `effect_0.dds` -> `effect_2.dds` (keeping
+/// zero padding), `effect.dds` -> `effect_2.dds`.
+fn numbered_sibling(anchor: &Path, page: usize) -> PathBuf {
+    let stem = texture_stem(anchor);
+    let extension = anchor.extension().map(|ext| ext.to_string_lossy().into_owned()).unwrap_or_else(|| "dds".to_string());
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let name = match stem[..stem.len() - digits].strip_suffix('_') {
+        Some(base) if digits > 0 => {
+            let first: usize = stem[stem.len() - digits..].parse().unwrap_or(0);
+            format!("{}_{:0width$}", base, first + page, width = digits)
+        }
+        _ => format!("{}_{}", stem, page),
+    };
+    anchor.with_file_name(format!("{}.{}", name, extension))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn written_sheets_read_back_frame_for_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let frames: Vec<(RgbaImage, u32)> = (0..3u8)
+            .map(|index| (RgbaImage::from_pixel(4, 2, Rgba([index * 80, 0, 0, 255])), 40 + index as u32 * 10))
+            .collect();
+        let atlas = write_sprite(&frames, dir.path(), "fire", Some(2)).unwrap();
+        assert_eq!(atlas, dir.path().join("fire.png"));
+
+        let sprite = dir.path().join("fire.sprite");
+        let mut sheet = SpriteSheet::from_xml_file(&sprite).unwrap();
+        let pages = sheet.page_paths(Path::new("unused.dds"), &sprite);
+        assert_eq!(pages, vec![atlas.clone()]);
+        sheet.assign_pages(&pages).unwrap();
+
+        let page = image::open(&atlas).unwrap();
+        assert_eq!((page.width(), page.height()), (8, 4));
+        let extracted = sheet.extract_frames(&[page]).unwrap();
+        assert_eq!(extracted.len(), 3);
+        for ((expected, _), actual) in frames.iter().zip(&extracted) {
+            assert_eq!(expected, actual);
+        }
+        let (durations, _) = sheet.frame_durations(&[(8, 4)], SpriteTimeUnit::Auto, 100).unwrap();
+        assert_eq!(durations, vec![40, 50, 60]);
+    }
+
+    #[test]
+    fn no_frames_is_an_error_not_an_empty_atlas() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(write_sprite(&[], dir.path(), "empty", None).is_err());
+        assert!(!dir.path().join("empty.png").exists());
+        assert!(!dir.path().join("empty.sprite").exists());
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use crate::dds::DdsHeader;
+
+/// `--srgb`: how texconv treats the colour space of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SrgbMode {
+    /// Keep sRGB textures sRGB, going by the DDS format; normal maps and
+    /// masks are left alone.
+    #[default]
+    Auto,
+    /// Treat every texture as sRGB.
+    Force,
+    /// No sRGB flags; texconv converts by the formats alone.
+    Linear,
+}
+
+/// The texconv colour-space flag for a run on `source` that writes
+/// `pixel_format` under `--srgb` `mode`. sRGB data written to 8-bit formats keeps its encoding
+/// (`-srgb`); written to a float format it is linearized (`-srgbi`), as
+/// HDR formats expect.
+pub fn srgb_flag(source: &Path, pixel_format: &str, mode: SrgbMode) -> Option<&'static str> {
+    let srgb = match mode {
+        SrgbMode::Linear => false,
+        SrgbMode::Force => true,
+        SrgbMode::Auto => !is_data_texture(source) && DdsHeader::read(source).is_ok_and(|header| header.is_srgb()),
+    };
+    match (srgb, pixel_format.ends_with("_FLOAT")) {
+        (false, _) => None,
+        (true, false) => Some("-srgb"),
+        (true, true) => Some("-srgbi"),
+    }
+}
+
+/// Normal maps (`_ddna`) and masks (`_mask`) hold data, not colours.
+fn is_data_texture(source: &Path) -> bool {
+    let name = source.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let stem = name.split('.').next().unwrap_or_default();
+    stem.ends_with("_ddna") || stem.ends_with("_mask")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::dx10_header;
+
+    const R8G8B8A8_UNORM: u32 = 28;
+    const R8G8B8A8_UNORM_SRGB: u32 = 29;
+    const BC1_UNORM_SRGB: u32 = 72;
+
+    fn texture(dir: &Path, name: &str, format: u32) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, dx10_header(format, 4, 4, 1)).unwrap();
+        path
+    }
+
+    #[test]
+    fn auto_keeps_srgb_colour_textures_srgb() {
+        let dir = tempfile::tempdir().unwrap();
+        let albedo = texture(dir.path(), "rock_diff.dds", BC1_UNORM_SRGB);
+        assert_eq!(srgb_flag(&albedo, "R8G8B8A8_UNORM", SrgbMode::Auto), Some("-srgb"));
+        assert_eq!(srgb_flag(&albedo, "R32G32B32A32_FLOAT", SrgbMode::Auto), Some("-srgbi"));
+    }
+
+    #[test]
+    fn auto_leaves_linear_formats_and_data_textures_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let linear = texture(dir.path(), "rock_diff.dds", R8G8B8A8_UNORM);
+        let normal = texture(dir.path(), "rock_ddna.dds", R8G8B8A8_UNORM_SRGB);
+        let mask = texture(dir.path(), "Rock_Mask.dds", BC1_UNORM_SRGB);
+        for path in [linear, normal, mask] {
+            assert_eq!(srgb_flag(&path, "R8G8B8A8_UNORM", SrgbMode::Auto), None, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn force_and_linear_override_the_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let linear = texture(dir.path(), "rock_diff.dds", R8G8B8A8_UNORM);
+        let srgb = texture(dir.path(), "sky.dds", R8G8B8A8_UNORM_SRGB);
+        assert_eq!(srgb_flag(&linear, "R8G8B8A8_UNORM", SrgbMode::Force), Some("-srgb"));
+        assert_eq!(srgb_flag(&srgb, "R8G8B8A8_UNORM", SrgbMode::Linear), None);
+    }
+
+    #[test]
+    fn split_parts_count_as_data_textures_by_their_stem() {
+        assert!(is_data_texture(Path::new("rock_ddna.dds.1")));
+        assert!(!is_data_texture(Path::new("rock_diff.dds")));
+    }
+}
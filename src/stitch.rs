@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use image::{GenericImage, RgbaImage};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::StitchArgs;
+use crate::console::Console;
+use crate::dds::DdsHeader;
+use crate::processor::decode_to_png;
+use crate::texconv::{setup_texconv, TexconvOptions};
+use crate::utils::{find_dds_files, texture_stem};
+
+/// Tiles of one stitched image, keyed by `(x, y)` grid position.
+struct TileGroup {
+    /// Output path relative to the output folder.
+    output: PathBuf,
+    tiles: BTreeMap<(u32, u32), PathBuf>,
+}
+
+/// `--pattern`: must capture `x` and `y`; `name` (optional) groups tiles.
+pub fn parse_stitch_pattern(value: &str) -> Result<Regex, String> {
+    let regex = Regex::new(value).map_err(|e| e.to_string())?;
+    let names: Vec<&str> = regex.capture_names().flatten().collect();
+    if !names.contains(&"x") || !names.contains(&"y") {
+        return Err("the pattern needs named captures (?P<x>...) and (?P<y>...)".to_string());
+    }
+    Ok(regex)
+}
+
+pub async fn run_stitch(cli: &StitchArgs, texconv: Option<&Path>) -> Result<()> {
+    let groups = find_tile_groups(cli)?;
+    if groups.is_empty() {
+        println!("❌ No grid-named tiles matching {} found!", cli.pattern);
+        return Ok(());
+    }
+
+    let texconv_path = setup_texconv(texconv).await?;
+    let console = Console::plain();
+    println!("📊 Found {} tile grid(s)", groups.len());
+
+    let mut failures = 0;
+    let mut skipped = 0;
+    for group in &groups {
+        let output_path = cli.output.join(&group.output);
+        match stitch_group(group, &output_path, &texconv_path, cli, &console).await {
+            Ok(true) => println!("✅ Stitched {}", output_path.display()),
+            Ok(false) => skipped += 1,
+            Err(e) => {
+                failures += 1;
+                println!("❌ Failed to stitch {}: {:#}", output_path.display(), e);
+            }
+        }
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{} of {} stitched images could not be created", failures, groups.len());
+    }
+    if skipped > 0 {
+        println!("🎉 Stitching completed; {} grid(s) were skipped", skipped);
+    } else {
+        println!("🎉 All stitched images created successfully!");
+    }
+    Ok(())
+}
+
+/// Groups the DDS files under the input folder whose stems match the
+/// pattern by folder and `name` capture.
+fn find_tile_groups(cli: &StitchArgs) -> Result<Vec<TileGroup>> {
+    let mut groups: BTreeMap<PathBuf, TileGroup> = BTreeMap::new();
+
+    for file in find_dds_files(&cli.input, None, None) {
+        let stem = texture_stem(&file);
+        let Some(captures) = cli.pattern.captures(&stem) else {
+            continue;
+        };
+        let coordinate = |name: &str| captures.name(name).and_then(|m| m.as_str().parse::<u32>().ok());
+        let (Some(x), Some(y)) = (coordinate("x"), coordinate("y")) else {
+            continue;
+        };
+
+        let folder = file.parent().and_then(|parent| parent.strip_prefix(&cli.input).ok()).unwrap_or(Path::new(""));
+        let name = match captures.name("name").map(|m| m.as_str()).filter(|name| !name.is_empty()) {
+            Some(name) => name.to_string(),
+            None => folder.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| "stitched".to_string()),
+        };
+        let file_name = cli.naming.cleanup().apply(&format!("{}.png", name));
+        let output = folder.join(file_name);
+
+        let group = groups.entry(output.clone()).or_insert_with(|| TileGroup { output, tiles: BTreeMap::new() });
+        if let Some(previous) = group.tiles.insert((x, y), file.clone()) {
+            anyhow::bail!("{} and {} both claim tile x={} y={}", previous.display(), file.display(), x, y);
+        }
+    }
+
+    Ok(groups.into_values().collect())
+}
+
+/// Converts and composites one grid. Returns `false` if the user declined
+/// an image over `--max-pixels`.
+async fn stitch_group(group: &TileGroup, output_path: &Path, texconv_path: &Path, cli: &StitchArgs, console: &Console) -> Result<bool> {
+    // Sizes come from the headers so the result can be checked before decoding anything
+    let mut sizes = BTreeMap::new();
+    for (position, tile) in &group.tiles {
+        let header = DdsHeader::read(tile)?;
+        sizes.entry((header.width, header.height)).or_insert_with(Vec::new).push(*position);
+    }
+    if sizes.len() > 1 {
+        let listing: Vec<String> = sizes
+            .iter()
+            .map(|((width, height), positions)| format!("{}x{} ({} tile(s))", width, height, positions.len()))
+            .collect();
+        anyhow::bail!("tiles have different sizes: {}", listing.join(", "));
+    }
+    let (tile_width, tile_height) = *sizes.keys().next().context("empty tile group")?;
+
+    let (min_x, max_x) = bounds(group.tiles.keys().map(|(x, _)| *x));
+    let (min_y, max_y) = bounds(group.tiles.keys().map(|(_, y)| *y));
+    let (columns, rows) = (max_x - min_x + 1, max_y - min_y + 1);
+    let (width, height) = (columns as u64 * tile_width as u64, rows as u64 * tile_height as u64);
+    let pixels = width * height;
+
+    println!(
+        "🧩 {}: {}x{} tiles of {}x{} -> {}x{} ({:.1} MP)",
+        output_path.display(), columns, rows, tile_width, tile_height, width, height, pixels as f64 / 1e6
+    );
+    if width > u32::MAX as u64 || height > u32::MAX as u64 {
+        anyhow::bail!("{}x{} is too large for a single image", width, height);
+    }
+    if pixels > cli.max_pixels && !cli.yes && !confirm_large(pixels, cli.max_pixels)? {
+        println!("⏭️  Skipped {} (over --max-pixels; pass --yes to stitch anyway)", output_path.display());
+        return Ok(false);
+    }
+
+    let missing: Vec<String> = (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+        .filter(|position| !group.tiles.contains_key(position))
+        .map(|(x, y)| format!("x={} y={}", x, y))
+        .collect();
+    if !missing.is_empty() {
+        println!("⚠️  {} tile(s) missing, left transparent: {}", missing.len(), missing.join(", "));
+    }
+
+    let work_dir = tempfile::Builder::new()
+        .prefix(".dds-converter-")
+        .tempdir()
+        .context("Failed to create staging directory")?;
+    let mut decoded = Vec::new();
+    for (index, ((x, y), tile)) in group.tiles.iter().enumerate() {
+        let tile_dir = work_dir.path().join(index.to_string());
+        std::fs::create_dir_all(&tile_dir).context("Failed to create staging directory")?;
+        let png = decode_to_png(tile, texconv_path, &TexconvOptions::default(), &tile_dir, cli.verbose, console).await?;
+        if cli.verbose {
+            println!("  🔄 x={} y={}: {}", x, y, tile.display());
+        }
+        decoded.push(((x - min_x, y - min_y), png));
+    }
+
+    let canvas = composite_tiles(&decoded, tile_width, tile_height, columns, rows)?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create output directory")?;
+    }
+    canvas.save(output_path)
+        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+    Ok(true)
+}
+
+/// Places each decoded tile PNG at its `(column, row)` on a transparent
+/// canvas. Tiles are opened one at a time and must all be `tile_width` x
+/// `tile_height`.
+fn composite_tiles(tiles: &[((u32, u32), PathBuf)], tile_width: u32, tile_height: u32, columns: u32, rows: u32) -> Result<RgbaImage> {
+    let mut canvas = RgbaImage::new(columns * tile_width, rows * tile_height);
+    for ((column, row), png) in tiles {
+        let tile = image::open(png)
+            .with_context(|| format!("Failed to open {}", png.display()))?
+            .to_rgba8();
+        if tile.dimensions() != (tile_width, tile_height) {
+            anyhow::bail!(
+                "decoded tile x={} y={} is {}x{}, expected {}x{}",
+                column, row, tile.width(), tile.height(), tile_width, tile_height
+            );
+        }
+        canvas.copy_from(&tile, column * tile_width, row * tile_height)
+            .context("Failed to place tile")?;
+    }
+    Ok(canvas)
+}
+
+fn bounds(values: impl Iterator<Item = u32>) -> (u32, u32) {
+    values.fold((u32::MAX, 0), |(low, high), value| (low.min(value), high.max(value)))
+}
+
+/// Asks on the terminal whether to go over the pixel budget; without a
+/// terminal the answer is no.
+fn confirm_large(pixels: u64, budget: u64) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    print!("⚠️  {:.1} MP is over the {:.1} MP budget. Stitch anyway? [y/N] ", pixels as f64 / 1e6, budget as f64 / 1e6);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::{write_tiles, TileGrid};
+
+    #[test]
+    fn tiles_cut_by_tile_stitch_back_into_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = RgbaImage::from_fn(12, 8, |x, y| image::Rgba([x as u8 * 20, y as u8 * 30, (x + y) as u8, 255]));
+        let png = dir.path().join("map.png");
+        source.save(&png).unwrap();
+
+        // A 3x2 grid of 4x4 tiles
+        let grid = TileGrid::new(12, 8, 4);
+        let tiles: Vec<((u32, u32), PathBuf)> = write_tiles(&png, &grid, false, &dir.path().join("tiles"), "map")
+            .unwrap()
+            .into_iter()
+            .map(|(tile, path)| ((tile.column, tile.row), path))
+            .collect();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(composite_tiles(&tiles, 4, 4, 3, 2).unwrap(), source);
+    }
+
+    #[test]
+    fn missing_tiles_leave_transparent_holes_and_odd_sizes_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let tile = dir.path().join("tile.png");
+        RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255])).save(&tile).unwrap();
+
+        let canvas = composite_tiles(&[((0, 0), tile.clone()), ((1, 1), tile.clone())], 2, 2, 2, 2).unwrap();
+        assert_eq!(canvas.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(canvas.get_pixel(3, 3).0, [255, 0, 0, 255]);
+        assert_eq!(canvas.get_pixel(2, 0).0, [0, 0, 0, 0]);
+        assert_eq!(canvas.get_pixel(0, 2).0, [0, 0, 0, 0]);
+
+        assert!(composite_tiles(&[((0, 0), tile)], 4, 4, 1, 1).is_err());
+    }
+
+    #[test]
+    fn patterns_need_x_and_y_captures() {
+        let pattern = parse_stitch_pattern(r"^(?P<name>.+)_(?P<x>\d+)_(?P<y>\d+)$").unwrap();
+        let captures = pattern.captures("map_07_03").unwrap();
+        assert_eq!((&captures["name"], &captures["x"], &captures["y"]), ("map", "07", "03"));
+        assert!(parse_stitch_pattern(r"^(?P<x>\d+)$").is_err());
+        assert!(parse_stitch_pattern("(").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::console::status;
+use crate::dedupe::LinkMode;
+use crate::diff::relative_key;
+use crate::errors::{classify, ErrorCode};
+use crate::info::format_bytes;
+use crate::optimize::OptimizeStats;
+use crate::png8::PaletteStats;
+use crate::processor::{CompanionStats, FileOutcome, SkipReason};
+
+/// Per-run tallies for the end-of-run summary.
+#[derive(Default)]
+pub struct RunSummary {
+    /// Files found before filtering.
+    pub found: usize,
+    pub converted: usize,
+    /// `--write-if-changed` inputs whose outputs were already up to date.
+    pub unchanged: usize,
+    pub companions: CompanionStats,
+    /// Bytes removed by `--strip-metadata`.
+    pub metadata_stripped: u64,
+    /// `--format png8` output sizes.
+    pub palette: PaletteStats,
+    /// `--optimize` savings.
+    pub optimized: OptimizeStats,
+    pub linked: BTreeMap<LinkMode, usize>,
+    /// `--dedupe-link` duplicates and how each got its output, in the
+    /// order they finished.
+    pub linked_files: Vec<(PathBuf, LinkMode)>,
+    /// Outputs from an earlier run that were replaced, and files written
+    /// next to one under a new name (`--on-conflict` / `--interactive`).
+    pub overwritten: usize,
+    pub renamed: usize,
+    /// Inputs converted (including ones `--write-if-changed` left as they
+    /// were), in the order they finished.
+    pub converted_files: Vec<PathBuf>,
+    /// Files retried with `--gpu cpu` after texconv failed on the GPU.
+    pub gpu_fallbacks: Vec<PathBuf>,
+    /// Files that succeeded after `--retries`.
+    pub retried: Vec<PathBuf>,
+    pub skipped: BTreeMap<SkipReason, Vec<PathBuf>>,
+    pub failed: Vec<(PathBuf, ErrorCode, String)>,
+    /// Files left out by `--include` and by `--exclude`.
+    pub not_included: usize,
+    pub excluded: usize,
+    /// Set when `--limit` / `--sample` left discovered files out of the run.
+    pub selection_note: Option<String>,
+    /// Sizes of the converted inputs (with their split and alpha parts) and
+    /// of their outputs, per output format.
+    pub sizes: BTreeMap<String, SizeTotals>,
+    /// `--delete-source`: sources removed and the bytes that freed.
+    pub deleted_sources: usize,
+    pub reclaimed_bytes: u64,
+    /// (input, output) pairs as planned before collision handling, in
+    /// `{index}` order; `--watch` plans the files it picks up after them.
+    pub planned: Vec<(PathBuf, PathBuf)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct SizeTotals {
+    pub files: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl SizeTotals {
+    /// Output bytes per input byte; below 1 the conversion saved space.
+    pub fn ratio(&self) -> f64 {
+        self.output_bytes as f64 / self.input_bytes.max(1) as f64
+    }
+}
+
+impl RunSummary {
+    pub fn record(&mut self, file: &Path, result: &Result<FileOutcome>) {
+        match result {
+            Ok(FileOutcome::Converted { companions, metadata_stripped, palette, optimized, unchanged, sizes, .. }) => {
+                if *unchanged {
+                    self.unchanged += 1;
+                } else {
+                    self.converted += 1;
+                }
+                self.converted_files.push(file.to_path_buf());
+                self.companions += *companions;
+                self.metadata_stripped += metadata_stripped;
+                self.palette += *palette;
+                self.optimized += *optimized;
+                let totals = self.sizes.entry(sizes.format.clone()).or_default();
+                totals.files += 1;
+                totals.input_bytes += sizes.input_bytes;
+                totals.output_bytes += sizes.output_bytes;
+            }
+            Ok(FileOutcome::Linked { method, companions }) => {
+                *self.linked.entry(*method).or_default() += 1;
+                self.linked_files.push((file.to_path_buf(), *method));
+                self.companions += *companions;
+            }
+            Ok(FileOutcome::Skipped(reason)) => {
+                self.skipped.entry(*reason).or_default().push(file.to_path_buf());
+            }
+            Err(e) => self.failed.push((file.to_path_buf(), classify(e), format!("{:#}", e))),
+        }
+    }
+
+    /// Writes the failed inputs, then `not_attempted` (files a stopped run
+    /// never started), to `path`, one path relative to `input` per line, for
+    /// `--retry-failed`. When there is nothing to retry, a list left by an
+    /// earlier run is removed instead so it can't be retried again.
+    pub fn write_failure_list(&self, path: &Path, input: &Path, not_attempted: &[PathBuf]) -> Result<()> {
+        if self.failed.is_empty() && not_attempted.is_empty() {
+            if path.is_file() {
+                std::fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            return Ok(());
+        }
+        let mut list = String::new();
+        for file in self.failed.iter().map(|(file, _, _)| file).chain(not_attempted) {
+            list.push_str(&relative_key(input, file));
+            list.push('\n');
+        }
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, list).with_context(|| format!("Failed to write {}", path.display()))?;
+        match not_attempted.len() {
+            0 => status!("📝 {} failed file(s) listed in {}; rerun them with --retry-failed {}", self.failed.len(), path.display(), path.display()),
+            skipped => status!(
+                "📝 {} failed and {} unattempted file(s) listed in {}; rerun them with --retry-failed {}",
+                self.failed.len(), skipped, path.display(), path.display()
+            ),
+        }
+        Ok(())
+    }
+
+    /// Sizes summed over every output format.
+    pub fn total_sizes(&self) -> SizeTotals {
+        self.sizes.values().fold(SizeTotals::default(), |total, format| SizeTotals {
+            files: total.files + format.files,
+            input_bytes: total.input_bytes + format.input_bytes,
+            output_bytes: total.output_bytes + format.output_bytes,
+        })
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.values().map(Vec::len).sum()
+    }
+
+    pub fn print(&self, verbose: bool) {
+        status!("📊 Converted: {}", self.converted);
+        if self.unchanged > 0 {
+            status!("💤 Unchanged (identical output already there): {}", self.unchanged);
+        }
+        if self.overwritten > 0 {
+            status!("♻️  Overwrote existing outputs: {}", self.overwritten);
+        }
+        if self.renamed > 0 {
+            status!("✏️  Written under a new name next to existing outputs: {}", self.renamed);
+        }
+        if !self.linked.is_empty() {
+            let methods: Vec<String> = self.linked
+                .iter()
+                .map(|(method, count)| format!("{} {}", count, method.name()))
+                .collect();
+            status!("🔗 Duplicates reusing an identical output: {} ({})", self.linked.values().sum::<usize>(), methods.join(", "));
+        }
+        if self.companions.copied > 0 {
+            status!("📎 Companion files copied: {}", self.companions.copied);
+        }
+        if self.companions.identical > 0 {
+            status!("📎 Companion files already identical: {}", self.companions.identical);
+        }
+        self.print_sizes();
+        if self.deleted_sources > 0 {
+            status!("🗑️  Sources deleted: {} ({} reclaimed)", self.deleted_sources, format_bytes(self.reclaimed_bytes));
+        }
+        if self.metadata_stripped > 0 {
+            status!("🧹 Metadata stripped: {}", format_bytes(self.metadata_stripped));
+        }
+        if self.palette.rgba_bytes > 0 {
+            let saved = self.palette.rgba_bytes.saturating_sub(self.palette.indexed_bytes);
+            status!(
+                "🎨 PNG-8: {} instead of ~{} as PNG-32 ({} or {:.0}% saved)",
+                format_bytes(self.palette.indexed_bytes),
+                format_bytes(self.palette.rgba_bytes),
+                format_bytes(saved),
+                saved as f64 * 100.0 / self.palette.rgba_bytes as f64
+            );
+        }
+        if self.palette.fallbacks > 0 {
+            status!("🎨 Kept as PNG-32 (over 256 colours): {}", self.palette.fallbacks);
+        }
+        if self.optimized.files > 0 {
+            let before = self.optimized.bytes_before.max(1);
+            status!(
+                "🗜️  Optimized {} PNG(s): {} saved ({:.1}%), {:.1}s of optimization time",
+                self.optimized.files,
+                format_bytes(self.optimized.saved()),
+                self.optimized.saved() as f64 * 100.0 / before as f64,
+                self.optimized.elapsed.as_secs_f64()
+            );
+        }
+        if self.optimized.skipped > 0 {
+            status!("🗜️  Not optimized (over --optimize-max-size): {}", self.optimized.skipped);
+        }
+        if !self.gpu_fallbacks.is_empty() {
+            status!("🖥️  Retried on the CPU after a GPU failure: {}", self.gpu_fallbacks.len());
+            if verbose {
+                for file in &self.gpu_fallbacks {
+                    status!("     {}", file.display());
+                }
+            }
+        }
+
+        if !self.retried.is_empty() {
+            status!("🔁 Succeeded after retrying: {}", self.retried.len());
+            if verbose {
+                for file in &self.retried {
+                    status!("     {}", file.display());
+                }
+            }
+        }
+
+        for (reason, files) in &self.skipped {
+            status!("⏭️  Skipped ({}): {}", reason.describe(), files.len());
+            if verbose {
+                for file in files {
+                    status!("     {}", file.display());
+                }
+            }
+        }
+
+        if !self.failed.is_empty() {
+            let mut by_code: BTreeMap<&str, usize> = BTreeMap::new();
+            for (_, code, _) in &self.failed {
+                *by_code.entry(code.as_str()).or_default() += 1;
+            }
+            let codes: Vec<String> = by_code.iter().map(|(code, count)| format!("{} {}", count, code)).collect();
+            println!("❌ Failed: {} ({})", self.failed.len(), codes.join(", "));
+            if verbose {
+                for (file, code, _) in &self.failed {
+                    status!("     [{}] {}", code, file.display());
+                }
+            }
+        }
+
+        if self.not_included > 0 {
+            status!("🚫 Not matching --include: {}", self.not_included);
+        }
+        if self.excluded > 0 {
+            status!("🚫 Excluded by --exclude: {}", self.excluded);
+        }
+        if let Some(note) = &self.selection_note {
+            status!("✂️  Partial run: {}; the output tree is incomplete", note);
+        }
+    }
+
+    /// Input vs. output size per format, with a total row when there are
+    /// several formats.
+    fn print_sizes(&self) {
+        if self.sizes.is_empty() {
+            return;
+        }
+        status!("📏 Sizes of converted files:");
+        status!("     {:<8} {:>6} {:>12} {:>12} {:>8}", "format", "files", "input", "output", "ratio");
+        let row = |name: &str, totals: &SizeTotals| {
+            status!(
+                "     {:<8} {:>6} {:>12} {:>12} {:>7.2}x",
+                name,
+                totals.files,
+                format_bytes(totals.input_bytes),
+                format_bytes(totals.output_bytes),
+                totals.ratio()
+            );
+        };
+        for (format, totals) in &self.sizes {
+            row(format, totals);
+        }
+        if self.sizes.len() > 1 {
+            row("total", &self.total_sizes());
+        }
+        let total = self.total_sizes();
+        if total.output_bytes <= total.input_bytes {
+            status!("     {} saved", format_bytes(total.input_bytes - total.output_bytes));
+        } else {
+            status!("     {} more than the inputs", format_bytes(total.output_bytes - total.input_bytes));
+        }
+    }
+}
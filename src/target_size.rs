@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+use image::RgbaImage;
+use std::path::Path;
+
+/// Quality for built-in lossy encodes, and the first try of a
+/// `--target-size` search.
+pub const DEFAULT_QUALITY: u8 = 90;
+
+/// Lowest quality a `--target-size` search goes to.
+pub const MIN_QUALITY: u8 = 10;
+
+/// Encodes per search, including the first try at `DEFAULT_QUALITY`.
+const MAX_ATTEMPTS: u32 = 6;
+
+/// Outcome of a `--target-size` quality search.
+#[derive(Debug, Clone)]
+pub struct QualitySearch {
+    pub quality: u8,
+    pub bytes: Vec<u8>,
+    pub attempts: u32,
+    /// False when even `MIN_QUALITY` was over the target.
+    pub fits: bool,
+}
+
+/// Finds the highest quality whose encode is at most `target` bytes, with
+/// at most `MAX_ATTEMPTS` calls to `encode`: `DEFAULT_QUALITY` first (done
+/// if it fits), then `MIN_QUALITY` (done, over target, if that doesn't),
+/// then a binary search between the two. The same inputs always try the
+/// same qualities.
+pub fn search_quality<F>(target: u64, mut encode: F) -> Result<QualitySearch>
+where
+    F: FnMut(u8) -> Result<Vec<u8>>,
+{
+    let first = encode(DEFAULT_QUALITY)?;
+    if first.len() as u64 <= target {
+        return Ok(QualitySearch { quality: DEFAULT_QUALITY, bytes: first, attempts: 1, fits: true });
+    }
+    let smallest = encode(MIN_QUALITY)?;
+    if smallest.len() as u64 > target {
+        return Ok(QualitySearch { quality: MIN_QUALITY, bytes: smallest, attempts: 2, fits: false });
+    }
+
+    // `low` always fits and `high` never does
+    let mut best = QualitySearch { quality: MIN_QUALITY, bytes: smallest, attempts: 2, fits: true };
+    let (mut low, mut high) = (MIN_QUALITY, DEFAULT_QUALITY);
+    while best.attempts < MAX_ATTEMPTS && high - low > 1 {
+        let quality = low + (high - low) / 2;
+        let bytes = encode(quality)?;
+        best.attempts += 1;
+        if bytes.len() as u64 <= target {
+            low = quality;
+            best.quality = quality;
+            best.bytes = bytes;
+        } else {
+            high = quality;
+        }
+    }
+    Ok(best)
+}
+
+/// `image` as a lossy WebP at `quality`, alpha included.
+pub fn encode_webp(image: &RgbaImage, quality: u8) -> Result<Vec<u8>> {
+    let encoder = webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height());
+    let encoded = encoder
+        .encode_simple(false, quality as f32)
+        .map_err(|e| anyhow::anyhow!("WebP encoding failed: {:?}", e))?;
+    Ok(encoded.to_vec())
+}
+
+/// `--jpeg-subsampling`: chroma resolution of built-in JPEG encodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum JpegSubsampling {
+    /// Chroma at half width and half height: smallest files.
+    #[value(name = "420")]
+    Yuv420,
+    /// Chroma at half width.
+    #[value(name = "422")]
+    Yuv422,
+    /// Full-resolution chroma: no colour bleeding on sharp edges or gradients.
+    #[default]
+    #[value(name = "444")]
+    Yuv444,
+}
+
+impl JpegSubsampling {
+    fn sampling_factor(self) -> SamplingFactor {
+        match self {
+            JpegSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+            JpegSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+            JpegSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+        }
+    }
+}
+
+/// `image` as a baseline JPEG at `quality`; alpha is dropped.
+pub fn encode_jpeg(image: &RgbaImage, quality: u8, subsampling: JpegSubsampling) -> Result<Vec<u8>> {
+    let width = u16::try_from(image.width()).context("Image is too wide for JPEG")?;
+    let height = u16::try_from(image.height()).context("Image is too tall for JPEG")?;
+    let rgb = image::DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+    let mut bytes = Vec::new();
+    let mut encoder = Encoder::new(&mut bytes, quality);
+    encoder.set_sampling_factor(subsampling.sampling_factor());
+    encoder
+        .encode(rgb.as_raw(), width, height, ColorType::Rgb)
+        .context("JPEG encoding failed")?;
+    Ok(bytes)
+}
+
+/// Encodes the RGBA PNG at `input` as `format` (`jpg` or `webp`) at
+/// `output`, searching for a quality that fits `target` bytes when one is
+/// given and using `quality` (or `DEFAULT_QUALITY`) otherwise.
+pub fn encode_lossy(input: &Path, output: &Path, format: &str, target: Option<u64>, quality: Option<u8>, subsampling: JpegSubsampling) -> Result<QualitySearch> {
+    let image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    let encode = |quality: u8| match format {
+        "webp" => encode_webp(&image, quality),
+        _ => encode_jpeg(&image, quality, subsampling),
+    };
+    let search = match target {
+        Some(target) => search_quality(target, encode)?,
+        None => {
+            let quality = quality.unwrap_or(DEFAULT_QUALITY);
+            QualitySearch { quality, bytes: encode(quality)?, attempts: 1, fits: true }
+        }
+    };
+    std::fs::write(output, &search.bytes)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(search)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a search against an encoder whose output is 100 bytes per
+    /// quality step; returns it and the qualities tried.
+    fn mocked(target: u64) -> (QualitySearch, Vec<u8>) {
+        let mut tried = Vec::new();
+        let search = search_quality(target, |quality| {
+            tried.push(quality);
+            Ok(vec![0; quality as usize * 100])
+        })
+        .unwrap();
+        (search, tried)
+    }
+
+    #[test]
+    fn first_encode_that_fits_ends_the_search() {
+        let (search, tried) = mocked(9_000);
+        assert_eq!(tried, [90]);
+        assert_eq!((search.quality, search.attempts, search.fits), (90, 1, true));
+        assert_eq!(search.bytes.len(), 9_000);
+    }
+
+    #[test]
+    fn binary_search_stops_at_the_attempt_limit() {
+        let (search, tried) = mocked(3_400);
+        assert_eq!(tried, [90, 10, 50, 30, 40, 35]);
+        // 34 would fit too, but six encodes is the limit
+        assert_eq!((search.quality, search.attempts, search.fits), (30, 6, true));
+        assert_eq!(search.bytes.len(), 3_000);
+        // Same target, same qualities
+        assert_eq!(mocked(3_400).1, tried);
+    }
+
+    #[test]
+    fn search_moves_towards_whichever_end_fits() {
+        // Everything under 90 fits: the search climbs
+        let (search, tried) = mocked(8_999);
+        assert_eq!(tried, [90, 10, 50, 70, 80, 85]);
+        assert_eq!(search.quality, 85);
+        // Only the minimum fits: it sinks and keeps the minimum's encode
+        let (search, tried) = mocked(1_000);
+        assert_eq!(tried, [90, 10, 50, 30, 20, 15]);
+        assert_eq!((search.quality, search.fits), (10, true));
+    }
+
+    #[test]
+    fn too_large_at_minimum_quality_keeps_the_smallest_encode() {
+        let (search, tried) = mocked(500);
+        assert_eq!(tried, [90, 10]);
+        assert_eq!((search.quality, search.attempts, search.fits), (10, 2, false));
+        assert_eq!(search.bytes.len(), 1_000);
+    }
+
+    #[test]
+    fn encoder_errors_end_the_search() {
+        let error = search_quality(100, |quality| match quality {
+            DEFAULT_QUALITY => Ok(vec![0; 1_000]),
+            _ => anyhow::bail!("encoder broke at {}", quality),
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "encoder broke at 10");
+    }
+}
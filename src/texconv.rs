@@ -1,9 +1,13 @@
 use anyhow::{Result, Context};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 use tokio::fs;
 use std::env;
 
+use crate::encoding::decode_process_output;
+use crate::srgb::SrgbMode;
+
 // Embutir o texconv.exe no binário
 const TEXCONV_EXE: &[u8] = include_bytes!("../texconv.exe");
 
@@ -17,13 +21,22 @@ pub async fn test_texconv(texconv_path: &Path) -> Result<()> {
     if test_output.status.code() != Some(0) && test_output.status.code() != Some(1) {
         anyhow::bail!("texconv.exe returned unexpected error code in test: code {:?}\n{}", 
                      test_output.status.code(),
-                     String::from_utf8_lossy(&test_output.stderr));
+                     decode_process_output(&test_output.stderr));
     }
     
     Ok(())
 }
 
-pub async fn setup_texconv() -> Result<PathBuf> {
+/// The texconv to run: `custom` (`--texconv-path`) if given, else the
+/// embedded one, extracted to the temp folder.
+pub async fn setup_texconv(custom: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = custom {
+        if !path.is_file() {
+            anyhow::bail!("--texconv-path {} doesn't exist", path.display());
+        }
+        test_texconv(path).await.with_context(|| format!("Error testing {}", path.display()))?;
+        return Ok(path.to_path_buf());
+    }
     let temp_dir = env::temp_dir().join("dds-converter-rust");
     fs::create_dir_all(&temp_dir).await
         .context("Failed to create temporary directory")?;
@@ -45,3 +58,116 @@ pub async fn setup_texconv() -> Result<PathBuf> {
     
     Ok(texconv_path)
 }
+
+/// `--gpu`: the adapter texconv uses for its GPU codecs (BC6H/BC7).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TexconvGpu {
+    /// texconv's own pick.
+    #[default]
+    Auto,
+    /// An adapter index from `--list-gpus`.
+    Adapter(u32),
+    /// `-nogpu`: the software codecs.
+    Cpu,
+}
+
+pub fn parse_gpu(value: &str) -> Result<TexconvGpu, String> {
+    match value.to_lowercase().as_str() {
+        "auto" => Ok(TexconvGpu::Auto),
+        "cpu" => Ok(TexconvGpu::Cpu),
+        _ => value
+            .parse()
+            .map(TexconvGpu::Adapter)
+            .map_err(|_| format!("'{}' is not an adapter index (see --list-gpus), 'cpu' or 'auto'", value)),
+    }
+}
+
+/// `--swizzle` / `swizzle = "..."`: texconv's `-swizzle` mask, one to four
+/// of `r`, `g`, `b`, `a`, `0` and `1`.
+pub fn parse_swizzle(value: &str) -> Result<String, String> {
+    let mask = value.trim().to_lowercase();
+    if mask.is_empty() || mask.len() > 4 || !mask.chars().all(|c| "rgba01".contains(c)) {
+        return Err(format!("'{}' is not a swizzle mask: up to four of r, g, b, a, 0 and 1 (e.g. bgra or rrr1)", value));
+    }
+    Ok(mask)
+}
+
+/// How every texconv run of a command is made: the `--gpu` adapter, the
+/// `--timeout` limit, the `--srgb` mode, the `--swizzle` mask and the
+/// `--texconv-arg` switches.
+#[derive(Debug, Clone, Default)]
+pub struct TexconvOptions {
+    pub gpu: TexconvGpu,
+    pub timeout: Option<Duration>,
+    pub srgb: SrgbMode,
+    /// Applied where texconv reads the texture itself, not to the tool's
+    /// own intermediate PNGs, so channels are only swapped once.
+    pub swizzle: Option<String>,
+    pub extra_args: Vec<String>,
+    /// `--verbose`: print each command line before running it.
+    pub echo: bool,
+}
+
+/// A texconv command with the `--gpu` arguments already in place; every
+/// texconv run starts from here.
+pub fn texconv_command(texconv_path: &Path, options: &TexconvOptions) -> tokio::process::Command {
+    crate::adaptive::texconv_spawned();
+    let mut command = tokio::process::Command::new(texconv_path);
+    match options.gpu {
+        TexconvGpu::Auto => {}
+        TexconvGpu::Adapter(index) => {
+            command.arg("-gpu").arg(index.to_string());
+        }
+        TexconvGpu::Cpu => {
+            command.arg("-nogpu");
+        }
+    }
+    command
+}
+
+/// `command` as a line to show the user, with arguments that contain
+/// spaces quoted.
+pub fn command_line(command: &tokio::process::Command) -> String {
+    let command = command.as_std();
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.contains(' ') { format!("\"{}\"", arg) } else { arg.into_owned() }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The adapters texconv lists under `<adapter>:` at the end of its usage
+/// text, e.g. `0: VID:10DE, PID:2520 - NVIDIA GeForce RTX 3060 Laptop GPU`.
+pub fn list_adapters(texconv_path: &Path) -> Result<Vec<String>> {
+    let output = Command::new(texconv_path)
+        .arg("-h")
+        .output()
+        .context("Failed to run texconv")?;
+    let usage = decode_process_output(&output.stdout);
+    Ok(usage
+        .lines()
+        .skip_while(|line| line.trim() != "<adapter>:")
+        .skip(1)
+        .map(str::trim)
+        .take_while(|line| line.split_once(':').is_some_and(|(index, _)| index.parse::<u32>().is_ok()))
+        .map(str::to_string)
+        .collect())
+}
+
+/// `--list-gpus`
+pub async fn run_list_gpus(texconv: Option<&Path>) -> Result<()> {
+    let texconv_path = setup_texconv(texconv).await?;
+    let adapters = list_adapters(&texconv_path)?;
+    if adapters.is_empty() {
+        println!("❌ texconv found no GPU adapters; use --gpu cpu");
+        return Ok(());
+    }
+    println!("🖥️  GPU adapters (pass the index to --gpu, or --gpu cpu for the software codecs):");
+    for adapter in adapters {
+        println!("  {}", adapter);
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use image::{GenericImage, RgbaImage};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::crop::{CropMode, CropRect};
+use crate::dds::DdsHeader;
+use crate::utils::texture_stem;
+
+/// `--tile N`: how a `width` x `height` image splits into `tile_size`
+/// squares, left to right and top to bottom.
+#[derive(Debug, Clone, Copy)]
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+/// One cell of a `TileGrid`; edge cells can be smaller than the tile size.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Tile {
+    pub column: u32,
+    pub row: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileGrid {
+    pub fn new(width: u32, height: u32, tile_size: u32) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+            columns: width.div_ceil(tile_size),
+            rows: height.div_ceil(tile_size),
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..self.rows).flat_map(move |row| {
+            (0..self.columns).map(move |column| {
+                let (x, y) = (column * self.tile_size, row * self.tile_size);
+                Tile {
+                    column,
+                    row,
+                    x,
+                    y,
+                    width: self.tile_size.min(self.width - x),
+                    height: self.tile_size.min(self.height - y),
+                }
+            })
+        })
+    }
+
+    /// `x03_y05`, zero-padded to the widest coordinate (at least two digits)
+    /// so tile names sort in grid order.
+    pub fn suffix(&self, tile: &Tile) -> String {
+        let digits = (self.columns.max(self.rows).saturating_sub(1)).to_string().len().max(2);
+        format!("x{:0digits$}_y{:0digits$}", tile.column, tile.row, digits = digits)
+    }
+}
+
+/// The grid `file` will get, going by its DDS header (and `--crop`), for
+/// `--dry-run`. `None` when the header can't be read or the crop won't fit.
+pub fn planned_grid(file: &Path, tile_size: u32, crop: Option<CropRect>, crop_mode: CropMode) -> Option<TileGrid> {
+    let header = DdsHeader::read(file).ok()?;
+    let (width, height) = match crop {
+        Some(rect) => {
+            let fitted = rect.fit(header.width, header.height, crop_mode)?;
+            (fitted.width, fitted.height)
+        }
+        None => (header.width, header.height),
+    };
+    Some(TileGrid::new(width, height, tile_size))
+}
+
+/// Slices the PNG at `png` into `out_dir`, one PNG per tile named
+/// `<stem>_<suffix>.png`. With `pad`, edge tiles are filled out to the full
+/// tile size with transparent pixels. Only the one decoded source is held
+/// in memory; each tile is written as soon as it is cut.
+pub fn write_tiles(png: &Path, grid: &TileGrid, pad: bool, out_dir: &Path, stem: &str) -> Result<Vec<(Tile, PathBuf)>> {
+    let image = image::open(png)
+        .with_context(|| format!("Failed to open {}", png.display()))?
+        .to_rgba8();
+    std::fs::create_dir_all(out_dir).context("Failed to create tile directory")?;
+
+    let mut written = Vec::new();
+    for tile in grid.tiles() {
+        let cut = image::imageops::crop_imm(&image, tile.x, tile.y, tile.width, tile.height).to_image();
+        let cut = if pad && (tile.width < grid.tile_size || tile.height < grid.tile_size) {
+            let mut padded = RgbaImage::new(grid.tile_size, grid.tile_size);
+            padded.copy_from(&cut, 0, 0).context("Failed to pad edge tile")?;
+            padded
+        } else {
+            cut
+        };
+
+        let path = out_dir.join(format!("{}_{}.png", stem, grid.suffix(&tile)));
+        cut.save(&path).with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push((tile, path));
+    }
+    Ok(written)
+}
+
+/// `<stem>.tiles.json`, written with `--tile-manifest` for deep-zoom viewers.
+#[derive(Serialize)]
+pub struct TileManifest {
+    pub source: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+    pub padded: bool,
+    pub columns: u32,
+    pub rows: u32,
+    pub tiles: Vec<TileEntry>,
+}
+
+#[derive(Serialize)]
+pub struct TileEntry {
+    /// Output file name, next to the manifest.
+    pub file: String,
+    #[serde(flatten)]
+    pub tile: Tile,
+}
+
+/// `dir/name.png` -> `dir/name.tiles.json`
+pub fn manifest_path(output: &Path) -> PathBuf {
+    output.with_file_name(format!("{}.tiles.json", texture_stem(output)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width` x `height` image where every pixel is unique.
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| image::Rgba([x as u8, y as u8, (x * 7 + y * 13) as u8, 255]))
+    }
+
+    #[test]
+    fn edge_tiles_are_smaller_and_names_sort_in_grid_order() {
+        let grid = TileGrid::new(10, 7, 4);
+        assert_eq!((grid.columns, grid.rows, grid.count()), (3, 2, 6));
+        let tiles: Vec<Tile> = grid.tiles().collect();
+        let last = tiles.last().unwrap();
+        assert_eq!((last.x, last.y, last.width, last.height), (8, 4, 2, 3));
+        assert_eq!(grid.suffix(&tiles[1]), "x01_y00");
+        assert_eq!(TileGrid::new(4096, 16, 256).suffix(&Tile { column: 15, row: 0, x: 0, y: 0, width: 1, height: 1 }), "x15_y00");
+    }
+
+    #[test]
+    fn tiles_reassemble_to_the_source_without_seams() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = gradient(10, 7);
+        let png = dir.path().join("map.png");
+        source.save(&png).unwrap();
+
+        let grid = TileGrid::new(10, 7, 4);
+        let written = write_tiles(&png, &grid, false, &dir.path().join("tiles"), "map").unwrap();
+        assert_eq!(written.len(), 6);
+        let mut reassembled = RgbaImage::new(10, 7);
+        for (tile, path) in &written {
+            assert!(path.file_name().unwrap().to_string_lossy().starts_with("map_x0"));
+            let cut = image::open(path).unwrap().to_rgba8();
+            assert_eq!(cut.dimensions(), (tile.width, tile.height));
+            reassembled.copy_from(&cut, tile.x, tile.y).unwrap();
+        }
+        assert_eq!(reassembled, source);
+    }
+
+    #[test]
+    fn padded_edge_tiles_are_full_size_and_transparent_outside_the_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("map.png");
+        gradient(10, 7).save(&png).unwrap();
+
+        let written = write_tiles(&png, &TileGrid::new(10, 7, 4), true, dir.path(), "map").unwrap();
+        let (_, corner) = written.last().unwrap();
+        let corner = image::open(corner).unwrap().to_rgba8();
+        assert_eq!(corner.dimensions(), (4, 4));
+        assert_eq!(corner.get_pixel(1, 2).0, [9, 6, (9 * 7 + 6 * 13) as u8, 255]);
+        assert_eq!(corner.get_pixel(2, 0).0[3], 0);
+        assert_eq!(corner.get_pixel(0, 3).0[3], 0);
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::errors::{Classified, ErrorCode};
+
+/// `Command::output`, but a run that outlives `limit` (`--timeout`) is
+/// killed and fails with `ErrorCode::Timeout`. `program` names it in that
+/// error.
+pub async fn output_with_timeout(command: &mut Command, limit: Option<Duration>, program: &str) -> Result<Output> {
+    // Dropping the child when the limit hits kills it
+    command.stdin(Stdio::null()).kill_on_drop(true);
+    let Some(limit) = limit else {
+        return command.output().await.with_context(|| format!("Failed to run {}", program));
+    };
+    match tokio::time::timeout(limit, command.output()).await {
+        Ok(output) => output.with_context(|| format!("Failed to run {}", program)),
+        Err(_) => Err(Classified::error(
+            ErrorCode::Timeout,
+            format!("{} timed out after {}s and was stopped", program, limit.as_secs()),
+        )),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    use crate::errors::classify;
+
+    #[tokio::test]
+    async fn a_run_past_the_limit_is_stopped() {
+        let started = Instant::now();
+        let mut command = Command::new("sleep");
+        command.arg("5");
+        let error = output_with_timeout(&mut command, Some(Duration::from_millis(200)), "sleep").await.unwrap_err();
+        assert_eq!(classify(&error), ErrorCode::Timeout);
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn a_run_within_the_limit_returns_its_output() {
+        let mut command = Command::new("echo");
+        command.arg("done");
+        let output = output_with_timeout(&mut command, Some(Duration::from_secs(5)), "echo").await.unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"done\n");
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+use crate::utils::texture_stem;
+
+/// An sRGB colour that textures are multiplied by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tint(pub [u8; 3]);
+
+/// `#RRGGBB`, `RRGGBB` or `0xRRGGBB`.
+pub fn parse_tint(value: &str) -> Result<Tint, String> {
+    let trimmed = value.trim();
+    let hex = trimmed.strip_prefix('#').or_else(|| trimmed.strip_prefix("0x")).unwrap_or(trimmed);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a colour like #ffb000", value));
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap_or(0);
+    Ok(Tint([channel(0), channel(1), channel(2)]))
+}
+
+impl fmt::Display for Tint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+pub fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (encoded * 255.0).round() as u8
+}
+
+impl Tint {
+    /// Multiplies the RGB of every pixel by the tint in linear light, so a
+    /// mid-gray mask keeps its brightness relative to the tint instead of
+    /// going muddy. Alpha is left alone.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        let linear: Vec<f32> = (0..=255u8).map(srgb_to_linear).collect();
+        let factors = self.0.map(|channel| linear[channel as usize]);
+        for pixel in image.pixels_mut() {
+            for (channel, factor) in factors.iter().enumerate() {
+                pixel[channel] = linear_to_srgb(linear[pixel[channel] as usize] * factor);
+            }
+        }
+    }
+}
+
+/// Writes the PNG at `input`, tinted, to `output`.
+pub fn tint_png(input: &Path, output: &Path, tint: Tint) -> Result<()> {
+    let mut image = image::open(input)
+        .with_context(|| format!("Failed to open {}", input.display()))?
+        .to_rgba8();
+    tint.apply(&mut image);
+    image.save(output)
+        .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+/// One entry of a `--tint-map` file: textures matching `pattern` get an
+/// extra output named `<stem>_<suffix>`, tinted with `color`.
+#[derive(Debug, Clone)]
+pub struct TintVariant {
+    pub suffix: String,
+    pub color: Tint,
+    pub pattern: Option<String>,
+    regex: Option<Regex>,
+}
+
+impl TintVariant {
+    /// Whether `file` matches the pattern, by file name or stem; every file
+    /// matches an entry without one.
+    pub fn matches(&self, file: &Path) -> bool {
+        let Some(regex) = &self.regex else {
+            return true;
+        };
+        let name = file.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        regex.is_match(&name) || regex.is_match(&texture_stem(file))
+    }
+}
+
+/// `--tint-map`: rarity or faction variants, one output per matching entry.
+#[derive(Debug, Clone)]
+pub struct TintMap {
+    pub variants: Vec<TintVariant>,
+}
+
+impl TintMap {
+    pub fn variants_for(&self, file: &Path) -> Vec<&TintVariant> {
+        self.variants.iter().filter(|variant| variant.matches(file)).collect()
+    }
+}
+
+/// Reads a `--tint-map` TOML file. Keys are output suffixes; values are a
+/// colour, or a table with `color` and an optional `match` file name
+/// pattern (`*` and `?` wildcards):
+///
+/// ```toml
+/// common = "#ffffff"
+/// rare = { color = "#0070dd", match = "icon_*" }
+/// ```
+pub fn load_tint_map(path: &str) -> Result<TintMap, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parse_tint_map(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn parse_tint_map(text: &str) -> Result<TintMap, String> {
+    let value: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let table = value.as_table().ok_or("expected a table of suffix = colour entries")?;
+
+    let mut variants = Vec::new();
+    for (suffix, entry) in table {
+        if suffix.is_empty() || suffix.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|']) {
+            return Err(format!("'{}' is not a usable file name suffix", suffix));
+        }
+        let (color, pattern) = match entry {
+            toml::Value::String(color) => (color.as_str(), None),
+            toml::Value::Table(entry) => {
+                let color = entry
+                    .get("color")
+                    .and_then(|color| color.as_str())
+                    .ok_or_else(|| format!("'{}' needs a color, e.g. color = \"#0070dd\"", suffix))?;
+                let pattern = match entry.get("match") {
+                    Some(pattern) => Some(pattern.as_str().ok_or_else(|| format!("'{}.match' must be a string", suffix))?),
+                    None => None,
+                };
+                (color, pattern)
+            }
+            _ => return Err(format!("'{}' must be a colour or a table with color and match", suffix)),
+        };
+        let regex = pattern
+            .map(|pattern| {
+                let body: String = pattern
+                    .chars()
+                    .map(|c| match c {
+                        '*' => ".*".to_string(),
+                        '?' => ".".to_string(),
+                        c => regex::escape(&c.to_string()),
+                    })
+                    .collect();
+                Regex::new(&format!("(?i)^{}$", body)).map_err(|e| e.to_string())
+            })
+            .transpose()?;
+        variants.push(TintVariant {
+            suffix: suffix.clone(),
+            color: parse_tint(color).map_err(|e| format!("{}: {}", suffix, e))?,
+            pattern: pattern.map(str::to_string),
+            regex,
+        });
+    }
+    if variants.is_empty() {
+        return Err("no tints defined".to_string());
+    }
+    Ok(TintMap { variants })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tinting_multiplies_in_linear_light() {
+        let tint = parse_tint("#ff8000").unwrap();
+        let mut image = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { image::Rgba([255, 255, 255, 255]) } else { image::Rgba([128, 128, 128, 77]) });
+        tint.apply(&mut image);
+        // White takes the tint's colour exactly
+        assert_eq!(image.get_pixel(0, 0).0, [255, 128, 0, 255]);
+        // 128 is 0.2159 linear; times 1.0, 0.2159 and 0.0 that is 0.2159
+        // (128 again), 0.0466 (61 in sRGB) and 0; alpha is kept
+        assert_eq!(image.get_pixel(1, 0).0, [128, 61, 0, 77]);
+    }
+
+    #[test]
+    fn colours_parse_with_or_without_a_prefix() {
+        assert_eq!(parse_tint("#0070dd"), Ok(Tint([0x00, 0x70, 0xdd])));
+        assert_eq!(parse_tint("0x0070DD"), Ok(Tint([0x00, 0x70, 0xdd])));
+        assert_eq!(parse_tint("0070dd").unwrap().to_string(), "#0070dd");
+        assert!(parse_tint("#07d").is_err());
+        assert!(parse_tint("#0070zz").is_err());
+    }
+
+    #[test]
+    fn tint_map_entries_match_by_pattern() {
+        let map = parse_tint_map("common = \"#ffffff\"\nrare = { color = \"#0070dd\", match = \"icon_*\" }\n").unwrap();
+        let suffixes = |file: &str| map.variants_for(Path::new(file)).iter().map(|variant| variant.suffix.clone()).collect::<Vec<_>>();
+        assert_eq!(suffixes("ui/Icon_Sword.dds"), ["common", "rare"]);
+        assert_eq!(suffixes("ui/banner.dds"), ["common"]);
+        assert!(parse_tint_map("rare = { match = \"*\" }").unwrap_err().contains("needs a color"));
+        assert!(parse_tint_map("\"a/b\" = \"#ffffff\"").unwrap_err().contains("not a usable file name suffix"));
+    }
+}
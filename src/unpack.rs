@@ -0,0 +1,145 @@
+use image::{GrayImage, RgbaImage};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::utils::texture_stem;
+
+const CHANNELS: [&str; 4] = ["r", "g", "b", "a"];
+
+/// `--unpack r=ao,g=roughness,b=metal[,a=height][,match=*_orm]`
+#[derive(Debug, Clone)]
+pub struct UnpackSpec {
+    /// Channel index and output suffix, in R, G, B, A order.
+    pub channels: Vec<(usize, String)>,
+    /// Inputs to unpack; `*` is the name the outputs are suffixed onto.
+    pub filter: Option<Regex>,
+}
+
+pub fn parse_unpack_spec(value: &str) -> Result<UnpackSpec, String> {
+    let mut suffixes: [Option<String>; 4] = Default::default();
+    let mut filter = None;
+
+    for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' should be CHANNEL=SUFFIX (e.g. r=ao)", part))?;
+        let (key, value) = (key.trim().to_lowercase(), value.trim());
+        if key == "match" {
+            if value.matches('*').count() != 1 || value.contains(['/', '\\']) {
+                return Err(format!("match={} needs exactly one '*' and no folders (e.g. *_orm)", value));
+            }
+            let (before, after) = value.split_once('*').unwrap_or_default();
+            let after = after.strip_suffix(".dds").unwrap_or(after);
+            filter = Some(
+                Regex::new(&format!("(?i)^{}(.+){}(\\.dds)?$", regex::escape(before), regex::escape(after)))
+                    .map_err(|e| e.to_string())?,
+            );
+            continue;
+        }
+        let index = CHANNELS
+            .iter()
+            .position(|channel| *channel == key)
+            .ok_or_else(|| format!("unknown channel '{}'; use r, g, b, a or match", key))?;
+        if suffixes[index].is_some() {
+            return Err(format!("channel '{}' is given twice", key));
+        }
+        if value.is_empty() || value.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|']) {
+            return Err(format!("'{}' is not a usable file name suffix", value));
+        }
+        if suffixes.iter().flatten().any(|suffix| suffix.eq_ignore_ascii_case(value)) {
+            return Err(format!("suffix '{}' is used for two channels", value));
+        }
+        suffixes[index] = Some(value.to_string());
+    }
+
+    let channels: Vec<(usize, String)> = suffixes
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, suffix)| suffix.map(|suffix| (index, suffix)))
+        .collect();
+    if channels.is_empty() {
+        return Err("map at least one channel, e.g. r=ao".to_string());
+    }
+    Ok(UnpackSpec { channels, filter })
+}
+
+impl UnpackSpec {
+    /// Whether `file` is unpacked at all, per `match=`.
+    pub fn matches(&self, file: &Path) -> bool {
+        self.base_name(file).is_some()
+    }
+
+    /// The name the channel suffixes go onto: what `*` matched, or the
+    /// plain stem without `match=`.
+    fn base_name(&self, file: &Path) -> Option<String> {
+        let name = file.file_name()?.to_string_lossy();
+        match &self.filter {
+            Some(filter) => filter.captures(&name).map(|captures| captures[1].to_string()),
+            None => Some(texture_stem(file)),
+        }
+    }
+
+    /// Where the outputs for `file` would sit if it were named after its
+    /// base name, for the usual output path mapping.
+    pub fn output_input_path(&self, file: &Path) -> Option<PathBuf> {
+        Some(file.with_file_name(format!("{}.dds", self.base_name(file)?)))
+    }
+}
+
+/// Channel `index` of `image` as a grayscale image.
+pub fn extract_channel(image: &RgbaImage, index: usize) -> GrayImage {
+    GrayImage::from_fn(image.width(), image.height(), |x, y| image::Luma([image.get_pixel(x, y)[index]]))
+}
+
+/// The value every pixel has if the channel is all 0 or all 255.
+pub fn constant_value(channel: &GrayImage) -> Option<u8> {
+    let first = channel.pixels().next()?[0];
+    (matches!(first, 0 | 255) && channel.pixels().all(|pixel| pixel[0] == first)).then_some(first)
+}
+
+/// `r`, `g`, `b` or `a`.
+pub fn channel_name(index: usize) -> &'static str {
+    CHANNELS[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_channel_comes_out_with_its_own_values() {
+        let image = RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8 * 10, y as u8 * 50, 128, 255 - x as u8]));
+        let expected: [&dyn Fn(u32, u32) -> u8; 4] = [&|x, _| x as u8 * 10, &|_, y| y as u8 * 50, &|_, _| 128, &|x, _| 255 - x as u8];
+        for (index, value) in expected.iter().enumerate() {
+            let channel = extract_channel(&image, index);
+            assert_eq!(channel.dimensions(), (3, 2));
+            for (x, y, pixel) in channel.enumerate_pixels() {
+                assert_eq!(pixel[0], value(x, y), "channel {} at {},{}", channel_name(index), x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn only_all_black_or_all_white_channels_are_constant() {
+        assert_eq!(constant_value(&GrayImage::from_pixel(4, 4, image::Luma([0]))), Some(0));
+        assert_eq!(constant_value(&GrayImage::from_pixel(4, 4, image::Luma([255]))), Some(255));
+        assert_eq!(constant_value(&GrayImage::from_pixel(4, 4, image::Luma([128]))), None);
+        let mut mixed = GrayImage::from_pixel(4, 4, image::Luma([255]));
+        mixed.put_pixel(3, 3, image::Luma([254]));
+        assert_eq!(constant_value(&mixed), None);
+    }
+
+    #[test]
+    fn outputs_are_named_after_what_match_captured() {
+        let spec = parse_unpack_spec("r=ao,g=roughness,b=metal,match=*_orm").unwrap();
+        assert_eq!(spec.channels, [(0, "ao".to_string()), (1, "roughness".to_string()), (2, "metal".to_string())]);
+        assert!(spec.matches(Path::new("maps/rock_ORM.dds")));
+        assert!(!spec.matches(Path::new("maps/rock_n.dds")));
+        assert_eq!(spec.output_input_path(Path::new("maps/rock_orm.dds")), Some(PathBuf::from("maps/rock.dds")));
+
+        let error = |spec: &str| parse_unpack_spec(spec).unwrap_err();
+        assert!(error("r=ao,g=AO").contains("used for two channels"));
+        assert!(error("r=a/o").contains("not a usable file name suffix"));
+        assert!(error("match=*_orm").contains("at least one channel"));
+    }
+}
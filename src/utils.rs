@@ -1,9 +1,20 @@
-use std::path::PathBuf;
+use clap::ValueEnum;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
 use walkdir::WalkDir;
 
-pub fn find_dds_files(input_dir: &std::path::Path) -> Vec<PathBuf> {
+use crate::cli::SelectionArgs;
+use crate::processor::SkipReason;
+
+/// `.dds` files under `input_dir`, at most `max_depth` levels down (1 is
+/// only the files directly inside it).
+pub fn find_dds_files(input_dir: &std::path::Path, excluded: Option<&Path>, max_depth: Option<usize>) -> Vec<PathBuf> {
     WalkDir::new(input_dir)
+        .max_depth(max_depth.unwrap_or(usize::MAX))
+        .sort_by_file_name()
         .into_iter()
+        .filter_entry(|entry| !is_excluded(entry.path(), excluded))
         .filter_map(|entry| {
             let entry = entry.ok()?;
             let path = entry.path();
@@ -18,3 +29,514 @@ pub fn find_dds_files(input_dir: &std::path::Path) -> Vec<PathBuf> {
         })
         .collect()
 }
+
+/// Splits `files` into those within `--min-size` / `--max-size` and those
+/// outside, with why. Files whose size can't be read are kept, to fail
+/// with a proper error later.
+pub fn filter_by_size(files: Vec<PathBuf>, min: u64, max: Option<u64>) -> (Vec<PathBuf>, Vec<(PathBuf, SkipReason)>) {
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    for file in files {
+        match std::fs::metadata(&file).map(|metadata| metadata.len()) {
+            Ok(size) if size < min => skipped.push((file, SkipReason::TooSmall)),
+            Ok(size) if max.is_some_and(|max| size > max) => skipped.push((file, SkipReason::TooLarge)),
+            _ => kept.push(file),
+        }
+    }
+    (kept, skipped)
+}
+
+/// Splits a single-file `--input` into the folder it sits in, used as the
+/// input root, and the file under that root.
+pub fn single_file_root(file: &Path) -> (PathBuf, PathBuf) {
+    let root = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file = root.join(file.file_name().unwrap_or_default());
+    (root, file)
+}
+
+/// File name without its final extension, keeping every other dot intact
+/// (`ui.hud.compass.dds` -> `ui.hud.compass`, `foo.dds.dds` -> `foo.dds`).
+pub fn texture_stem(path: &Path) -> String {
+    let file_name = path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    match file_name.rfind('.') {
+        Some(pos) if pos > 0 => file_name[..pos].to_string(),
+        _ => file_name,
+    }
+}
+
+/// Output stem of a texture: the name with exactly one final `.dds`
+/// removed, whatever its case (`foo.DDS` -> `foo`, `foo.1.dds` -> `foo.1`,
+/// `foo.dds.dds` -> `foo.dds`). Other names lose their final extension
+/// like with `texture_stem`.
+pub fn dds_stem(path: &Path) -> String {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let split = file_name.len().checked_sub(4).filter(|&split| split > 0 && file_name.is_char_boundary(split));
+    match split {
+        Some(split) if file_name[split..].eq_ignore_ascii_case(".dds") => file_name[..split].to_string(),
+        _ => texture_stem(path),
+    }
+}
+
+/// `dir/name.png` + `2` -> `dir/name_2.png`
+pub fn with_suffix(output: &Path, suffix: &str) -> PathBuf {
+    let stem = texture_stem(output);
+    let name = match output.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, suffix),
+    };
+    output.with_file_name(name)
+}
+
+pub fn is_excluded(path: &Path, excluded: Option<&Path>) -> bool {
+    excluded.map(|dir| path.starts_with(dir)).unwrap_or(false)
+}
+
+/// `--include` / `--exclude` pattern, matched case-insensitively against a path relative
+/// to `--input` with `/` separators: `*` and `?` stay within one folder,
+/// `**` spans any number of them. A pattern without `/` is matched against
+/// the file name alone, like in `.gitignore`.
+#[derive(Debug, Clone)]
+pub struct GlobPattern {
+    pub pattern: String,
+    regex: Regex,
+}
+
+pub fn parse_glob(value: &str) -> Result<GlobPattern, String> {
+    let pattern = value.trim().replace('\\', "/");
+    if pattern.is_empty() {
+        return Err("the pattern is empty".to_string());
+    }
+    let mut regex = String::from("(?i)^");
+    let mut chars = pattern.trim_start_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no folder at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    regex.push('$');
+    let regex = Regex::new(&regex).map_err(|e| e.to_string())?;
+    Ok(GlobPattern { pattern, regex })
+}
+
+impl GlobPattern {
+    /// Whether `relative` (with `/` separators) matches.
+    pub fn matches(&self, relative: &str) -> bool {
+        if self.pattern.contains('/') {
+            self.regex.is_match(relative)
+        } else {
+            self.regex.is_match(relative.rsplit('/').next().unwrap_or(relative))
+        }
+    }
+}
+
+/// Discovered files after `--include` and `--exclude`.
+#[derive(Debug, Default)]
+pub struct PathFilter {
+    pub kept: Vec<PathBuf>,
+    /// Files that matched no `--include` pattern.
+    pub not_included: usize,
+    /// Included files that matched an `--exclude` pattern, each with the
+    /// first one it matched.
+    pub excluded: Vec<(PathBuf, String)>,
+}
+
+/// Keeps the `files` whose path relative to `input_dir` matches any of
+/// `include` (all of them if it's empty) and none of `exclude`.
+pub fn filter_paths(files: Vec<PathBuf>, input_dir: &Path, include: &[GlobPattern], exclude: &[GlobPattern]) -> PathFilter {
+    let mut filter = PathFilter::default();
+    for file in files {
+        let relative = file.strip_prefix(input_dir).unwrap_or(&file).to_string_lossy().replace('\\', "/");
+        if !include.is_empty() && !include.iter().any(|pattern| pattern.matches(&relative)) {
+            filter.not_included += 1;
+            continue;
+        }
+        match exclude.iter().find(|pattern| pattern.matches(&relative)) {
+            Some(pattern) => filter.excluded.push((file, pattern.pattern.clone())),
+            None => filter.kept.push(file),
+        }
+    }
+    filter
+}
+
+/// Where the output directory sits relative to the input tree.
+pub enum OutputPlacement {
+    Separate,
+    Same,
+    /// Output lives inside the input tree; holds the output path as WalkDir
+    /// will see it (input + relative part), so it can be filtered directly.
+    Nested(PathBuf),
+}
+
+pub fn output_placement(input_dir: &Path, output_dir: &Path) -> OutputPlacement {
+    let input = canonicalize_lenient(input_dir);
+    let output = canonicalize_lenient(output_dir);
+
+    match output.strip_prefix(&input) {
+        Ok(relative) if relative.as_os_str().is_empty() => OutputPlacement::Same,
+        Ok(relative) => OutputPlacement::Nested(input_dir.join(relative)),
+        Err(_) => OutputPlacement::Separate,
+    }
+}
+
+/// Canonicalizes a path that may not exist yet by resolving its longest
+/// existing ancestor and re-appending the rest.
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+
+    let absolute = std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+
+    let mut existing = absolute.as_path();
+    let mut remainder = Vec::new();
+    while let Some(parent) = existing.parent() {
+        if let Some(name) = existing.file_name() {
+            remainder.push(name.to_os_string());
+        }
+        existing = parent;
+        if let Ok(canonical) = existing.canonicalize() {
+            return remainder.iter().rev().fold(canonical, |acc, name| acc.join(name));
+        }
+    }
+
+    absolute
+}
+
+/// Applies `--limit` / `--sample` to the discovered items. The returned note
+/// describes the cut (e.g. "first 10 of 5000") and is `None` when every item
+/// is kept, so callers can tell users the output tree is partial.
+pub fn select_items<T>(items: Vec<T>, selection: &SelectionArgs) -> (Vec<T>, Option<String>) {
+    let total = items.len();
+
+    if let Some(limit) = selection.limit.map(|limit| limit as usize).filter(|&limit| limit < total) {
+        let mut items = items;
+        items.truncate(limit);
+        return (items, Some(format!("limited to the first {} of {}", limit, total)));
+    }
+
+    if let Some(count) = selection.sample.filter(|&count| count < total) {
+        let seed = selection.seed.unwrap_or_else(|| fastrand::u64(..));
+        let mut rng = fastrand::Rng::with_seed(seed);
+
+        // Partial Fisher-Yates over the indices, then keep discovery order
+        let mut indices: Vec<usize> = (0..total).collect();
+        for i in 0..count {
+            let j = rng.usize(i..total);
+            indices.swap(i, j);
+        }
+        let mut picked = indices[..count].to_vec();
+        picked.sort_unstable();
+
+        let mut picked = picked.into_iter().peekable();
+        let items = items
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, item)| picked.next_if_eq(&i).map(|_| item))
+            .collect();
+        return (items, Some(format!("random sample of {} of {} (--seed {})", count, total, seed)));
+    }
+
+    (items, None)
+}
+
+/// Names Windows refuses for files regardless of extension (`aux.png` is as
+/// reserved as `aux`).
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_invalid_name_char(c: char) -> bool {
+    matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') || c.is_control()
+}
+
+/// Makes a single path component valid on Windows: invalid characters become
+/// `replacement`, trailing dots and spaces are replaced, and reserved device
+/// names get a `_` appended to the part before the first dot.
+pub fn sanitize_file_name(name: &str, replacement: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if is_invalid_name_char(c) { replacement.to_string() } else { c.to_string() })
+        .collect();
+
+    let trimmed_len = sanitized.trim_end_matches(['.', ' ']).len();
+    if trimmed_len < sanitized.len() {
+        let trailing = sanitized.len() - trimmed_len;
+        sanitized.truncate(trimmed_len);
+        sanitized.push_str(&replacement.repeat(trailing));
+    }
+
+    let base_len = sanitized.find('.').unwrap_or(sanitized.len());
+    if RESERVED_NAMES.iter().any(|reserved| sanitized[..base_len].eq_ignore_ascii_case(reserved)) {
+        sanitized.insert(base_len, '_');
+    }
+
+    sanitized
+}
+
+/// `--normalize-unicode`: the form output names are written in, so a name
+/// typed on Windows and one unpacked from a macOS archive (decomposed)
+/// come out as the same bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum UnicodeForm {
+    /// Composed: `é` as one code point
+    #[default]
+    Nfc,
+    /// Decomposed: `e` followed by a combining accent
+    Nfd,
+    /// Keep names as the sources spell them
+    None,
+}
+
+/// What happens to each output file and folder name.
+#[derive(Debug, Clone, Copy)]
+pub struct NameCleanup<'a> {
+    /// Replacement for characters Windows can't store; `None` keeps them.
+    pub sanitize: Option<&'a str>,
+    pub unicode: UnicodeForm,
+    /// `--lowercase`, with full Unicode case mapping (`Ä` -> `ä`).
+    pub lowercase: bool,
+}
+
+impl NameCleanup<'_> {
+    /// `name` lowercased, normalized, then sanitized.
+    pub fn apply(&self, name: &str) -> String {
+        // Lowercasing first, since it can leave combining marks (`İ` -> `i̇`)
+        // that normalization then composes
+        let name = if self.lowercase { name.to_lowercase() } else { name.to_string() };
+        let normalized: String = match self.unicode {
+            UnicodeForm::Nfc => name.nfc().collect(),
+            UnicodeForm::Nfd => name.nfd().collect(),
+            UnicodeForm::None => name,
+        };
+        match self.sanitize {
+            Some(replacement) => sanitize_file_name(&normalized, replacement),
+            None => normalized,
+        }
+    }
+
+    /// Only `--lowercase`: names as the sources spell them otherwise, to
+    /// tell which outputs the cleanup renamed.
+    pub fn raw(self) -> Self {
+        NameCleanup { sanitize: None, unicode: UnicodeForm::None, ..self }
+    }
+}
+
+/// Whether file names that differ only in case are the same file in `dir`
+/// (or its nearest existing folder), as on Windows and by default on macOS.
+/// Found out by creating a temporary lowercase name and looking it up in
+/// uppercase; if that fails, assumed from the platform.
+pub fn case_insensitive_names(dir: &Path) -> bool {
+    let folder = dir.ancestors().find(|folder| folder.is_dir()).unwrap_or(Path::new("."));
+    let Ok(probe) = tempfile::Builder::new().prefix(".dds-converter-case-").tempfile_in(folder) else {
+        return cfg!(any(windows, target_os = "macos"));
+    };
+    let name = probe.path().file_name().unwrap_or_default().to_string_lossy().to_uppercase();
+    folder.join(name).exists()
+}
+
+/// Why the cleanup renamed an output: `raw` is where it would go with
+/// `NameCleanup::raw`, `unsanitized` without `--sanitize-names`. `None`
+/// when the name came out unchanged.
+pub fn cleanup_reason(raw: &Path, unsanitized: &Path, output: &Path) -> Option<&'static str> {
+    if raw == output {
+        None
+    } else if unsanitized != output {
+        Some("not a valid Windows file name")
+    } else {
+        Some("Unicode normalized")
+    }
+}
+
+/// Rejects `--prefix` / `--suffix` values that can't be part of a file name.
+pub fn parse_name_affix(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("the text can't be empty".to_string());
+    }
+    if value.chars().any(is_invalid_name_char) {
+        return Err(format!("'{}' contains characters that are not allowed in file names", value));
+    }
+    Ok(value.to_string())
+}
+
+/// Rejects `--sanitize-replacement` values that would themselves be invalid.
+pub fn parse_sanitize_replacement(value: &str) -> Result<String, String> {
+    if value.chars().any(|c| is_invalid_name_char(c) || c == '.' || c == ' ') {
+        return Err(format!("'{}' contains characters that are not allowed in file names", value));
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dds_stem_strips_exactly_one_final_dds() {
+        assert_eq!(dds_stem(Path::new("foo.dds")), "foo");
+        assert_eq!(dds_stem(Path::new("foo.1.dds")), "foo.1");
+        assert_eq!(dds_stem(Path::new("foo.DDS")), "foo");
+        assert_eq!(dds_stem(Path::new("foo.dds.dds")), "foo.dds");
+        assert_eq!(dds_stem(Path::new("dir/loading.screen.Dds")), "loading.screen");
+    }
+
+    #[test]
+    fn dds_stem_falls_back_to_the_final_extension() {
+        assert_eq!(dds_stem(Path::new("texture.dds.bak")), "texture.dds");
+        assert_eq!(dds_stem(Path::new(".dds")), ".dds");
+        assert_eq!(dds_stem(Path::new("noext")), "noext");
+    }
+
+    #[test]
+    fn composed_and_decomposed_names_normalize_to_the_same_bytes() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let nfc = NameCleanup { sanitize: None, unicode: UnicodeForm::Nfc, lowercase: false };
+        let nfd = NameCleanup { unicode: UnicodeForm::Nfd, ..nfc };
+        assert_eq!(nfc.apply(composed), composed);
+        assert_eq!(nfc.apply(decomposed), composed);
+        assert_eq!(nfd.apply(composed), decomposed);
+        assert_eq!(nfd.apply(decomposed), decomposed);
+        assert_eq!(nfc.raw().apply(decomposed), decomposed);
+    }
+
+    #[test]
+    fn case_sensitivity_is_probed_in_the_target_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let insensitive = case_insensitive_names(dir.path());
+        std::fs::write(dir.path().join("rock.png"), b"").unwrap();
+        assert_eq!(dir.path().join("ROCK.PNG").exists(), insensitive);
+        // A folder that doesn't exist yet is probed in its parent
+        assert_eq!(case_insensitive_names(&dir.path().join("out/textures")), insensitive);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn cleanup_reason_compares_with_the_raw_name() {
+        let composed = Path::new("out/caf\u{e9}.png");
+        let decomposed = Path::new("out/cafe\u{301}.png");
+        assert_eq!(cleanup_reason(composed, composed, composed), None);
+        assert_eq!(cleanup_reason(decomposed, composed, composed), Some("Unicode normalized"));
+        assert_eq!(cleanup_reason(Path::new("out/a?.png"), Path::new("out/a?.png"), Path::new("out/a_.png")), Some("not a valid Windows file name"));
+    }
+
+    #[test]
+    fn output_placement_tells_same_nested_and_separate_folders_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in");
+        std::fs::create_dir_all(input.join("sub")).unwrap();
+        std::fs::create_dir_all(dir.path().join("in2")).unwrap();
+
+        assert!(matches!(output_placement(&input, &input), OutputPlacement::Same));
+        // Spelled differently, still the same folder
+        assert!(matches!(output_placement(&input, &input.join("sub").join("..")), OutputPlacement::Same));
+        match output_placement(&input, &input.join("sub")) {
+            OutputPlacement::Nested(excluded) => assert_eq!(excluded, input.join("sub")),
+            _ => panic!("sub is inside in"),
+        }
+        // Doesn't exist yet, but would be inside
+        match output_placement(&input, &input.join("out").join("png")) {
+            OutputPlacement::Nested(excluded) => assert_eq!(excluded, input.join("out").join("png")),
+            _ => panic!("out/png is inside in"),
+        }
+        // A shared name prefix isn't containment
+        assert!(matches!(output_placement(&input, &dir.path().join("in2")), OutputPlacement::Separate));
+        assert!(matches!(output_placement(&input, dir.path()), OutputPlacement::Separate));
+    }
+
+    #[test]
+    fn nested_output_is_left_out_of_discovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in");
+        for name in ["a.dds", "sub/b.dds", "out/a.dds", "out/deeper/c.dds"] {
+            let path = input.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        let OutputPlacement::Nested(excluded) = output_placement(&input, &input.join("out")) else {
+            panic!("out is inside in");
+        };
+        let found = find_dds_files(&input, Some(&excluded), None);
+        assert_eq!(found, [input.join("a.dds"), input.join("sub/b.dds")]);
+        assert_eq!(find_dds_files(&input, None, None).len(), 4);
+    }
+
+    #[test]
+    fn size_filter_keeps_the_bounds_and_reports_why() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = [("tiny.dds", 127), ("floor.dds", 128), ("ceiling.dds", 4096), ("huge.dds", 4097)]
+            .into_iter()
+            .map(|(name, size)| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, vec![0; size]).unwrap();
+                path
+            })
+            .collect();
+        let missing = dir.path().join("missing.dds");
+        let mut input = files.clone();
+        input.push(missing.clone());
+
+        let (kept, skipped) = filter_by_size(input, 128, Some(4096));
+        assert_eq!(kept, [files[1].clone(), files[2].clone(), missing]);
+        assert_eq!(skipped, [(files[0].clone(), SkipReason::TooSmall), (files[3].clone(), SkipReason::TooLarge)]);
+    }
+
+    #[test]
+    fn every_windows_reserved_name_gets_an_underscore() {
+        for reserved in RESERVED_NAMES {
+            let lower = reserved.to_lowercase();
+            let table = [
+                (reserved.to_string(), format!("{}_", reserved)),
+                (format!("{}.png", lower), format!("{}_.png", lower)),
+                (format!("{}.tar.gz", reserved), format!("{}_.tar.gz", reserved)),
+            ];
+            for (name, expected) in table {
+                assert_eq!(sanitize_file_name(&name, "_"), expected, "{}", name);
+            }
+        }
+        // Only the whole part before the first dot counts
+        for name in ["console.png", "auxiliary.png", "com10.png", "lpt.png", "nul_.png", "my.aux.png"] {
+            assert_eq!(sanitize_file_name(name, "_"), name);
+        }
+    }
+
+    #[test]
+    fn invalid_characters_and_trailing_dots_are_replaced() {
+        let table = [
+            ("what?.png", "what_.png"),
+            ("a<b>c:d\"e|f*.png", "a_b_c_d_e_f_.png"),
+            ("tab\there.png", "tab_here.png"),
+            ("folder. ", "folder__"),
+            // Windows would read this as `aux`; replacing the tail is enough
+            ("aux. ", "aux__"),
+            ("plain.png", "plain.png"),
+        ];
+        for (name, expected) in table {
+            assert_eq!(sanitize_file_name(name, "_"), expected, "{}", name);
+        }
+        assert_eq!(sanitize_file_name("a?b", "-"), "a-b");
+        assert!(parse_sanitize_replacement("?").is_err());
+        assert_eq!(parse_sanitize_replacement("-").unwrap(), "-");
+    }
+}
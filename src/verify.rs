@@ -0,0 +1,114 @@
+use anyhow::Result;
+use image::ImageFormat;
+use std::path::Path;
+
+use crate::dds::DdsHeader;
+use crate::errors::{Classified, ErrorCode};
+
+/// `--verify`: checks that a written output decodes. Formats the image
+/// crate reads are decoded in full, which catches truncated files; DDS
+/// outputs get their header checked; anything else only has to be
+/// non-empty.
+pub fn verify_output(path: &Path) -> Result<()> {
+    let corrupt = |reason: String| Classified::error(ErrorCode::CorruptOutput, format!("{} failed verification: {}", path.display(), reason));
+
+    let len = std::fs::metadata(path).map(|metadata| metadata.len()).map_err(|e| corrupt(e.to_string()))?;
+    if len == 0 {
+        return Err(corrupt("the file is empty".to_string()));
+    }
+
+    let is_dds = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dds"));
+    let (width, height) = if is_dds {
+        let header = DdsHeader::read(path).map_err(|e| corrupt(format!("{:#}", e)))?;
+        (header.width, header.height)
+    } else if ImageFormat::from_path(path).is_ok_and(|format| format.can_read()) {
+        let image = image::open(path).map_err(|e| corrupt(e.to_string()))?;
+        (image.width(), image.height())
+    } else {
+        return Ok(());
+    };
+
+    if width == 0 || height == 0 {
+        return Err(corrupt(format!("the image is {}x{}", width, height)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dds::dx10_header;
+    use crate::errors::classify;
+
+    /// The bytes of a 16x16 PNG.
+    fn png() -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(16, 16, image::Rgba([200, 100, 50, 255])).write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    fn verify_bytes(name: &str, bytes: &[u8]) -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        verify_output(&path)
+    }
+
+    fn assert_corrupt(result: Result<()>, reason: &str) {
+        let error = result.expect_err("verification passed");
+        assert_eq!(classify(&error), ErrorCode::CorruptOutput);
+        assert!(error.to_string().contains(reason), "{}", error);
+    }
+
+    #[test]
+    fn complete_outputs_pass() {
+        verify_bytes("rock.png", &png()).unwrap();
+        let mut dds = dx10_header(28, 4, 4, 1);
+        dds.extend([0; 64]);
+        verify_bytes("rock.dds", &dds).unwrap();
+        // Formats the image crate can't read only have to be non-empty
+        verify_bytes("rock.hdr2", b"anything").unwrap();
+    }
+
+    #[test]
+    fn empty_files_fail() {
+        assert_corrupt(verify_bytes("rock.png", b""), "the file is empty");
+        assert_corrupt(verify_bytes("rock.hdr2", b""), "the file is empty");
+    }
+
+    #[test]
+    fn truncated_pngs_fail() {
+        let png = png();
+        assert_corrupt(verify_bytes("rock.png", &png[..png.len() / 2]), "failed verification");
+        // Just the signature and header chunk: no image data at all
+        assert_corrupt(verify_bytes("rock.png", &png[..33]), "failed verification");
+    }
+
+    #[test]
+    fn bad_dds_headers_fail() {
+        assert_corrupt(verify_bytes("rock.dds", b"not a dds file at all"), "failed verification");
+        let mut zero_sized = dx10_header(28, 0, 4, 1);
+        zero_sized.extend([0; 64]);
+        assert_corrupt(verify_bytes("rock.dds", &zero_sized), "failed verification");
+    }
+
+    #[test]
+    fn missing_outputs_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_corrupt(verify_output(&dir.path().join("gone.png")), "failed verification");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_are_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("rock.png");
+        std::fs::write(&target, png()).unwrap();
+        let link = dir.path().join("copy.png");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        verify_output(&link).unwrap();
+
+        std::fs::remove_file(&target).unwrap();
+        assert_corrupt(verify_output(&link), "failed verification");
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::adaptive::Concurrency;
+use crate::cli::ConvertArgs;
+use crate::collision::{resolve_collisions, ExistingAction};
+use crate::console::{status, Console};
+use crate::content_hash::PreviousManifest;
+use crate::disk_space::SpaceGate;
+use crate::driver::{FileDriver, FileRun};
+use crate::errors::{classify, Classified, ErrorCode};
+use crate::exit_status::RunStatus;
+use crate::overrides::{load_overrides, OverrideRules};
+use crate::processor::{planned_output, FileOutcome, SkipReason};
+use crate::run_log::RunLog;
+use crate::summary::RunSummary;
+use crate::texconv::setup_texconv;
+use crate::utils::{case_insensitive_names, filter_by_size, filter_paths, is_excluded, output_placement, OutputPlacement};
+
+/// How often pending files are checked for a size that stopped changing.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a file's size must stay the same before it is converted, so a
+/// file still being copied isn't picked up half-written.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// A file waiting for its size to settle.
+struct Settling {
+    size: Option<u64>,
+    since: Instant,
+}
+
+/// `--watch`: after the first pass, converts `.dds` files that appear or
+/// change under `--input` until Ctrl-C, then waits for the conversions in
+/// flight. Every file gets its own log line instead of a progress bar.
+pub async fn run_watch(cli: ConvertArgs, log: Option<Arc<RunLog>>, summary: &mut RunSummary) -> Result<RunStatus> {
+    let cli = Arc::new(cli);
+    let texconv_path = setup_texconv(cli.texconv_path.as_deref()).await?;
+    // The initial run has shown the rules' warnings already
+    let (rules, _) = OverrideRules::load(cli.rules.as_deref())?;
+    let excluded = match cli.output.as_deref().map(|output| output_placement(&cli.input, output)) {
+        Some(OutputPlacement::Nested(excluded)) => Some(excluded),
+        _ => None,
+    };
+
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = sender.send(event);
+    })
+    .context("Failed to start watching the input folder")?;
+    watcher
+        .watch(&cli.input, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", cli.input.display()))?;
+    status!("👀 Watching {} for new or changed .dds files (Ctrl-C to stop)", cli.input.display());
+
+    let console = Console::plain();
+    let workers = match cli.concurrency {
+        Concurrency::Fixed(workers) => workers,
+        Concurrency::Adaptive => cli.concurrency_max
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)),
+    };
+    let semaphore = Arc::new(Semaphore::new(workers));
+    let driver = Arc::new(FileDriver {
+        texconv_path,
+        zip: None,
+        space_gate: (!cli.no_pause_on_full).then(|| Arc::new(SpaceGate::new(cli.target_folder(), None))),
+        prompter: None,
+        manifest: Arc::new(PreviousManifest::load(&cli, &HashSet::new())?),
+    });
+    let mut pending: HashMap<PathBuf, Settling> = HashMap::new();
+    let mut in_flight = HashSet::new();
+    let mut tasks = JoinSet::new();
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else { break };
+                match event {
+                    Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                        for path in event.paths {
+                            if let Some(texture) = texture_for(&path).filter(|texture| wanted(&cli, texture, excluded.as_deref())) {
+                                pending.insert(texture, Settling { size: None, since: Instant::now() });
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => console.error(format!("⚠️  Watch error: {}", e)),
+                }
+            }
+            _ = ticker.tick() => {
+                for file in settled(&mut pending, &in_flight) {
+                    let planned = match plan_output(&cli, &file, &rules, &mut summary.planned, &console) {
+                        Ok(planned) => planned,
+                        Err(e) => {
+                            console.error(format!("❌ Error in {} [{}]: {:#}", file.display(), classify(&e), e));
+                            summary.record(&file, &Err(e));
+                            continue;
+                        }
+                    };
+                    let Some((file_cli, output_path)) = planned else {
+                        summary.record(&file, &Ok(FileOutcome::Skipped(SkipReason::Collision)));
+                        continue;
+                    };
+                    in_flight.insert(file.clone());
+                    let (semaphore, driver, console, log) = (semaphore.clone(), driver.clone(), console.clone(), log.clone());
+                    tasks.spawn(async move {
+                        let run = match semaphore.acquire_owned().await {
+                            Ok(_permit) => {
+                                let started = Instant::now();
+                                let run = convert(&file, output_path, &file_cli, &driver, &console).await;
+                                if let Some(log) = log.as_ref().filter(|_| !is_locked(&run.result)) {
+                                    log.file(&file, &run.output_path, started.elapsed(), &run.result);
+                                }
+                                run
+                            }
+                            Err(e) => FileRun { result: Err(e.into()), output_path, conflict: None, gpu_fallback: false, retried: false },
+                        };
+                        (file, run)
+                    });
+                }
+            }
+            Some(joined) = tasks.join_next() => {
+                let (file, run) = joined?;
+                in_flight.remove(&file);
+                // Still being written by whatever is copying it in: try again once it settles
+                if is_locked(&run.result) {
+                    pending.insert(file, Settling { size: None, since: Instant::now() });
+                    continue;
+                }
+                record(summary, &file, &run);
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    drop(watcher);
+    if !tasks.is_empty() {
+        status!("🛑 Stopping: waiting for {} conversion(s) in progress (Ctrl-C again to quit now)", tasks.len());
+    }
+    loop {
+        tokio::select! {
+            joined = tasks.join_next() => {
+                let Some(joined) = joined else { break };
+                let (file, run) = joined?;
+                record(summary, &file, &run);
+            }
+            _ = tokio::signal::ctrl_c() => anyhow::bail!("Interrupted with conversions still running"),
+        }
+    }
+
+    summary.print(cli.verbose);
+    Ok(if summary.failed.is_empty() { RunStatus::Success } else { RunStatus::FileErrors })
+}
+
+/// Adds how converting `file` went to the session's summary.
+fn record(summary: &mut RunSummary, file: &Path, run: &FileRun) {
+    summary.record(file, &run.result);
+    if run.gpu_fallback {
+        summary.gpu_fallbacks.push(file.to_path_buf());
+    }
+    if run.retried {
+        summary.retried.push(file.to_path_buf());
+    }
+    if let Ok(FileOutcome::Converted { .. }) = &run.result {
+        match run.conflict {
+            Some(ExistingAction::Overwrite) => summary.overwritten += 1,
+            Some(ExistingAction::Rename) => summary.renamed += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Whether `result` failed because the file was still locked, so it goes
+/// back to waiting instead of counting as a failure.
+fn is_locked(result: &Result<FileOutcome>) -> bool {
+    matches!(result, Err(e) if classify(e) == ErrorCode::SharingViolation)
+}
+
+/// The `.dds` a changed file belongs to: itself, or the texture of a split
+/// or alpha part (`foo.dds.1`, `foo.dds.a`). The name is cut at its last
+/// `.dds`, so `ui.compass.dds.bak.dds` is a texture of its own.
+fn texture_for(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    let end = name.to_ascii_lowercase().rfind(".dds").map(|start| start + ".dds".len())?;
+    let suffix = &name[end..];
+    let is_part = suffix.is_empty()
+        || suffix.strip_prefix('.').is_some_and(|part| {
+            let part = part.strip_suffix('a').unwrap_or(part);
+            part.is_empty() || part.chars().all(|c| c.is_ascii_digit())
+        });
+    is_part.then(|| path.with_file_name(&name[..end]))
+}
+
+/// Whether `file` passes the same filters as discovery.
+fn wanted(cli: &ConvertArgs, file: &Path, excluded: Option<&Path>) -> bool {
+    if is_excluded(file, excluded) {
+        return false;
+    }
+    if let Some(depth) = cli.selection.max_depth() {
+        if file.strip_prefix(&cli.input).map_or(0, |relative| relative.components().count()) > depth {
+            return false;
+        }
+    }
+    !filter_paths(vec![file.to_path_buf()], &cli.input, &cli.include, &cli.exclude).kept.is_empty()
+}
+
+/// Settling files whose size hasn't changed for `SETTLE_TIME`, taken out of
+/// `pending`. Files that disappeared are dropped; ones being converted wait.
+fn settled(pending: &mut HashMap<PathBuf, Settling>, in_flight: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+    pending.retain(|file, state| {
+        let Ok(size) = std::fs::metadata(file).map(|metadata| metadata.len()) else {
+            return false;
+        };
+        if state.size != Some(size) {
+            *state = Settling { size: Some(size), since: Instant::now() };
+        } else if state.since.elapsed() >= SETTLE_TIME && !in_flight.contains(file) {
+            ready.push(file.clone());
+            return false;
+        }
+        true
+    });
+    ready.sort();
+    ready
+}
+
+/// The options (with per-file overrides) and output path for a settled
+/// `file`. New files are numbered after everything planned so far and go
+/// through `--on-collision` together with it, as they would have in the
+/// first pass; `None` means the policy skips the file.
+fn plan_output(
+    cli: &Arc<ConvertArgs>,
+    file: &Path,
+    rules: &OverrideRules,
+    planned: &mut Vec<(PathBuf, PathBuf)>,
+    console: &Console,
+) -> Result<Option<(Arc<ConvertArgs>, PathBuf)>> {
+    let (overrides, warnings) = load_overrides(&[file.to_path_buf()], &cli.input, rules)?;
+    for warning in warnings {
+        console.println(format!("⚠️  {}", warning));
+    }
+    let file_cli = match overrides.get(file) {
+        Some(file_overrides) => Arc::new(file_overrides.apply(cli)),
+        None => cli.clone(),
+    };
+
+    // A file converted before keeps its number
+    let known = planned.iter().position(|(input, _)| input == file);
+    let index = known.unwrap_or(planned.len()) + 1;
+    let output = planned_output(&file_cli, file, &file_cli.format, index, file_cli.naming.cleanup());
+    match known {
+        Some(position) => planned[position].1 = output,
+        None => planned.push((file.to_path_buf(), output)),
+    }
+
+    let plan = match resolve_collisions(planned.clone(), &cli.input, case_insensitive_names(cli.target_folder()), cli.collision_policy()) {
+        Ok(plan) => plan,
+        Err(e) => {
+            if known.is_none() {
+                planned.pop();
+            }
+            return Err(Classified::error(ErrorCode::OutputExists, format!("{:#}", e)));
+        }
+    };
+    if let Some((_, kept)) = plan.skipped.iter().find(|(input, _)| input == file) {
+        console.println(format!("⏭️  {}: same output name as {}", file.display(), kept.display()));
+        return Ok(None);
+    }
+    let resolved = plan.outputs.into_iter().find(|resolved| resolved.input == file).map(|resolved| {
+        if let Some(note) = &resolved.note {
+            console.println(format!("✏️  {} -> {} ({})", file.display(), resolved.output.display(), note));
+        }
+        resolved.output
+    });
+    Ok(resolved.map(|output| (file_cli, output)))
+}
+
+/// Converts one settled file as a normal run would: size limits, then the
+/// same per-file steps as the first pass (`--incremental`, `--on-conflict`,
+/// the disk-full pause, the CPU retry after a GPU failure and `--retries`).
+/// Prints the outcome.
+async fn convert(file: &Path, output_path: PathBuf, cli: &Arc<ConvertArgs>, driver: &FileDriver, console: &Console) -> FileRun {
+    let (_, too_big_or_small) = filter_by_size(vec![file.to_path_buf()], cli.min_size, cli.max_size);
+    if let Some((_, reason)) = too_big_or_small.first() {
+        console.println(format!("⏭️  {}: {}", file.display(), reason.describe()));
+        let result = Ok(FileOutcome::Skipped(*reason));
+        return FileRun { result, output_path, conflict: None, gpu_fallback: false, retried: false };
+    }
+
+    let started = Instant::now();
+    let run = driver.convert(file, output_path, cli, console).await;
+    match &run.result {
+        Ok(FileOutcome::Skipped(reason)) => console.println(format!("⏭️  {}: {}", file.display(), reason.describe())),
+        Ok(_) => console.println(format!(
+            "✅ {} -> {} ({:.1}s)",
+            file.display(),
+            run.output_path.display(),
+            started.elapsed().as_secs_f64()
+        )),
+        Err(e) if classify(e) == ErrorCode::SharingViolation => {
+            console.println(format!("⏳ {} is still locked; trying again shortly", file.display()));
+        }
+        Err(e) => console.error(format!("❌ Error in {} [{}]: {}", file.display(), classify(e), e)),
+    }
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_files_map_to_their_texture() {
+        let table = [
+            ("in/rock.dds", Some("in/rock.dds")),
+            ("in/Rock.DDS", Some("in/Rock.DDS")),
+            ("in/rock.dds.1", Some("in/rock.dds")),
+            ("in/rock.dds.12", Some("in/rock.dds")),
+            ("in/rock.dds.a", Some("in/rock.dds")),
+            ("in/rock.dds.3a", Some("in/rock.dds")),
+            ("in/ui.hud.compass.dds.bak.dds", Some("in/ui.hud.compass.dds.bak.dds")),
+            ("in/ui.hud.compass.dds.bak.dds.2", Some("in/ui.hud.compass.dds.bak.dds")),
+            ("in/rock.dds.bak", None),
+            ("in/rock.dds.1b", None),
+            ("in/rock.png", None),
+        ];
+        for (changed, texture) in table {
+            assert_eq!(texture_for(Path::new(changed)), texture.map(PathBuf::from), "{}", changed);
+        }
+    }
+}
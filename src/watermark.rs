@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{imageops::FilterType, Rgba, Rgba32FImage, RgbaImage};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where `--watermark` goes on the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+/// `--watermark IMAGE[:POSITION[:OPACITY[:SCALE]]]`
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    /// Decoded once, shared by every file.
+    pub image: Arc<RgbaImage>,
+    pub position: WatermarkPosition,
+    pub opacity: f32,
+    /// Longer side of the stamp as a fraction of the image's smaller side.
+    pub scale: f32,
+}
+
+/// Parses and loads a `--watermark` spec. The image path may itself contain
+/// `:` (as in `C:\logo.png`); the options start at the first `:` followed
+/// by a position name.
+pub fn parse_watermark(value: &str) -> Result<Watermark, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    let split = (1..parts.len()).find(|&index| WatermarkPosition::from_str(parts[index].trim(), true).is_ok());
+    let (path, options) = match split {
+        Some(index) => (parts[..index].join(":"), &parts[index..]),
+        None => (value.to_string(), &parts[parts.len()..]),
+    };
+    if options.len() > 3 {
+        return Err(format!("'{}' should be IMAGE[:POSITION[:OPACITY[:SCALE]]]", value));
+    }
+
+    let position = match options.first() {
+        Some(position) => WatermarkPosition::from_str(position.trim(), true)?,
+        None => WatermarkPosition::BottomRight,
+    };
+    let fraction = |text: Option<&&str>, name: &str, default: f32| -> Result<f32, String> {
+        let Some(text) = text else {
+            return Ok(default);
+        };
+        text.trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|amount| *amount > 0.0 && *amount <= 1.0)
+            .ok_or_else(|| format!("watermark {} '{}' must be a number above 0 and at most 1", name, text.trim()))
+    };
+    let opacity = fraction(options.get(1), "opacity", 1.0)?;
+    let scale = fraction(options.get(2), "scale", 0.1)?;
+
+    let image = image::open(&path)
+        .map_err(|e| format!("Failed to load watermark {}: {}", path, e))?
+        .to_rgba8();
+    Ok(Watermark { image: Arc::new(image), position, opacity, scale })
+}
+
+/// `--watermark-margin`: 0 to 0.5.
+pub fn parse_margin(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|margin| (0.0..=0.5).contains(margin))
+        .ok_or_else(|| format!("'{}' must be a fraction from 0 to 0.5", value))
+}
+
+/// `image` as floats with premultiplied alpha, for resizing without dark
+/// fringes.
+pub fn premultiplied(image: &RgbaImage) -> Rgba32FImage {
+    Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0.map(|channel| channel as f32 / 255.0);
+        image::Rgba([r * a, g * a, b * a, a])
+    })
+}
+
+/// Blends the premultiplied `source` over the straight-alpha `pixel`
+/// (source-over, premultiplied math).
+pub fn blend_over(pixel: &mut Rgba<u8>, source: [f32; 4]) {
+    let [sr, sg, sb, sa] = source.map(|channel| channel.clamp(0.0, 1.0));
+    let [dr, dg, db, da] = pixel.0.map(|channel| channel as f32 / 255.0);
+    let out_alpha = sa + da * (1.0 - sa);
+    let blend = |source: f32, dest: f32| {
+        let premultiplied = source + dest * da * (1.0 - sa);
+        if out_alpha > 0.0 { premultiplied / out_alpha } else { 0.0 }
+    };
+    let color = [blend(sr, dr), blend(sg, dg), blend(sb, db), out_alpha];
+    pixel.0 = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+}
+
+impl Watermark {
+    /// Composites the watermark onto `target`, scaled and placed for its
+    /// size with `margin` (a fraction of the smaller side) to the edges.
+    /// The stamp is resized with premultiplied alpha so its transparent
+    /// parts don't bleed dark fringes, then blended source-over.
+    pub fn apply(&self, target: &mut RgbaImage, margin: f32) {
+        let (width, height) = target.dimensions();
+        let short_side = width.min(height) as f32;
+        let longest = self.image.width().max(self.image.height()).max(1) as f32;
+        let factor = self.scale * short_side / longest;
+        let stamp_width = ((self.image.width() as f32 * factor).round() as u32).clamp(1, width);
+        let stamp_height = ((self.image.height() as f32 * factor).round() as u32).clamp(1, height);
+        let stamp = image::imageops::resize(&premultiplied(&self.image), stamp_width, stamp_height, FilterType::Triangle);
+
+        let inset = (margin * short_side).round() as u32;
+        let right = width.saturating_sub(stamp_width + inset);
+        let bottom = height.saturating_sub(stamp_height + inset);
+        let left = inset.min(right);
+        let top = inset.min(bottom);
+        let (x0, y0) = match self.position {
+            WatermarkPosition::TopLeft => (left, top),
+            WatermarkPosition::TopRight => (right, top),
+            WatermarkPosition::BottomLeft => (left, bottom),
+            WatermarkPosition::BottomRight => (right, bottom),
+            WatermarkPosition::Center => ((width - stamp_width) / 2, (height - stamp_height) / 2),
+        };
+
+        for (x, y, source) in stamp.enumerate_pixels() {
+            blend_over(target.get_pixel_mut(x0 + x, y0 + y), source.0.map(|channel| channel * self.opacity));
+        }
+    }
+}
+
+/// `--watermark` on a decoded PNG: writes the stamped copy into `work_dir`
+/// and returns it, or `None` if the image's smaller side is under
+/// `min_size`.
+pub fn watermark_png(png: &Path, work_dir: &Path, watermark: &Watermark, margin: f32, min_size: u32) -> Result<Option<PathBuf>> {
+    let mut image = image::open(png)
+        .with_context(|| format!("Failed to open {}", png.display()))?
+        .to_rgba8();
+    if image.width().min(image.height()) < min_size {
+        return Ok(None);
+    }
+    watermark.apply(&mut image, margin);
+    std::fs::create_dir_all(work_dir).context("Failed to create watermark directory")?;
+    let stamped = work_dir.join(png.file_name().unwrap_or_default());
+    image.save(&stamped)
+        .with_context(|| format!("Failed to write {}", stamped.display()))?;
+    Ok(Some(stamped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(color: [u8; 4], size: u32, position: WatermarkPosition, opacity: f32) -> Watermark {
+        let image = RgbaImage::from_pixel(size, size, Rgba(color));
+        Watermark { image: Arc::new(image), position, opacity, scale: 0.2 }
+    }
+
+    #[test]
+    fn blending_is_premultiplied_source_over() {
+        let half_red = premultiplied(&RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 128]))).get_pixel(0, 0).0;
+
+        let mut opaque = Rgba([0, 0, 255, 255]);
+        blend_over(&mut opaque, half_red);
+        assert_eq!(opaque.0, [128, 0, 127, 255]);
+
+        // Over nothing the colour comes back unchanged, not darkened by alpha
+        let mut empty = Rgba([0, 0, 0, 0]);
+        blend_over(&mut empty, half_red);
+        assert_eq!(empty.0, [255, 0, 0, 128]);
+
+        // Alpha 0.502 + 0.502 * 0.498 = 0.752; red 0.502 / 0.752, blue 0.25 / 0.752
+        let mut half_blue = Rgba([0, 0, 255, 128]);
+        blend_over(&mut half_blue, half_red);
+        assert_eq!(half_blue.0, [170, 0, 85, 192]);
+    }
+
+    #[test]
+    fn stamp_is_scaled_and_inset_from_the_chosen_corner() {
+        let white = RgbaImage::from_pixel(100, 50, Rgba([255, 255, 255, 255]));
+        // 0.2 of the 50 pixel side is a 10x10 stamp; 0.1 margin is 5 pixels
+        let mut image = white.clone();
+        stamp([0, 0, 0, 255], 4, WatermarkPosition::BottomRight, 1.0).apply(&mut image, 0.1);
+        assert_eq!(image.get_pixel(85, 35).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(94, 44).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(84, 35).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(95, 44).0, [255, 255, 255, 255]);
+        assert_eq!(image.pixels().filter(|pixel| pixel.0[0] == 0).count(), 100);
+
+        let mut image = white.clone();
+        stamp([0, 0, 0, 255], 4, WatermarkPosition::TopLeft, 1.0).apply(&mut image, 0.0);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(10, 10).0, [255, 255, 255, 255]);
+
+        let mut image = white;
+        stamp([0, 0, 0, 255], 4, WatermarkPosition::Center, 1.0).apply(&mut image, 0.1);
+        assert_eq!(image.get_pixel(45, 20).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(44, 20).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn opacity_scales_the_stamp_before_blending() {
+        let mut image = RgbaImage::from_pixel(50, 50, Rgba([255, 255, 255, 255]));
+        stamp([0, 0, 0, 255], 10, WatermarkPosition::TopLeft, 0.5).apply(&mut image, 0.0);
+        assert_eq!(image.get_pixel(0, 0).0, [128, 128, 128, 255]);
+
+        // A half-transparent stamp at half opacity lets three quarters through
+        let mut image = RgbaImage::from_pixel(50, 50, Rgba([0, 0, 0, 255]));
+        stamp([255, 255, 255, 128], 10, WatermarkPosition::TopLeft, 0.5).apply(&mut image, 0.0);
+        assert_eq!(image.get_pixel(0, 0).0, [64, 64, 64, 255]);
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use image::RgbaImage;
+use libwebp_sys::*;
+
+/// Encodes an animated WebP in-process with libwebp's AnimEncoder.
+///
+/// `durations_ms` holds one display time per frame. Frames smaller than the
+/// largest one are placed at the top-left of a transparent canvas, since the
+/// encoder needs every frame at canvas size.
+pub fn encode_animated_webp(frames: &[RgbaImage], durations_ms: &[u32], quality: f32) -> Result<Vec<u8>> {
+    if frames.is_empty() {
+        anyhow::bail!("No frames to encode");
+    }
+    if frames.len() != durations_ms.len() {
+        anyhow::bail!("Got {} frames but {} durations", frames.len(), durations_ms.len());
+    }
+
+    let width = frames.iter().map(|f| f.width()).max().unwrap_or(0);
+    let height = frames.iter().map(|f| f.height()).max().unwrap_or(0);
+
+    let mut config = WebPConfig::new().map_err(|_| anyhow::anyhow!("libwebp config init failed"))?;
+    config.quality = quality;
+    config.lossless = 0;
+
+    unsafe {
+        let mut options = std::mem::MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+        if WebPAnimEncoderOptionsInitInternal(options.as_mut_ptr(), WebPGetMuxABIVersion()) == 0 {
+            anyhow::bail!("libwebp animation options init failed");
+        }
+        let mut options = options.assume_init();
+        options.anim_params.loop_count = 0;
+
+        let encoder = WebPAnimEncoderNewInternal(width as i32, height as i32, &options, WebPGetMuxABIVersion());
+        if encoder.is_null() {
+            anyhow::bail!("libwebp animation encoder could not be created");
+        }
+
+        let result = add_frames(encoder, frames, durations_ms, width, height, &config)
+            .and_then(|_| assemble(encoder));
+        WebPAnimEncoderDelete(encoder);
+        result
+    }
+}
+
+unsafe fn add_frames(
+    encoder: *mut WebPAnimEncoder,
+    frames: &[RgbaImage],
+    durations_ms: &[u32],
+    width: u32,
+    height: u32,
+    config: &WebPConfig,
+) -> Result<()> {
+    let mut timestamp: i32 = 0;
+
+    for (frame, &duration) in frames.iter().zip(durations_ms) {
+        let canvas;
+        let pixels = if frame.dimensions() == (width, height) {
+            frame
+        } else {
+            let mut padded = RgbaImage::new(width, height);
+            image::imageops::replace(&mut padded, frame, 0, 0);
+            canvas = padded;
+            &canvas
+        };
+
+        let mut picture = WebPPicture::new().map_err(|_| anyhow::anyhow!("libwebp picture init failed"))?;
+        picture.use_argb = 1;
+        picture.width = width as i32;
+        picture.height = height as i32;
+
+        if WebPPictureImportRGBA(&mut picture, pixels.as_raw().as_ptr(), (width * 4) as i32) == 0 {
+            WebPPictureFree(&mut picture);
+            anyhow::bail!("libwebp could not import frame pixels");
+        }
+
+        let added = WebPAnimEncoderAdd(encoder, &mut picture, timestamp, config);
+        WebPPictureFree(&mut picture);
+        if added == 0 {
+            anyhow::bail!("libwebp rejected frame: {}", encoder_error(encoder));
+        }
+
+        timestamp = timestamp.saturating_add(duration as i32);
+    }
+
+    // The closing call carries the end timestamp, which sets the last frame's duration
+    if WebPAnimEncoderAdd(encoder, std::ptr::null_mut(), timestamp, std::ptr::null()) == 0 {
+        anyhow::bail!("libwebp could not finish the animation: {}", encoder_error(encoder));
+    }
+
+    Ok(())
+}
+
+unsafe fn assemble(encoder: *mut WebPAnimEncoder) -> Result<Vec<u8>> {
+    let mut data = WebPData::default();
+    if WebPAnimEncoderAssemble(encoder, &mut data) == 0 {
+        anyhow::bail!("libwebp could not assemble the animation: {}", encoder_error(encoder));
+    }
+
+    let bytes = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+    WebPDataClear(&mut data);
+    Ok(bytes)
+}
+
+unsafe fn encoder_error(encoder: *mut WebPAnimEncoder) -> String {
+    let message = WebPAnimEncoderGetError(encoder);
+    if message.is_null() {
+        "unknown error".to_string()
+    } else {
+        std::ffi::CStr::from_ptr(message).to_string_lossy().into_owned()
+    }
+}
+
+/// Decodes an animated (or still) WebP with libwebp's AnimDecoder into
+/// full-canvas RGBA frames and their display times in milliseconds.
+pub fn decode_animated_webp(bytes: &[u8]) -> Result<Vec<(RgbaImage, u32)>> {
+    unsafe {
+        let mut options = std::mem::MaybeUninit::<WebPAnimDecoderOptions>::uninit();
+        if WebPAnimDecoderOptionsInitInternal(options.as_mut_ptr(), WebPGetDemuxABIVersion()) == 0 {
+            anyhow::bail!("libwebp animation decoder options init failed");
+        }
+        let mut options = options.assume_init();
+        options.color_mode = WEBP_CSP_MODE::MODE_RGBA;
+
+        let data = WebPData { bytes: bytes.as_ptr(), size: bytes.len() };
+        let decoder = WebPAnimDecoderNewInternal(&data, &options, WebPGetDemuxABIVersion());
+        if decoder.is_null() {
+            anyhow::bail!("not a valid WebP file");
+        }
+        let result = read_frames(decoder);
+        WebPAnimDecoderDelete(decoder);
+        result
+    }
+}
+
+unsafe fn read_frames(decoder: *mut WebPAnimDecoder) -> Result<Vec<(RgbaImage, u32)>> {
+    let mut info = std::mem::MaybeUninit::<WebPAnimInfo>::uninit();
+    if WebPAnimDecoderGetInfo(decoder, info.as_mut_ptr()) == 0 {
+        anyhow::bail!("libwebp could not read the animation info");
+    }
+    let info = info.assume_init();
+    let (width, height) = (info.canvas_width, info.canvas_height);
+
+    let mut frames = Vec::new();
+    let mut previous = 0;
+    while WebPAnimDecoderHasMoreFrames(decoder) != 0 {
+        let mut buffer: *mut u8 = std::ptr::null_mut();
+        let mut timestamp = 0;
+        if WebPAnimDecoderGetNext(decoder, &mut buffer, &mut timestamp) == 0 {
+            anyhow::bail!("libwebp could not decode frame {}", frames.len() + 1);
+        }
+        // Timestamps are when each frame ends
+        let pixels = std::slice::from_raw_parts(buffer, (width * height * 4) as usize).to_vec();
+        let frame = RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow::anyhow!("frame has the wrong size"))?;
+        frames.push((frame, timestamp.saturating_sub(previous).max(0) as u32));
+        previous = timestamp;
+    }
+    Ok(frames)
+}
@@ -0,0 +1,60 @@
+//! `--alpha` reaching every output a texture produces, not just the main
+//! one.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+
+/// Converts a 4x4, 3-mip texture with `extra` switches and returns whether
+/// each output (sorted by name) still has alpha.
+fn outputs_with_alpha(extra: &[&str]) -> Vec<(String, bool)> {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("rock.dds"), 4, 4, 3, [0, 0, 255, 128]);
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let output = dir.path().join("out");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(extra));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    let mut outputs: Vec<(String, bool)> = std::fs::read_dir(&output)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .map(|path| {
+            let alpha = image::open(&path).unwrap().color().has_alpha();
+            (path.file_name().unwrap().to_string_lossy().into_owned(), alpha)
+        })
+        .collect();
+    outputs.sort();
+    outputs
+}
+
+#[test]
+fn every_mip_level_is_flattened() {
+    let outputs = outputs_with_alpha(&["--all-mips", "--alpha", "strip"]);
+    assert_eq!(outputs.len(), 3, "{:?}", outputs);
+    assert!(outputs.iter().all(|(_, alpha)| !alpha), "{:?}", outputs);
+}
+
+#[test]
+fn every_tile_is_flattened() {
+    let outputs = outputs_with_alpha(&["--tile", "2", "--alpha", "black"]);
+    assert_eq!(outputs.len(), 4, "{:?}", outputs);
+    assert!(outputs.iter().all(|(_, alpha)| !alpha), "{:?}", outputs);
+}
+
+#[test]
+fn keep_leaves_alpha_alone() {
+    let outputs = outputs_with_alpha(&["--all-mips"]);
+    assert!(outputs.iter().all(|(_, alpha)| *alpha), "{:?}", outputs);
+}
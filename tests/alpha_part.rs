@@ -0,0 +1,110 @@
+//! A `.dds.a` alpha companion merged into the output's alpha channel, end
+//! to end through a texconv stand-in that decodes the colour and alpha
+//! textures to different images.
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use common::{bin, run, stderr, stdout, write_dds};
+
+/// Alpha values of the fixture's companion, one per column.
+const COLUMNS: [u8; 4] = [0, 85, 170, 255];
+
+/// A texconv stand-in that decodes the staged `alpha.dds` to a grey ramp of
+/// `alpha_size` and anything else to opaque red 4x4.
+fn alpha_texconv(dir: &Path, alpha_size: u32) -> PathBuf {
+    let color = dir.join("color.png");
+    let alpha = dir.join("alpha.png");
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])).save(&color).unwrap();
+    image::GrayImage::from_fn(alpha_size, alpha_size, |x, _| image::Luma([COLUMNS[(x * 4 / alpha_size) as usize]]))
+        .save(&alpha)
+        .unwrap();
+
+    let script = dir.join("texconv.sh");
+    let body = format!(
+        r#"#!/bin/sh
+[ "$1" = "-h" ] && exit 1
+out=""; ft="png"; prev=""; last=""
+for a in "$@"; do
+  [ "$prev" = "-o" ] && out="$a"
+  [ "$prev" = "-ft" ] && ft="$a"
+  prev="$a"; last="$a"
+done
+name=$(basename "$last")
+image="{}"
+[ "$name" = "alpha.dds" ] && image="{}"
+cp "$image" "$out/${{name%.*}}.$ft"
+"#,
+        color.display(),
+        alpha.display()
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+/// Converts `rock.dds` with its `rock.dds.a` and returns the output and
+/// what was printed.
+fn convert(dir: &Path, alpha_size: u32, extra: &[&str]) -> (Option<image::RgbaImage>, String) {
+    let input = dir.join("in");
+    write_dds(&input.join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("rock.dds.a"), alpha_size, alpha_size, 1, [128, 128, 128, 255]);
+    let texconv = alpha_texconv(dir, alpha_size);
+    let output = dir.join("out");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(extra));
+    let printed = format!("{}\n{}", stdout(&result), stderr(&result));
+    let image = image::open(output.join("rock.png")).ok().map(|image| image.to_rgba8());
+    (image, printed)
+}
+
+#[test]
+fn companion_luminance_becomes_the_alpha_channel() {
+    let dir = tempfile::tempdir().unwrap();
+    let (image, printed) = convert(dir.path(), 4, &["--verbose"]);
+    let image = image.unwrap_or_else(|| panic!("{}", printed));
+    for (x, y, pixel) in image.enumerate_pixels() {
+        assert_eq!(pixel.0, [255, 0, 0, COLUMNS[x as usize]], "({}, {})", x, y);
+    }
+    assert!(printed.contains("🎭 Merged alpha from"), "{}", printed);
+    // The companion isn't converted on its own
+    assert_eq!(std::fs::read_dir(dir.path().join("out")).unwrap().count(), 1);
+}
+
+#[test]
+fn companion_a_mip_larger_is_scaled_with_a_warning() {
+    let dir = tempfile::tempdir().unwrap();
+    let (image, printed) = convert(dir.path(), 8, &[]);
+    let image = image.unwrap_or_else(|| panic!("{}", printed));
+    assert!(printed.contains("Alpha part is 8x8, texture is 4x4; resizing to match"), "{}", printed);
+    assert_eq!(image.dimensions(), (8, 8));
+    assert_eq!(image.get_pixel(0, 0)[3], 0);
+    assert_eq!(image.get_pixel(7, 7)[3], 255);
+}
+
+#[test]
+fn companion_of_another_size_fails_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (image, printed) = convert(dir.path(), 3, &[]);
+    assert!(image.is_none());
+    assert!(printed.contains("Alpha part is 3x3 but the texture is 4x4"), "{}", printed);
+}
+
+#[test]
+fn no_merge_alpha_part_keeps_the_colour_texture_as_is() {
+    let dir = tempfile::tempdir().unwrap();
+    let (image, printed) = convert(dir.path(), 4, &["--no-merge-alpha-part"]);
+    let image = image.unwrap_or_else(|| panic!("{}", printed));
+    assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 0, 255]));
+}
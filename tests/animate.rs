@@ -0,0 +1,53 @@
+mod common;
+
+use common::{bin, run, stderr, stdout, write_gif, write_png};
+
+#[test]
+fn animated_files_and_image_sequences_are_both_animated() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    std::fs::create_dir_all(&input).unwrap();
+    write_gif(&input.join("fire.gif"), 3);
+    for frame in 1..=3 {
+        write_png(&input.join(format!("glow_{:02}.png", frame)));
+    }
+    let output = dir.path().join("out");
+
+    let result = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(&output));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(output.join("fire.webp").is_file(), "{}", stdout(&result));
+    assert!(output.join("glow.webp").is_file(), "{}", stdout(&result));
+}
+
+#[test]
+fn same_named_sequences_in_sibling_folders_get_their_folder_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for folder in ["fire", "ice"] {
+        std::fs::create_dir_all(input.join(folder)).unwrap();
+        for frame in 1..=3 {
+            write_png(&input.join(folder).join(format!("glow_{:02}.png", frame)));
+        }
+    }
+    let output = dir.path().join("out");
+
+    let result = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(&output).arg("--dry-run"));
+    let printed = stdout(&result);
+    assert!(result.status.success(), "{}\n{}", printed, stderr(&result));
+    for folder in ["fire", "ice"] {
+        let line = format!(
+            "  {} -> {}",
+            input.join(folder).join("glow_01.png").display(),
+            output.join(folder).join(format!("{}_glow.webp", folder)).display()
+        );
+        assert!(printed.contains(&line), "{}", printed);
+    }
+    assert_eq!(printed.matches("(named after its folder: another animation has the same name)").count(), 2, "{}", printed);
+    assert!(!output.exists());
+
+    let result = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(&output));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(output.join("fire/fire_glow.webp").is_file(), "{}", stdout(&result));
+    assert!(output.join("ice/ice_glow.webp").is_file(), "{}", stdout(&result));
+    assert!(!output.join("glow.webp").exists());
+}
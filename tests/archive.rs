@@ -0,0 +1,33 @@
+//! `--output-zip` runs against an archive left by an earlier run.
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds};
+
+#[cfg(unix)]
+#[test]
+fn skip_existing_skips_archived_entries_and_keeps_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let archive = dir.path().join("icons.zip");
+    write_dds(&input.join("a/rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    common::write_png(&dir.path().join("out.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("out.png"));
+    let convert = || {
+        let mut command = bin();
+        command.arg("--texconv-path").arg(&texconv).arg("-i").arg(&input).arg("--output-zip").arg(&archive).arg("--skip-existing");
+        run(&mut command)
+    };
+
+    let first = convert();
+    assert!(first.status.success(), "{}", stderr(&first));
+    write_dds(&input.join("b/sand.dds"), 4, 4, 1, [0, 255, 0, 255]);
+
+    let second = convert();
+    assert!(second.status.success(), "{}", stderr(&second));
+    assert!(stdout(&second).contains("Skipped (output already exists): 1"), "{}", stdout(&second));
+    let archive = zip::ZipArchive::new(std::fs::File::open(&archive).unwrap()).unwrap();
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort();
+    assert_eq!(names, ["a/rock.png", "b/sand.png"]);
+}
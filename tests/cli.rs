@@ -0,0 +1,76 @@
+//! The subcommand invocations and the flat ones they replaced.
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds};
+
+#[test]
+fn info_subcommand_and_legacy_flag_print_the_same_table() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("rock.dds"), 8, 4, 1, [0, 0, 255, 255]);
+
+    let new = run(bin().args(["info", "--input"]).arg(dir.path()));
+    let old = run(bin().args(["--info", "--input"]).arg(dir.path()));
+    assert!(new.status.success() && old.status.success());
+    assert!(stdout(&new).contains("8x4"));
+    assert_eq!(stdout(&new), stdout(&old));
+    assert!(!stderr(&new).contains("deprecated"));
+    assert!(stderr(&old).contains("use `dds-converter info ...`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn convert_subcommand_and_flat_flags_plan_the_same_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("a/rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    common::write_png(&dir.path().join("out.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("out.png"));
+    let args = |command: &mut std::process::Command| {
+        command.arg("--texconv-path").arg(&texconv).arg("-i").arg(&input).arg("-o").arg(dir.path().join("out")).args(["--format", "tga", "--dry-run"]);
+    };
+
+    let mut new = bin();
+    new.arg("convert");
+    args(&mut new);
+    let new = run(&mut new);
+    let mut old = bin();
+    args(&mut old);
+    let old = run(&mut old);
+
+    assert!(new.status.success(), "{}", stderr(&new));
+    assert!(old.status.success(), "{}", stderr(&old));
+    assert!(stdout(&new).contains("rock.tga"));
+    assert_eq!(stdout(&new), stdout(&old));
+    assert!(stderr(&old).contains("use `dds-converter convert ...`"));
+}
+
+#[test]
+fn animation_mode_flag_maps_to_animate() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    std::fs::create_dir_all(&input).unwrap();
+    for frame in 1..=3 {
+        common::write_png(&input.join(format!("glow_{:02}.png", frame)));
+    }
+    let output = dir.path().join("out");
+
+    let new = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(&output).arg("--dry-run"));
+    let old = run(bin().arg("--animation-mode").arg("-i").arg(&input).arg("-o").arg(&output).arg("--dry-run"));
+    assert!(new.status.success(), "{}", stderr(&new));
+    assert!(old.status.success(), "{}", stderr(&old));
+    assert!(stdout(&new).contains("glow"));
+    assert_eq!(stdout(&new), stdout(&old));
+    assert!(stderr(&old).contains("use `dds-converter animate ...`"));
+    assert!(!output.exists());
+}
+
+#[test]
+fn flags_without_values_for_the_other_mode_are_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("rock.dds"), 2, 2, 1, [0, 0, 0, 255]);
+    // --format belongs to convert; the old CLI ignored it with --info
+    let old = run(bin().args(["--info", "--format", "jpg", "--input"]).arg(dir.path()));
+    assert!(old.status.success(), "{}", stderr(&old));
+    assert!(stdout(&old).contains("2x2"));
+}
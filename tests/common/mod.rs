@@ -0,0 +1,177 @@
+//! Helpers for running the built binary against generated textures and a
+//! stand-in texconv script.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+pub fn bin() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_dds-converter"));
+    for variable in ["DDS_CONVERTER_INPUT", "DDS_CONVERTER_OUTPUT", "DDS_CONVERTER_FORMAT", "DDS_CONVERTER_CONCURRENCY", "DDS_CONVERTER_TEXCONV_PATH", "DDS_CONVERTER_CONFIG"] {
+        command.env_remove(variable);
+    }
+    command.env("RUST_BACKTRACE", "0");
+    command
+}
+
+pub fn run(command: &mut Command) -> Output {
+    command.output().expect("failed to run dds-converter")
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// An uncompressed 32-bit BGRA DDS of `width`x`height` with `mips` levels,
+/// every pixel `bgra`.
+pub fn write_dds(path: &Path, width: u32, height: u32, mips: u32, bgra: [u8; 4]) {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"DDS ");
+    let flags = 0x1 | 0x2 | 0x4 | 0x1000 | 0x8 | if mips > 1 { 0x20000 } else { 0 };
+    for value in [124, flags, height, width, width * 4, 0, mips] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    bytes.extend_from_slice(&[0; 44]);
+    for value in [32, 0x41, 0, 32, 0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    let caps = 0x1000 | if mips > 1 { 0x40_0008 } else { 0 };
+    for value in [caps, 0, 0, 0, 0] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    for level in 0..mips {
+        let pixels = (width >> level).max(1) * (height >> level).max(1);
+        for _ in 0..pixels {
+            bytes.extend_from_slice(&bgra);
+        }
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+/// A DX10 DDS of `width`x`height` in the 32-bit DXGI `format` (e.g. 29,
+/// R8G8B8A8_UNORM_SRGB), every pixel `rgba`.
+pub fn write_dds_dxgi(path: &Path, width: u32, height: u32, format: u32, rgba: [u8; 4]) {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"DDS ");
+    for value in [124, 0x1 | 0x2 | 0x4 | 0x1000, height, width, width * 4, 0, 1] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    bytes.extend_from_slice(&[0; 44]);
+    bytes.extend_from_slice(&u32::to_le_bytes(32));
+    bytes.extend_from_slice(&u32::to_le_bytes(0x4));
+    bytes.extend_from_slice(b"DX10");
+    bytes.extend_from_slice(&[0; 20]);
+    for value in [0x1000, 0, 0, 0, 0, format, 3, 0, 1, 0] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    for _ in 0..width * height {
+        bytes.extend_from_slice(&rgba);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+/// A shell script that behaves like texconv for the tests: it answers
+/// `-h`, and for a conversion writes `image` as `<out>/<stem>.<ft>`.
+/// Inputs whose name contains `fail` make it exit 1 without output; ones
+/// with `slow` make it hang for a minute, ones with `gpu` fail with a
+/// Direct3D error unless run with `-nogpu`, and ones with `flaky` fail with
+/// "Access is denied" the first time only.
+#[cfg(unix)]
+pub fn stub_texconv(dir: &Path, image: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join("texconv.sh");
+    let body = format!(
+        r#"#!/bin/sh
+[ "$1" = "-h" ] && exit 1
+out=""; ft="png"; prev=""; last=""; nogpu=""
+for a in "$@"; do
+  [ "$a" = "-nogpu" ] && nogpu=1
+  [ "$prev" = "-o" ] && out="$a"
+  [ "$prev" = "-ft" ] && ft="$a"
+  prev="$a"; last="$a"
+done
+name=$(basename "$last")
+case "$name" in *flaky*) [ ! -e "$0.$name" ] && touch "$0.$name" && echo "FAILED (80070005: Access is denied.)" >&2 && exit 1;; esac
+case "$name" in *fail*) echo "ERROR: injected failure for $name" >&2; exit 1;; *slow*) exec sleep 60;; *gpu*) [ -z "$nogpu" ] && echo "ERROR: Failed to create Direct3D device" >&2 && exit 1;; esac
+cp "{}" "$out/${{name%.*}}.$ft"
+"#,
+        image.display()
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+/// Runs `convert` from `<dir>/in` to `<dir>/out` with [`stub_texconv`]
+/// handing out `<dir>/decoded.png`, which is written first unless the test
+/// put its own there.
+#[cfg(unix)]
+pub fn convert(dir: &Path, extra: &[&str]) -> Output {
+    let decoded = dir.join("decoded.png");
+    if !decoded.exists() {
+        write_png(&decoded);
+    }
+    let texconv = stub_texconv(dir, &decoded);
+    run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(dir.join("in"))
+        .arg("-o")
+        .arg(dir.join("out"))
+        .args(extra))
+}
+
+/// Files under `<dir>/out`, relative to it with `/` separators, sorted;
+/// empty when nothing was written.
+pub fn written(dir: &Path) -> Vec<String> {
+    let output = dir.join("out");
+    if !output.exists() {
+        return Vec::new();
+    }
+    let mut names: Vec<String> = walkdir::WalkDir::new(&output)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| !entry.file_type().is_dir())
+        .map(|entry| entry.path().strip_prefix(&output).unwrap().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// What `--on-collision suffix-hash` appends to the output of the input at
+/// `relative` (to --input, with `/` separators).
+pub fn suffix_hash(relative: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(relative.as_bytes()).iter().take(4).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A 4x4 RGBA PNG for the stub texconv to hand out.
+pub fn write_png(path: &Path) {
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([200, 100, 50, 255])).save(path).unwrap();
+}
+
+/// A 4x4 GIF with `frames` frames.
+pub fn write_gif(path: &Path, frames: u8) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = gif::Encoder::new(file, 4, 4, &[0, 0, 0, 255, 255, 255]).unwrap();
+    encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+    for index in 0..frames {
+        let mut frame = gif::Frame::from_indexed_pixels(4, 4, &[index % 2; 16], None);
+        frame.delay = 5;
+        encoder.write_frame(&frame).unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+//! `--content-hash` reruns find their earlier outputs through the manifest.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+
+#[test]
+fn skip_existing_looks_hashed_outputs_up_in_the_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let output = dir.path().join("out");
+    write_dds(&input.join("ui/sword.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let convert = || {
+        run(bin()
+            .arg("--texconv-path").arg(&texconv)
+            .arg("-i").arg(&input)
+            .arg("-o").arg(&output)
+            .args(["--content-hash", "--hash-manifest", "--skip-existing"]))
+    };
+    let manifest = || -> serde_json::Map<String, serde_json::Value> {
+        serde_json::from_str(&std::fs::read_to_string(output.join("manifest.json")).unwrap()).unwrap()
+    };
+
+    let first = convert();
+    assert!(first.status.success(), "{}", stderr(&first));
+    let hashed = manifest()["ui/sword.png"].as_str().unwrap().to_string();
+    assert!(output.join(&hashed).is_file());
+
+    write_dds(&input.join("ui/shield.dds"), 4, 4, 1, [0, 255, 0, 255]);
+    let second = convert();
+    assert!(second.status.success(), "{}", stderr(&second));
+    assert!(stdout(&second).contains("Skipped (output already exists): 1"), "{}", stdout(&second));
+    let entries = manifest();
+    assert_eq!(entries.keys().collect::<Vec<_>>(), ["ui/shield.png", "ui/sword.png"]);
+    assert_eq!(entries["ui/sword.png"], hashed.as_str());
+
+    // Once the hashed output is gone, the file is converted again
+    std::fs::remove_file(output.join(&hashed)).unwrap();
+    let third = convert();
+    assert!(third.status.success(), "{}", stderr(&third));
+    assert!(stdout(&third).contains("Skipped (output already exists): 1"), "{}", stdout(&third));
+    assert!(output.join(&hashed).is_file());
+}
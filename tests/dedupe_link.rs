@@ -0,0 +1,61 @@
+//! `--dedupe-link`: duplicates get their output linked or copied, pass
+//! `--verify` through the link, and the method shows per file in the
+//! report and the run log.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{convert, stderr, stdout, write_dds};
+use serde_json::{json, Value};
+
+/// Two identical textures, `in/a/rock.dds` and `in/b/rock.dds`, converted
+/// with `--dedupe-link mode --verify`; returns what was printed, the report
+/// and the run log's file records.
+fn dedupe(dir: &Path, mode: &str) -> (String, Value, Vec<Value>) {
+    for folder in ["a", "b"] {
+        write_dds(&dir.join("in").join(folder).join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    }
+    let (report, log) = (dir.join("report.json"), dir.join("log.jsonl"));
+    let (report, log) = (report.to_str().unwrap(), log.to_str().unwrap());
+    let result = convert(dir, &["--dedupe-link", mode, "--verify", "--verbose", "--concurrency", "1", "--report", report, "--log-file", log]);
+    let printed = format!("{}{}", stdout(&result), stderr(&result));
+    assert!(result.status.success(), "{}", printed);
+    let report = serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    let files = std::fs::read_to_string(dir.join("log.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|record| record["event"] == "file")
+        .collect();
+    (printed, report, files)
+}
+
+#[test]
+fn symlinked_duplicates_are_verified_through_the_link() {
+    let dir = tempfile::tempdir().unwrap();
+    let (printed, report, files) = dedupe(dir.path(), "symlink");
+    let duplicate = dir.path().join("out/b/rock.png");
+    assert!(std::fs::symlink_metadata(&duplicate).unwrap().file_type().is_symlink());
+    assert!(printed.contains(&format!("🔍 Verified {}", duplicate.display())), "{}", printed);
+
+    let input = dir.path().join("in/b/rock.dds").display().to_string();
+    assert_eq!(report["linked_files"], json!([{ "input": input, "method": "symlink" }]));
+    let linked: Vec<&Value> = files.iter().filter(|record| record["status"] == "linked").collect();
+    assert_eq!(linked.len(), 1);
+    assert_eq!((&linked[0]["input"], &linked[0]["method"]), (&json!(input), &json!("symlink")));
+    // Converted files don't get a method
+    assert!(files.iter().filter(|record| record["status"] == "converted").all(|record| record.get("method").is_none()));
+}
+
+#[test]
+fn each_mode_is_recorded_as_made() {
+    for mode in ["hardlink", "copy"] {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, report, files) = dedupe(dir.path(), mode);
+        assert_eq!(report["linked_files"][0]["method"], mode);
+        assert!(files.iter().any(|record| record["status"] == "linked" && record["method"] == mode));
+        image::open(dir.path().join("out/b/rock.png")).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+//! `--dry-run-json` over a fixture tree: stdout is exactly one JSON array
+//! with the documented fields, skipped files included with their reason.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{convert, stderr, stdout, write_dds, write_png};
+use serde_json::{json, Value};
+
+/// `rock.dds` (192 bytes), `big.dds` (over 4KB), `tiny.dds` (under the
+/// 128-byte floor), `lod/rock_lod1.dds` and `done.dds`, whose PNG is
+/// already in `out`.
+fn fixture(dir: &Path) {
+    let input = dir.join("in");
+    write_dds(&input.join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("big.dds"), 64, 64, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("lod/rock_lod1.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("done.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    std::fs::write(input.join("tiny.dds"), [0; 10]).unwrap();
+    std::fs::create_dir_all(dir.join("out")).unwrap();
+    write_png(&dir.join("out/done.png"));
+}
+
+/// Whether the run succeeded, and what it printed.
+fn dry_run_json(dir: &Path, extra: &[&str]) -> (bool, Value) {
+    let result = convert(dir, &[&["--dry-run-json"], extra].concat());
+    // Nothing but the array, so scripts can parse stdout as it is
+    let listing = serde_json::from_str(&stdout(&result)).unwrap_or_else(|error| panic!("{}: {}\n{}", error, stdout(&result), stderr(&result)));
+    (result.status.success(), listing)
+}
+
+#[test]
+fn every_discovered_file_is_listed_with_its_fate() {
+    let dir = tempfile::tempdir().unwrap();
+    fixture(dir.path());
+    let path = |relative: &str| dir.path().join(relative).display().to_string();
+
+    let (success, listing) = dry_run_json(dir.path(), &["--max-size", "4KB", "--exclude", "**/lod/**", "--skip-existing"]);
+    assert!(success);
+    assert_eq!(
+        listing,
+        json!([
+            { "input": path("in/big.dds"), "output": null, "size_bytes": 16512, "skipped_reason": "larger than --max-size" },
+            { "input": path("in/done.dds"), "output": path("out/done.png"), "size_bytes": 192, "skipped_reason": "output already exists" },
+            { "input": path("in/lod/rock_lod1.dds"), "output": null, "size_bytes": 192, "skipped_reason": "excluded by **/lod/**" },
+            { "input": path("in/rock.dds"), "output": path("out/rock.png"), "size_bytes": 192, "skipped_reason": null },
+            { "input": path("in/tiny.dds"), "output": null, "size_bytes": 10, "skipped_reason": "smaller than --min-size" },
+        ])
+    );
+    // A dry run writes nothing
+    assert_eq!(std::fs::read_dir(dir.path().join("out")).unwrap().count(), 1);
+}
+
+#[test]
+fn every_entry_has_exactly_the_documented_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    fixture(dir.path());
+    let (success, listing) = dry_run_json(dir.path(), &[]);
+    assert!(success);
+    let entries = listing.as_array().expect("a JSON array");
+    assert_eq!(entries.len(), 5);
+    for entry in entries {
+        let object = entry.as_object().expect("an object per file");
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["input", "output", "size_bytes", "skipped_reason"]);
+        assert!(object["input"].is_string());
+        assert!(object["output"].is_string() || object["output"].is_null());
+        assert!(object["size_bytes"].is_u64());
+        assert!(object["skipped_reason"].is_string() || object["skipped_reason"].is_null());
+    }
+}
+
+#[test]
+fn a_run_with_nothing_left_still_lists_the_filtered_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fixture(dir.path());
+    // Nothing matched, which fails the run, but the listing is still there
+    let (success, listing) = dry_run_json(dir.path(), &["--min-size", "1MB"]);
+    assert!(!success);
+    let reasons: Vec<&str> = listing.as_array().unwrap().iter().map(|entry| entry["skipped_reason"].as_str().unwrap()).collect();
+    assert_eq!(reasons, ["smaller than --min-size"; 5]);
+}
@@ -0,0 +1,64 @@
+//! `--max-errors` stopping a run whose files keep failing.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+
+#[test]
+fn max_errors_stops_the_run_with_file_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for name in ["a_fail", "b_fail", "c_fail", "d_fail", "e_fail", "f_ok"] {
+        write_dds(&input.join(format!("{}.dds", name)), 4, 4, 1, [0, 0, 255, 255]);
+    }
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let report = dir.path().join("report.json");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(dir.path().join("out"))
+        .args(["--continue-on-error", "--max-errors", "1", "--concurrency", "1", "--report"])
+        .arg(&report));
+
+    let output = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert_eq!(result.status.code(), Some(2), "{}", output);
+    assert!(output.contains("more than --max-errors 1"), "{}", output);
+    assert!(output.contains("were not attempted"), "{}", output);
+
+    let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let failed = report["failed"].as_array().map_or(0, Vec::len);
+    assert_eq!(failed, 2, "{}", report);
+}
+
+#[test]
+fn failure_list_of_a_stopped_run_includes_unattempted_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for name in ["a_fail", "b_fail", "c_ok", "d_ok"] {
+        write_dds(&input.join(format!("{}.dds", name)), 4, 4, 1, [0, 0, 255, 255]);
+    }
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let output = dir.path().join("out");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["--continue-on-error", "--max-errors", "1", "--concurrency", "1"]));
+    assert_eq!(result.status.code(), Some(2), "{}\n{}", stdout(&result), stderr(&result));
+
+    let list = std::fs::read_to_string(output.join("failed-files.txt")).unwrap();
+    assert_eq!(list.lines().collect::<Vec<_>>(), ["a_fail.dds", "b_fail.dds", "c_ok.dds", "d_ok.dds"]);
+}
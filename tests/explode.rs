@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_gif, write_png};
+use std::path::Path;
+
+fn frame_delays(manifest: &Path) -> Vec<u64> {
+    let entries: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(manifest).unwrap()).unwrap();
+    entries.as_array().unwrap().iter().map(|entry| entry["delay_ms"].as_u64().unwrap()).collect()
+}
+
+#[test]
+fn animate_explode_animate_keeps_frames_and_timing() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    std::fs::create_dir_all(&input).unwrap();
+    write_gif(&input.join("fire.gif"), 3);
+
+    let animated = dir.path().join("animated");
+    let result = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(&animated));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(animated.join("fire.webp").is_file(), "{}", stdout(&result));
+
+    let sheet = dir.path().join("sheet");
+    let result = run(bin().arg("explode").arg("-i").arg(animated.join("fire.webp")).arg("-o").arg(&sheet).arg("--to").arg("sprite"));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(sheet.join("fire.png").is_file() && sheet.join("fire.sprite").is_file(), "{}", stdout(&result));
+
+    let rebuilt = dir.path().join("rebuilt");
+    let result = run(bin().arg("animate").arg("-i").arg(&sheet).arg("-o").arg(&rebuilt));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(rebuilt.join("fire.webp").is_file(), "{}", stdout(&result));
+
+    let frames = dir.path().join("frames");
+    let result = run(bin().arg("explode").arg("-i").arg(rebuilt.join("fire.webp")).arg("-o").arg(&frames));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert_eq!(frame_delays(&frames.join("fire").join("fire.frames.json")), vec![50, 50, 50]);
+}
+
+#[test]
+fn a_png_next_to_a_sprite_file_is_not_a_sheet() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    std::fs::create_dir_all(&input).unwrap();
+    write_png(&input.join("icon.png"));
+    std::fs::write(
+        input.join("icon.sprite"),
+        "<Sprite>\n  <Cell topLeft=\"0,0\" topRight=\"1,0\" bottomLeft=\"0,1\" bottomRight=\"1,1\" />\n</Sprite>\n",
+    )
+    .unwrap();
+    let output = dir.path().join("out");
+
+    let result = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(&output));
+    assert!(!output.join("icon.webp").exists(), "{}", stdout(&result));
+}
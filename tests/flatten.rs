@@ -0,0 +1,60 @@
+//! `--flatten`: every output lands directly in --output, and inputs from
+//! different folders that share a stem are told apart.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{convert, stderr, stdout, suffix_hash, write_dds, written};
+
+/// `in/a/diffuse.dds`, `in/b/diffuse.dds` and `in/c/deeper/normal.dds`.
+fn game_dump(dir: &Path) {
+    for name in ["a/diffuse", "b/diffuse", "c/deeper/normal"] {
+        write_dds(&dir.join("in").join(format!("{}.dds", name)), 4, 4, 1, [0, 0, 255, 255]);
+    }
+}
+
+#[test]
+fn identical_stems_get_a_hash_of_their_path_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    game_dump(dir.path());
+    let (a, b) = (format!("diffuse_{}.png", suffix_hash("a/diffuse.dds")), format!("diffuse_{}.png", suffix_hash("b/diffuse.dds")));
+
+    let result = convert(dir.path(), &["--flatten", "--dry-run"]);
+    let printed = stdout(&result);
+    assert!(result.status.success(), "{}\n{}", printed, stderr(&result));
+    for (input, output) in [("a/diffuse.dds", &a), ("b/diffuse.dds", &b)] {
+        let line = format!("  {} -> {}", dir.path().join("in").join(input).display(), dir.path().join("out").join(output).display());
+        assert!(printed.contains(&line), "{}", printed);
+    }
+    assert_eq!(printed.matches("(suffixed with a hash of its path: 2 inputs share this name)").count(), 2, "{}", printed);
+    assert!(!dir.path().join("out").exists());
+
+    let result = convert(dir.path(), &["--flatten"]);
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    let mut expected = vec![a, b, "normal.png".to_string()];
+    expected.sort();
+    assert_eq!(written(dir.path()), expected);
+}
+
+#[test]
+fn identical_stems_can_be_counted_instead() {
+    let dir = tempfile::tempdir().unwrap();
+    game_dump(dir.path());
+    let result = convert(dir.path(), &["--flatten", "--on-collision", "suffix-counter"]);
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert_eq!(written(dir.path()), ["diffuse.png", "diffuse_2.png", "normal.png"]);
+}
+
+#[test]
+fn identical_stems_fail_with_on_collision_error() {
+    let dir = tempfile::tempdir().unwrap();
+    game_dump(dir.path());
+    let result = convert(dir.path(), &["--flatten", "--on-collision", "error"]);
+    assert!(!result.status.success());
+    let printed = format!("{}{}", stdout(&result), stderr(&result));
+    assert!(printed.contains("1 output path(s) would be written by more than one input"), "{}", printed);
+    assert!(printed.contains(&format!("<- {}", dir.path().join("in/b/diffuse.dds").display())), "{}", printed);
+    assert!(!dir.path().join("out").exists());
+}
@@ -0,0 +1,66 @@
+//! `--gpu`: the retry on the CPU after a GPU failure, as `--report` shows
+//! it, and `animate` passing the adapter on.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+
+#[test]
+fn report_marks_files_converted_after_a_gpu_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("rock_gpu.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("sand.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let report = dir.path().join("report.json");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(dir.path().join("out"))
+        .arg("--report")
+        .arg(&report));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+
+    let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let files = report["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2, "{}", report);
+    for file in files {
+        let fell_back = file["input"].as_str().unwrap().ends_with("rock_gpu.dds");
+        assert_eq!(file["gpu_fallback"], fell_back, "{}", report);
+    }
+}
+
+#[test]
+fn animate_decodes_frames_on_the_chosen_adapter() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for frame in 1..=3 {
+        write_dds(&input.join(format!("glow_gpu_{:02}.dds", frame)), 4, 4, 1, [0, 0, 255, 255]);
+    }
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let output = dir.path().join("out");
+
+    let result = run(bin()
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("animate")
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["--gpu", "cpu", "--verbose"]));
+    let text = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(result.status.success(), "{}", text);
+    assert!(output.join("glow_gpu.webp").is_file(), "{}", text);
+    let commands: Vec<&str> = text.lines().filter(|line| line.starts_with("🔧")).collect();
+    assert_eq!(commands.len(), 3, "{}", text);
+    assert!(commands.iter().all(|line| line.contains(" -nogpu ")), "{}", text);
+}
@@ -0,0 +1,36 @@
+//! The in-process JPEG encode behind `--jpeg-quality` and
+//! `--jpeg-subsampling`.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds};
+
+#[test]
+fn temporary_png_is_removed_when_the_jpeg_encode_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    // Wider than a JPEG can be, so texconv's PNG decodes but won't encode
+    let stub = dir.path().join("stub.png");
+    image::RgbaImage::new(70_000, 1).save(&stub).unwrap();
+    let texconv = common::stub_texconv(dir.path(), &stub);
+    let output = dir.path().join("out");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["--format", "jpg", "--jpeg-quality", "80"]));
+    let printed = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(!result.status.success(), "{}", printed);
+    assert!(printed.contains("too wide for JPEG"), "{}", printed);
+
+    // Neither the staging folder with the PNG nor a partial JPEG is left
+    let left: Vec<_> = std::fs::read_dir(&output).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert!(left.is_empty(), "{:?}", left);
+}
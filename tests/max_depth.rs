@@ -0,0 +1,61 @@
+//! `--max-depth` over a nested tree: how deep discovery goes for
+//! conversions and animations, and how it combines with --strip-segments.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{bin, convert, run, stderr, stdout, write_dds, write_png, written};
+
+/// `in/top.dds`, `in/a/mid.dds`, `in/a/b/deep.dds` and `in/a/b/c/deepest.dds`.
+fn nested_tree(dir: &Path) {
+    for name in ["top", "a/mid", "a/b/deep", "a/b/c/deepest"] {
+        write_dds(&dir.join("in").join(format!("{}.dds", name)), 4, 4, 1, [0, 0, 255, 255]);
+    }
+}
+
+#[test]
+fn discovery_stops_at_the_given_depth() {
+    let dir = tempfile::tempdir().unwrap();
+    nested_tree(dir.path());
+    let cases: [(&[&str], &[&str]); 5] = [
+        (&["--max-depth", "1"], &["top.png"]),
+        (&["--max-depth", "2"], &["a/mid.png", "top.png"]),
+        (&["--max-depth", "3"], &["a/b/deep.png", "a/mid.png", "top.png"]),
+        (&[], &["a/b/c/deepest.png", "a/b/deep.png", "a/mid.png", "top.png"]),
+        // The walk stops at a/b, but the stripped segment is still `a`
+        (&["--max-depth", "3", "--strip-segments", "1"], &["b/deep.png", "mid.png", "top.png"]),
+    ];
+    for (extra, expected) in cases {
+        let result = convert(dir.path(), extra);
+        assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+        assert_eq!(written(dir.path()), expected, "{:?}", extra);
+        std::fs::remove_dir_all(dir.path().join("out")).unwrap();
+    }
+}
+
+#[test]
+fn zero_is_rejected() {
+    let result = run(bin().args(["convert", "-i", ".", "-o", "out", "--max-depth", "0"]));
+    assert!(!result.status.success());
+    assert!(stderr(&result).contains("--max-depth"), "{}", stderr(&result));
+}
+
+#[test]
+fn animation_discovery_stops_at_the_same_depth() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for folder in ["", "fx", "fx/archive"] {
+        std::fs::create_dir_all(input.join(folder)).unwrap();
+        for frame in 1..=2 {
+            write_png(&input.join(folder).join(format!("glow_{:02}.png", frame)));
+        }
+    }
+    let result = run(bin().arg("animate").arg("-i").arg(&input).arg("-o").arg(dir.path().join("out")).args(["--dry-run", "--max-depth", "2"]));
+    let printed = stdout(&result);
+    assert!(result.status.success(), "{}\n{}", printed, stderr(&result));
+    assert!(printed.contains(&input.join("glow_01.png").display().to_string()), "{}", printed);
+    assert!(printed.contains(&input.join("fx/glow_01.png").display().to_string()), "{}", printed);
+    assert!(!printed.contains("archive"), "{}", printed);
+}
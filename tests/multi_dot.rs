@@ -0,0 +1,47 @@
+//! Multi-dot input names against a texconv that cuts its output name at the
+//! first dot, as some versions do: the output still lands on the planned
+//! name.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn outputs_keep_every_dot_whatever_texconv_names_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for name in ["ui.hud.compass.dds", "ui.hud.compass.dds.bak.dds", "rock.DDS"] {
+        write_dds(&input.join(name), 4, 4, 1, [0, 0, 255, 255]);
+    }
+    let stub = dir.path().join("stub.png");
+    write_png(&stub);
+    let texconv = dir.path().join("texconv.sh");
+    std::fs::write(
+        &texconv,
+        format!(
+            "#!/bin/sh\n[ \"$1\" = \"-h\" ] && exit 1\nout=\"\"; prev=\"\"; last=\"\"\nfor a in \"$@\"; do [ \"$prev\" = \"-o\" ] && out=\"$a\"; prev=\"$a\"; last=\"$a\"; done\nname=$(basename \"$last\")\ncp \"{}\" \"$out/${{name%%.*}}.png\"\n",
+            stub.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&texconv, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let output = dir.path().join("out");
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    let mut names: Vec<String> = std::fs::read_dir(&output)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["rock.png", "ui.hud.compass.dds.bak.png", "ui.hud.compass.png"]);
+}
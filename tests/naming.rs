@@ -0,0 +1,49 @@
+//! `--normalize-unicode`: composed and decomposed spellings of a name land
+//! on the same output, and the rename is reported.
+#![cfg(unix)]
+
+mod common;
+
+use common::{stderr, stdout, write_dds, written};
+
+const COMPOSED: &str = "caf\u{e9}";
+const DECOMPOSED: &str = "cafe\u{301}";
+
+/// Converts `in/<decomposed>.dds` plus whatever else the test put in `in`.
+fn convert(dir: &std::path::Path, extra: &[&str]) -> (String, Vec<String>) {
+    write_dds(&dir.join("in").join(format!("{}.dds", DECOMPOSED)), 4, 4, 1, [0, 0, 255, 255]);
+    let result = common::convert(dir, extra);
+    let text = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(result.status.success(), "{}", text);
+    (text, written(dir))
+}
+
+#[test]
+fn a_decomposed_name_is_written_composed_and_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let (text, names) = convert(dir.path(), &[]);
+    assert_eq!(names, [format!("{}.png", COMPOSED)]);
+    assert!(
+        text.lines().any(|line| line.starts_with("⚠️  Renamed") && line.contains(DECOMPOSED) && line.ends_with("(Unicode normalized)")),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn composed_and_decomposed_names_collide_after_normalizing() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("in").join(format!("{}.dds", COMPOSED)), 4, 4, 1, [0, 0, 255, 255]);
+    let (text, names) = convert(dir.path(), &["--on-collision", "suffix-counter"]);
+    assert_eq!(names.len(), 2, "{:?}", names);
+    assert!(names.iter().all(|name| name.starts_with(COMPOSED)), "{:?}", names);
+    assert!(text.contains("(Unicode normalized)"), "{}", text);
+}
+
+#[test]
+fn none_keeps_the_source_bytes_without_a_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let (text, names) = convert(dir.path(), &["--normalize-unicode", "none"]);
+    assert_eq!(names, [format!("{}.png", DECOMPOSED)]);
+    assert!(!text.contains("Renamed"), "{}", text);
+}
@@ -0,0 +1,95 @@
+//! `--reproducible`: converting the same texture twice gives byte-identical
+//! files, even when texconv stamps each run's output differently.
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use common::{bin, run, stderr, stdout, write_dds};
+
+/// A 4x4 PNG of the same pixels, with a creation time of `run`.
+fn stamped_png(path: &Path, run: u32) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), 4, 4);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.add_text_chunk("Creation Time".to_string(), format!("2024-01-0{} 12:00:00", run)).unwrap();
+    encoder.add_text_chunk("Software".to_string(), format!("texconv run {}", run)).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&[200; 4 * 4 * 4]).unwrap();
+}
+
+/// A 4x4 JPEG of the same pixels, with an EXIF segment stamped with `run`.
+fn stamped_jpeg(path: &Path, run: u32) {
+    let mut bytes = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, 90);
+    encoder.add_app_segment(1, format!("Exif\0\0DateTime 2024:01:0{} 12:00:00", run).as_bytes()).unwrap();
+    encoder.encode(&[200; 4 * 4 * 3], 4, 4, jpeg_encoder::ColorType::Rgb).unwrap();
+    std::fs::write(path, bytes).unwrap();
+}
+
+/// A texconv stand-in that hands out `<dir>/fixtures/<run>.<ft>`, the run
+/// coming from `STUB_RUN`.
+fn stamping_texconv(dir: &Path) -> PathBuf {
+    let fixtures = dir.join("fixtures");
+    std::fs::create_dir_all(&fixtures).unwrap();
+    for run in [1, 2] {
+        stamped_png(&fixtures.join(format!("{}.png", run)), run);
+        stamped_jpeg(&fixtures.join(format!("{}.jpg", run)), run);
+    }
+
+    let script = dir.join("texconv.sh");
+    let body = format!(
+        r#"#!/bin/sh
+[ "$1" = "-h" ] && exit 1
+out=""; ft="png"; prev=""; last=""
+for a in "$@"; do
+  [ "$prev" = "-o" ] && out="$a"
+  [ "$prev" = "-ft" ] && ft="$a"
+  prev="$a"; last="$a"
+done
+name=$(basename "$last")
+cp "{}/$STUB_RUN.$ft" "$out/${{name%.*}}.$ft"
+"#,
+        fixtures.display()
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+/// The bytes of `rock.<format>` after a run as texconv run `run`.
+fn convert(dir: &Path, run: u32, format: &str, reproducible: bool) -> Vec<u8> {
+    let output = dir.join(format!("out-{}-{}", format, run));
+    let result = self::run(bin()
+        .env("STUB_RUN", run.to_string())
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(dir.join("texconv.sh"))
+        .arg("-i")
+        .arg(dir.join("in"))
+        .arg("-o")
+        .arg(&output)
+        .args(["--format", format])
+        .args(reproducible.then_some("--reproducible")));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    std::fs::read(output.join(format!("rock.{}", format))).unwrap()
+}
+
+#[test]
+fn two_runs_give_byte_identical_png_jpeg_and_webp() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("in").join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    stamping_texconv(dir.path());
+
+    for format in ["png", "jpg", "webp"] {
+        let first = convert(dir.path(), 1, format, true);
+        let second = convert(dir.path(), 2, format, true);
+        assert!(first == second, "{} outputs differ between runs", format);
+    }
+    // Without the flag texconv's stamps come through
+    for format in ["png", "jpg"] {
+        assert_ne!(convert(dir.path(), 1, format, false), convert(dir.path(), 2, format, false), "{}", format);
+    }
+}
@@ -0,0 +1,135 @@
+//! `.sprite` sheets animated end to end: per-cell timing read back from
+//! the WebP's frames, and cells spread over several texture pages.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{bin, run, stderr, stdout};
+
+const RED: u16 = 0xf800;
+const GREEN: u16 = 0x07e0;
+const BLUE: u16 = 0x001f;
+const YELLOW: u16 = 0xffe0;
+
+/// Writes `<dir>/in/<stem>.dds`, a DXT1 texture (which the animation path
+/// decodes itself) of `colors.len()` 4x4 frames side by side, each a solid
+/// RGB565 colour.
+fn write_page(dir: &Path, stem: &str, colors: &[u16]) {
+    let width = 4 * colors.len() as u32;
+    let mut bytes = b"DDS ".to_vec();
+    for value in [124, 0x1 | 0x2 | 0x4 | 0x1000 | 0x8_0000, 4, width, colors.len() as u32 * 8, 0, 1] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    bytes.extend_from_slice(&[0; 44]);
+    bytes.extend_from_slice(&u32::to_le_bytes(32));
+    bytes.extend_from_slice(&u32::to_le_bytes(0x4));
+    bytes.extend_from_slice(b"DXT1");
+    bytes.extend_from_slice(&[0; 20]);
+    for value in [0x1000, 0, 0, 0, 0] {
+        bytes.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    // One block per frame: both endpoints the colour, every index 0
+    for color in colors {
+        bytes.extend_from_slice(&color.to_le_bytes());
+        bytes.extend_from_slice(&color.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+    }
+    std::fs::create_dir_all(dir.join("in")).unwrap();
+    std::fs::write(dir.join("in").join(format!("{}.dds", stem)), bytes).unwrap();
+}
+
+/// A `<Cell>` covering frame `index` of `count` side by side, with `extra`
+/// attributes.
+fn cell(index: usize, count: usize, extra: &str) -> String {
+    let (left, right) = (index as f32 / count as f32, (index + 1) as f32 / count as f32);
+    format!(
+        "  <Cell topLeft=\"{left},0\" topRight=\"{right},0\" bottomLeft=\"{left},1\" bottomRight=\"{right},1\" {extra}/>\n"
+    )
+}
+
+/// Runs `animate` over `<dir>/in` and returns what was printed.
+fn animate(dir: &Path, extra: &[&str]) -> String {
+    let result = run(bin()
+        .arg("animate")
+        .arg("-i")
+        .arg(dir.join("in"))
+        .arg("-o")
+        .arg(dir.join("out"))
+        .args(extra));
+    let printed = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(result.status.success(), "{}", printed);
+    printed
+}
+
+/// Duration in ms of each frame of an animated WebP, from its ANMF chunks.
+fn frame_durations(webp: &[u8]) -> Vec<u32> {
+    assert_eq!((&webp[..4], &webp[8..12]), (b"RIFF".as_slice(), b"WEBP".as_slice()));
+    let mut durations = Vec::new();
+    let mut at = 12;
+    while at + 8 <= webp.len() {
+        let size = u32::from_le_bytes(webp[at + 4..at + 8].try_into().unwrap()) as usize;
+        if &webp[at..at + 4] == b"ANMF" {
+            let duration = &webp[at + 8 + 12..at + 8 + 15];
+            durations.push(u32::from_le_bytes([duration[0], duration[1], duration[2], 0]));
+        }
+        at += 8 + size + size % 2;
+    }
+    durations
+}
+
+#[test]
+fn mixed_cell_durations_time_the_frames() {
+    let dir = tempfile::tempdir().unwrap();
+    write_page(dir.path(), "charge", &[RED, GREEN, BLUE, YELLOW]);
+    // Milliseconds with and without a suffix, seconds, and one cell untimed
+    let cells = [cell(0, 4, "duration=\"40\""), cell(1, 4, ""), cell(2, 4, "time=\"0.25s\""), cell(3, 4, "duration=\"120ms\"")];
+    std::fs::write(dir.path().join("in/charge.sprite"), format!("<Sprite>\n{}</Sprite>\n", cells.concat())).unwrap();
+
+    let printed = animate(dir.path(), &["--verbose", "--frame-delay", "70"]);
+    assert!(printed.contains("⏱️  Using per-frame timing from the sprite file (3 of 4 cells timed"), "{}", printed);
+    let webp = std::fs::read(dir.path().join("out/charge.webp")).unwrap();
+    assert_eq!(frame_durations(&webp), [40, 70, 250, 120]);
+}
+
+#[test]
+fn untimed_sheets_use_the_frame_delay() {
+    let dir = tempfile::tempdir().unwrap();
+    write_page(dir.path(), "idle", &[RED, GREEN]);
+    let cells = [cell(0, 2, ""), cell(1, 2, "")];
+    std::fs::write(dir.path().join("in/idle.sprite"), format!("<Sprite>\n{}</Sprite>\n", cells.concat())).unwrap();
+
+    let printed = animate(dir.path(), &["--verbose", "--frame-delay", "70"]);
+    assert!(!printed.contains("timing from the sprite file"), "{}", printed);
+    let webp = std::fs::read(dir.path().join("out/idle.webp")).unwrap();
+    assert_eq!(frame_durations(&webp), [70, 70]);
+}
+
+#[test]
+fn cells_on_later_pages_come_from_the_numbered_siblings() {
+    let dir = tempfile::tempdir().unwrap();
+    write_page(dir.path(), "effect_0", &[RED, GREEN]);
+    write_page(dir.path(), "effect_1", &[BLUE, YELLOW]);
+    // Pages by index and by name, interleaved so a wrong pick shows
+    let cells = [cell(1, 2, "page=\"1\""), cell(0, 2, ""), cell(0, 2, "texture=\"effect_1.dds\""), cell(1, 2, "page=\"0\"")];
+    std::fs::write(dir.path().join("in/effect.sprite"), format!("<Sprite>\n{}</Sprite>\n", cells.concat())).unwrap();
+
+    let printed = animate(dir.path(), &["--verbose"]);
+    assert!(printed.contains("Sheet spans 2 texture pages"), "{}", printed);
+    // effect_1 is a page of the sheet, not an animation of its own
+    let outputs: Vec<_> = std::fs::read_dir(dir.path().join("out")).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(outputs, ["effect.webp"]);
+
+    let frames = dir.path().join("frames");
+    let result = run(bin().arg("explode").arg("-i").arg(dir.path().join("out/effect.webp")).arg("-o").arg(&frames));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    let colors: Vec<[u8; 3]> = (1..=4)
+        .map(|index| {
+            let frame = image::open(frames.join(format!("effect/effect_{:03}.png", index))).unwrap().to_rgb8();
+            // Lossy WebP: round each channel to full on or off
+            frame.get_pixel(2, 2).0.map(|channel| if channel > 127 { 255 } else { 0 })
+        })
+        .collect();
+    assert_eq!(colors, [[255, 255, 0], [255, 0, 0], [0, 0, 255], [0, 255, 0]]);
+}
@@ -0,0 +1,84 @@
+//! `--srgb` brightness: an sRGB colour texture keeps its brightness, while
+//! texconv run without the sRGB flag linearizes it and darkens the PNG.
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use common::{bin, run, stderr, stdout, write_dds_dxgi};
+
+const R8G8B8A8_UNORM_SRGB: u32 = 29;
+/// The fixtures' sRGB grey, and what texconv makes of it when it converts
+/// sRGB to linear: ((128 / 255 + 0.055) / 1.055)^2.4 * 255.
+const SRGB_GREY: u8 = 128;
+const LINEARIZED_GREY: u8 = 55;
+
+/// A texconv stand-in that writes the "after" fixture texconv would: the
+/// source grey with `-srgb`, the linearized grey without it.
+fn srgb_texconv(dir: &Path) -> PathBuf {
+    let kept = dir.join("kept.png");
+    let linearized = dir.join("linearized.png");
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([SRGB_GREY, SRGB_GREY, SRGB_GREY, 255])).save(&kept).unwrap();
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([LINEARIZED_GREY, LINEARIZED_GREY, LINEARIZED_GREY, 255])).save(&linearized).unwrap();
+
+    let script = dir.join("texconv.sh");
+    let body = format!(
+        r#"#!/bin/sh
+[ "$1" = "-h" ] && exit 1
+out=""; ft="png"; prev=""; last=""; image="{}"
+for a in "$@"; do
+  [ "$prev" = "-o" ] && out="$a"
+  [ "$prev" = "-ft" ] && ft="$a"
+  [ "$a" = "-srgb" ] && image="{}"
+  prev="$a"; last="$a"
+done
+name=$(basename "$last")
+cp "$image" "$out/${{name%.*}}.$ft"
+"#,
+        linearized.display(),
+        kept.display()
+    );
+    std::fs::write(&script, body).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+fn average_brightness(path: &Path) -> f64 {
+    let image = image::open(path).unwrap().to_rgb8();
+    let sum: u64 = image.pixels().flat_map(|pixel| pixel.0).map(u64::from).sum();
+    sum as f64 / (image.width() * image.height() * 3) as f64
+}
+
+/// Converts the sRGB grey `names` with `args` and returns each output's
+/// average brightness.
+fn convert(names: &[&str], args: &[&str]) -> Vec<f64> {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let output = dir.path().join("out");
+    for name in names {
+        write_dds_dxgi(&input.join(format!("{}.dds", name)), 4, 4, R8G8B8A8_UNORM_SRGB, [SRGB_GREY, SRGB_GREY, SRGB_GREY, 255]);
+    }
+    let texconv = srgb_texconv(dir.path());
+
+    let result = run(bin().arg("convert").arg("--texconv-path").arg(&texconv).arg("-i").arg(&input).arg("-o").arg(&output).args(args));
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    names.iter().map(|name| average_brightness(&output.join(format!("{}.png", name)))).collect()
+}
+
+#[test]
+fn auto_keeps_an_srgb_albedo_as_bright_as_its_source() {
+    assert_eq!(convert(&["rock_diff"], &[]), [SRGB_GREY as f64]);
+}
+
+#[test]
+fn linear_darkens_an_srgb_albedo() {
+    let [brightness] = convert(&["rock_diff"], &["--srgb", "linear"])[..] else { unreachable!() };
+    assert!(brightness < SRGB_GREY as f64 / 2.0, "{}", brightness);
+}
+
+#[test]
+fn auto_does_not_gamma_convert_normal_maps_and_masks() {
+    assert_eq!(convert(&["rock_ddna", "rock_mask"], &[]), [LINEARIZED_GREY as f64; 2]);
+}
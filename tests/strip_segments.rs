@@ -0,0 +1,70 @@
+//! `--strip-segments` trees whose stripped paths collide: caught before
+//! anything is converted, flagged in dry runs, and told apart with
+//! `--dedupe-collisions`.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{convert, stderr, stdout, suffix_hash, write_dds, written};
+
+/// `in/a/b/c/rock.dds` and `in/x/y/z/rock.dds`, plus `in/a/b/c/moss.dds`
+/// which collides with nothing.
+fn colliding_tree(dir: &Path) {
+    for name in ["a/b/c/rock", "x/y/z/rock", "a/b/c/moss"] {
+        write_dds(&dir.join("in").join(format!("{}.dds", name)), 4, 4, 1, [0, 0, 255, 255]);
+    }
+}
+
+#[test]
+fn colliding_outputs_stop_the_run_before_anything_is_converted() {
+    let dir = tempfile::tempdir().unwrap();
+    colliding_tree(dir.path());
+    for extra in [&["--strip-segments", "3"][..], &["--strip-segments", "3", "--dry-run"]] {
+        let result = convert(dir.path(), extra);
+        assert!(!result.status.success(), "{:?}", extra);
+        let printed = format!("{}{}", stdout(&result), stderr(&result));
+        assert!(printed.contains("1 output path(s) would be written by more than one input"), "{}", printed);
+        assert!(printed.contains(&format!("\n  {}", dir.path().join("out/rock.png").display())), "{}", printed);
+        for input in ["a/b/c/rock.dds", "x/y/z/rock.dds"] {
+            assert!(printed.contains(&format!("<- {}", dir.path().join("in").join(input).display())), "{}", printed);
+        }
+        assert!(!printed.contains("moss"), "{}", printed);
+        assert!(!dir.path().join("out").exists());
+    }
+}
+
+#[test]
+fn dedupe_collisions_suffixes_a_hash_of_each_path() {
+    let dir = tempfile::tempdir().unwrap();
+    colliding_tree(dir.path());
+    let (abc, xyz) = (format!("rock_{}.png", suffix_hash("a/b/c/rock.dds")), format!("rock_{}.png", suffix_hash("x/y/z/rock.dds")));
+
+    let result = convert(dir.path(), &["--strip-segments", "3", "--dedupe-collisions", "--dry-run"]);
+    let printed = stdout(&result);
+    assert!(result.status.success(), "{}\n{}", printed, stderr(&result));
+    for (input, output) in [("a/b/c/rock.dds", &abc), ("x/y/z/rock.dds", &xyz)] {
+        let line = format!("  {} -> {}", dir.path().join("in").join(input).display(), dir.path().join("out").join(output).display());
+        assert!(printed.contains(&line), "{}", printed);
+    }
+    assert_eq!(printed.matches("(suffixed with a hash of its path: 2 inputs share this name)").count(), 2, "{}", printed);
+
+    // The same names on every run, whatever else is in the tree
+    for _ in 0..2 {
+        let result = convert(dir.path(), &["--strip-segments", "3", "--dedupe-collisions"]);
+        assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+        let mut expected = vec![abc.clone(), xyz.clone(), "moss.png".to_string()];
+        expected.sort();
+        assert_eq!(written(dir.path()), expected);
+    }
+}
+
+#[test]
+fn stripping_fewer_segments_keeps_the_trees_apart() {
+    let dir = tempfile::tempdir().unwrap();
+    colliding_tree(dir.path());
+    let result = convert(dir.path(), &["--strip-segments", "2"]);
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert_eq!(written(dir.path()), ["c/moss.png", "c/rock.png", "z/rock.png"]);
+}
@@ -0,0 +1,66 @@
+//! `--texconv-arg` switches reaching texconv, and `--verbose` echoing the
+//! command line each file is converted with.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+
+#[test]
+fn verbose_echoes_each_files_command_with_the_extra_switches() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("sand.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(dir.path().join("out"))
+        .args(["--verbose", "--texconv-arg", "-sepalpha"]));
+
+    let text = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(result.status.success(), "{}", text);
+    for name in ["rock.dds", "sand.dds"] {
+        let line = text
+            .lines()
+            .find(|line| line.starts_with("🔧") && line.ends_with(name))
+            .unwrap_or_else(|| panic!("no command line for {}:\n{}", name, text));
+        assert!(line.contains(&format!("{} ", texconv.display())), "{}", line);
+        assert!(line.contains(" -sepalpha "), "{}", line);
+        assert!(!line.contains('<'), "{}", line);
+    }
+}
+
+#[test]
+fn a_sidecar_swizzle_reaches_only_its_own_texture() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("sand.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    std::fs::write(input.join("rock.dds.toml"), "swizzle = \"bgra\"").unwrap();
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(dir.path().join("out"))
+        .arg("--verbose"));
+
+    let text = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(result.status.success(), "{}", text);
+    let command = |name: &str| text.lines().find(|line| line.starts_with("🔧") && line.ends_with(name)).unwrap_or_default().to_string();
+    assert!(command("rock.dds").contains(" -swizzle bgra "), "{}", text);
+    assert!(!command("sand.dds").is_empty() && !command("sand.dds").contains("-swizzle"), "{}", text);
+}
@@ -0,0 +1,37 @@
+//! `--timeout` stopping a texconv run that hangs.
+#![cfg(unix)]
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{bin, run, stderr, stdout, write_dds, write_png};
+
+#[test]
+fn a_hanging_texconv_is_stopped_and_the_file_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    write_dds(&input.join("a_slow.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&input.join("b_ok.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_png(&dir.path().join("stub.png"));
+    let texconv = common::stub_texconv(dir.path(), &dir.path().join("stub.png"));
+    let output = dir.path().join("out");
+
+    let started = Instant::now();
+    let result = run(bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .args(["--continue-on-error", "--timeout", "1"]));
+
+    let text = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(started.elapsed() < Duration::from_secs(30), "{}", text);
+    assert!(!result.status.success(), "{}", text);
+    assert!(text.contains("timed out after 1s"), "{}", text);
+    assert!(output.join("b_ok.png").is_file(), "{}", text);
+    assert!(!output.join("a_slow.png").exists(), "{}", text);
+}
@@ -0,0 +1,52 @@
+//! What a `--verbose` run prints when the progress bar is off (stdout is
+//! not a terminal here): whole lines, in order, nothing interleaved.
+#![cfg(unix)]
+
+mod common;
+
+use common::{convert, stderr, stdout, write_dds};
+
+/// `text` with `dir` and the random part of staging folder names replaced,
+/// so runs compare equal.
+fn normalized(text: &str, dir: &std::path::Path) -> String {
+    let text = text.replace(&dir.display().to_string(), "<dir>");
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        match line.split_once(".dds-converter-") {
+            Some((before, after)) => lines.push(format!("{}.dds-converter-XXXXXX{}", before, &after[6..])),
+            None => lines.push(line.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+#[test]
+fn verbose_run_prints_clean_unbroken_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("in/a.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    write_dds(&dir.path().join("in/b.dds"), 4, 4, 1, [0, 0, 255, 255]);
+
+    let result = convert(dir.path(), &["--verbose", "-c", "1"]);
+    assert!(result.status.success(), "{}", stderr(&result));
+    assert_eq!(stderr(&result), "");
+
+    let expected = "\
+✅ texconv.exe extracted to: <dir>/texconv.sh
+🔍 Searching for DDS files in: <dir>/in
+📊 Found 2 DDS files
+🔄 Processing: <dir>/in/a.dds -> <dir>/out/a.png
+🔧 <dir>/texconv.sh -f R8G8B8A8_UNORM -ft png -y -o <dir>/out/.dds-converter-XXXXXX/out <dir>/in/a.dds
+✅ Done: <dir>/out/a.png
+🔄 Processing: <dir>/in/b.dds -> <dir>/out/b.png
+🔧 <dir>/texconv.sh -f R8G8B8A8_UNORM -ft png -y -o <dir>/out/.dds-converter-XXXXXX/out <dir>/in/b.dds
+✅ Done: <dir>/out/b.png
+📊 Converted: 2
+📏 Sizes of converted files:
+     format    files        input       output    ratio
+     png           2        384 B        264 B    0.69x
+     120 B saved
+🎉 All files were processed successfully!";
+    assert_eq!(normalized(&stdout(&result), dir.path()), expected);
+    // No progress bar redraws or cursor movement mixed into the lines
+    assert!(!stdout(&result).contains(['\r', '\x1b']));
+}
@@ -0,0 +1,47 @@
+//! `--verify`: an output texconv wrote truncated fails the file as
+//! corrupt_output and is removed, so the next run converts it again.
+#![cfg(unix)]
+
+mod common;
+
+use std::path::Path;
+
+use common::{convert, stderr, stdout, write_dds, write_png};
+
+/// Has the stub texconv hand out the first half of a valid PNG.
+fn truncate_decoded(dir: &Path) {
+    write_png(&dir.join("decoded.png"));
+    let bytes = std::fs::read(dir.join("decoded.png")).unwrap();
+    std::fs::write(dir.join("decoded.png"), &bytes[..bytes.len() / 2]).unwrap();
+}
+
+#[test]
+fn a_truncated_output_fails_and_is_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("in/rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    truncate_decoded(dir.path());
+
+    let result = convert(dir.path(), &["--verify"]);
+    assert!(!result.status.success());
+    let printed = format!("{}{}", stdout(&result), stderr(&result));
+    assert!(printed.contains("[corrupt_output]"), "{}", printed);
+    assert!(printed.contains("failed verification"), "{}", printed);
+    assert!(!dir.path().join("out/rock.png").exists());
+
+    // With the bad file gone, --skip-existing converts it again
+    write_png(&dir.path().join("decoded.png"));
+    let result = convert(dir.path(), &["--verify", "--skip-existing"]);
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    image::open(dir.path().join("out/rock.png")).unwrap();
+}
+
+#[test]
+fn without_verify_the_truncated_output_is_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dds(&dir.path().join("in/rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    truncate_decoded(dir.path());
+
+    let result = convert(dir.path(), &[]);
+    assert!(result.status.success(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(dir.path().join("out/rock.png").is_file());
+}
@@ -0,0 +1,104 @@
+//! `convert --watch` picking up files dropped in after the first pass.
+#![cfg(unix)]
+
+mod common;
+
+use common::{bin, stderr, stdout, write_dds, write_png};
+use std::path::Path;
+use std::process::{Child, Output, Stdio};
+use std::time::{Duration, Instant};
+
+/// Starts `--watch` on `input` with `args`, waits for the first pass to
+/// finish, runs `drop_files`, then stops it with Ctrl-C once `done` holds
+/// (or after a timeout).
+fn watch(input: &Path, output: &Path, args: &[&str], drop_files: impl FnOnce(), done: impl Fn() -> bool) -> Output {
+    let dir = input.parent().unwrap();
+    write_png(&dir.join("stub.png"));
+    let texconv = common::stub_texconv(dir, &dir.join("stub.png"));
+    let child: Child = bin()
+        .arg("convert")
+        .arg("--texconv-path")
+        .arg(&texconv)
+        .arg("-i")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .arg("--watch")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let wait_for = |condition: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while !condition() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    };
+    // The first pass writes at least one output before the watcher starts
+    wait_for(&|| std::fs::read_dir(output).is_ok_and(|mut entries| entries.next().is_some()));
+    std::thread::sleep(Duration::from_millis(500));
+    drop_files();
+    wait_for(&done);
+
+    std::process::Command::new("kill").arg("-INT").arg(child.id().to_string()).status().unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn watched_files_continue_the_index() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let output = dir.path().join("out");
+    write_dds(&input.join("a.dds"), 4, 4, 1, [0, 0, 255, 255]);
+
+    let result = watch(
+        &input,
+        &output,
+        &["--name-template", "{index}_{stem}.{ext}"],
+        || write_dds(&input.join("b.dds"), 4, 4, 1, [0, 255, 0, 255]),
+        || output.join("2_b.png").exists(),
+    );
+    assert!(output.join("1_a.png").is_file(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(output.join("2_b.png").is_file(), "{}\n{}", stdout(&result), stderr(&result));
+}
+
+#[test]
+fn watched_files_go_through_the_collision_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let output = dir.path().join("out");
+    write_dds(&input.join("a/rock.dds"), 4, 4, 1, [0, 0, 255, 255]);
+    // A file in a folder created after the watch started can be missed
+    std::fs::create_dir_all(input.join("b")).unwrap();
+
+    let result = watch(
+        &input,
+        &output,
+        &["--strip-segments", "1", "--on-collision", "suffix-counter"],
+        || write_dds(&input.join("b/rock.dds"), 4, 4, 1, [0, 255, 0, 255]),
+        || output.join("rock_2.png").exists(),
+    );
+    assert!(output.join("rock.png").is_file(), "{}\n{}", stdout(&result), stderr(&result));
+    assert!(output.join("rock_2.png").is_file(), "{}\n{}", stdout(&result), stderr(&result));
+}
+
+#[test]
+fn watched_files_get_the_retries_of_a_normal_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let output = dir.path().join("out");
+    write_dds(&input.join("a.dds"), 4, 4, 1, [0, 0, 255, 255]);
+
+    let result = watch(
+        &input,
+        &output,
+        &["--retries", "1"],
+        || write_dds(&input.join("b_flaky.dds"), 4, 4, 1, [0, 255, 0, 255]),
+        || output.join("b_flaky.png").exists(),
+    );
+    let text = format!("{}\n{}", stdout(&result), stderr(&result));
+    assert!(output.join("b_flaky.png").is_file(), "{}", text);
+    assert!(text.contains("[access_denied]; retrying"), "{}", text);
+}